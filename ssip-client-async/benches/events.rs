@@ -0,0 +1,133 @@
+// Count the allocations made when a stream of index marks is parsed.
+//
+// The events are received by a client from a socket and decoded from memory with a
+// decoder.
+//
+// cargo bench --bench events
+
+#[cfg(unix)]
+mod bench {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::io::{self, Write};
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Instant;
+
+    use ssip_client_async::{fifo, ClientResult, Decoder, ParseMode, Response, Utf8Mode};
+
+    /// Allocator counting the allocations.
+    pub struct Counting;
+
+    static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    const BURST: usize = 100_000;
+
+    /// Size of the chunks given to the decoder.
+    const CHUNK: usize = 4096;
+
+    /// Index marks of a message, as sent by the server.
+    fn events() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for index in 0..BURST {
+            write!(
+                bytes,
+                "700-21\r\n700-1\r\n700-mark{}\r\n700 INDEX MARK\r\n",
+                index
+            )
+            .unwrap();
+        }
+        bytes
+    }
+
+    fn report(name: &str, before: usize, start: Instant) {
+        let elapsed = start.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!(
+            "{}: {} events in {:?}: {:.2} allocations per event",
+            name,
+            BURST,
+            elapsed,
+            allocations as f64 / BURST as f64
+        );
+    }
+
+    fn receive_event(bytes: Vec<u8>) -> ClientResult<()> {
+        let (local, remote) = UnixStream::pair()?;
+        let writer = thread::spawn(move || {
+            let mut remote = remote;
+            remote.write_all(&bytes)
+        });
+        let local = Mutex::new(Some(local));
+        let mut client = fifo::sync::Builder::new()
+            .with_stream(move || {
+                local
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+            })
+            .build()?;
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..BURST {
+            client.receive_event()?;
+        }
+        report("receive_event", before, start);
+
+        drop(client);
+        writer.join().unwrap()?;
+        Ok(())
+    }
+
+    fn decode_response(bytes: &[u8]) -> ClientResult<()> {
+        let mut decoder = Decoder::new(ParseMode::Strict, Utf8Mode::Strict);
+        let mut count = 0;
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for chunk in bytes.chunks(CHUNK) {
+            decoder.feed(chunk);
+            while let Some(response) = decoder.decode_response() {
+                if let Response::EventIndexMark(..) = response? {
+                    count += 1;
+                }
+            }
+        }
+        report("decode_response", before, start);
+        assert_eq!(BURST, count);
+        Ok(())
+    }
+
+    pub fn run() -> ClientResult<()> {
+        let bytes = events();
+        decode_response(&bytes)?;
+        receive_event(bytes)
+    }
+}
+
+#[cfg(unix)]
+#[global_allocator]
+static GLOBAL: bench::Counting = bench::Counting;
+
+#[cfg(unix)]
+fn main() -> ssip_client_async::ClientResult<()> {
+    bench::run()
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("benchmark only available on unix.");
+}
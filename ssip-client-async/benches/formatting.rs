@@ -0,0 +1,119 @@
+// Count the allocations made when a burst of requests is sent.
+//
+// Requests with names or texts are sent by cloning them into a request and by borrowing
+// them with a request reference.
+//
+// cargo bench --bench formatting
+
+#[cfg(unix)]
+mod bench {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::io::{self, Read};
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Instant;
+
+    use ssip_client_async::{
+        fifo, ClientName, ClientResult, ClientScope, MessageScope, Request, RequestRef,
+    };
+
+    /// Allocator counting the allocations.
+    pub struct Counting;
+
+    static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    const BURST: usize = 10_000;
+
+    fn report(name: &str, before: usize, start: Instant) {
+        let elapsed = start.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!(
+            "{}: {} requests in {:?}: {:.2} allocations per request",
+            name,
+            BURST,
+            elapsed,
+            allocations as f64 / BURST as f64
+        );
+    }
+
+    pub fn run() -> ClientResult<()> {
+        let (local, remote) = UnixStream::pair()?;
+        let reader = thread::spawn(move || {
+            let mut remote = remote;
+            io::copy(&mut remote.by_ref(), &mut io::sink())
+        });
+        let local = Mutex::new(Some(local));
+        let mut client = fifo::sync::Builder::new()
+            .with_stream(move || {
+                local
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+            })
+            .build()?;
+        let requests = [
+            Request::SetRate(ClientScope::Current, 10),
+            Request::SetPitch(ClientScope::Current, -10),
+            Request::Stop(MessageScope::Last),
+        ];
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for index in 0..BURST {
+            client.send(requests[index % requests.len()].clone())?;
+        }
+        report("numbers", before, start);
+
+        let name = ClientName::new("joe", "hello")?;
+        let voice = String::from("Alex");
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..BURST / 2 {
+            client.send(Request::SetName(name.clone()))?;
+            client.send(Request::SetSynthesisVoice(
+                ClientScope::Current,
+                voice.clone(),
+            ))?;
+        }
+        report("cloned", before, start);
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..BURST / 2 {
+            client.send_ref(RequestRef::SetName(&name))?;
+            client.send_ref(RequestRef::SetSynthesisVoice(ClientScope::Current, &voice))?;
+        }
+        report("borrowed", before, start);
+
+        drop(client);
+        reader.join().unwrap()?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[global_allocator]
+static GLOBAL: bench::Counting = bench::Counting;
+
+#[cfg(unix)]
+fn main() -> ssip_client_async::ClientResult<()> {
+    bench::run()
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("benchmark only available on unix.");
+}
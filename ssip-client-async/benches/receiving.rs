@@ -0,0 +1,105 @@
+// Count the allocations made when answers with several lines are received.
+//
+// With a reused vector, the remaining allocations are the strings of the status line.
+//
+// cargo bench --bench receiving
+
+#[cfg(unix)]
+mod bench {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::io::{self, Write};
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Instant;
+
+    use ssip_client_async::{fifo, ClientResult, OK_VOICES_LIST_SENT};
+
+    /// Allocator counting the allocations.
+    pub struct Counting;
+
+    static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    const BURST: usize = 10_000;
+
+    const ANSWER: &[u8] =
+        b"249-MALE1\r\n249-FEMALE1\r\n249-CHILD_MALE\r\n249 OK VOICE LIST SENT\r\n";
+
+    fn report(name: &str, before: usize, start: Instant) {
+        let elapsed = start.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!(
+            "{}: {} answers in {:?}: {:.2} allocations per answer",
+            name,
+            BURST,
+            elapsed,
+            allocations as f64 / BURST as f64
+        );
+    }
+
+    pub fn run() -> ClientResult<()> {
+        let (local, remote) = UnixStream::pair()?;
+        let writer = thread::spawn(move || {
+            let mut remote = remote;
+            for _ in 0..2 * BURST {
+                remote.write_all(ANSWER)?;
+            }
+            Ok::<(), io::Error>(())
+        });
+        let local = Mutex::new(Some(local));
+        let mut client = fifo::sync::Builder::new()
+            .with_stream(move || {
+                local
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+            })
+            .build()?;
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..BURST {
+            client.receive_lines(OK_VOICES_LIST_SENT)?;
+        }
+        report("receive_lines", before, start);
+
+        let mut lines = Vec::new();
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..BURST {
+            client.receive_into(&mut lines)?;
+        }
+        report("receive_into", before, start);
+
+        drop(client);
+        writer.join().unwrap()?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[global_allocator]
+static GLOBAL: bench::Counting = bench::Counting;
+
+#[cfg(unix)]
+fn main() -> ssip_client_async::ClientResult<()> {
+    bench::run()
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("benchmark only available on unix.");
+}
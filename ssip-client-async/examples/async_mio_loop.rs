@@ -8,7 +8,9 @@ use std::{
 };
 
 #[cfg(all(unix, feature = "async-mio"))]
-use ssip_client::{fifo, ClientError, ClientName, ClientResult, QueuedClient, Request, Response};
+use ssip_client_async::{
+    fifo, ClientError, ClientName, ClientResult, QueuedClient, Request, Response,
+};
 
 #[cfg(all(unix, feature = "async-mio"))]
 fn main() -> ClientResult<()> {
@@ -25,7 +27,7 @@ fn main() -> ClientResult<()> {
         .register(&mut source_fd, stdin_token, Interest::READABLE)?;
 
     // Register the SSIP client
-    let mut ssip_client = QueuedClient::new(fifo::Builder::new().build()?);
+    let mut ssip_client = QueuedClient::new(fifo::mio::Builder::new().build()?);
     let speech_input_token = Token(1);
     let speech_output_token = Token(2);
     ssip_client.register(&poll, speech_input_token, speech_output_token)?;
@@ -34,7 +36,7 @@ fn main() -> ClientResult<()> {
     let mut events = Events::with_capacity(16);
     let mut speech_writable = false;
     let mut send_requests = VecDeque::with_capacity(4);
-    ssip_client.push(Request::SetName(ClientName::new("joe", "async")));
+    ssip_client.push(Request::SetName(ClientName::new("joe", "async")?));
 
     fn prompt() -> io::Result<()> {
         let mut stdout = io::stdout();
@@ -73,6 +75,7 @@ fn main() -> ClientResult<()> {
                 match ssip_client.receive_next() {
                     Err(ClientError::Io(err)) => return Err(ClientError::from(err)),
                     Err(ClientError::Ssip(err)) => eprintln!("SSIP error: {:?}", err),
+                    Err(ClientError::NotReady) => (),
                     Err(_) => panic!("internal error"),
                     Ok(result) => match result {
                         Response::MessageQueued | Response::ClientNameSet => (),
@@ -1,13 +1,15 @@
-#[cfg(all(unix, not(feature = "async-mio")))]
+#[cfg(unix)]
 use std::{
     collections::VecDeque,
     io::{self, Write},
 };
 
-#[cfg(all(unix, not(feature = "async-mio")))]
-use ssip_client::{fifo, ClientError, ClientName, ClientResult, QueuedClient, Request, Response};
+#[cfg(unix)]
+use ssip_client_async::{
+    fifo, ClientError, ClientName, ClientResult, QueuedClient, Request, Response,
+};
 
-#[cfg(all(unix, not(feature = "async-mio")))]
+#[cfg(unix)]
 fn main() -> ClientResult<()> {
     #[derive(Clone, Eq, PartialEq)]
     enum SourceKey {
@@ -20,7 +22,7 @@ fn main() -> ClientResult<()> {
     let mut events = popol::Events::with_capacity(4);
 
     let stdin = io::stdin();
-    let mut ssip_client = QueuedClient::new(fifo::Builder::new().nonblocking().build()?);
+    let mut ssip_client = QueuedClient::new(fifo::sync::Builder::new().nonblocking().build()?);
 
     sources.register(SourceKey::Stdin, &stdin, popol::interest::READ);
     sources.register(
@@ -37,7 +39,7 @@ fn main() -> ClientResult<()> {
     // Loop for events
     let mut speech_writable = false;
     let mut send_requests = VecDeque::with_capacity(4);
-    ssip_client.push(Request::SetName(ClientName::new("joe", "async")));
+    ssip_client.push(Request::SetName(ClientName::new("joe", "async")?));
 
     fn prompt() -> io::Result<()> {
         let mut stdout = io::stdout();
@@ -74,6 +76,7 @@ fn main() -> ClientResult<()> {
                 SourceKey::SpeechIn => match ssip_client.receive_next() {
                     Err(ClientError::Io(err)) => return Err(ClientError::from(err)),
                     Err(ClientError::Ssip(err)) => eprintln!("SSIP error: {:?}", err),
+                    Err(ClientError::NotReady) => (),
                     Err(_) => panic!("internal error"),
                     Ok(result) => match result {
                         Response::MessageQueued | Response::ClientNameSet => (),
@@ -113,11 +116,6 @@ fn main() -> ClientResult<()> {
     }
 }
 
-#[cfg(all(unix, feature = "async-mio"))]
-fn main() {
-    println!("asynchronous client not implemented");
-}
-
 #[cfg(not(unix))]
 fn main() {
     println!("example only available on unix.");
@@ -1,5 +1,5 @@
-extern crate tokio;
-use ssip_client::{
+#[cfg(all(unix, feature = "tokio"))]
+use ssip_client_async::{
     fifo::asynchronous_tokio::Builder, types::ClientScope, ClientName, ClientResult,
 };
 
@@ -10,7 +10,7 @@ async fn main() -> ClientResult<()> {
     let mut client = Builder::new().build().await?;
     println!("Client created.");
     client
-        .set_client_name(ClientName::new("test", "hello"))
+        .set_client_name(ClientName::new("test", "hello")?)
         .await?
         .check_client_name_set()
         .await?;
@@ -25,7 +25,7 @@ async fn main() -> ClientResult<()> {
         .receive_message_id()
         .await?;
     println!("message: {}", msg_id);
-    let volume = client.get_volume().await?.receive_u8().await?;
+    let volume = client.get_volume().await?.receive_i8().await?;
     println!("volume: {}", volume);
     match client
         .set_volume(ClientScope::Current, 1)
@@ -36,7 +36,7 @@ async fn main() -> ClientResult<()> {
         Ok(id) => println!("Volume change ID: {:?}", id),
         Err(e) => println!("Error: {:?}", e),
     };
-    let volume = client.get_volume().await?.receive_u8().await?;
+    let volume = client.get_volume().await?.receive_i8().await?;
     println!("volume: {}", volume);
     let msg_id = client
         .speak()
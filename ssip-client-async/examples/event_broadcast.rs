@@ -0,0 +1,105 @@
+// Share the events of one connection between a logger and a progress bar.
+//
+// cargo run --example event_broadcast --features tokio
+
+#[cfg(all(unix, feature = "tokio"))]
+use ssip_client_async::{
+    broadcast::EventBroadcaster, fifo::asynchronous_tokio::Builder, ClientName, ClientResult,
+    Event, EventType, MessageId, NotificationType, OK_NOTIFICATION_SET,
+};
+#[cfg(all(unix, feature = "tokio"))]
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+#[cfg(all(unix, feature = "tokio"))]
+const SENTENCES: [&str; 4] = [
+    "The first sentence.",
+    "The second sentence.",
+    "The third sentence.",
+    "The last sentence.",
+];
+
+/// Print all the events.
+#[cfg(all(unix, feature = "tokio"))]
+async fn log_events(mut events: Receiver<Event>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => println!("event: {:?}", event),
+            Err(RecvError::Lagged(count)) => println!("{} events lost", count),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Show the number of messages spoken until they are all finished.
+#[cfg(all(unix, feature = "tokio"))]
+async fn show_progress(mut events: Receiver<Event>, mut pending: Vec<MessageId>) {
+    let total = pending.len();
+    while !pending.is_empty() {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        if matches!(event.ntype, EventType::End | EventType::Cancel) {
+            pending.retain(|id| Some(*id) != event.message_id());
+            let done = total - pending.len();
+            println!(
+                "[{}{}] {}/{}",
+                "#".repeat(done),
+                " ".repeat(total - done),
+                done,
+                total
+            );
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "tokio"))]
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ClientResult<()> {
+    let mut client = Builder::new().build().await?;
+    client
+        .set_client_name(ClientName::new("example", "broadcast")?)
+        .await?
+        .check_client_name_set()
+        .await?;
+    client
+        .set_notification(NotificationType::All, true)
+        .await?
+        .check_status(OK_NOTIFICATION_SET)
+        .await?;
+    let broadcaster = EventBroadcaster::default();
+    let logger = tokio::spawn(log_events(broadcaster.subscribe()));
+    let mut ids = Vec::new();
+    for sentence in SENTENCES {
+        let id = client
+            .speak()
+            .await?
+            .check_receiving_data()
+            .await?
+            .send_line(sentence)
+            .await?
+            .receive_message_id()
+            .await?;
+        ids.push(id);
+    }
+    let progress = tokio::spawn(show_progress(broadcaster.subscribe(), ids));
+    tokio::select! {
+        result = broadcaster.run(&mut client) => result?,
+        _ = progress => (),
+    }
+    drop(broadcaster);
+    let _ = logger.await;
+    client.quit().await?;
+    Ok(())
+}
+
+#[cfg(all(unix, not(feature = "tokio")))]
+fn main() {
+    println!("see hello.rs for an example of a synchronous client.");
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("example only available on unix.");
+}
@@ -0,0 +1,25 @@
+#[cfg(unix)]
+use ssip_client_async::prelude::*;
+
+#[cfg(unix)]
+fn main() -> ClientResult<()> {
+    let mut client = fifo::sync::Builder::new().build()?;
+    client
+        .set_client_name(ClientName::new("joe", "hello")?)?
+        .check_client_name_set()?;
+    let msg_id = client
+        .speak()?
+        .check_receiving_data()?
+        .send_line("hello")?
+        .receive_message_id()?;
+    println!("message: {}", msg_id);
+    let volume = client.get_volume()?.receive_i8()?;
+    println!("volume: {}", volume);
+    client.quit()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("example only available on unix.");
+}
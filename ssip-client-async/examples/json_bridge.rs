@@ -0,0 +1,40 @@
+// Translate requests in JSON read on the standard input to SSIP and print the responses
+// in JSON, one per line.
+//
+// echo '{"SetName":{"user":"joe","application":"bridge","component":"main"}}' \
+//   | cargo run --features json-proto --example json-bridge
+
+#[cfg(unix)]
+use ssip_client_async::{fifo, json, ClientResult, Request, Response};
+
+#[cfg(unix)]
+fn main() -> ClientResult<()> {
+    use std::io::BufRead;
+
+    let mut client = fifo::sync::Builder::new().build()?;
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match json::from_json(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                eprintln!("{}", err);
+                continue;
+            }
+        };
+        let quit = matches!(request, Request::Quit);
+        let response: Response = client.send(request)?.receive()?;
+        println!("{}", json::to_json(&response)?);
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("example only available on unix.");
+}
@@ -1,10 +1,10 @@
-#[cfg(all(unix, not(feature = "async-mio")))]
-use ssip_client::{
+#[cfg(unix)]
+use ssip_client_async::{
     fifo, ClientName, ClientResult, SynthesisVoice, OK_OUTPUT_MODULES_LIST_SENT,
     OK_VOICES_LIST_SENT,
 };
 
-#[cfg(all(unix, not(feature = "async-mio")))]
+#[cfg(unix)]
 fn main() -> ClientResult<()> {
     fn voice_to_string(voice: &SynthesisVoice) -> String {
         match &voice.language {
@@ -12,7 +12,7 @@ fn main() -> ClientResult<()> {
                 Some(dialect) => format!("{} [{}_{}]", voice.name, language, dialect),
                 None => format!("{} [{}]", voice.name, language),
             },
-            None => format!("{}", voice.name),
+            None => voice.name.to_string(),
         }
     }
 
@@ -23,9 +23,9 @@ fn main() -> ClientResult<()> {
         }
     }
 
-    let mut client = fifo::Builder::new().build()?;
+    let mut client = fifo::sync::Builder::new().build()?;
     client
-        .set_client_name(ClientName::new("joe", "list"))?
+        .set_client_name(ClientName::new("joe", "list")?)?
         .check_client_name_set()?;
 
     const OUTPUT_MODULE_TITLE: &str = "output modules";
@@ -44,21 +44,13 @@ fn main() -> ClientResult<()> {
     let voices = client.list_synthesis_voices()?.receive_synthesis_voices()?;
     print_list(
         SYNTHESIS_VOICES_TITLE,
-        &voices
-            .iter()
-            .map(|ref v| voice_to_string(v))
-            .collect::<Vec<String>>(),
+        &voices.iter().map(voice_to_string).collect::<Vec<String>>(),
     );
 
     client.quit().unwrap();
     Ok(())
 }
 
-#[cfg(all(unix, feature = "async-mio"))]
-fn main() {
-    println!("asynchronous client not implemented");
-}
-
 #[cfg(not(unix))]
 fn main() {
     println!("example only available on unix.");
@@ -1,13 +1,13 @@
-#[cfg(all(unix, not(feature = "async-mio")))]
-use ssip_client::{
+#[cfg(unix)]
+use ssip_client_async::{
     fifo, ClientName, ClientResult, EventType, NotificationType, OK_NOTIFICATION_SET,
 };
 
-#[cfg(all(unix, not(feature = "async-mio")))]
+#[cfg(unix)]
 fn main() -> ClientResult<()> {
-    let mut client = fifo::Builder::new().build()?;
+    let mut client = fifo::sync::Builder::new().build()?;
     client
-        .set_client_name(ClientName::new("joe", "notifications"))?
+        .set_client_name(ClientName::new("joe", "notifications")?)?
         .check_client_name_set()?;
     // Enabling notifications
     client
@@ -26,7 +26,9 @@ fn main() -> ClientResult<()> {
             Ok(event) => {
                 println!(
                     "event {}: message {} client {}",
-                    event.ntype, event.id.message, event.id.client
+                    event.ntype,
+                    event.raw().message,
+                    event.raw().client
                 );
                 if matches!(event.ntype, EventType::End) {
                     break;
@@ -43,11 +45,6 @@ fn main() -> ClientResult<()> {
     Ok(())
 }
 
-#[cfg(all(unix, feature = "async-mio"))]
-fn main() {
-    println!("asynchronous client not implemented");
-}
-
 #[cfg(not(unix))]
 fn main() {
     println!("example only available on unix.");
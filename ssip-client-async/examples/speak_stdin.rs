@@ -0,0 +1,18 @@
+// Speak the standard input with the process-wide client.
+//
+// cat file | cargo run --features global --example speak-stdin
+
+#[cfg(unix)]
+use ssip_client_async::{global, ClientResult};
+
+#[cfg(unix)]
+fn main() -> ClientResult<()> {
+    let ids = global::default().say_stdin()?;
+    println!("messages: {}", ids.len());
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("example only available on unix.");
+}
@@ -7,16 +7,22 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use std::io::{self, Read, Write};
-
 use crate::constants::*;
+use crate::demux::OverflowPolicy;
+use crate::diagnostics::{Diagnostic, Stats, TimeBudgets, Timings};
 use crate::protocol::{
-    flush_lines, flush_lines_async_std, parse_event_id, parse_single_integer, parse_single_value,
-    parse_typed_lines, write_lines_async_std,
+    check_no_lines, flush_lines_async_std, frame_len, parse_event, parse_response,
+    parse_single_integer, parse_single_value, parse_typed_lines, probe_accepted,
+    report_unknown_event, split_text, success_code, text_lines, type_response, Decoder, EventQueue,
+    ParseMode, UnknownEventHandler, Utf8Mode, INTERRUPT_BATCH, PROBE_TEXT,
 };
+use crate::sanitize::{auto_text, is_ssml, sanitize_line, TextSanitizer};
+use crate::session::SessionTemplate;
+use crate::state::{Lifecycle, Settings};
 use crate::types::*;
 
 use async_std::io::{BufRead as AsyncBufRead, Write as AsyncWrite};
+use futures_lite::Stream;
 
 /// Convert boolean to ON or OFF
 fn on_off(value: bool) -> &'static str {
@@ -29,10 +35,10 @@ fn on_off(value: bool) -> &'static str {
 
 macro_rules! send_one_line {
     ($self:expr, $fmt:expr, $( $arg:expr ),+) => {
-        flush_lines(&mut $self.output, &[format!($fmt, $( $arg ),+).as_str()])
+        $self.flush_frame(&[format!($fmt, $( $arg ),+).as_str()]).await
     };
     ($self:expr, $fmt:expr) => {
-        flush_lines(&mut $self.output, &[$fmt])
+        $self.flush_frame(&[$fmt]).await
     }
 }
 
@@ -64,203 +70,474 @@ macro_rules! send_range {
 pub struct AsyncClient<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> {
     input: R,
     output: W,
+    parse_mode: ParseMode,
+    utf8_mode: Utf8Mode,
+    unknown_event_handler: Option<UnknownEventHandler>,
+    history_settings: HistorySettings,
+    timings: Timings,
+    lifecycle: Lifecycle,
+    text_sanitizer: Option<TextSanitizer>,
+    label: Option<String>,
+    /// Decoder of the answers, kept to avoid an allocation for each line.
+    decoder: Decoder,
+    /// Lines of the events received, kept to reuse the vector and its strings.
+    event_lines: Vec<String>,
+    /// Events received while waiting for the answer to a request.
+    events: EventQueue,
+    protocol_version: ProtocolVersion,
+    /// SSML mode and synthesis voice accepted by the server for this client.
+    settings: Settings,
+    last_status: Option<StatusLine>,
+    max_message_bytes: Option<usize>,
+    stats: Stats,
 }
 impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
-    pub(crate) fn new(input: R, output: W) -> Self {
-        Self { input, output }
+    /// Create a SSIP client on an asynchronous reader and writer.
+    ///
+    /// Builders create clients on local sockets. On other targets, such as `wasm32-wasi`,
+    /// any pair of streams connected to the server can be used.
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            parse_mode: ParseMode::default(),
+            utf8_mode: Utf8Mode::default(),
+            unknown_event_handler: None,
+            history_settings: HistorySettings::default(),
+            timings: Timings::default(),
+            lifecycle: Lifecycle::default(),
+            text_sanitizer: None,
+            label: None,
+            decoder: Decoder::new(ParseMode::default(), Utf8Mode::default()),
+            event_lines: Vec::new(),
+            events: EventQueue::default(),
+            protocol_version: ProtocolVersion::default(),
+            settings: Settings::default(),
+            last_status: None,
+            max_message_bytes: None,
+            stats: Stats::default(),
+        }
+    }
+    /// Set how strictly the answers of the server are parsed. Default is strict.
+    pub fn set_parse_mode(&mut self, mode: ParseMode) -> &mut Self {
+        self.parse_mode = mode;
+        self.decoder.set_parse_mode(mode);
+        self
+    }
+
+    /// Set how the lines received from the server are decoded. Default is strict.
+    ///
+    /// With [`Utf8Mode::Lossy`], a line that is not valid UTF-8 doesn't fail the connection.
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) -> &mut Self {
+        self.utf8_mode = mode.clone();
+        self.decoder.set_utf8_mode(mode);
+        self
+    }
+
+    /// Set the time expected for the answer of each request.
+    pub fn set_time_budgets(&mut self, budgets: TimeBudgets) -> &mut Self {
+        self.timings.set_budgets(budgets);
+        self
+    }
+
+    /// Set the function called with diagnostics such as [`Diagnostic::SlowServer`].
+    ///
+    /// Answers are only timed once a function is set.
+    pub fn set_diagnostics<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&Diagnostic) + Send + Sync + 'static,
+    {
+        self.timings.set_hook(std::sync::Arc::new(hook));
+        self
+    }
+
+    /// Set the sanitizer that cleans the lines of the messages before they are sent.
+    ///
+    /// It applies to [`AsyncClient::send_line`], [`AsyncClient::send_lines`] and the methods based
+    /// on them.
+    pub fn set_text_sanitizer(&mut self, sanitizer: TextSanitizer) -> &mut Self {
+        self.text_sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Set the function called when an event with an unknown code is skipped.
+    ///
+    /// Such events are logged and skipped by [`AsyncClient::receive_event`]. They are returned as
+    /// [`Response::UnknownEvent`] by [`AsyncClient::receive`].
+    pub fn on_unknown_event<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ReturnCode, &[String]) + Send + Sync + 'static,
+    {
+        self.unknown_event_handler = Some(std::sync::Arc::new(handler));
+        self
+    }
+
+    /// Stage of the connection, updated with the requests sent and the answers received.
+    ///
+    /// Requests that are not allowed in the current stage fail with
+    /// [`ClientError::InvalidState`] before anything is written.
+    pub fn state(&self) -> ConnectionState {
+        self.lifecycle.state()
+    }
+
+    /// Set the name of the connection in the logs, such as `SSIP[label](out): SPEAK`.
+    pub fn set_label(&mut self, label: &str) -> &mut Self {
+        self.label = Some(label.to_string());
+        self.decoder.set_label(label);
+        self
+    }
+
+    /// Check that the answer to each request is read before the next request is sent.
+    ///
+    /// A forgotten `check_status` leaves an answer unread, which is then taken as the answer
+    /// to the next request. In this mode, sending a request while an answer is unread panics
+    /// in debug builds and logs a warning in release builds. It must not be enabled when
+    /// several requests are sent before reading the answers on purpose. Default is off.
+    pub fn set_strict_replies(&mut self, enabled: bool) -> &mut Self {
+        self.lifecycle.set_strict_replies(enabled);
+        self
+    }
+
+    /// Keep at most `capacity` events received while waiting for the answer to a request.
+    ///
+    /// When the buffer is full, the policy decides which event is lost. Default is
+    /// [`DEFAULT_EVENT_CAPACITY`](crate::demux::DEFAULT_EVENT_CAPACITY) events, discarding the
+    /// oldest.
+    pub fn set_event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+        self.events.configure(capacity, policy);
+        self
+    }
+
+    /// Number of events discarded or refused because the event buffer was full.
+    pub fn event_overflows(&self) -> u64 {
+        self.events.overflows()
+    }
+
+    /// Name of the connection in the logs, if any.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Set the version of the protocol spoken by the server. Default is the current one.
+    ///
+    /// Requests that don't exist in this version fail with [`ClientError::Unsupported`].
+    pub fn set_protocol_version(&mut self, version: ProtocolVersion) -> &mut Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Version of the protocol spoken by the server.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
     }
+
+    /// Maximum size in bytes of the messages spoken by [`AsyncClient::say_chunked`].
+    ///
+    /// Longer texts are split in several messages. Default is no limit.
+    pub fn set_max_message_bytes(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_message_bytes = max;
+        self
+    }
+
+    /// Counters of the traffic since the connection or the last reset.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Reset the counters of the traffic.
+    pub fn reset_stats(&mut self) -> &mut Self {
+        self.stats = Stats::default();
+        self
+    }
+
+    /// Status line of the last answer received, successful or not.
+    ///
+    /// Helpers that only return a value, such as `receive_message_id`, drop the message of
+    /// the server. It is kept here. Events are not answers and don't change it.
+    pub fn last_status(&self) -> Option<&StatusLine> {
+        self.last_status.as_ref()
+    }
+
+    /// SSML mode last accepted by the server for this client, off for a new connection.
+    pub fn ssml_mode(&self) -> bool {
+        self.settings.ssml_mode()
+    }
+
+    /// Synthesis voice last accepted by the server for this client, if any.
+    ///
+    /// SSIP has no command to read the synthesis voice. Setting a voice type for the client
+    /// replaces it.
+    pub fn synthesis_voice(&self) -> Option<&str> {
+        self.settings.synthesis_voice()
+    }
+
+    /// History settings requested by this client.
+    pub fn history_settings(&self) -> &HistorySettings {
+        &self.history_settings
+    }
+
+    /// Write a frame of lines and flush it.
+    ///
+    /// All the writes end here. The client owns its output, so no other write can take
+    /// place while the frame is written.
+    async fn flush_frame(&mut self, lines: &[&str]) -> ClientResult<()> {
+        flush_lines_async_std(&mut self.output, lines, self.label.as_deref()).await?;
+        self.stats.bytes_sent += frame_len(lines);
+        Ok(())
+    }
+
+    async fn write_data(&mut self, lines: &[&str]) -> ClientResult<()> {
+        const END_OF_DATA: &str = ".";
+        let lines = lines
+            .iter()
+            .map(|line| sanitize_line(self.text_sanitizer.as_ref(), line))
+            .collect::<Vec<_>>();
+        let lines = lines
+            .iter()
+            .map(|s| s.as_ref())
+            .chain(std::iter::once(END_OF_DATA))
+            .collect::<Vec<&str>>();
+        self.flush_frame(&lines).await
+    }
+
+    /// Check, write and record the lines of a message sent as request `name`.
+    async fn send_data(&mut self, name: &'static str, lines: &[&str]) -> ClientResult<&mut Self> {
+        self.lifecycle.check(name)?;
+        self.write_data(lines).await?;
+        self.timings.sent(name);
+        self.stats.sent(name);
+        self.lifecycle.sent(name);
+        Ok(self)
+    }
+
+    /// Send lines of a message without ending it.
+    ///
+    /// The lines are flushed at once, so that the connection is not idle while the rest of
+    /// a long text is produced. Over TCP, middleboxes may otherwise drop the connection
+    /// before the final dot; the `keepalive` option of the TCP builders also helps. The
+    /// message is ended by [`AsyncClient::send_lines`] or [`AsyncClient::send_line`].
+    pub async fn send_partial_lines(&mut self, lines: &[String]) -> ClientResult<&mut Self> {
+        self.lifecycle.check("SendLines")?;
+        let lines = lines
+            .iter()
+            .map(|line| sanitize_line(self.text_sanitizer.as_ref(), line))
+            .collect::<Vec<_>>();
+        let frame = lines.iter().map(|s| s.as_ref()).collect::<Vec<&str>>();
+        self.flush_frame(&frame).await?;
+        Ok(self)
+    }
+
     /// Send lines of text (terminated by a single dot).
     pub async fn send_lines(&mut self, lines: &[String]) -> ClientResult<&mut Self> {
-        const END_OF_DATA: [&str; 1] = ["."];
-        write_lines_async_std(
-            &mut self.output,
-            lines
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<&str>>()
-                .as_slice(),
-        )
-        .await?;
-        flush_lines_async_std(&mut self.output, &END_OF_DATA).await?;
-        Ok(self)
+        let lines = lines.iter().map(String::as_str).collect::<Vec<&str>>();
+        self.send_data("SendLines", &lines).await
     }
+
+    /// Send one line of text (terminated by a single dot).
+    pub async fn send_line(&mut self, line: &str) -> ClientResult<&mut Self> {
+        self.send_data("SendLine", &[line]).await
+    }
+
     /// Receive answer from server
-    async fn receive_answer(&mut self, lines: &mut Vec<String>) -> ClientStatus {
-        crate::protocol::receive_answer_async_std(&mut self.input, Some(lines)).await
+    async fn receive_answer(&mut self, lines: Option<&mut Vec<String>>) -> ClientStatus {
+        let status = crate::protocol::receive_answer_async_std(
+            &mut self.input,
+            &mut self.decoder,
+            &mut self.stats.bytes_received,
+            lines,
+        )
+        .await;
+        self.timings.received(&status);
+        self.lifecycle.received(&status);
+        self.settings.received(self.lifecycle.answered(), &status);
+        self.stats.received(&status);
+        match &status {
+            Ok(line) | Err(ClientError::Ssip(line)) if !(700..800).contains(&line.code) => {
+                self.last_status = Some(line.clone());
+            }
+            _ => (),
+        }
+        status
+    }
+
+    /// Receive the answer to a request, keeping the events received before it.
+    ///
+    /// The events are returned first by the next calls to receive an event.
+    async fn receive_reply(&mut self, lines: Option<&mut Vec<String>>) -> ClientStatus {
+        let mut event_lines = Vec::new();
+        let lines_expected = lines.is_some();
+        let lines = lines.unwrap_or(&mut event_lines);
+        loop {
+            let status = self.receive_answer(Some(lines)).await?;
+            let is_event = self.events.keep(
+                &status,
+                lines,
+                self.unknown_event_handler.as_ref(),
+                self.label.as_deref(),
+            )?;
+            if !is_event {
+                if !lines_expected {
+                    check_no_lines(lines)?;
+                }
+                return Ok(status);
+            }
+        }
+    }
+    /// Receive the response to a request with the expected status, keeping the events.
+    async fn receive_response(&mut self, expected_code: ReturnCode) -> ClientResult<Response> {
+        let mut lines = Vec::new();
+        let status = self.receive_reply(Some(&mut lines)).await?;
+        if status.code != expected_code {
+            return Err(ClientError::UnexpectedStatus(status.code));
+        }
+        type_response(
+            self.lifecycle.answered(),
+            parse_response(status, lines, self.parse_mode)?,
+        )
     }
+
     /// Receive one response.
     pub async fn receive(&mut self) -> ClientResult<Response> {
-        const MSG_CURSOR_SET_FIRST: &str = "OK CURSOR SET FIRST";
         let mut lines = Vec::new();
-        let status = self.receive_answer(&mut lines).await?;
-        match status.code {
-            OK_LANGUAGE_SET => Ok(Response::LanguageSet),
-            OK_PRIORITY_SET => Ok(Response::PrioritySet),
-            OK_RATE_SET => Ok(Response::RateSet),
-            OK_PITCH_SET => Ok(Response::PitchSet),
-            OK_PUNCTUATION_SET => Ok(Response::PunctuationSet),
-            OK_CAP_LET_RECOGN_SET => Ok(Response::CapLetRecognSet),
-            OK_SPELLING_SET => Ok(Response::SpellingSet),
-            OK_CLIENT_NAME_SET => Ok(Response::ClientNameSet),
-            OK_VOICE_SET => Ok(Response::VoiceSet),
-            OK_STOPPED => Ok(Response::Stopped),
-            OK_PAUSED => Ok(Response::Paused),
-            OK_RESUMED => Ok(Response::Resumed),
-            OK_CANCELED => Ok(Response::Canceled),
-            OK_TABLE_SET => Ok(Response::TableSet),
-            OK_OUTPUT_MODULE_SET => Ok(Response::OutputModuleSet),
-            OK_PAUSE_CONTEXT_SET => Ok(Response::PauseContextSet),
-            OK_VOLUME_SET => Ok(Response::VolumeSet),
-            OK_SSML_MODE_SET => Ok(Response::SsmlModeSet),
-            // Warning OK_CUR_SET_FIRST == OK_NOTIFICATION_SET == 220. Matching message to make the difference
-            OK_NOTIFICATION_SET => {
-                if status.message == MSG_CURSOR_SET_FIRST {
-                    //OK_CUR_SET_FIRST => Ok(Response::HistoryCurSetFirst)
-                    Ok(Response::HistoryCurSetFirst)
-                } else {
-                    Ok(Response::NotificationSet)
-                }
-            }
-            OK_CUR_SET_LAST => Ok(Response::HistoryCurSetLast),
-            OK_CUR_SET_POS => Ok(Response::HistoryCurSetPos),
-            OK_PITCH_RANGE_SET => Ok(Response::PitchRangeSet),
-            OK_DEBUG_SET => Ok(Response::DebugSet),
-            OK_CUR_MOV_FOR => Ok(Response::HistoryCurMoveFor),
-            OK_CUR_MOV_BACK => Ok(Response::HistoryCurMoveBack),
-            OK_MESSAGE_QUEUED => Ok(Response::MessageQueued),
-            OK_SND_ICON_QUEUED => Ok(Response::SoundIconQueued),
-            OK_MSG_CANCELED => Ok(Response::MessageCanceled),
-            OK_RECEIVING_DATA => Ok(Response::ReceivingData),
-            OK_BYE => Ok(Response::Bye),
-            OK_CLIENTS_LIST_SENT => Ok(Response::HistoryClientListSent(parse_typed_lines::<
-                HistoryClientStatus,
-            >(&lines)?)),
-            OK_MSGS_LIST_SENT => Ok(Response::HistoryMsgsListSent(lines)),
-            OK_LAST_MSG => Ok(Response::HistoryLastMsg(parse_single_value(&lines)?)),
-            OK_CUR_POS_RET => Ok(Response::HistoryCurPosRet(parse_single_value(&lines)?)),
-            OK_TABLE_LIST_SENT => Ok(Response::TableListSent(lines)),
-            OK_CLIENT_ID_SENT => Ok(Response::HistoryClientIdSent(parse_single_integer(&lines)?)),
-            OK_MSG_TEXT_SENT => Ok(Response::MessageTextSent),
-            OK_HELP_SENT => Ok(Response::HelpSent(lines)),
-            OK_VOICES_LIST_SENT => Ok(Response::VoicesListSent(
-                parse_typed_lines::<SynthesisVoice>(&lines)?,
-            )),
-            OK_OUTPUT_MODULES_LIST_SENT => Ok(Response::OutputModulesListSent(lines)),
-            OK_GET => Ok(Response::Get(parse_single_value(&lines)?)),
-            OK_INSIDE_BLOCK => Ok(Response::InsideBlock),
-            OK_OUTSIDE_BLOCK => Ok(Response::OutsideBlock),
-            OK_NOT_IMPLEMENTED => Ok(Response::NotImplemented),
-            EVENT_INDEX_MARK => match lines.len() {
-                0 | 1 | 2 => Err(ClientError::TooFewLines),
-                3 => Ok(Response::EventIndexMark(
-                    parse_event_id(&lines)?,
-                    lines[2].to_owned(),
-                )),
-                _ => Err(ClientError::TooManyLines),
-            },
-            EVENT_BEGIN => Ok(Response::EventBegin(parse_event_id(&lines)?)),
-            EVENT_END => Ok(Response::EventEnd(parse_event_id(&lines)?)),
-            EVENT_CANCELED => Ok(Response::EventCanceled(parse_event_id(&lines)?)),
-            EVENT_PAUSED => Ok(Response::EventPaused(parse_event_id(&lines)?)),
-            EVENT_RESUMED => Ok(Response::EventResumed(parse_event_id(&lines)?)),
-            _ => panic!("error should have been caught earlier"),
-        }
+        let status = self.receive_answer(Some(&mut lines)).await?;
+        type_response(
+            self.lifecycle.answered(),
+            parse_response(status, lines, self.parse_mode)?,
+        )
+    }
+
+    /// Receive the lines and the status of an answer in a vector owned by the caller.
+    ///
+    /// The lines replace the content of the vector. The strings already in the vector are
+    /// reused, so that reusing the same vector doesn't allocate memory once it is large
+    /// enough. Unlike [`AsyncClient::receive_lines`], the status code is not checked.
+    pub async fn receive_into(&mut self, lines: &mut Vec<String>) -> ClientStatus {
+        self.receive_answer(Some(lines)).await
     }
 
     /// Send a request
     pub async fn send(&mut self, request: Request) -> ClientResult<&mut Self> {
+        self.send_ref(RequestRef::from(&request)).await
+    }
+
+    /// Send a request borrowing its data.
+    ///
+    /// It's the same as [`AsyncClient::send`] except that the names, texts and settings don't
+    /// have to be moved or cloned into the request.
+    pub async fn send_ref(&mut self, request: RequestRef<'_>) -> ClientResult<&mut Self> {
+        let name = (&request).into();
+        self.lifecycle.check(name)?;
+        request.validate_scopes()?;
+        if !self.protocol_version.supports(request) {
+            return Err(ClientError::Unsupported(name, self.protocol_version));
+        }
+        self.history_settings.update(request);
+        self.settings.sent(request);
         match request {
-            Request::SetName(client_name) => send_one_line!(
+            RequestRef::SetName(client_name) => send_one_line!(
                 self,
                 "SET self CLIENT_NAME {}:{}:{}",
                 client_name.user,
                 client_name.application,
                 client_name.component
             ),
-            Request::Speak => send_one_line!(self, "SPEAK"),
-            Request::SendLine(line) => send_one_line!(self, &line).map(|_| ()),
-            Request::SendLines(lines) => self.send_lines(&lines).await.map(|_| ()),
-            Request::SpeakChar(ch) => send_one_line!(self, "CHAR {}", ch),
-            Request::SpeakKey(key) => send_one_line!(self, "KEY {}", key),
-            Request::Stop(scope) => send_one_line!(self, "STOP {}", scope),
-            Request::Cancel(scope) => send_one_line!(self, "CANCEL {}", scope),
-            Request::Pause(scope) => send_one_line!(self, "PAUSE {}", scope),
-            Request::Resume(scope) => send_one_line!(self, "RESUME {}", scope),
-            Request::SetPriority(prio) => send_one_line!(self, "SET self PRIORITY {}", prio),
-            Request::SetDebug(value) => send_toggle!(self, "SET all DEBUG {}", value),
-            Request::SetOutputModule(scope, value) => {
+            RequestRef::Speak => send_one_line!(self, "SPEAK"),
+            RequestRef::SendLine(line) => self.write_data(&[line]).await,
+            RequestRef::SendLines(lines) => {
+                let lines = lines.iter().map(String::as_str).collect::<Vec<&str>>();
+                self.write_data(&lines).await
+            }
+            RequestRef::SpeakChar(ch) => send_one_line!(self, "CHAR {}", ch),
+            RequestRef::SpeakKey(key) => send_one_line!(self, "KEY {}", key),
+            RequestRef::Stop(scope) => send_one_line!(self, "STOP {}", scope),
+            RequestRef::Cancel(scope) => send_one_line!(self, "CANCEL {}", scope),
+            RequestRef::Pause(scope) => send_one_line!(self, "PAUSE {}", scope),
+            RequestRef::Resume(scope) => send_one_line!(self, "RESUME {}", scope),
+            RequestRef::SetPriority(prio) => send_one_line!(self, "SET self PRIORITY {}", prio),
+            RequestRef::SetDebug(value) => send_toggle!(self, "SET all DEBUG {}", value),
+            RequestRef::SetOutputModule(scope, value) => {
                 send_one_line!(self, "SET {} OUTPUT_MODULE {}", scope, value)
             }
-            Request::GetOutputModule => send_one_line!(self, "GET OUTPUT_MODULE"),
-            Request::ListOutputModules => send_one_line!(self, "LIST OUTPUT_MODULES"),
-            Request::SetLanguage(scope, lang) => {
+            RequestRef::GetOutputModule => send_one_line!(self, "GET OUTPUT_MODULE"),
+            RequestRef::ListOutputModules => send_one_line!(self, "LIST OUTPUT_MODULES"),
+            RequestRef::SetLanguage(scope, lang) => {
                 send_one_line!(self, "SET {} LANGUAGE {}", scope, lang)
             }
-            Request::GetLanguage => send_one_line!(self, "GET LANGUAGE"),
-            Request::SetSsmlMode(value) => send_toggle!(self, "SET self SSML_MODE {}", value),
-            Request::SetPunctuationMode(scope, mode) => {
+            RequestRef::GetLanguage => send_one_line!(self, "GET LANGUAGE"),
+            RequestRef::SetSsmlMode(value) => send_toggle!(self, "SET self SSML_MODE {}", value),
+            RequestRef::SetPunctuationMode(scope, mode) => {
                 send_one_line!(self, "SET {} PUNCTUATION {}", scope, mode)
             }
-            Request::SetSpelling(scope, value) => {
+            RequestRef::SetSpelling(scope, value) => {
                 send_toggle!(self, "SET {} SPELLING {}", scope, value)
             }
-            Request::SetCapitalLettersRecognitionMode(scope, mode) => {
+            RequestRef::SetCapitalLettersRecognitionMode(scope, mode) => {
                 send_one_line!(self, "SET {} CAP_LET_RECOGN {}", scope, mode)
             }
-            Request::SetVoiceType(scope, value) => {
+            RequestRef::SetTable(scope, name) => {
+                send_one_line!(self, "SET {} TABLE {}", scope, name)
+            }
+            RequestRef::ListTables => send_one_line!(self, "LIST TABLES"),
+            RequestRef::SetVoiceType(scope, value) => {
                 send_one_line!(self, "SET {} VOICE_TYPE {}", scope, value)
             }
-            Request::GetVoiceType => send_one_line!(self, "GET VOICE_TYPE"),
-            Request::ListVoiceTypes => send_one_line!(self, "LIST VOICES"),
-            Request::SetSynthesisVoice(scope, value) => {
+            RequestRef::GetVoiceType => send_one_line!(self, "GET VOICE_TYPE"),
+            RequestRef::ListVoiceTypes => send_one_line!(self, "LIST VOICES"),
+            RequestRef::SetSynthesisVoice(scope, value) => {
                 send_one_line!(self, "SET {} SYNTHESIS_VOICE {}", scope, value)
             }
-            Request::ListSynthesisVoices => send_one_line!(self, "LIST SYNTHESIS_VOICES"),
-            Request::SetRate(scope, value) => send_range!(self, "SET {} RATE {}", scope, value),
-            Request::GetRate => send_one_line!(self, "GET RATE"),
-            Request::SetPitch(scope, value) => send_range!(self, "SET {} PITCH {}", scope, value),
-            Request::GetPitch => send_one_line!(self, "GET PITCH"),
-            Request::SetVolume(scope, value) => {
+            RequestRef::ListSynthesisVoices => send_one_line!(self, "LIST SYNTHESIS_VOICES"),
+            RequestRef::SetRate(scope, value) => send_range!(self, "SET {} RATE {}", scope, value),
+            RequestRef::GetRate => send_one_line!(self, "GET RATE"),
+            RequestRef::SetPitch(scope, value) => {
+                send_range!(self, "SET {} PITCH {}", scope, value)
+            }
+            RequestRef::GetPitch => send_one_line!(self, "GET PITCH"),
+            RequestRef::SetPitchRange(scope, value) => {
+                send_range!(self, "SET {} PITCH_RANGE {}", scope, value)
+            }
+            RequestRef::SetVolume(scope, value) => {
                 send_range!(self, "SET {} VOLUME {}", scope, value)
             }
-            Request::GetVolume => send_one_line!(self, "GET VOLUME"),
-            Request::SetPauseContext(scope, value) => {
+            RequestRef::GetVolume => send_one_line!(self, "GET VOLUME"),
+            RequestRef::SetPauseContext(scope, value) => {
                 send_one_line!(self, "SET {} PAUSE_CONTEXT {}", scope, value)
             }
-            Request::SetHistory(scope, value) => {
+            RequestRef::GetPauseContext => send_one_line!(self, "GET PAUSE_CONTEXT"),
+            RequestRef::SetHistory(scope, value) => {
                 send_toggle!(self, "SET {} HISTORY {}", scope, value)
             }
-            Request::SetNotification(ntype, value) => {
+            RequestRef::SetNotification(ntype, value) => {
                 send_toggle!(self, "SET self NOTIFICATION {} {}", ntype, value)
             }
-            Request::Begin => send_one_line!(self, "BLOCK BEGIN"),
-            Request::End => send_one_line!(self, "BLOCK END"),
-            Request::HistoryGetClients => send_one_line!(self, "HISTORY GET CLIENT_LIST"),
-            Request::HistoryGetClientId => send_one_line!(self, "HISTORY GET CLIENT_ID"),
-            Request::HistoryGetClientMsgs(scope, start, number) => send_one_line!(
+            RequestRef::Begin => send_one_line!(self, "BLOCK BEGIN"),
+            RequestRef::End => send_one_line!(self, "BLOCK END"),
+            RequestRef::HistoryGetClients => send_one_line!(self, "HISTORY GET CLIENT_LIST"),
+            RequestRef::HistoryGetClientId => send_one_line!(self, "HISTORY GET CLIENT_ID"),
+            RequestRef::HistoryGetClientMsgs(scope, start, number) => send_one_line!(
                 self,
                 "HISTORY GET CLIENT_MESSAGES {} {}_{}",
                 scope,
                 start,
                 number
             ),
-            Request::HistoryGetLastMsgId => send_one_line!(self, "HISTORY GET LAST"),
-            Request::HistoryGetMsg(id) => send_one_line!(self, "HISTORY GET MESSAGE {}", id),
-            Request::HistoryCursorGet => send_one_line!(self, "HISTORY CURSOR GET"),
-            Request::HistoryCursorSet(scope, pos) => {
+            RequestRef::HistoryGetLastMsgId => send_one_line!(self, "HISTORY GET LAST"),
+            RequestRef::HistoryGetMsg(id) => send_one_line!(self, "HISTORY GET MESSAGE {}", id),
+            RequestRef::HistoryCursorGet => send_one_line!(self, "HISTORY CURSOR GET"),
+            RequestRef::HistoryCursorSet(scope, pos) => {
                 send_one_line!(self, "HISTORY CURSOR SET {} {}", scope, pos)
             }
-            Request::HistoryCursorMove(direction) => {
+            RequestRef::HistoryCursorMove(direction) => {
                 send_one_line!(self, "HISTORY CURSOR {}", direction)
             }
-            Request::HistorySpeak(id) => send_one_line!(self, "HISTORY SAY {}", id),
-            Request::HistorySort(direction, key) => {
+            RequestRef::HistorySpeak(id) => send_one_line!(self, "HISTORY SAY {}", id),
+            RequestRef::HistorySort(direction, key) => {
                 send_one_line!(self, "HISTORY SORT {} {}", direction, key)
             }
-            Request::HistorySetShortMsgLength(length) => {
+            RequestRef::HistorySetShortMsgLength(length) => {
                 send_one_line!(self, "HISTORY SET SHORT_MESSAGE_LENGTH {}", length)
             }
-            Request::HistorySetMsgTypeOrdering(ordering) => {
+            RequestRef::HistorySetMsgTypeOrdering(ordering) => {
                 send_one_line!(
                     self,
                     "HISTORY SET MESSAGE_TYPE_ORDERING \"{}\"",
@@ -271,11 +548,16 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
                         .join(" ")
                 )
             }
-            Request::HistorySearch(scope, condition) => {
+            RequestRef::HistorySearch(scope, condition) => {
                 send_one_line!(self, "HISTORY SEARCH {} \"{}\"", scope, condition)
             }
-            Request::Quit => send_one_line!(self, "QUIT"),
+            RequestRef::Quit => send_one_line!(self, "QUIT"),
+            // Requests added to the types crate and not implemented yet by this client.
+            _ => Err(ClientError::Unsupported(name, self.protocol_version)),
         }?;
+        self.timings.sent(name);
+        self.stats.sent(name);
+        self.lifecycle.sent(name);
         Ok(self)
     }
 
@@ -352,10 +634,9 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
     pub async fn set_language(
         &mut self,
         scope: ClientScope,
-        value: &str,
+        value: LanguageTag,
     ) -> ClientResult<&mut Self> {
-        self.send(Request::SetLanguage(scope, value.to_string()))
-            .await
+        self.send(Request::SetLanguage(scope, value)).await
     }
 
     /// Get the current language
@@ -396,14 +677,23 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
             .await
     }
 
-    /// Set the voice type (MALE1, FEMALE1, …)
+    /// Set the symbol table, such as a braille or a punctuation table
+    pub async fn set_table(&mut self, scope: ClientScope, name: &str) -> ClientResult<&mut Self> {
+        self.send(Request::SetTable(scope, name.to_string())).await
+    }
+
+    /// List the available tables
+    pub async fn list_tables(&mut self) -> ClientResult<&mut Self> {
+        self.send(Request::ListTables).await
+    }
+
+    /// Set the voice type
     pub async fn set_voice_type(
         &mut self,
         scope: ClientScope,
-        value: &str,
+        value: VoiceType,
     ) -> ClientResult<&mut Self> {
-        self.send(Request::SetVoiceType(scope, value.to_string()))
-            .await
+        self.send(Request::SetVoiceType(scope, value)).await
     }
 
     /// Get the current pre-defined voice
@@ -451,6 +741,17 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
         self.send(Request::GetPitch).await
     }
 
+    /// Set the pitch range of speech. n is an integer value within the range from -100 to 100.
+    ///
+    /// It requires version 0.9 of the protocol.
+    pub async fn set_pitch_range(
+        &mut self,
+        scope: ClientScope,
+        value: i8,
+    ) -> ClientResult<&mut Self> {
+        self.send(Request::SetPitchRange(scope, value)).await
+    }
+
     /// Set the volume of speech. n is an integer value within the range from -100 to 100.
     pub async fn set_volume(&mut self, scope: ClientScope, value: i8) -> ClientResult<&mut Self> {
         self.send(Request::SetVolume(scope, value)).await
@@ -470,6 +771,11 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
         self.send(Request::SetPauseContext(scope, value)).await
     }
 
+    /// Get the number of sentences repeated when a paused text is resumed.
+    pub async fn get_pause_context(&mut self) -> ClientResult<&mut Self> {
+        self.send(Request::GetPauseContext).await
+    }
+
     /// Enable notification events
     pub async fn set_notification(
         &mut self,
@@ -479,6 +785,22 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
         self.send(Request::SetNotification(ntype, value)).await
     }
 
+    /// Enable notification events of a type, or all of them with [`NotificationType::All`].
+    pub async fn enable_notifications(
+        &mut self,
+        ntype: NotificationType,
+    ) -> ClientResult<&mut Self> {
+        self.set_notification(ntype, true).await
+    }
+
+    /// Disable notification events of a type, or all of them with [`NotificationType::All`].
+    pub async fn disable_notifications(
+        &mut self,
+        ntype: NotificationType,
+    ) -> ClientResult<&mut Self> {
+        self.set_notification(ntype, false).await
+    }
+
     /// Open a block
     pub async fn block_begin(&mut self) -> ClientResult<&mut Self> {
         self.send(Request::Begin).await
@@ -604,7 +926,7 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
 
     /// Check status of answer, discard lines.
     pub async fn check_status(&mut self, expected_code: ReturnCode) -> ClientResult<&mut Self> {
-        self.receive_answer(None).await.and_then(|status| {
+        self.receive_reply(None).await.and_then(|status| {
             if status.code == expected_code {
                 Ok(self)
             } else {
@@ -616,7 +938,7 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
     /// Receive lines
     pub async fn receive_lines(&mut self, expected_code: ReturnCode) -> ClientResult<Vec<String>> {
         let mut lines = Vec::new();
-        let status = self.receive_answer(Some(&mut lines)).await?;
+        let status = self.receive_reply(Some(&mut lines)).await?;
         if status.code == expected_code {
             Ok(lines)
         } else {
@@ -632,46 +954,96 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
     }
 
     /// Receive signed 8-bit integer
-    pub async fn receive_i8(&mut self) -> ClientResult<u8> {
+    pub async fn receive_i8(&mut self) -> ClientResult<i8> {
         self.receive_string(OK_GET).await.and_then(|s| {
             s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid signed integer"))
+                .map_err(|err| ClientError::invalid_value("invalid signed 8-bit integer", &s, err))
         })
     }
 
     /// Receive unsigned 8-bit integer
+    #[deprecated(
+        since = "0.15.0",
+        note = "rate, pitch and volume can be negative, use receive_i8"
+    )]
     pub async fn receive_u8(&mut self) -> ClientResult<u8> {
         self.receive_string(OK_GET).await.and_then(|s| {
-            s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid unsigned 8-bit integer"))
+            s.parse().map_err(|err| {
+                ClientError::invalid_value("invalid unsigned 8-bit integer", &s, err)
+            })
+        })
+    }
+
+    /// Receive unsigned 16-bit integer
+    pub async fn receive_u16(&mut self) -> ClientResult<u16> {
+        self.receive_string(OK_GET).await.and_then(|s| {
+            s.parse().map_err(|err| {
+                ClientError::invalid_value("invalid unsigned 16-bit integer", &s, err)
+            })
         })
     }
 
     /// Receive cursor pos
     pub async fn receive_cursor_pos(&mut self) -> ClientResult<u16> {
-        self.receive_string(OK_CUR_POS_RET).await.and_then(|s| {
-            s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid unsigned 16-bit integer"))
-        })
+        self.receive_cursor_position().await.map(|pos| pos.0)
+    }
+
+    /// Receive cursor position
+    pub async fn receive_cursor_position(&mut self) -> ClientResult<CursorPosition> {
+        self.receive_string(OK_CUR_POS_RET)
+            .await
+            .and_then(|s| s.parse())
     }
 
     /// Receive message id
+    ///
+    /// Fails with [`ClientError::TooFewLines`] if the server doesn't send the id.
     pub async fn receive_message_id(&mut self) -> ClientResult<MessageId> {
         let mut lines = Vec::new();
-        match self.receive_answer(Some(&mut lines)).await?.code {
+        match self.receive_reply(Some(&mut lines)).await?.code {
             OK_MESSAGE_QUEUED | OK_LAST_MSG => Ok(parse_single_integer(&lines)?),
             _ => Err(ClientError::invalid_data("not a message id")),
         }
     }
 
+    /// Receive message id if the server sent one.
+    ///
+    /// Some output modules answer `225 OK MESSAGE QUEUED` without the line with the id.
+    /// Unlike [`AsyncClient::receive_message_id`], `None` is returned in this case.
+    pub async fn receive_optional_message_id(&mut self) -> ClientResult<Option<MessageId>> {
+        let mut lines = Vec::new();
+        match self.receive_reply(Some(&mut lines)).await?.code {
+            OK_MESSAGE_QUEUED | OK_LAST_MSG if lines.is_empty() => Ok(None),
+            OK_MESSAGE_QUEUED | OK_LAST_MSG => Ok(Some(parse_single_integer(&lines)?)),
+            _ => Err(ClientError::invalid_data("not a message id")),
+        }
+    }
+
     /// Receive client id
     pub async fn receive_client_id(&mut self) -> ClientResult<ClientId> {
         self.receive_string(OK_CLIENT_ID_SENT).await.and_then(|s| {
             s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid client id"))
+                .map_err(|err| ClientError::invalid_value("invalid client id", &s, err))
         })
     }
 
+    /// Receive the current language
+    pub async fn receive_language(&mut self) -> ClientResult<LanguageTag> {
+        self.receive_string(OK_GET).await.and_then(|s| s.parse())
+    }
+
+    /// Receive the current voice type
+    pub async fn receive_voice_type(&mut self) -> ClientResult<VoiceType> {
+        self.receive_string(OK_GET).await.and_then(|s| s.parse())
+    }
+
+    /// Receive a list of voice types
+    pub async fn receive_voice_types(&mut self) -> ClientResult<Vec<VoiceType>> {
+        self.receive_lines(OK_VOICES_LIST_SENT)
+            .await
+            .and_then(|lines| parse_typed_lines::<VoiceType>(&lines))
+    }
+
     /// Receive a list of synthesis voices
     pub async fn receive_synthesis_voices(&mut self) -> ClientResult<Vec<SynthesisVoice>> {
         self.receive_lines(OK_VOICES_LIST_SENT)
@@ -680,34 +1052,50 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
     }
 
     /// Receive a notification
+    ///
+    /// Events received while waiting for the answer to a request are returned first. They
+    /// are kept in a bounded buffer, see [`AsyncClient::set_event_buffer`].
+    /// Events with a code unknown to this version are skipped.
     pub async fn receive_event(&mut self) -> ClientResult<Event> {
-        let mut lines = Vec::new();
-        self.receive_answer(Some(&mut lines))
-            .await
-            .and_then(|status| {
-                if lines.len() < 2 {
-                    Err(ClientError::unexpected_eof("event truncated"))
-                } else {
-                    let message = &lines[0];
-                    let client = &lines[1];
-                    match status.code {
-                        700 => {
-                            if lines.len() != 3 {
-                                Err(ClientError::unexpected_eof("index markevent truncated"))
-                            } else {
-                                let mark = lines[3].to_owned();
-                                Ok(Event::index_mark(mark, message, client))
-                            }
-                        }
-                        701 => Ok(Event::begin(message, client)),
-                        702 => Ok(Event::end(message, client)),
-                        703 => Ok(Event::cancel(message, client)),
-                        704 => Ok(Event::pause(message, client)),
-                        705 => Ok(Event::resume(message, client)),
-                        _ => Err(ClientError::invalid_data("wrong status code for event")),
-                    }
-                }
-            })
+        if let Some(event) = self.events.pop()? {
+            return Ok(event);
+        }
+        let mut lines = std::mem::take(&mut self.event_lines);
+        let event = self.receive_event_into(&mut lines).await;
+        self.event_lines = lines;
+        event
+    }
+
+    /// Receive a notification with its lines in a vector, skipping the unknown events.
+    async fn receive_event_into(&mut self, lines: &mut Vec<String>) -> ClientResult<Event> {
+        loop {
+            let status = self.receive_answer(Some(lines)).await?;
+            match parse_event(status.code, lines)? {
+                Some(event) => return Ok(event),
+                None => report_unknown_event(
+                    self.unknown_event_handler.as_ref(),
+                    status.code,
+                    lines,
+                    self.label.as_deref(),
+                ),
+            }
+        }
+    }
+
+    /// Turn the client into a stream of notifications.
+    ///
+    /// Events with a code unknown to this version are skipped, as with
+    /// [`AsyncClient::receive_event`]. Other errors are items of the stream, which ends after
+    /// an I/O error.
+    pub fn into_events(self) -> impl Stream<Item = ClientResult<Event>> {
+        futures_lite::stream::unfold(Some(self), |client| async move {
+            let mut client = client?;
+            match client.receive_event().await {
+                Ok(event) => Some((Ok(event), Some(client))),
+                Err(err @ ClientError::Io(_)) => Some((Err(err), None)),
+                Err(err) => Some((Err(err), Some(client))),
+            }
+        })
     }
 
     /// Receive a list of client status from history.
@@ -726,4 +1114,375 @@ impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> AsyncClient<R, W> {
     pub async fn check_receiving_data(&mut self) -> ClientResult<&mut Self> {
         self.check_status(OK_RECEIVING_DATA).await
     }
+
+    /// Replay the requests of a session template and check the responses.
+    ///
+    /// Each answer must have the success code of its request. Events received meanwhile,
+    /// for example once the notifications of the template are enabled, are kept.
+    pub async fn apply_session(&mut self, template: &SessionTemplate) -> ClientResult<&mut Self> {
+        for request in template.requests() {
+            self.send_checked(request).await?;
+        }
+        Ok(self)
+    }
+
+    /// Silence everything immediately.
+    ///
+    /// If the server is waiting for the lines of a message, the message is ended first,
+    /// including when the answer to `SPEAK` is not read yet. Then all the messages are
+    /// canceled and stopped.
+    pub async fn emergency_stop(&mut self) -> ClientResult<&mut Self> {
+        if self.lifecycle.speak_pending() {
+            // Until the answer is read, the requests would be taken as lines of the message.
+            match self.receive_reply(None).await {
+                Ok(_) | Err(ClientError::Ssip(_)) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        if self.lifecycle.state() == ConnectionState::Speaking {
+            self.end_message().await?;
+        }
+        self.cancel(MessageScope::All)
+            .await?
+            .check_status(OK_CANCELED)
+            .await?
+            .stop(MessageScope::All)
+            .await?
+            .check_status(OK_STOPPED)
+            .await
+    }
+
+    /// Stop a message being spoken and check the answer.
+    pub async fn stop_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.stop(MessageScope::from(id))
+            .await?
+            .check_status(OK_STOPPED)
+            .await
+    }
+
+    /// Cancel a message, whether it is spoken or queued and check the answer.
+    pub async fn cancel_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.cancel(MessageScope::from(id))
+            .await?
+            .check_status(OK_CANCELED)
+            .await
+    }
+
+    /// Pause a message and check the answer.
+    pub async fn pause_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.pause(MessageScope::from(id))
+            .await?
+            .check_status(OK_PAUSED)
+            .await
+    }
+
+    /// Resume a paused message and check the answer.
+    pub async fn resume_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.resume(MessageScope::from(id))
+            .await?
+            .check_status(OK_RESUMED)
+            .await
+    }
+
+    /// Speak a message with its own priority, language and voice type.
+    ///
+    /// The language and voice type are overridden with [`AsyncClient::say_with`]. The
+    /// priority is then reset to `text`, since SSIP has no command to read it, even if the
+    /// message is rejected. Return the message id.
+    pub async fn send_message(&mut self, message: &Message) -> ClientResult<MessageId> {
+        self.send_ref(RequestRef::SetPriority(&message.priority))
+            .await?
+            .check_status(OK_PRIORITY_SET)
+            .await?;
+        let msg_id = self.say_with(&message.text, &message.overrides()).await;
+        let reset = if message.priority != Priority::Text {
+            self.reset_priority().await
+        } else {
+            Ok(())
+        };
+        let msg_id = msg_id?;
+        reset?;
+        Ok(msg_id)
+    }
+
+    /// Set the priority back to `text`.
+    async fn reset_priority(&mut self) -> ClientResult<()> {
+        self.set_priority(Priority::Text)
+            .await?
+            .check_status(OK_PRIORITY_SET)
+            .await?;
+        Ok(())
+    }
+
+    /// Cancel the messages of this client and speak a text with priority `important`.
+    ///
+    /// `CANCEL self`, `SET self PRIORITY important` and `SPEAK` are written in a single
+    /// frame before any answer is read, which saves two round trips between the moment the
+    /// current speech is interrupted and the moment the new text is received. The answers
+    /// to the three requests are read even if one of them is an error, and the priority is
+    /// reset to `text` afterwards, whether the message is queued or not. Return the message
+    /// id.
+    pub async fn interrupt_say(&mut self, text: &str) -> ClientResult<MessageId> {
+        for (name, _) in INTERRUPT_BATCH {
+            self.lifecycle.check(name)?;
+        }
+        let lines = INTERRUPT_BATCH.map(|(_, line)| line);
+        self.flush_frame(&lines).await?;
+        for (name, _) in INTERRUPT_BATCH {
+            self.timings.sent(name);
+            self.stats.sent(name);
+            self.lifecycle.sent(name);
+        }
+        let msg_id = match self.receive_interrupt_batch().await {
+            Ok(()) => self.speak_lines(text).await,
+            Err(err) => {
+                if self.lifecycle.state() == ConnectionState::Speaking {
+                    self.end_message().await?;
+                }
+                Err(err)
+            }
+        };
+        let reset = self.reset_priority().await;
+        let msg_id = msg_id?;
+        reset?;
+        Ok(msg_id)
+    }
+
+    /// Read the answers to the requests written by `interrupt_say` and return the first
+    /// error.
+    ///
+    /// All the answers are read, so that none is taken for the answer to a later request.
+    async fn receive_interrupt_batch(&mut self) -> ClientResult<()> {
+        let mut result = Ok(());
+        for code in [OK_CANCELED, OK_PRIORITY_SET, OK_RECEIVING_DATA] {
+            let status = self.check_status(code).await.map(|_| ());
+            if let Err(ClientError::Io(_)) = status {
+                return status;
+            }
+            result = result.and(status);
+        }
+        result
+    }
+
+    /// Send the lines of a message accepted by the server and return its id.
+    async fn speak_lines(&mut self, text: &str) -> ClientResult<MessageId> {
+        self.send_lines(&text_lines(text))
+            .await?
+            .receive_message_id()
+            .await
+    }
+
+    /// End the message the server is waiting for without any line, ignoring its rejection.
+    async fn end_message(&mut self) -> ClientResult<()> {
+        let mut lines = Vec::new();
+        self.send_lines(&[]).await?;
+        match self.receive_reply(Some(&mut lines)).await {
+            Ok(_) | Err(ClientError::Ssip(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Speak a text with settings used for this message only.
+    ///
+    /// The overridden settings are read, set before the message and restored once it is
+    /// queued, or once it is rejected. Events received meanwhile are kept. Return the
+    /// message id.
+    pub async fn say_with(&mut self, text: &str, overrides: &Overrides) -> ClientResult<MessageId> {
+        let mut responses = Vec::new();
+        for request in overrides.queries() {
+            responses.push(self.send(request).await?.receive_response(OK_GET).await?);
+        }
+        let previous = overrides.previous(responses)?;
+        let msg_id = self.speak_overridden(text, overrides).await;
+        let restored = self.apply_overrides(&previous).await;
+        let msg_id = msg_id?;
+        restored?;
+        Ok(msg_id)
+    }
+
+    /// Apply the overrides and speak the text.
+    async fn speak_overridden(
+        &mut self,
+        text: &str,
+        overrides: &Overrides,
+    ) -> ClientResult<MessageId> {
+        self.apply_overrides(overrides).await?;
+        self.speak()
+            .await?
+            .check_receiving_data()
+            .await?
+            .send_lines(&text_lines(text))
+            .await?
+            .receive_message_id()
+            .await
+    }
+
+    /// Speak a text in another encoding than UTF-8, converted before it is sent.
+    ///
+    /// Fail with an [`EncodingError`](crate::encoding::EncodingError) wrapped in an
+    /// I/O error of kind `InvalidData` if the bytes are not valid in the encoding. Return
+    /// the message id.
+    #[cfg(feature = "encoding")]
+    pub async fn say_bytes(
+        &mut self,
+        bytes: &[u8],
+        encoding: &'static crate::encoding::Encoding,
+    ) -> ClientResult<MessageId> {
+        let text = crate::encoding::decode(bytes, encoding)?;
+        self.speak()
+            .await?
+            .check_receiving_data()
+            .await?
+            .send_lines(&text_lines(&text))
+            .await?
+            .receive_message_id()
+            .await
+    }
+
+    /// Speak a long text as several messages.
+    ///
+    /// The text is split with [`split_text`] in pieces of at
+    /// most the size set by [`AsyncClient::set_max_message_bytes`], or sent as a single message
+    /// without limit. Return the ids of the messages.
+    pub async fn say_chunked(&mut self, text: &str) -> ClientResult<Vec<MessageId>> {
+        let pieces = match self.max_message_bytes {
+            Some(max) => split_text(text, max),
+            None => vec![text],
+        };
+        let mut ids = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            let msg_id = self
+                .speak()
+                .await?
+                .check_receiving_data()
+                .await?
+                .send_lines(&text_lines(piece))
+                .await?
+                .receive_message_id()
+                .await?;
+            ids.push(msg_id);
+        }
+        Ok(ids)
+    }
+
+    /// Speak a text that is either SSML or plain text.
+    ///
+    /// SSML, recognized by its root `speak` element, is spoken in SSML mode, enabled for
+    /// this message only if it is off. Plain text is sent as is, or escaped in a `speak`
+    /// element when SSML mode is on. Return the message id.
+    pub async fn speak_auto(&mut self, text: &str) -> ClientResult<MessageId> {
+        let toggle = !self.settings.ssml_mode() && is_ssml(text);
+        if toggle {
+            self.set_ssml_mode(true)
+                .await?
+                .check_status(OK_SSML_MODE_SET)
+                .await?;
+        }
+        let lines = text_lines(&auto_text(text, self.settings.ssml_mode()));
+        let msg_id = self
+            .speak()
+            .await?
+            .check_receiving_data()
+            .await?
+            .send_lines(&lines)
+            .await?
+            .receive_message_id()
+            .await;
+        if toggle {
+            self.set_ssml_mode(false)
+                .await?
+                .check_status(OK_SSML_MODE_SET)
+                .await?;
+        }
+        msg_id
+    }
+
+    /// Check that a synthesis voice actually works with the current output module.
+    ///
+    /// The voice is set for this client and an inaudible message is spoken. Return false if
+    /// the server rejects either of them with an error (3xx or 4xx). The previous synthesis
+    /// voice is restored afterwards if it was set by this client, otherwise the current voice
+    /// type is set again so that the server chooses the voice.
+    pub async fn probe_voice(&mut self, voice: &str) -> ClientResult<bool> {
+        let previous_voice = self.settings.synthesis_voice().map(String::from);
+        let previous_voice_type = match previous_voice {
+            Some(_) => None,
+            None => Some(self.get_voice_type().await?.receive_voice_type().await?),
+        };
+        let accepted = probe_accepted(self.try_voice(voice).await)?;
+        if let Some(voice) = &previous_voice {
+            self.set_synthesis_voice(ClientScope::Current, voice)
+                .await?
+                .check_status(OK_VOICE_SET)
+                .await?;
+        }
+        if let Some(voice_type) = previous_voice_type {
+            self.set_voice_type(ClientScope::Current, voice_type)
+                .await?
+                .check_status(OK_VOICE_SET)
+                .await?;
+        }
+        Ok(accepted)
+    }
+
+    /// Set a synthesis voice and speak the probe text with it.
+    async fn try_voice(&mut self, voice: &str) -> ClientResult<()> {
+        self.set_synthesis_voice(ClientScope::Current, voice)
+            .await?
+            .check_status(OK_VOICE_SET)
+            .await?
+            .speak()
+            .await?
+            .check_receiving_data()
+            .await?
+            .send_line(PROBE_TEXT)
+            .await?
+            .receive_message_id()
+            .await?;
+        Ok(())
+    }
+
+    /// Send the requests of the overrides and check their status.
+    async fn apply_overrides(&mut self, overrides: &Overrides) -> ClientResult<()> {
+        for request in overrides.requests() {
+            self.send_checked(request).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a request and check that it succeeded, keeping the events received meanwhile.
+    async fn send_checked(&mut self, request: Request) -> ClientResult<()> {
+        let expected_code = success_code(&request);
+        self.send(request).await?;
+        match expected_code {
+            Some(code) => self.check_status(code).await.map(|_| ()),
+            None => self.receive_reply(None).await.map(|_| ()),
+        }
+    }
+
+    /// Set the synthesis voice that best matches the language tags in order of preference.
+    ///
+    /// The voices of the current output module are listed and the voice is chosen with
+    /// [`choose_voice`]. Return the voice or `None` if no voice matches.
+    pub async fn set_best_voice(
+        &mut self,
+        scope: ClientScope,
+        preferences: &[&str],
+    ) -> ClientResult<Option<SynthesisVoice>> {
+        let voices = self
+            .list_synthesis_voices()
+            .await?
+            .receive_synthesis_voices()
+            .await?;
+        match choose_voice(&voices, preferences) {
+            Some(voice) => {
+                self.set_synthesis_voice(scope, &voice.name)
+                    .await?
+                    .check_status(OK_VOICE_SET)
+                    .await?;
+                Ok(Some(voice.clone()))
+            }
+            None => Ok(None),
+        }
+    }
 }
@@ -0,0 +1,132 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Events of one connection shared by several tasks.
+
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncWrite};
+use tokio::sync::broadcast;
+
+use crate::tokio::AsyncClient;
+use crate::types::{ClientError, ClientResult, Event};
+
+/// Default number of events kept for the slowest subscriber.
+pub const DEFAULT_BROADCAST_CAPACITY: usize = 64;
+
+/// Send the events received by a tokio client to several subscribers.
+///
+/// Each subscriber receives every event sent after it subscribed. A subscriber that falls
+/// more than `capacity` events behind loses the oldest ones and receives
+/// [`broadcast::error::RecvError::Lagged`]. When the broadcaster is dropped, subscribers
+/// receive [`broadcast::error::RecvError::Closed`].
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{broadcast::EventBroadcaster, fifo, ClientResult};
+/// # async fn run() -> ClientResult<()> {
+/// let mut client = fifo::asynchronous_tokio::Builder::new().build().await?;
+/// let broadcaster = EventBroadcaster::default();
+/// let mut events = broadcaster.subscribe();
+/// tokio::spawn(async move {
+///     while let Ok(event) = events.recv().await {
+///         println!("{:?}", event);
+///     }
+/// });
+/// broadcaster.run(&mut client).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventBroadcaster {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBroadcaster {
+    /// Broadcaster keeping at most `capacity` events for the slowest subscriber.
+    ///
+    /// A capacity of 0 is replaced by 1.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity.max(1)).0,
+        }
+    }
+
+    /// New receiver of the events.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Number of subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Send an event to the current subscribers.
+    ///
+    /// Return the number of subscribers that received it. An event sent when there is no
+    /// subscriber is lost.
+    pub fn send(&self, event: Event) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Receive the events of the client and send them to the subscribers.
+    ///
+    /// Return when the server closes the connection, or with the error of the client. The
+    /// client must not wait for answers to requests at the same time.
+    pub async fn run<R, W>(&self, client: &mut AsyncClient<R, W>) -> ClientResult<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        loop {
+            match client.receive_event().await {
+                Ok(event) => {
+                    self.send(event);
+                }
+                Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(())
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Default for EventBroadcaster {
+    fn default() -> Self {
+        Self::new(DEFAULT_BROADCAST_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tokio::sync::broadcast::error::RecvError;
+
+    use super::EventBroadcaster;
+    use crate::tokio::AsyncClient;
+    use crate::types::Event;
+
+    #[tokio::test]
+    async fn fan_out() {
+        let input = "701-21\r\n701-1\r\n701 BEGIN\r\n702-21\r\n702-1\r\n702 END\r\n";
+        let mut client = AsyncClient::new(input.as_bytes(), tokio::io::sink());
+        let broadcaster = EventBroadcaster::new(4);
+        let mut logger = broadcaster.subscribe();
+        let mut progress = broadcaster.subscribe();
+        assert_eq!(2, broadcaster.subscriber_count());
+        broadcaster.run(&mut client).await.unwrap();
+        for receiver in [&mut logger, &mut progress] {
+            assert_eq!(Event::begin(21, 1), receiver.recv().await.unwrap());
+            assert_eq!(Event::end(21, 1), receiver.recv().await.unwrap());
+        }
+        drop(broadcaster);
+        assert!(matches!(logger.recv().await, Err(RecvError::Closed)));
+    }
+}
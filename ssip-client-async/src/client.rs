@@ -7,21 +7,36 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use std::io::{self, Read, Write};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::{Arc, Mutex};
 
 use crate::constants::*;
+use crate::demux::OverflowPolicy;
+use crate::diagnostics::{Diagnostic, Stats, TimeBudgets, Timings};
+use crate::guard::SpeakGuard;
+use crate::history::HistoryCursor;
 use crate::protocol::{
-    flush_lines, parse_event_id, parse_single_integer, parse_single_value, parse_typed_lines,
-    write_lines,
+    check_no_lines, end_of_stream, flush_line_fmt, flush_lines, frame_len, parse_event,
+    parse_response, parse_single_integer, parse_single_value, parse_typed_lines, probe_accepted,
+    report_unknown_event, split_text, success_code, text_lines, type_response, Decoder, EventQueue,
+    ParseMode, UnknownEventHandler, Utf8Mode, INTERRUPT_BATCH, PROBE_TEXT,
 };
+use crate::sanitize::{auto_text, is_ssml, sanitize_line, TextSanitizer};
+use crate::session::SessionTemplate;
+use crate::state::{Lifecycle, Settings};
 use crate::types::*;
 
 // Trick to have common implementation for std and mio streams..
-#[cfg(all(not(feature = "async-mio"), unix))]
-pub use std::os::unix::io::AsRawFd as Source;
+#[cfg(any(unix, target_os = "wasi"))]
+pub use std::os::fd::AsRawFd as Source;
 
-#[cfg(feature = "async-mio")]
-pub use mio::event::Source;
+/// Bound on streams for targets without file descriptors.
+#[cfg(not(any(unix, target_os = "wasi")))]
+pub trait Source {}
+
+#[cfg(not(any(unix, target_os = "wasi")))]
+impl<T> Source for T {}
 
 /// Convert boolean to ON or OFF
 fn on_off(value: bool) -> &'static str {
@@ -34,10 +49,10 @@ fn on_off(value: bool) -> &'static str {
 
 macro_rules! send_one_line {
     ($self:expr, $fmt:expr, $( $arg:expr ),+) => {
-        flush_lines(&mut $self.output, &[format!($fmt, $( $arg ),+).as_str()])
+        $self.flush_frame_fmt(format_args!($fmt, $( $arg ),+))
     };
     ($self:expr, $fmt:expr) => {
-        flush_lines(&mut $self.output, &[$fmt])
+        $self.flush_frame(&[$fmt])
     }
 }
 
@@ -61,6 +76,25 @@ macro_rules! send_range {
     };
 }
 
+/// Stream that can be duplicated to share a connection.
+pub trait TryClone: Sized {
+    /// Create a new handle on the same underlying socket.
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+#[cfg(unix)]
+impl TryClone for std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        std::os::unix::net::UnixStream::try_clone(self)
+    }
+}
+
+impl TryClone for std::net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        std::net::TcpStream::try_clone(self)
+    }
+}
+
 /// SSIP client on generic stream
 ///
 /// There are two ways to send requests and receive responses:
@@ -69,143 +103,418 @@ macro_rules! send_range {
 pub struct Client<S: Read + Write + Source> {
     input: io::BufReader<S>,
     output: io::BufWriter<S>,
+    /// Lock shared by the clones so that requests are written atomically.
+    write_lock: Arc<Mutex<()>>,
+    parse_mode: ParseMode,
+    utf8_mode: Utf8Mode,
+    unknown_event_handler: Option<UnknownEventHandler>,
+    history_settings: HistorySettings,
+    timings: Timings,
+    lifecycle: Lifecycle,
+    text_sanitizer: Option<TextSanitizer>,
+    label: Option<String>,
+    /// Decoder of the answers, kept to avoid an allocation for each line.
+    decoder: Decoder,
+    /// Answer partially received by [`Client::try_receive`] and the bytes after it.
+    partial: Option<Decoder>,
+    /// Lines of the events received, kept to reuse the vector and its strings.
+    event_lines: Vec<String>,
+    /// Events received while waiting for the answer to a request.
+    events: EventQueue,
+    protocol_version: ProtocolVersion,
+    /// SSML mode and synthesis voice accepted by the server for this client.
+    settings: Settings,
+    last_status: Option<StatusLine>,
+    max_message_bytes: Option<usize>,
+    stats: Stats,
 }
 
 impl<S: Read + Write + Source> Client<S> {
     /// Create a SSIP client on the reader and writer.
     pub(crate) fn new(input: io::BufReader<S>, output: io::BufWriter<S>) -> Self {
         // https://stackoverflow.com/questions/58467659/how-to-store-tcpstream-with-bufreader-and-bufwriter-in-a-data-structure
-        Self { input, output }
+        Self {
+            input,
+            output,
+            write_lock: Arc::new(Mutex::new(())),
+            parse_mode: ParseMode::default(),
+            utf8_mode: Utf8Mode::default(),
+            unknown_event_handler: None,
+            history_settings: HistorySettings::default(),
+            timings: Timings::default(),
+            lifecycle: Lifecycle::default(),
+            text_sanitizer: None,
+            label: None,
+            decoder: Decoder::new(ParseMode::default(), Utf8Mode::default()),
+            partial: None,
+            event_lines: Vec::new(),
+            events: EventQueue::default(),
+            protocol_version: ProtocolVersion::default(),
+            settings: Settings::default(),
+            last_status: None,
+            max_message_bytes: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Set how strictly the answers of the server are parsed. Default is strict.
+    pub fn set_parse_mode(&mut self, mode: ParseMode) -> &mut Self {
+        self.parse_mode = mode;
+        self.decoder.set_parse_mode(mode);
+        self
+    }
+
+    /// Set how the lines received from the server are decoded. Default is strict.
+    ///
+    /// With [`Utf8Mode::Lossy`], a line that is not valid UTF-8 doesn't fail the connection.
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) -> &mut Self {
+        self.utf8_mode = mode.clone();
+        self.decoder.set_utf8_mode(mode);
+        self
+    }
+
+    /// Set the time expected for the answer of each request.
+    pub fn set_time_budgets(&mut self, budgets: TimeBudgets) -> &mut Self {
+        self.timings.set_budgets(budgets);
+        self
+    }
+
+    /// Set the function called with diagnostics such as [`Diagnostic::SlowServer`].
+    ///
+    /// Answers are only timed once a function is set.
+    pub fn set_diagnostics<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&Diagnostic) + Send + Sync + 'static,
+    {
+        self.timings.set_hook(Arc::new(hook));
+        self
+    }
+
+    /// Set the sanitizer that cleans the lines of the messages before they are sent.
+    ///
+    /// It applies to [`Client::send_line`], [`Client::send_lines`] and the methods based on them.
+    pub fn set_text_sanitizer(&mut self, sanitizer: TextSanitizer) -> &mut Self {
+        self.text_sanitizer = Some(sanitizer);
+        self
     }
 
-    #[cfg(all(not(feature = "async-mio"), unix))]
-    /// Input source for asynchronous API based on `poll`.
-    pub(crate) fn input_source(&self) -> &S {
+    /// Set the function called when an event with an unknown code is skipped.
+    ///
+    /// Such events are logged and skipped by [`Client::receive_event`]. They are returned as
+    /// [`Response::UnknownEvent`] by [`Client::receive`].
+    pub fn on_unknown_event<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ReturnCode, &[String]) + Send + Sync + 'static,
+    {
+        self.unknown_event_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Stage of the connection, updated with the requests sent and the answers received.
+    ///
+    /// Requests that are not allowed in the current stage fail with
+    /// [`ClientError::InvalidState`] before anything is written.
+    pub fn state(&self) -> ConnectionState {
+        self.lifecycle.state()
+    }
+
+    /// Set the name of the connection in the logs, such as `SSIP[label](out): SPEAK`.
+    pub fn set_label(&mut self, label: &str) -> &mut Self {
+        self.label = Some(label.to_string());
+        self.decoder.set_label(label);
+        self
+    }
+
+    /// Check that the answer to each request is read before the next request is sent.
+    ///
+    /// A forgotten `check_status` leaves an answer unread, which is then taken as the answer
+    /// to the next request. In this mode, sending a request while an answer is unread panics
+    /// in debug builds and logs a warning in release builds. It must not be enabled when
+    /// several requests are sent before reading the answers on purpose. Default is off.
+    pub fn set_strict_replies(&mut self, enabled: bool) -> &mut Self {
+        self.lifecycle.set_strict_replies(enabled);
+        self
+    }
+
+    /// Keep at most `capacity` events received while waiting for the answer to a request.
+    ///
+    /// When the buffer is full, the policy decides which event is lost. Default is
+    /// [`DEFAULT_EVENT_CAPACITY`](crate::demux::DEFAULT_EVENT_CAPACITY) events, discarding the
+    /// oldest.
+    pub fn set_event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+        self.events.configure(capacity, policy);
+        self
+    }
+
+    /// Number of events discarded or refused because the event buffer was full.
+    pub fn event_overflows(&self) -> u64 {
+        self.events.overflows()
+    }
+
+    /// Name of the connection in the logs, if any.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Set the version of the protocol spoken by the server. Default is the current one.
+    ///
+    /// Requests that don't exist in this version fail with [`ClientError::Unsupported`].
+    pub fn set_protocol_version(&mut self, version: ProtocolVersion) -> &mut Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Version of the protocol spoken by the server.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Maximum size in bytes of the messages spoken by [`Client::say_chunked`].
+    ///
+    /// Longer texts are split in several messages. Default is no limit.
+    pub fn set_max_message_bytes(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_message_bytes = max;
+        self
+    }
+
+    /// Counters of the traffic since the connection or the last reset.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Reset the counters of the traffic.
+    pub fn reset_stats(&mut self) -> &mut Self {
+        self.stats = Stats::default();
+        self
+    }
+
+    /// Status line of the last answer received, successful or not.
+    ///
+    /// Helpers that only return a value, such as `receive_message_id`, drop the message of
+    /// the server. It is kept here. Events are not answers and don't change it.
+    pub fn last_status(&self) -> Option<&StatusLine> {
+        self.last_status.as_ref()
+    }
+
+    /// SSML mode last accepted by the server for this client, off for a new connection.
+    pub fn ssml_mode(&self) -> bool {
+        self.settings.ssml_mode()
+    }
+
+    /// Synthesis voice last accepted by the server for this client, if any.
+    ///
+    /// SSIP has no command to read the synthesis voice. Setting a voice type for the client
+    /// replaces it.
+    pub fn synthesis_voice(&self) -> Option<&str> {
+        self.settings.synthesis_voice()
+    }
+
+    /// History settings requested by this client.
+    pub fn history_settings(&self) -> &HistorySettings {
+        &self.history_settings
+    }
+
+    /// Write lines and flush them while holding the write lock.
+    fn flush_frame(&mut self, lines: &[&str]) -> ClientResult<()> {
+        let _guard = self
+            .write_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        flush_lines(&mut self.output, lines, self.label.as_deref())?;
+        self.stats.bytes_sent += frame_len(lines);
+        Ok(())
+    }
+
+    /// Write a single formatted line and flush it while holding the write lock.
+    fn flush_frame_fmt(&mut self, line: fmt::Arguments) -> ClientResult<()> {
+        let _guard = self
+            .write_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let count = flush_line_fmt(&mut self.output, line, self.label.as_deref())?;
+        self.stats.bytes_sent += count as u64;
+        Ok(())
+    }
+
+    /// Input stream for asynchronous API based on `poll`.
+    pub(crate) fn input_stream(&self) -> &S {
         self.input.get_ref()
     }
 
-    #[cfg(all(not(feature = "async-mio"), unix))]
-    /// Output source for asynchronous API based on `poll`.
-    pub(crate) fn output_source(&self) -> &S {
+    /// Output stream for asynchronous API based on `poll`.
+    pub(crate) fn output_stream(&self) -> &S {
         self.output.get_ref()
     }
 
+    /// Send lines of a message without ending it.
+    ///
+    /// The lines are flushed at once, so that the connection is not idle while the rest of
+    /// a long text is produced. Over TCP, middleboxes may otherwise drop the connection
+    /// before the final dot; the `keepalive` option of the TCP builders also helps. The
+    /// message is ended by [`Client::send_lines`] or [`Client::send_line`].
+    pub fn send_partial_lines(&mut self, lines: &[String]) -> ClientResult<&mut Self> {
+        self.lifecycle.check("SendLines")?;
+        let lines = lines
+            .iter()
+            .map(|line| sanitize_line(self.text_sanitizer.as_ref(), line))
+            .collect::<Vec<_>>();
+        let frame = lines.iter().map(|s| s.as_ref()).collect::<Vec<&str>>();
+        self.flush_frame(&frame)?;
+        Ok(self)
+    }
+
     /// Send lines of text (terminated by a single dot).
     pub fn send_lines(&mut self, lines: &[String]) -> ClientResult<&mut Self> {
-        const END_OF_DATA: [&str; 1] = ["."];
-        write_lines(
-            &mut self.output,
-            lines
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<&str>>()
-                .as_slice(),
-        )?;
-        flush_lines(&mut self.output, &END_OF_DATA)?;
+        const END_OF_DATA: &str = ".";
+        self.lifecycle.check("SendLines")?;
+        let lines = lines
+            .iter()
+            .map(|line| sanitize_line(self.text_sanitizer.as_ref(), line))
+            .collect::<Vec<_>>();
+        let frame = lines
+            .iter()
+            .map(|s| s.as_ref())
+            .chain(std::iter::once(END_OF_DATA))
+            .collect::<Vec<&str>>();
+        self.flush_frame(&frame)?;
+        self.timings.sent("SendLines");
+        self.stats.sent("SendLines");
+        self.lifecycle.sent("SendLines");
         Ok(self)
     }
 
     /// Send one line of text (terminated by a single dot).
     pub fn send_line(&mut self, line: &str) -> ClientResult<&mut Self> {
         const END_OF_DATA: &str = ".";
-        flush_lines(&mut self.output, &[line, END_OF_DATA])?;
+        self.lifecycle.check("SendLine")?;
+        let line = sanitize_line(self.text_sanitizer.as_ref(), line);
+        self.flush_frame(&[&line, END_OF_DATA])?;
+        self.timings.sent("SendLine");
+        self.stats.sent("SendLine");
+        self.lifecycle.sent("SendLine");
         Ok(self)
     }
 
     /// Send a request
     pub fn send(&mut self, request: Request) -> ClientResult<&mut Self> {
+        self.send_ref(RequestRef::from(&request))
+    }
+
+    /// Send a request borrowing its data.
+    ///
+    /// It's the same as [`Client::send`] except that the names, texts and settings don't
+    /// have to be moved or cloned into the request.
+    pub fn send_ref(&mut self, request: RequestRef<'_>) -> ClientResult<&mut Self> {
+        let name = (&request).into();
+        self.lifecycle.check(name)?;
+        request.validate_scopes()?;
+        if !self.protocol_version.supports(request) {
+            return Err(ClientError::Unsupported(name, self.protocol_version));
+        }
+        self.history_settings.update(request);
+        self.settings.sent(request);
         match request {
-            Request::SetName(client_name) => send_one_line!(
+            RequestRef::SetName(client_name) => send_one_line!(
                 self,
                 "SET self CLIENT_NAME {}:{}:{}",
                 client_name.user,
                 client_name.application,
                 client_name.component
             ),
-            Request::Speak => send_one_line!(self, "SPEAK"),
-            Request::SendLine(line) => self.send_line(&line).map(|_| ()),
-            Request::SendLines(lines) => self.send_lines(&lines).map(|_| ()),
-            Request::SpeakChar(ch) => send_one_line!(self, "CHAR {}", ch),
-            Request::SpeakKey(key) => send_one_line!(self, "KEY {}", key),
-            Request::Stop(scope) => send_one_line!(self, "STOP {}", scope),
-            Request::Cancel(scope) => send_one_line!(self, "CANCEL {}", scope),
-            Request::Pause(scope) => send_one_line!(self, "PAUSE {}", scope),
-            Request::Resume(scope) => send_one_line!(self, "RESUME {}", scope),
-            Request::SetPriority(prio) => send_one_line!(self, "SET self PRIORITY {}", prio),
-            Request::SetDebug(value) => send_toggle!(self, "SET all DEBUG {}", value),
-            Request::SetOutputModule(scope, value) => {
+            RequestRef::Speak => send_one_line!(self, "SPEAK"),
+            RequestRef::SendLine(line) => return self.send_line(line),
+            RequestRef::SendLines(lines) => return self.send_lines(lines),
+            RequestRef::SpeakChar(ch) => send_one_line!(self, "CHAR {}", ch),
+            RequestRef::SpeakKey(key) => send_one_line!(self, "KEY {}", key),
+            RequestRef::Stop(scope) => send_one_line!(self, "STOP {}", scope),
+            RequestRef::Cancel(scope) => send_one_line!(self, "CANCEL {}", scope),
+            RequestRef::Pause(scope) => send_one_line!(self, "PAUSE {}", scope),
+            RequestRef::Resume(scope) => send_one_line!(self, "RESUME {}", scope),
+            RequestRef::SetPriority(prio) => send_one_line!(self, "SET self PRIORITY {}", prio),
+            RequestRef::SetDebug(value) => send_toggle!(self, "SET all DEBUG {}", value),
+            RequestRef::SetOutputModule(scope, value) => {
                 send_one_line!(self, "SET {} OUTPUT_MODULE {}", scope, value)
             }
-            Request::GetOutputModule => send_one_line!(self, "GET OUTPUT_MODULE"),
-            Request::ListOutputModules => send_one_line!(self, "LIST OUTPUT_MODULES"),
-            Request::SetLanguage(scope, lang) => {
+            RequestRef::GetOutputModule => send_one_line!(self, "GET OUTPUT_MODULE"),
+            RequestRef::ListOutputModules => send_one_line!(self, "LIST OUTPUT_MODULES"),
+            RequestRef::SetLanguage(scope, lang) => {
                 send_one_line!(self, "SET {} LANGUAGE {}", scope, lang)
             }
-            Request::GetLanguage => send_one_line!(self, "GET LANGUAGE"),
-            Request::SetSsmlMode(value) => send_toggle!(self, "SET self SSML_MODE {}", value),
-            Request::SetPunctuationMode(scope, mode) => {
+            RequestRef::GetLanguage => send_one_line!(self, "GET LANGUAGE"),
+            RequestRef::SetSsmlMode(value) => send_toggle!(self, "SET self SSML_MODE {}", value),
+            RequestRef::SetPunctuationMode(scope, mode) => {
                 send_one_line!(self, "SET {} PUNCTUATION {}", scope, mode)
             }
-            Request::SetSpelling(scope, value) => {
+            RequestRef::SetSpelling(scope, value) => {
                 send_toggle!(self, "SET {} SPELLING {}", scope, value)
             }
-            Request::SetCapitalLettersRecognitionMode(scope, mode) => {
+            RequestRef::SetCapitalLettersRecognitionMode(scope, mode) => {
                 send_one_line!(self, "SET {} CAP_LET_RECOGN {}", scope, mode)
             }
-            Request::SetVoiceType(scope, value) => {
+            RequestRef::SetTable(scope, name) => {
+                send_one_line!(self, "SET {} TABLE {}", scope, name)
+            }
+            RequestRef::ListTables => send_one_line!(self, "LIST TABLES"),
+            RequestRef::SetVoiceType(scope, value) => {
                 send_one_line!(self, "SET {} VOICE_TYPE {}", scope, value)
             }
-            Request::GetVoiceType => send_one_line!(self, "GET VOICE_TYPE"),
-            Request::ListVoiceTypes => send_one_line!(self, "LIST VOICES"),
-            Request::SetSynthesisVoice(scope, value) => {
+            RequestRef::GetVoiceType => send_one_line!(self, "GET VOICE_TYPE"),
+            RequestRef::ListVoiceTypes => send_one_line!(self, "LIST VOICES"),
+            RequestRef::SetSynthesisVoice(scope, value) => {
                 send_one_line!(self, "SET {} SYNTHESIS_VOICE {}", scope, value)
             }
-            Request::ListSynthesisVoices => send_one_line!(self, "LIST SYNTHESIS_VOICES"),
-            Request::SetRate(scope, value) => send_range!(self, "SET {} RATE {}", scope, value),
-            Request::GetRate => send_one_line!(self, "GET RATE"),
-            Request::SetPitch(scope, value) => send_range!(self, "SET {} PITCH {}", scope, value),
-            Request::GetPitch => send_one_line!(self, "GET PITCH"),
-            Request::SetVolume(scope, value) => {
+            RequestRef::ListSynthesisVoices => send_one_line!(self, "LIST SYNTHESIS_VOICES"),
+            RequestRef::SetRate(scope, value) => send_range!(self, "SET {} RATE {}", scope, value),
+            RequestRef::GetRate => send_one_line!(self, "GET RATE"),
+            RequestRef::SetPitch(scope, value) => {
+                send_range!(self, "SET {} PITCH {}", scope, value)
+            }
+            RequestRef::GetPitch => send_one_line!(self, "GET PITCH"),
+            RequestRef::SetPitchRange(scope, value) => {
+                send_range!(self, "SET {} PITCH_RANGE {}", scope, value)
+            }
+            RequestRef::SetVolume(scope, value) => {
                 send_range!(self, "SET {} VOLUME {}", scope, value)
             }
-            Request::GetVolume => send_one_line!(self, "GET VOLUME"),
-            Request::SetPauseContext(scope, value) => {
+            RequestRef::GetVolume => send_one_line!(self, "GET VOLUME"),
+            RequestRef::SetPauseContext(scope, value) => {
                 send_one_line!(self, "SET {} PAUSE_CONTEXT {}", scope, value)
             }
-            Request::SetHistory(scope, value) => {
+            RequestRef::GetPauseContext => send_one_line!(self, "GET PAUSE_CONTEXT"),
+            RequestRef::SetHistory(scope, value) => {
                 send_toggle!(self, "SET {} HISTORY {}", scope, value)
             }
-            Request::SetNotification(ntype, value) => {
+            RequestRef::SetNotification(ntype, value) => {
                 send_toggle!(self, "SET self NOTIFICATION {} {}", ntype, value)
             }
-            Request::Begin => send_one_line!(self, "BLOCK BEGIN"),
-            Request::End => send_one_line!(self, "BLOCK END"),
-            Request::HistoryGetClients => send_one_line!(self, "HISTORY GET CLIENT_LIST"),
-            Request::HistoryGetClientId => send_one_line!(self, "HISTORY GET CLIENT_ID"),
-            Request::HistoryGetClientMsgs(scope, start, number) => send_one_line!(
+            RequestRef::Begin => send_one_line!(self, "BLOCK BEGIN"),
+            RequestRef::End => send_one_line!(self, "BLOCK END"),
+            RequestRef::HistoryGetClients => send_one_line!(self, "HISTORY GET CLIENT_LIST"),
+            RequestRef::HistoryGetClientId => send_one_line!(self, "HISTORY GET CLIENT_ID"),
+            RequestRef::HistoryGetClientMsgs(scope, start, number) => send_one_line!(
                 self,
                 "HISTORY GET CLIENT_MESSAGES {} {}_{}",
                 scope,
                 start,
                 number
             ),
-            Request::HistoryGetLastMsgId => send_one_line!(self, "HISTORY GET LAST"),
-            Request::HistoryGetMsg(id) => send_one_line!(self, "HISTORY GET MESSAGE {}", id),
-            Request::HistoryCursorGet => send_one_line!(self, "HISTORY CURSOR GET"),
-            Request::HistoryCursorSet(scope, pos) => {
+            RequestRef::HistoryGetLastMsgId => send_one_line!(self, "HISTORY GET LAST"),
+            RequestRef::HistoryGetMsg(id) => send_one_line!(self, "HISTORY GET MESSAGE {}", id),
+            RequestRef::HistoryCursorGet => send_one_line!(self, "HISTORY CURSOR GET"),
+            RequestRef::HistoryCursorSet(scope, pos) => {
                 send_one_line!(self, "HISTORY CURSOR SET {} {}", scope, pos)
             }
-            Request::HistoryCursorMove(direction) => {
+            RequestRef::HistoryCursorMove(direction) => {
                 send_one_line!(self, "HISTORY CURSOR {}", direction)
             }
-            Request::HistorySpeak(id) => send_one_line!(self, "HISTORY SAY {}", id),
-            Request::HistorySort(direction, key) => {
+            RequestRef::HistorySpeak(id) => send_one_line!(self, "HISTORY SAY {}", id),
+            RequestRef::HistorySort(direction, key) => {
                 send_one_line!(self, "HISTORY SORT {} {}", direction, key)
             }
-            Request::HistorySetShortMsgLength(length) => {
+            RequestRef::HistorySetShortMsgLength(length) => {
                 send_one_line!(self, "HISTORY SET SHORT_MESSAGE_LENGTH {}", length)
             }
-            Request::HistorySetMsgTypeOrdering(ordering) => {
+            RequestRef::HistorySetMsgTypeOrdering(ordering) => {
                 send_one_line!(
                     self,
                     "HISTORY SET MESSAGE_TYPE_ORDERING \"{}\"",
@@ -216,11 +525,16 @@ impl<S: Read + Write + Source> Client<S> {
                         .join(" ")
                 )
             }
-            Request::HistorySearch(scope, condition) => {
+            RequestRef::HistorySearch(scope, condition) => {
                 send_one_line!(self, "HISTORY SEARCH {} \"{}\"", scope, condition)
             }
-            Request::Quit => send_one_line!(self, "QUIT"),
+            RequestRef::Quit => send_one_line!(self, "QUIT"),
+            // Requests added to the types crate and not implemented yet by this client.
+            _ => Err(ClientError::Unsupported(name, self.protocol_version)),
         }?;
+        self.timings.sent(name);
+        self.stats.sent(name);
+        self.lifecycle.sent(name);
         Ok(self)
     }
 
@@ -294,8 +608,12 @@ impl<S: Read + Write + Source> Client<S> {
     }
 
     /// Set language code
-    pub fn set_language(&mut self, scope: ClientScope, value: &str) -> ClientResult<&mut Self> {
-        self.send(Request::SetLanguage(scope, value.to_string()))
+    pub fn set_language(
+        &mut self,
+        scope: ClientScope,
+        value: LanguageTag,
+    ) -> ClientResult<&mut Self> {
+        self.send(Request::SetLanguage(scope, value))
     }
 
     /// Get the current language
@@ -331,9 +649,23 @@ impl<S: Read + Write + Source> Client<S> {
         self.send(Request::SetCapitalLettersRecognitionMode(scope, mode))
     }
 
-    /// Set the voice type (MALE1, FEMALE1, …)
-    pub fn set_voice_type(&mut self, scope: ClientScope, value: &str) -> ClientResult<&mut Self> {
-        self.send(Request::SetVoiceType(scope, value.to_string()))
+    /// Set the symbol table, such as a braille or a punctuation table
+    pub fn set_table(&mut self, scope: ClientScope, name: &str) -> ClientResult<&mut Self> {
+        self.send(Request::SetTable(scope, name.to_string()))
+    }
+
+    /// List the available tables
+    pub fn list_tables(&mut self) -> ClientResult<&mut Self> {
+        self.send(Request::ListTables)
+    }
+
+    /// Set the voice type
+    pub fn set_voice_type(
+        &mut self,
+        scope: ClientScope,
+        value: VoiceType,
+    ) -> ClientResult<&mut Self> {
+        self.send(Request::SetVoiceType(scope, value))
     }
 
     /// Get the current pre-defined voice
@@ -380,6 +712,13 @@ impl<S: Read + Write + Source> Client<S> {
         self.send(Request::GetPitch)
     }
 
+    /// Set the pitch range of speech. n is an integer value within the range from -100 to 100.
+    ///
+    /// It requires version 0.9 of the protocol.
+    pub fn set_pitch_range(&mut self, scope: ClientScope, value: i8) -> ClientResult<&mut Self> {
+        self.send(Request::SetPitchRange(scope, value))
+    }
+
     /// Set the volume of speech. n is an integer value within the range from -100 to 100.
     pub fn set_volume(&mut self, scope: ClientScope, value: i8) -> ClientResult<&mut Self> {
         self.send(Request::SetVolume(scope, value))
@@ -395,6 +734,11 @@ impl<S: Read + Write + Source> Client<S> {
         self.send(Request::SetPauseContext(scope, value))
     }
 
+    /// Get the number of sentences repeated when a paused text is resumed.
+    pub fn get_pause_context(&mut self) -> ClientResult<&mut Self> {
+        self.send(Request::GetPauseContext)
+    }
+
     /// Enable notification events
     pub fn set_notification(
         &mut self,
@@ -404,6 +748,16 @@ impl<S: Read + Write + Source> Client<S> {
         self.send(Request::SetNotification(ntype, value))
     }
 
+    /// Enable notification events of a type, or all of them with [`NotificationType::All`].
+    pub fn enable_notifications(&mut self, ntype: NotificationType) -> ClientResult<&mut Self> {
+        self.set_notification(ntype, true)
+    }
+
+    /// Disable notification events of a type, or all of them with [`NotificationType::All`].
+    pub fn disable_notifications(&mut self, ntype: NotificationType) -> ClientResult<&mut Self> {
+        self.set_notification(ntype, false)
+    }
+
     /// Open a block
     pub fn block_begin(&mut self) -> ClientResult<&mut Self> {
         self.send(Request::Begin)
@@ -473,6 +827,11 @@ impl<S: Read + Write + Source> Client<S> {
         self.send(Request::HistoryCursorMove(direction))
     }
 
+    /// Cursor to browse the history of the clients in the scope.
+    pub fn history_cursor(&mut self, scope: ClientScope) -> HistoryCursor<'_, S> {
+        HistoryCursor::new(self, scope)
+    }
+
     /// Speak the message from history.
     pub fn history_speak(&mut self, msg_id: MessageId) -> ClientResult<&mut Self> {
         self.send(Request::HistorySpeak(msg_id))
@@ -512,92 +871,175 @@ impl<S: Read + Write + Source> Client<S> {
     }
 
     /// Receive answer from server
-    fn receive_answer(&mut self, lines: &mut Vec<String>) -> ClientStatus {
-        crate::protocol::receive_answer(&mut self.input, Some(lines))
+    fn receive_answer(&mut self, lines: Option<&mut Vec<String>>) -> ClientStatus {
+        let status = if self.partial.is_some() {
+            let mut partial_lines = Vec::new();
+            let lines = lines.unwrap_or(&mut partial_lines);
+            self.decode_answer(lines, true).and_then(|status| {
+                status.ok_or_else(|| ClientError::Io(io::ErrorKind::WouldBlock.into()))
+            })
+        } else {
+            crate::protocol::receive_answer(
+                &mut self.input,
+                &mut self.decoder,
+                &mut self.stats.bytes_received,
+                lines,
+            )
+        };
+        self.answer_received(status)
+    }
+
+    /// Receive an answer if it is complete, without blocking on a nonblocking stream.
+    ///
+    /// The bytes of an incomplete answer are kept until the next call.
+    fn try_receive_answer(&mut self, lines: &mut Vec<String>) -> ClientResult<Option<StatusLine>> {
+        if self.partial.is_none() {
+            self.partial = Some(self.decoder.same_settings());
+        }
+        match self.decode_answer(lines, false).transpose() {
+            Some(status) => self.answer_received(status).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decode the answer from the bytes kept in the decoder and the bytes received.
+    ///
+    /// Without `block`, return `None` instead of failing when the stream would block.
+    fn decode_answer(
+        &mut self,
+        lines: &mut Vec<String>,
+        block: bool,
+    ) -> ClientResult<Option<StatusLine>> {
+        let decoder = match self.partial.as_mut() {
+            Some(decoder) => decoder,
+            None => return Ok(None),
+        };
+        loop {
+            if let Some(status) = decoder.decode() {
+                decoder.take_lines(lines);
+                if decoder.pending() == 0 {
+                    self.partial = None;
+                }
+                return status.map(Some);
+            }
+            let count = match self.input.fill_buf() {
+                Ok([]) => return Err(end_of_stream()),
+                Ok(bytes) => {
+                    decoder.feed(bytes);
+                    bytes.len()
+                }
+                Err(err) if !block && err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(err) => return Err(ClientError::Io(err)),
+            };
+            self.input.consume(count);
+            self.stats.bytes_received += count as u64;
+        }
+    }
+
+    /// Update the state of the client with the answer received.
+    fn answer_received(&mut self, status: ClientStatus) -> ClientStatus {
+        self.timings.received(&status);
+        self.lifecycle.received(&status);
+        self.settings.received(self.lifecycle.answered(), &status);
+        self.stats.received(&status);
+        match &status {
+            Ok(line) | Err(ClientError::Ssip(line)) if !(700..800).contains(&line.code) => {
+                self.last_status = Some(line.clone());
+            }
+            _ => (),
+        }
+        status
+    }
+
+    /// Receive the answer to a request, keeping the events received before it.
+    ///
+    /// The events are returned first by the next calls to receive an event.
+    fn receive_reply(&mut self, lines: Option<&mut Vec<String>>) -> ClientStatus {
+        let mut event_lines = Vec::new();
+        let lines_expected = lines.is_some();
+        let lines = lines.unwrap_or(&mut event_lines);
+        loop {
+            let status = self.receive_answer(Some(lines))?;
+            let is_event = self.events.keep(
+                &status,
+                lines,
+                self.unknown_event_handler.as_ref(),
+                self.label.as_deref(),
+            )?;
+            if !is_event {
+                if !lines_expected {
+                    check_no_lines(lines)?;
+                }
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Receive the response to a request with the expected status, keeping the events.
+    fn receive_response(&mut self, expected_code: ReturnCode) -> ClientResult<Response> {
+        let mut lines = Vec::new();
+        let status = self.receive_reply(Some(&mut lines))?;
+        if status.code != expected_code {
+            return Err(ClientError::UnexpectedStatus(status.code));
+        }
+        type_response(
+            self.lifecycle.answered(),
+            parse_response(status, lines, self.parse_mode)?,
+        )
     }
 
     /// Receive one response.
     pub fn receive(&mut self) -> ClientResult<Response> {
-        const MSG_CURSOR_SET_FIRST: &str = "OK CURSOR SET FIRST";
         let mut lines = Vec::new();
-        let status = self.receive_answer(&mut lines)?;
-        match status.code {
-            OK_LANGUAGE_SET => Ok(Response::LanguageSet),
-            OK_PRIORITY_SET => Ok(Response::PrioritySet),
-            OK_RATE_SET => Ok(Response::RateSet),
-            OK_PITCH_SET => Ok(Response::PitchSet),
-            OK_PUNCTUATION_SET => Ok(Response::PunctuationSet),
-            OK_CAP_LET_RECOGN_SET => Ok(Response::CapLetRecognSet),
-            OK_SPELLING_SET => Ok(Response::SpellingSet),
-            OK_CLIENT_NAME_SET => Ok(Response::ClientNameSet),
-            OK_VOICE_SET => Ok(Response::VoiceSet),
-            OK_STOPPED => Ok(Response::Stopped),
-            OK_PAUSED => Ok(Response::Paused),
-            OK_RESUMED => Ok(Response::Resumed),
-            OK_CANCELED => Ok(Response::Canceled),
-            OK_TABLE_SET => Ok(Response::TableSet),
-            OK_OUTPUT_MODULE_SET => Ok(Response::OutputModuleSet),
-            OK_PAUSE_CONTEXT_SET => Ok(Response::PauseContextSet),
-            OK_VOLUME_SET => Ok(Response::VolumeSet),
-            OK_SSML_MODE_SET => Ok(Response::SsmlModeSet),
-            // Warning OK_CUR_SET_FIRST == OK_NOTIFICATION_SET == 220. Matching message to make the difference
-            OK_NOTIFICATION_SET => {
-                if status.message == MSG_CURSOR_SET_FIRST {
-                    //OK_CUR_SET_FIRST => Ok(Response::HistoryCurSetFirst)
-                    Ok(Response::HistoryCurSetFirst)
-                } else {
-                    Ok(Response::NotificationSet)
-                }
-            }
-            OK_CUR_SET_LAST => Ok(Response::HistoryCurSetLast),
-            OK_CUR_SET_POS => Ok(Response::HistoryCurSetPos),
-            OK_PITCH_RANGE_SET => Ok(Response::PitchRangeSet),
-            OK_DEBUG_SET => Ok(Response::DebugSet),
-            OK_CUR_MOV_FOR => Ok(Response::HistoryCurMoveFor),
-            OK_CUR_MOV_BACK => Ok(Response::HistoryCurMoveBack),
-            OK_MESSAGE_QUEUED => Ok(Response::MessageQueued),
-            OK_SND_ICON_QUEUED => Ok(Response::SoundIconQueued),
-            OK_MSG_CANCELED => Ok(Response::MessageCanceled),
-            OK_RECEIVING_DATA => Ok(Response::ReceivingData),
-            OK_BYE => Ok(Response::Bye),
-            OK_CLIENTS_LIST_SENT => Ok(Response::HistoryClientListSent(parse_typed_lines::<
-                HistoryClientStatus,
-            >(&lines)?)),
-            OK_MSGS_LIST_SENT => Ok(Response::HistoryMsgsListSent(lines)),
-            OK_LAST_MSG => Ok(Response::HistoryLastMsg(parse_single_value(&lines)?)),
-            OK_CUR_POS_RET => Ok(Response::HistoryCurPosRet(parse_single_value(&lines)?)),
-            OK_TABLE_LIST_SENT => Ok(Response::TableListSent(lines)),
-            OK_CLIENT_ID_SENT => Ok(Response::HistoryClientIdSent(parse_single_integer(&lines)?)),
-            OK_MSG_TEXT_SENT => Ok(Response::MessageTextSent),
-            OK_HELP_SENT => Ok(Response::HelpSent(lines)),
-            OK_VOICES_LIST_SENT => Ok(Response::VoicesListSent(
-                parse_typed_lines::<SynthesisVoice>(&lines)?,
-            )),
-            OK_OUTPUT_MODULES_LIST_SENT => Ok(Response::OutputModulesListSent(lines)),
-            OK_GET => Ok(Response::Get(parse_single_value(&lines)?)),
-            OK_INSIDE_BLOCK => Ok(Response::InsideBlock),
-            OK_OUTSIDE_BLOCK => Ok(Response::OutsideBlock),
-            OK_NOT_IMPLEMENTED => Ok(Response::NotImplemented),
-            EVENT_INDEX_MARK => match lines.len() {
-                0..=2 => Err(ClientError::TooFewLines),
-                3 => Ok(Response::EventIndexMark(
-                    parse_event_id(&lines)?,
-                    lines[2].to_owned(),
-                )),
-                _ => Err(ClientError::TooManyLines),
-            },
-            EVENT_BEGIN => Ok(Response::EventBegin(parse_event_id(&lines)?)),
-            EVENT_END => Ok(Response::EventEnd(parse_event_id(&lines)?)),
-            EVENT_CANCELED => Ok(Response::EventCanceled(parse_event_id(&lines)?)),
-            EVENT_PAUSED => Ok(Response::EventPaused(parse_event_id(&lines)?)),
-            EVENT_RESUMED => Ok(Response::EventResumed(parse_event_id(&lines)?)),
-            _ => panic!("error should have been caught earlier"),
+        let status = self.receive_answer(Some(&mut lines))?;
+        type_response(
+            self.lifecycle.answered(),
+            parse_response(status, lines, self.parse_mode)?,
+        )
+    }
+
+    /// Receive the lines and the status of an answer in a vector owned by the caller.
+    ///
+    /// The lines replace the content of the vector. The strings already in the vector are
+    /// reused, so that reusing the same vector doesn't allocate memory once it is large
+    /// enough. Unlike [`Client::receive_lines`], the status code is not checked.
+    ///
+    /// Example
+    /// ```no_run
+    /// use ssip_client_async::{fifo, ClientScope, OK_GET};
+    /// let mut client = fifo::Builder::new().build()?;
+    /// let mut lines = Vec::new();
+    /// for _ in 0..10 {
+    ///     client.get_rate()?;
+    ///     if client.receive_into(&mut lines)?.code == OK_GET {
+    ///         println!("rate: {}", lines[0]);
+    ///     }
+    /// }
+    /// # Ok::<(), ssip_client_async::ClientError>(())
+    /// ```
+    pub fn receive_into(&mut self, lines: &mut Vec<String>) -> ClientStatus {
+        self.receive_answer(Some(lines))
+    }
+
+    /// Receive one response if any, without blocking on a nonblocking stream.
+    ///
+    /// Return `Ok(None)` instead of failing when the answer is not completely received yet.
+    /// The lines already received are kept and the next call continues the answer.
+    pub fn try_receive(&mut self) -> ClientResult<Option<Response>> {
+        let mut lines = Vec::new();
+        match self.try_receive_answer(&mut lines)? {
+            Some(status) => type_response(
+                self.lifecycle.answered(),
+                parse_response(status, lines, self.parse_mode)?,
+            )
+            .map(Some),
+            None => Ok(None),
         }
     }
 
     /// Check status of answer, discard lines.
     pub fn check_status(&mut self, expected_code: ReturnCode) -> ClientResult<&mut Self> {
-        crate::protocol::receive_answer(&mut self.input, None).and_then(|status| {
+        self.receive_reply(None).and_then(|status| {
             if status.code == expected_code {
                 Ok(self)
             } else {
@@ -609,7 +1051,7 @@ impl<S: Read + Write + Source> Client<S> {
     /// Receive lines
     pub fn receive_lines(&mut self, expected_code: ReturnCode) -> ClientResult<Vec<String>> {
         let mut lines = Vec::new();
-        let status = self.receive_answer(&mut lines)?;
+        let status = self.receive_reply(Some(&mut lines))?;
         if status.code == expected_code {
             Ok(lines)
         } else {
@@ -624,79 +1066,163 @@ impl<S: Read + Write + Source> Client<S> {
     }
 
     /// Receive signed 8-bit integer
-    pub fn receive_i8(&mut self) -> ClientResult<u8> {
+    pub fn receive_i8(&mut self) -> ClientResult<i8> {
         self.receive_string(OK_GET).and_then(|s| {
             s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid signed integer"))
+                .map_err(|err| ClientError::invalid_value("invalid signed 8-bit integer", &s, err))
         })
     }
 
     /// Receive unsigned 8-bit integer
+    #[deprecated(
+        since = "0.15.0",
+        note = "rate, pitch and volume can be negative, use receive_i8"
+    )]
     pub fn receive_u8(&mut self) -> ClientResult<u8> {
         self.receive_string(OK_GET).and_then(|s| {
-            s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid unsigned 8-bit integer"))
+            s.parse().map_err(|err| {
+                ClientError::invalid_value("invalid unsigned 8-bit integer", &s, err)
+            })
+        })
+    }
+
+    /// Receive unsigned 16-bit integer
+    pub fn receive_u16(&mut self) -> ClientResult<u16> {
+        self.receive_string(OK_GET).and_then(|s| {
+            s.parse().map_err(|err| {
+                ClientError::invalid_value("invalid unsigned 16-bit integer", &s, err)
+            })
         })
     }
 
     /// Receive cursor pos
     pub fn receive_cursor_pos(&mut self) -> ClientResult<u16> {
-        self.receive_string(OK_CUR_POS_RET).and_then(|s| {
-            s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid unsigned 16-bit integer"))
-        })
+        self.receive_cursor_position().map(|pos| pos.0)
+    }
+
+    /// Receive cursor position
+    pub fn receive_cursor_position(&mut self) -> ClientResult<CursorPosition> {
+        self.receive_string(OK_CUR_POS_RET).and_then(|s| s.parse())
     }
 
     /// Receive message id
+    ///
+    /// Fails with [`ClientError::TooFewLines`] if the server doesn't send the id.
     pub fn receive_message_id(&mut self) -> ClientResult<MessageId> {
         let mut lines = Vec::new();
-        match self.receive_answer(&mut lines)?.code {
+        match self.receive_reply(Some(&mut lines))?.code {
             OK_MESSAGE_QUEUED | OK_LAST_MSG => Ok(parse_single_integer(&lines)?),
             _ => Err(ClientError::invalid_data("not a message id")),
         }
     }
 
+    /// Receive message id if the server sent one.
+    ///
+    /// Some output modules answer `225 OK MESSAGE QUEUED` without the line with the id.
+    /// Unlike [`Client::receive_message_id`], `None` is returned in this case.
+    pub fn receive_optional_message_id(&mut self) -> ClientResult<Option<MessageId>> {
+        let mut lines = Vec::new();
+        match self.receive_reply(Some(&mut lines))?.code {
+            OK_MESSAGE_QUEUED | OK_LAST_MSG if lines.is_empty() => Ok(None),
+            OK_MESSAGE_QUEUED | OK_LAST_MSG => Ok(Some(parse_single_integer(&lines)?)),
+            _ => Err(ClientError::invalid_data("not a message id")),
+        }
+    }
+
     /// Receive client id
     pub fn receive_client_id(&mut self) -> ClientResult<ClientId> {
         self.receive_string(OK_CLIENT_ID_SENT).and_then(|s| {
             s.parse()
-                .map_err(|_| ClientError::invalid_data("invalid client id"))
+                .map_err(|err| ClientError::invalid_value("invalid client id", &s, err))
         })
     }
 
+    /// Receive the current language
+    pub fn receive_language(&mut self) -> ClientResult<LanguageTag> {
+        self.receive_string(OK_GET).and_then(|s| s.parse())
+    }
+
+    /// Receive the current voice type
+    pub fn receive_voice_type(&mut self) -> ClientResult<VoiceType> {
+        self.receive_string(OK_GET).and_then(|s| s.parse())
+    }
+
+    /// Receive a list of voice types
+    pub fn receive_voice_types(&mut self) -> ClientResult<Vec<VoiceType>> {
+        self.receive_lines(OK_VOICES_LIST_SENT)
+            .and_then(|lines| parse_typed_lines::<VoiceType>(&lines))
+    }
+
     /// Receive a list of synthesis voices
     pub fn receive_synthesis_voices(&mut self) -> ClientResult<Vec<SynthesisVoice>> {
         self.receive_lines(OK_VOICES_LIST_SENT)
             .and_then(|lines| parse_typed_lines::<SynthesisVoice>(&lines))
     }
 
+    /// Receive a notification if any, without blocking on a nonblocking stream.
+    ///
+    /// Like [`Client::try_receive`], return `Ok(None)` when nothing was received yet.
+    pub fn try_receive_event(&mut self) -> ClientResult<Option<Event>> {
+        if let Some(event) = self.events.pop()? {
+            return Ok(Some(event));
+        }
+        let mut lines = std::mem::take(&mut self.event_lines);
+        let event = loop {
+            match self.try_receive_answer(&mut lines) {
+                Ok(Some(status)) => match self.known_event(&status, &mut lines) {
+                    Ok(None) => continue,
+                    result => break result,
+                },
+                Ok(None) => break Ok(None),
+                Err(err) => break Err(err),
+            }
+        };
+        self.event_lines = lines;
+        event
+    }
+
     /// Receive a notification
+    ///
+    /// Events received while waiting for the answer to a request are returned first. They
+    /// are kept in a bounded buffer, see [`Client::set_event_buffer`].
+    /// Events with a code unknown to this version are skipped.
     pub fn receive_event(&mut self) -> ClientResult<Event> {
-        let mut lines = Vec::new();
-        crate::protocol::receive_answer(&mut self.input, Some(&mut lines)).and_then(|status| {
-            if lines.len() < 2 {
-                Err(ClientError::unexpected_eof("event truncated"))
-            } else {
-                let message = &lines[0];
-                let client = &lines[1];
-                match status.code {
-                    700 => {
-                        if lines.len() != 3 {
-                            Err(ClientError::unexpected_eof("index markevent truncated"))
-                        } else {
-                            let mark = lines[3].to_owned();
-                            Ok(Event::index_mark(mark, message, client))
-                        }
-                    }
-                    701 => Ok(Event::begin(message, client)),
-                    702 => Ok(Event::end(message, client)),
-                    703 => Ok(Event::cancel(message, client)),
-                    704 => Ok(Event::pause(message, client)),
-                    705 => Ok(Event::resume(message, client)),
-                    _ => Err(ClientError::invalid_data("wrong status code for event")),
-                }
+        if let Some(event) = self.events.pop()? {
+            return Ok(event);
+        }
+        loop {
+            if let Some(event) = self.receive_known_event()? {
+                return Ok(event);
             }
-        })
+        }
+    }
+
+    /// Receive a notification, or report it and return `None` if its code is unknown.
+    fn receive_known_event(&mut self) -> ClientResult<Option<Event>> {
+        let mut lines = std::mem::take(&mut self.event_lines);
+        let event = self
+            .receive_answer(Some(&mut lines))
+            .and_then(|status| self.known_event(&status, &mut lines));
+        self.event_lines = lines;
+        event
+    }
+
+    /// Parse a notification, or report it and return `None` if its code is unknown.
+    fn known_event(
+        &self,
+        status: &StatusLine,
+        lines: &mut [String],
+    ) -> ClientResult<Option<Event>> {
+        let event = parse_event(status.code, lines)?;
+        if event.is_none() {
+            report_unknown_event(
+                self.unknown_event_handler.as_ref(),
+                status.code,
+                lines,
+                self.label.as_deref(),
+            );
+        }
+        Ok(event)
     }
 
     /// Receive a list of client status from history.
@@ -715,8 +1241,324 @@ impl<S: Read + Write + Source> Client<S> {
         self.check_status(OK_RECEIVING_DATA)
     }
 
+    /// Replay the requests of a session template and check the responses.
+    ///
+    /// Each answer must have the success code of its request. Events received meanwhile,
+    /// for example once the notifications of the template are enabled, are kept.
+    pub fn apply_session(&mut self, template: &SessionTemplate) -> ClientResult<&mut Self> {
+        for request in template.requests() {
+            self.send_checked(request)?;
+        }
+        Ok(self)
+    }
+
+    /// Silence everything immediately.
+    ///
+    /// If the server is waiting for the lines of a message, the message is ended first,
+    /// including when the answer to `SPEAK` is not read yet. Then all the messages are
+    /// canceled and stopped.
+    pub fn emergency_stop(&mut self) -> ClientResult<&mut Self> {
+        if self.lifecycle.speak_pending() {
+            // Until the answer is read, the requests would be taken as lines of the message.
+            match self.receive_reply(None) {
+                Ok(_) | Err(ClientError::Ssip(_)) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        if self.lifecycle.state() == ConnectionState::Speaking {
+            self.end_message()?;
+        }
+        self.cancel(MessageScope::All)?
+            .check_status(OK_CANCELED)?
+            .stop(MessageScope::All)?
+            .check_status(OK_STOPPED)
+    }
+
+    /// Stop a message being spoken and check the answer.
+    pub fn stop_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.stop(MessageScope::from(id))?.check_status(OK_STOPPED)
+    }
+
+    /// Cancel a message, whether it is spoken or queued and check the answer.
+    pub fn cancel_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.cancel(MessageScope::from(id))?
+            .check_status(OK_CANCELED)
+    }
+
+    /// Pause a message and check the answer.
+    pub fn pause_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.pause(MessageScope::from(id))?.check_status(OK_PAUSED)
+    }
+
+    /// Resume a paused message and check the answer.
+    pub fn resume_message(&mut self, id: MessageId) -> ClientResult<&mut Self> {
+        self.resume(MessageScope::from(id))?
+            .check_status(OK_RESUMED)
+    }
+
+    /// Speak a message with its own priority, language and voice type.
+    ///
+    /// The language and voice type are overridden with [`Client::say_with`]. The priority is
+    /// then reset to `text`, since SSIP has no command to read it, even if the message is
+    /// rejected. Return the message id.
+    pub fn send_message(&mut self, message: &Message) -> ClientResult<MessageId> {
+        self.send_ref(RequestRef::SetPriority(&message.priority))?
+            .check_status(OK_PRIORITY_SET)?;
+        let msg_id = self.say_with(&message.text, &message.overrides());
+        let reset = if message.priority != Priority::Text {
+            self.reset_priority()
+        } else {
+            Ok(())
+        };
+        let msg_id = msg_id?;
+        reset?;
+        Ok(msg_id)
+    }
+
+    /// Set the priority back to `text`.
+    fn reset_priority(&mut self) -> ClientResult<()> {
+        self.set_priority(Priority::Text)?
+            .check_status(OK_PRIORITY_SET)?;
+        Ok(())
+    }
+
+    /// Cancel the messages of this client and speak a text with priority `important`.
+    ///
+    /// `CANCEL self`, `SET self PRIORITY important` and `SPEAK` are written in a single
+    /// frame before any answer is read, which saves two round trips between the moment the
+    /// current speech is interrupted and the moment the new text is received. The answers
+    /// to the three requests are read even if one of them is an error, and the priority is
+    /// reset to `text` afterwards, whether the message is queued or not. Return the message
+    /// id.
+    pub fn interrupt_say(&mut self, text: &str) -> ClientResult<MessageId> {
+        for (name, _) in INTERRUPT_BATCH {
+            self.lifecycle.check(name)?;
+        }
+        let lines = INTERRUPT_BATCH.map(|(_, line)| line);
+        self.flush_frame(&lines)?;
+        for (name, _) in INTERRUPT_BATCH {
+            self.timings.sent(name);
+            self.stats.sent(name);
+            self.lifecycle.sent(name);
+        }
+        let msg_id = match self.receive_interrupt_batch() {
+            Ok(()) => self.speak_lines(text),
+            Err(err) => {
+                if self.lifecycle.state() == ConnectionState::Speaking {
+                    self.end_message()?;
+                }
+                Err(err)
+            }
+        };
+        let reset = self.reset_priority();
+        let msg_id = msg_id?;
+        reset?;
+        Ok(msg_id)
+    }
+
+    /// Read the answers to the requests written by `interrupt_say` and return the first
+    /// error.
+    ///
+    /// All the answers are read, so that none is taken for the answer to a later request.
+    fn receive_interrupt_batch(&mut self) -> ClientResult<()> {
+        let mut result = Ok(());
+        for code in [OK_CANCELED, OK_PRIORITY_SET, OK_RECEIVING_DATA] {
+            let status = self.check_status(code).map(|_| ());
+            if let Err(ClientError::Io(_)) = status {
+                return status;
+            }
+            result = result.and(status);
+        }
+        result
+    }
+
+    /// Send the lines of a message accepted by the server and return its id.
+    fn speak_lines(&mut self, text: &str) -> ClientResult<MessageId> {
+        self.send_lines(&text_lines(text))?.receive_message_id()
+    }
+
+    /// End the message the server is waiting for without any line, ignoring its rejection.
+    fn end_message(&mut self) -> ClientResult<()> {
+        let mut lines = Vec::new();
+        self.send_lines(&[])?;
+        match self.receive_reply(Some(&mut lines)) {
+            Ok(_) | Err(ClientError::Ssip(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Speak a text with settings used for this message only.
+    ///
+    /// The overridden settings are read, set before the message and restored once it is
+    /// queued, or once it is rejected. Events received meanwhile are kept. Return the
+    /// message id.
+    pub fn say_with(&mut self, text: &str, overrides: &Overrides) -> ClientResult<MessageId> {
+        let mut responses = Vec::new();
+        for request in overrides.queries() {
+            responses.push(self.send(request)?.receive_response(OK_GET)?);
+        }
+        let previous = overrides.previous(responses)?;
+        let msg_id = self.apply_overrides(overrides).and_then(|()| {
+            self.speak()?
+                .check_receiving_data()?
+                .send_lines(&text_lines(text))?
+                .receive_message_id()
+        });
+        let restored = self.apply_overrides(&previous);
+        let msg_id = msg_id?;
+        restored?;
+        Ok(msg_id)
+    }
+
+    /// Speak a text in another encoding than UTF-8, converted before it is sent.
+    ///
+    /// Fail with an [`EncodingError`](crate::encoding::EncodingError) wrapped in an
+    /// I/O error of kind `InvalidData` if the bytes are not valid in the encoding. Return
+    /// the message id.
+    #[cfg(feature = "encoding")]
+    pub fn say_bytes(
+        &mut self,
+        bytes: &[u8],
+        encoding: &'static crate::encoding::Encoding,
+    ) -> ClientResult<MessageId> {
+        let text = crate::encoding::decode(bytes, encoding)?;
+        self.speak()?
+            .check_receiving_data()?
+            .send_lines(&text_lines(&text))?
+            .receive_message_id()
+    }
+
+    /// Speak a long text as several messages.
+    ///
+    /// The text is split with [`split_text`] in pieces of at
+    /// most the size set by [`Client::set_max_message_bytes`], or sent as a single message
+    /// without limit. Return the ids of the messages.
+    pub fn say_chunked(&mut self, text: &str) -> ClientResult<Vec<MessageId>> {
+        let pieces = match self.max_message_bytes {
+            Some(max) => split_text(text, max),
+            None => vec![text],
+        };
+        let mut ids = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            let msg_id = self
+                .speak()?
+                .check_receiving_data()?
+                .send_lines(&text_lines(piece))?
+                .receive_message_id()?;
+            ids.push(msg_id);
+        }
+        Ok(ids)
+    }
+
+    /// Speak a text that is canceled if the guard is dropped before the message ends.
+    pub fn say_guarded(&mut self, text: &str) -> ClientResult<SpeakGuard<'_, S>> {
+        let msg_id = self
+            .speak()?
+            .check_receiving_data()?
+            .send_lines(&text_lines(text))?
+            .receive_message_id()?;
+        Ok(SpeakGuard::new(self, msg_id))
+    }
+
+    /// Speak a text that is either SSML or plain text.
+    ///
+    /// SSML, recognized by its root `speak` element, is spoken in SSML mode, enabled for
+    /// this message only if it is off. Plain text is sent as is, or escaped in a `speak`
+    /// element when SSML mode is on. Return the message id.
+    pub fn speak_auto(&mut self, text: &str) -> ClientResult<MessageId> {
+        let toggle = !self.settings.ssml_mode() && is_ssml(text);
+        if toggle {
+            self.set_ssml_mode(true)?.check_status(OK_SSML_MODE_SET)?;
+        }
+        let lines = text_lines(&auto_text(text, self.settings.ssml_mode()));
+        let msg_id = self
+            .speak()?
+            .check_receiving_data()?
+            .send_lines(&lines)?
+            .receive_message_id();
+        if toggle {
+            self.set_ssml_mode(false)?.check_status(OK_SSML_MODE_SET)?;
+        }
+        msg_id
+    }
+
+    /// Check that a synthesis voice actually works with the current output module.
+    ///
+    /// The voice is set for this client and an inaudible message is spoken. Return false if
+    /// the server rejects either of them with an error (3xx or 4xx). The previous synthesis
+    /// voice is restored afterwards if it was set by this client, otherwise the current voice
+    /// type is set again so that the server chooses the voice.
+    pub fn probe_voice(&mut self, voice: &str) -> ClientResult<bool> {
+        let previous_voice = self.settings.synthesis_voice().map(String::from);
+        let previous_voice_type = match previous_voice {
+            Some(_) => None,
+            None => Some(self.get_voice_type()?.receive_voice_type()?),
+        };
+        let accepted = probe_accepted(self.try_voice(voice))?;
+        if let Some(voice) = &previous_voice {
+            self.set_synthesis_voice(ClientScope::Current, voice)?
+                .check_status(OK_VOICE_SET)?;
+        }
+        if let Some(voice_type) = previous_voice_type {
+            self.set_voice_type(ClientScope::Current, voice_type)?
+                .check_status(OK_VOICE_SET)?;
+        }
+        Ok(accepted)
+    }
+
+    /// Set a synthesis voice and speak the probe text with it.
+    fn try_voice(&mut self, voice: &str) -> ClientResult<()> {
+        self.set_synthesis_voice(ClientScope::Current, voice)?
+            .check_status(OK_VOICE_SET)?
+            .speak()?
+            .check_receiving_data()?
+            .send_line(PROBE_TEXT)?
+            .receive_message_id()?;
+        Ok(())
+    }
+
+    /// Send the requests of the overrides and check their status.
+    fn apply_overrides(&mut self, overrides: &Overrides) -> ClientResult<()> {
+        for request in overrides.requests() {
+            self.send_checked(request)?;
+        }
+        Ok(())
+    }
+
+    /// Send a request and check that it succeeded, keeping the events received meanwhile.
+    fn send_checked(&mut self, request: Request) -> ClientResult<()> {
+        let expected_code = success_code(&request);
+        self.send(request)?;
+        match expected_code {
+            Some(code) => self.check_status(code).map(|_| ()),
+            None => self.receive_reply(None).map(|_| ()),
+        }
+    }
+
+    /// Set the synthesis voice that best matches the language tags in order of preference.
+    ///
+    /// The voices of the current output module are listed and the voice is chosen with
+    /// [`choose_voice`]. Return the voice or `None` if no voice matches.
+    pub fn set_best_voice(
+        &mut self,
+        scope: ClientScope,
+        preferences: &[&str],
+    ) -> ClientResult<Option<SynthesisVoice>> {
+        let voices = self.list_synthesis_voices()?.receive_synthesis_voices()?;
+        match choose_voice(&voices, preferences) {
+            Some(voice) => {
+                self.set_synthesis_voice(scope, &voice.name)?
+                    .check_status(OK_VOICE_SET)?;
+                Ok(Some(voice.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "async-mio")]
+impl<S: Read + Write + Source + mio::event::Source> Client<S> {
     /// Register the socket for polling.
-    #[cfg(feature = "async-mio")]
     pub fn register(
         &mut self,
         poll: &mio::Poll,
@@ -730,3 +1572,39 @@ impl<S: Read + Write + Source> Client<S> {
         Ok(())
     }
 }
+
+impl<S: Read + Write + Source + TryClone> Client<S> {
+    /// Create an independent handle on the same connection.
+    ///
+    /// The new client has its own buffers. Requests are written atomically, even when both
+    /// handles are used from different threads. However the connection is shared: responses
+    /// are received by the handle that reads first, whatever handle sent the request. It is
+    /// typically used to have one thread waiting for events while others only send requests
+    /// that don't expect an answer, or when access to the clients is otherwise synchronized.
+    /// Data buffered but not yet read by the original client is not visible to the clone.
+    /// Answers are only timed against the requests sent by the same handle.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            input: io::BufReader::new(self.input.get_ref().try_clone()?),
+            output: io::BufWriter::new(self.output.get_ref().try_clone()?),
+            write_lock: Arc::clone(&self.write_lock),
+            parse_mode: self.parse_mode,
+            utf8_mode: self.utf8_mode.clone(),
+            unknown_event_handler: self.unknown_event_handler.clone(),
+            history_settings: self.history_settings.clone(),
+            timings: self.timings.same_settings(),
+            lifecycle: self.lifecycle.same_state(),
+            text_sanitizer: self.text_sanitizer.clone(),
+            label: self.label.clone(),
+            decoder: self.decoder.same_settings(),
+            partial: None,
+            event_lines: Vec::new(),
+            events: self.events.same_settings(),
+            protocol_version: self.protocol_version,
+            settings: self.settings.same_values(),
+            last_status: None,
+            max_message_bytes: self.max_message_bytes,
+            stats: Stats::default(),
+        })
+    }
+}
@@ -0,0 +1,338 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Functions with the names and semantics of `libspeechd`, the C client library.
+//!
+//! They ease porting code written against the C library or its Python bindings. Each
+//! function sends the request and checks the answer, like its C counterpart. Failures are
+//! returned as errors instead of `-1`. Settings apply to the connection (`SET self`) unless
+//! the name of the function ends with `_all`.
+//!
+//! Example
+//! ```no_run
+//! use ssip_client_async::compat::speechd::*;
+//! use ssip_client_async::Priority;
+//! let mut connection = spd_open("hello", None, None)?;
+//! spd_set_voice_rate(&mut connection, 20)?;
+//! let id = spd_say(&mut connection, Priority::Text, "hello")?;
+//! spd_close(connection);
+//! # Ok::<(), ssip_client_async::ClientError>(())
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::{
+    client::{Client, Source},
+    constants::*,
+    protocol::text_lines,
+    types::*,
+};
+
+#[cfg(unix)]
+use crate::fifo::{self, sync::UnixStream};
+
+/// Connection returned by [`spd_open`].
+#[cfg(unix)]
+pub type SpdConnection = Client<UnixStream>;
+
+/// Kind of text sent by [`spd_say`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpdDataMode {
+    Text,
+    Ssml,
+}
+
+/// Open a connection on the default socket and set the client name.
+///
+/// The name is `user:client_name:connection_name`. The connection name defaults to
+/// `main` and the user to the one of the process.
+#[cfg(unix)]
+pub fn spd_open(
+    client_name: &str,
+    connection_name: Option<&str>,
+    user_name: Option<&str>,
+) -> ClientResult<SpdConnection> {
+    let default_name = ClientName::from_env();
+    let name = ClientName::with_component(
+        user_name.unwrap_or(&default_name.user),
+        client_name,
+        connection_name.unwrap_or(&default_name.component),
+    )?;
+    let mut connection = fifo::sync::Builder::new().build()?;
+    connection.set_client_name(name)?.check_client_name_set()?;
+    Ok(connection)
+}
+
+/// Close the connection.
+pub fn spd_close<S: Read + Write + Source>(mut connection: Client<S>) {
+    let _ = connection
+        .quit()
+        .and_then(|connection| connection.check_status(OK_BYE));
+}
+
+/// Speak a text with a priority and return the message id.
+pub fn spd_say<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+    priority: Priority,
+    text: &str,
+) -> ClientResult<MessageId> {
+    connection
+        .set_priority(priority)?
+        .check_status(OK_PRIORITY_SET)?
+        .speak()?
+        .check_receiving_data()?
+        .send_lines(&text_lines(text))?
+        .receive_message_id()
+}
+
+/// Speak a character with a priority.
+pub fn spd_char<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+    priority: Priority,
+    character: char,
+) -> ClientResult<()> {
+    connection
+        .set_priority(priority)?
+        .check_status(OK_PRIORITY_SET)?
+        .speak_char(character)?
+        .receive_optional_message_id()
+        .map(|_| ())
+}
+
+/// Speak a key with a priority.
+pub fn spd_key<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+    priority: Priority,
+    key_name: KeyName,
+) -> ClientResult<()> {
+    connection
+        .set_priority(priority)?
+        .check_status(OK_PRIORITY_SET)?
+        .speak_key(key_name)?
+        .receive_optional_message_id()
+        .map(|_| ())
+}
+
+macro_rules! spd_control {
+    ($name:ident, $name_all:ident, $name_uid:ident, $method:ident, $code:expr, $doc:expr) => {
+        #[doc = concat!($doc, " the last message of the connection.")]
+        pub fn $name<S: Read + Write + Source>(connection: &mut Client<S>) -> ClientResult<()> {
+            connection
+                .$method(MessageScope::Last)?
+                .check_status($code)
+                .map(|_| ())
+        }
+
+        #[doc = concat!($doc, " the messages of all the clients.")]
+        pub fn $name_all<S: Read + Write + Source>(connection: &mut Client<S>) -> ClientResult<()> {
+            connection
+                .$method(MessageScope::All)?
+                .check_status($code)
+                .map(|_| ())
+        }
+
+        #[doc = concat!($doc, " a message.")]
+        pub fn $name_uid<S: Read + Write + Source>(
+            connection: &mut Client<S>,
+            id: MessageId,
+        ) -> ClientResult<()> {
+            connection
+                .$method(MessageScope::Message(id))?
+                .check_status($code)
+                .map(|_| ())
+        }
+    };
+}
+
+spd_control!(
+    spd_stop,
+    spd_stop_all,
+    spd_stop_uid,
+    stop,
+    OK_STOPPED,
+    "Stop"
+);
+spd_control!(
+    spd_cancel,
+    spd_cancel_all,
+    spd_cancel_uid,
+    cancel,
+    OK_CANCELED,
+    "Cancel"
+);
+spd_control!(
+    spd_pause,
+    spd_pause_all,
+    spd_pause_uid,
+    pause,
+    OK_PAUSED,
+    "Pause"
+);
+spd_control!(
+    spd_resume,
+    spd_resume_all,
+    spd_resume_uid,
+    resume,
+    OK_RESUMED,
+    "Resume"
+);
+
+macro_rules! spd_set {
+    ($name:ident, $name_all:ident, $method:ident, $type:ty, $code:expr, $doc:expr) => {
+        #[doc = concat!("Set the ", $doc, " of the connection.")]
+        pub fn $name<S: Read + Write + Source>(
+            connection: &mut Client<S>,
+            value: $type,
+        ) -> ClientResult<()> {
+            connection
+                .$method(ClientScope::Current, value)?
+                .check_status($code)
+                .map(|_| ())
+        }
+
+        #[doc = concat!("Set the ", $doc, " of all the clients.")]
+        pub fn $name_all<S: Read + Write + Source>(
+            connection: &mut Client<S>,
+            value: $type,
+        ) -> ClientResult<()> {
+            connection
+                .$method(ClientScope::All, value)?
+                .check_status($code)
+                .map(|_| ())
+        }
+    };
+}
+
+spd_set!(
+    spd_set_voice_rate,
+    spd_set_voice_rate_all,
+    set_rate,
+    i8,
+    OK_RATE_SET,
+    "rate, from -100 to 100"
+);
+spd_set!(
+    spd_set_voice_pitch,
+    spd_set_voice_pitch_all,
+    set_pitch,
+    i8,
+    OK_PITCH_SET,
+    "pitch, from -100 to 100"
+);
+spd_set!(
+    spd_set_volume,
+    spd_set_volume_all,
+    set_volume,
+    i8,
+    OK_VOLUME_SET,
+    "volume, from -100 to 100"
+);
+spd_set!(
+    spd_set_language,
+    spd_set_language_all,
+    set_language,
+    LanguageTag,
+    OK_LANGUAGE_SET,
+    "language"
+);
+spd_set!(
+    spd_set_output_module,
+    spd_set_output_module_all,
+    set_output_module,
+    &str,
+    OK_OUTPUT_MODULE_SET,
+    "output module"
+);
+spd_set!(
+    spd_set_synthesis_voice,
+    spd_set_synthesis_voice_all,
+    set_synthesis_voice,
+    &str,
+    OK_VOICE_SET,
+    "voice of the synthesizer"
+);
+spd_set!(
+    spd_set_voice_type,
+    spd_set_voice_type_all,
+    set_voice_type,
+    VoiceType,
+    OK_VOICE_SET,
+    "voice type"
+);
+spd_set!(
+    spd_set_punctuation,
+    spd_set_punctuation_all,
+    set_punctuation_mode,
+    PunctuationMode,
+    OK_PUNCTUATION_SET,
+    "punctuation mode"
+);
+spd_set!(
+    spd_set_spelling,
+    spd_set_spelling_all,
+    set_spelling,
+    bool,
+    OK_SPELLING_SET,
+    "spelling mode"
+);
+
+/// Set whether the text of the messages is plain text or SSML.
+pub fn spd_set_data_mode<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+    mode: SpdDataMode,
+) -> ClientResult<()> {
+    connection
+        .set_ssml_mode(mode == SpdDataMode::Ssml)?
+        .check_status(OK_SSML_MODE_SET)
+        .map(|_| ())
+}
+
+/// Rate of the connection.
+pub fn spd_get_voice_rate<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+) -> ClientResult<i8> {
+    connection.get_rate()?.receive_i8()
+}
+
+/// Pitch of the connection.
+pub fn spd_get_voice_pitch<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+) -> ClientResult<i8> {
+    connection.get_pitch()?.receive_i8()
+}
+
+/// Volume of the connection.
+pub fn spd_get_volume<S: Read + Write + Source>(connection: &mut Client<S>) -> ClientResult<i8> {
+    connection.get_volume()?.receive_i8()
+}
+
+/// Output module of the connection.
+pub fn spd_get_output_module<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+) -> ClientResult<String> {
+    connection.get_output_module()?.receive_string(OK_GET)
+}
+
+/// Names of the output modules.
+pub fn spd_list_modules<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+) -> ClientResult<Vec<String>> {
+    connection
+        .list_output_modules()?
+        .receive_lines(OK_OUTPUT_MODULES_LIST_SENT)
+}
+
+/// Voices of the current output module.
+pub fn spd_list_synthesis_voices<S: Read + Write + Source>(
+    connection: &mut Client<S>,
+) -> ClientResult<Vec<SynthesisVoice>> {
+    connection
+        .list_synthesis_voices()?
+        .receive_synthesis_voices()
+}
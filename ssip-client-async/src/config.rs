@@ -0,0 +1,172 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Communication settings of the server read from `speechd.conf`.
+//!
+//! Speech Dispatcher reads the configuration of the user if it exists, otherwise the
+//! system configuration. The settings found there can be passed to the builders, so that
+//! clients connect where the server listens.
+//!
+//! Example
+//! ```no_run
+//! use ssip_client_async::{config::ServerConfig, fifo};
+//! let config = ServerConfig::load()?;
+//! let mut client = fifo::Builder::new().config(&config).build()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SPEECHD_APPLICATION_NAME: &str = "speech-dispatcher";
+const SPEECHD_CONFIG_NAME: &str = "speechd.conf";
+const SYSTEM_CONFIG_DIR: &str = "/etc/speech-dispatcher";
+
+/// Port of the server when it listens on TCP and the configuration doesn't set one.
+pub const DEFAULT_PORT: u16 = 6560;
+
+/// Value of `SocketPath` meaning the standard socket.
+const DEFAULT_SOCKET_PATH: &str = "default";
+
+/// How the server accepts connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommunicationMethod {
+    #[default]
+    UnixSocket,
+    InetSocket,
+}
+
+/// Communication settings of the server.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServerConfig {
+    pub method: CommunicationMethod,
+    /// Unix socket, `None` for the standard one.
+    pub socket_path: Option<PathBuf>,
+    /// TCP port, `None` for the default one.
+    pub port: Option<u16>,
+}
+
+/// Remove the quotes around a value.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn invalid_value(option: &str, value: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid value for {}: {}", option, value),
+    )
+}
+
+impl ServerConfig {
+    /// Parse the content of a configuration file.
+    ///
+    /// Options unrelated to the communication are ignored.
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let mut config = Self::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (option, value) = match line.split_once(char::is_whitespace) {
+                Some((option, value)) => (option, unquote(value.trim())),
+                None => continue,
+            };
+            if option.eq_ignore_ascii_case("CommunicationMethod") {
+                config.method = match value {
+                    "unix_socket" => CommunicationMethod::UnixSocket,
+                    "inet_socket" => CommunicationMethod::InetSocket,
+                    _ => return Err(invalid_value(option, value)),
+                };
+            } else if option.eq_ignore_ascii_case("SocketPath") {
+                config.socket_path = (value != DEFAULT_SOCKET_PATH).then(|| PathBuf::from(value));
+            } else if option.eq_ignore_ascii_case("Port") {
+                config.port = Some(value.parse().map_err(|_| invalid_value(option, value))?);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Read a configuration file.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Configuration files in the order Speech Dispatcher looks for them.
+    pub fn paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Some(config_dir) = dirs::config_dir() {
+            paths.push(
+                config_dir
+                    .join(SPEECHD_APPLICATION_NAME)
+                    .join(SPEECHD_CONFIG_NAME),
+            );
+        }
+        paths.push(Path::new(SYSTEM_CONFIG_DIR).join(SPEECHD_CONFIG_NAME));
+        paths
+    }
+
+    /// Read the first configuration file found, or return the default configuration.
+    pub fn load() -> io::Result<Self> {
+        for path in Self::paths() {
+            match Self::read(&path) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                result => return result,
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// TCP port of the server.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_PORT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use super::{CommunicationMethod, ServerConfig};
+
+    #[test]
+    fn parse_default_config() {
+        let config = ServerConfig::parse(
+            "# CommunicationMethod \"unix_socket\"\n\n# SocketPath \"default\"\n# Port 6560\nDefaultRate 0\n",
+        )
+        .unwrap();
+        assert_eq!(ServerConfig::default(), config);
+        assert_eq!(6560, config.port());
+    }
+
+    #[test]
+    fn parse_communication_settings() {
+        let config = ServerConfig::parse(
+            "CommunicationMethod \"inet_socket\"\n  socketpath  \"/run/speechd.sock\"\nPort 7560\n",
+        )
+        .unwrap();
+        assert_eq!(CommunicationMethod::InetSocket, config.method);
+        assert_eq!(Some(PathBuf::from("/run/speechd.sock")), config.socket_path);
+        assert_eq!(7560, config.port());
+        let config = ServerConfig::parse("SocketPath \"default\"\n").unwrap();
+        assert_eq!(None, config.socket_path);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert!(ServerConfig::parse("Port 65536\n").is_err());
+        assert!(ServerConfig::parse("CommunicationMethod \"pipe\"\n").is_err());
+    }
+}
@@ -7,7 +7,7 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use crate::types::ReturnCode;
+use crate::types::{ClientError, ReturnCode};
 
 /// Successful completion: OK LANGUAGE SET
 pub const OK_LANGUAGE_SET: ReturnCode = 201;
@@ -305,3 +305,91 @@ pub const EVENT_PAUSED: ReturnCode = 704;
 
 /// Event: RESUMED
 pub const EVENT_RESUMED: ReturnCode = 705;
+
+/// Category of a return code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReturnCodeKind {
+    /// Successful completion (2xx)
+    Ok(ReturnCode),
+    /// Server error (3xx)
+    ServerError(ReturnCode),
+    /// Client error (4xx and 5xx)
+    ClientError(ReturnCode),
+    /// Event notification (7xx)
+    Event(ReturnCode),
+}
+
+impl ReturnCodeKind {
+    /// Numeric return code.
+    pub fn code(&self) -> ReturnCode {
+        match self {
+            ReturnCodeKind::Ok(code)
+            | ReturnCodeKind::ServerError(code)
+            | ReturnCodeKind::ClientError(code)
+            | ReturnCodeKind::Event(code) => *code,
+        }
+    }
+
+    /// Return true if the code is a successful completion.
+    pub fn is_success(&self) -> bool {
+        matches!(self, ReturnCodeKind::Ok(_))
+    }
+
+    /// Return true if the code is a server or client error.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            ReturnCodeKind::ServerError(_) | ReturnCodeKind::ClientError(_)
+        )
+    }
+
+    /// Return true if the code is an event notification.
+    pub fn is_event(&self) -> bool {
+        matches!(self, ReturnCodeKind::Event(_))
+    }
+}
+
+impl TryFrom<ReturnCode> for ReturnCodeKind {
+    type Error = ClientError;
+
+    fn try_from(code: ReturnCode) -> Result<Self, Self::Error> {
+        match code {
+            OK_LANGUAGE_SET..=OK_NOT_IMPLEMENTED => Ok(ReturnCodeKind::Ok(code)),
+            ERR_INTERNAL..=399 => Ok(ReturnCodeKind::ServerError(code)),
+            ERR_NO_CLIENT..=599 => Ok(ReturnCodeKind::ClientError(code)),
+            EVENT_INDEX_MARK..=799 => Ok(ReturnCodeKind::Event(code)),
+            _ => Err(ClientError::invalid_text(
+                "invalid return code",
+                &code.to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn return_code_kind() {
+        let kind = ReturnCodeKind::try_from(OK_CLIENT_NAME_SET).unwrap();
+        assert_eq!(ReturnCodeKind::Ok(OK_CLIENT_NAME_SET), kind);
+        assert!(kind.is_success());
+        assert!(!kind.is_error());
+        assert_eq!(
+            ReturnCodeKind::ServerError(ERR_NO_OUTPUT_MODULE),
+            ReturnCodeKind::try_from(ERR_NO_OUTPUT_MODULE).unwrap()
+        );
+        assert_eq!(
+            ReturnCodeKind::ClientError(ERR_PARAMETER_INVALID),
+            ReturnCodeKind::try_from(ERR_PARAMETER_INVALID).unwrap()
+        );
+        let kind = ReturnCodeKind::try_from(EVENT_RESUMED).unwrap();
+        assert!(kind.is_event());
+        assert_eq!(EVENT_RESUMED, kind.code());
+        for code in &[0, 100, 600, 800, 999] {
+            assert!(ReturnCodeKind::try_from(*code).is_err());
+        }
+    }
+}
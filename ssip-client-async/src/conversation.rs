@@ -0,0 +1,83 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+/// Script of a conversation with a server, for tests.
+///
+/// Each step sends one or more lines and expects one or more answers. An answer has a
+/// code, optional continuation lines between brackets and a status message. Lines are
+/// terminated by CRLF. The result is an array of pairs (question, answer) of static
+/// strings.
+///
+/// Example
+/// ```
+/// use ssip_client_async::conversation;
+/// const COMMUNICATION: [(&str, &str); 3] = conversation! {
+///     send "SET self RATE 15"; reply 203 "OK RATE SET";
+///     send "SPEAK"; reply 230 "OK RECEIVING DATA";
+///     send "hello", "."; reply 225 ["21"] "OK MESSAGE QUEUED";
+/// };
+/// assert_eq!(("SET self RATE 15\r\n", "203 OK RATE SET\r\n"), COMMUNICATION[0]);
+/// assert_eq!(
+///     ("hello\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+///     COMMUNICATION[2]
+/// );
+/// ```
+///
+/// Events sent after the answer are additional replies:
+/// ```
+/// use ssip_client_async::conversation;
+/// let communication = conversation! {
+///     send "SET self NOTIFICATION all on";
+///     reply 220 "OK NOTIFICATION SET" reply 702 ["21", "1"] "END";
+/// };
+/// assert_eq!(
+///     "220 OK NOTIFICATION SET\r\n702-21\r\n702-1\r\n702 END\r\n",
+///     communication[0].1
+/// );
+/// ```
+#[macro_export]
+macro_rules! conversation {
+    ($(
+        send $($question:literal),+ ;
+        $(reply $code:literal $([$($line:literal),* $(,)?])? $status:literal)+ ;
+    )*) => {
+        [$((
+            concat!($($question, "\r\n"),+),
+            concat!($($($($code, "-", $line, "\r\n",)*)? $code, " ", $status, "\r\n",)+),
+        )),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn conversation() {
+        const COMMUNICATION: [(&str, &str); 2] = conversation! {
+            send "SET self CLIENT_NAME test:test:main"; reply 208 "OK CLIENT NAME SET";
+            send "LIST OUTPUT_MODULES";
+            reply 250 ["espeak-ng", "dummy",] "OK MODULE LIST SENT";
+        };
+        assert_eq!(
+            [
+                (
+                    "SET self CLIENT_NAME test:test:main\r\n",
+                    "208 OK CLIENT NAME SET\r\n"
+                ),
+                (
+                    "LIST OUTPUT_MODULES\r\n",
+                    "250-espeak-ng\r\n250-dummy\r\n250 OK MODULE LIST SENT\r\n"
+                ),
+            ],
+            COMMUNICATION
+        );
+        let empty: [(&str, &str); 0] = conversation! {};
+        assert!(empty.is_empty());
+    }
+}
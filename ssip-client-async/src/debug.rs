@@ -0,0 +1,186 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Debug logs of the server.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    client::{Client, Source},
+    constants::OK_DEBUG_SET,
+    types::*,
+};
+
+/// Location of the debug logs returned by the server.
+///
+/// The server writes a log file for itself and for each output module in the directory
+/// returned when debugging is enabled. With an asynchronous client, the session is created
+/// with [`DebugSession::new`] from the string received after `set_debug(true)`.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{debug::DebugSession, fifo};
+/// let mut client = fifo::Builder::new().build()?;
+/// let session = DebugSession::start(&mut client)?;
+/// for (path, lines) in session.tail(20)? {
+///     println!("==> {} <==", path.display());
+///     lines.iter().for_each(|line| println!("{}", line));
+/// }
+/// session.stop(&mut client)?;
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DebugSession {
+    path: PathBuf,
+}
+
+impl DebugSession {
+    /// Session with logs in a directory or a single file.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Enable debugging with the synchronous client and keep the location of the logs.
+    pub fn start<S: Read + Write + Source>(client: &mut Client<S>) -> ClientResult<Self> {
+        let path = client.set_debug(true)?.receive_string(OK_DEBUG_SET)?;
+        Ok(Self::new(path))
+    }
+
+    /// Disable debugging. The log files are left on disk.
+    pub fn stop<S: Read + Write + Source>(self, client: &mut Client<S>) -> ClientResult<()> {
+        client.set_debug(false)?.receive_lines(OK_DEBUG_SET)?;
+        Ok(())
+    }
+
+    /// Location returned by the server.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Log files sorted by name.
+    ///
+    /// If the location is a file, it's the only log file.
+    pub fn log_files(&self) -> io::Result<Vec<PathBuf>> {
+        if self.path.is_file() {
+            return Ok(vec![self.path.clone()]);
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Last `count` lines of each log file.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    #[cfg(unix)]
+    pub fn tail(&self, count: usize) -> io::Result<Vec<(PathBuf, Vec<String>)>> {
+        self.log_files()?
+            .into_iter()
+            .map(|path| {
+                let lines = tail_lines(fs::File::open(&path)?, count)?;
+                Ok((path, lines))
+            })
+            .collect()
+    }
+}
+
+/// Last `count` lines of a stream.
+#[cfg(unix)]
+fn tail_lines<R: Read>(input: R, count: usize) -> io::Result<Vec<String>> {
+    use std::collections::VecDeque;
+    use std::io::{BufRead, BufReader};
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let mut input = BufReader::new(input);
+    let mut lines = VecDeque::with_capacity(count);
+    let mut bytes = Vec::new();
+    loop {
+        bytes.clear();
+        if input.read_until(b'\n', &mut bytes)? == 0 {
+            break;
+        }
+        if lines.len() == count {
+            lines.pop_front();
+        }
+        let line = String::from_utf8_lossy(&bytes);
+        lines.push_back(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    Ok(lines.into())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+
+    use super::DebugSession;
+
+    #[test]
+    fn log_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("speech-dispatcher.log"),
+            "one\ntwo\nthree\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("espeak-ng.log"), "module\n").unwrap();
+        fs::create_dir(dir.path().join("old")).unwrap();
+        let session = DebugSession::new(dir.path());
+        assert_eq!(
+            vec![
+                dir.path().join("espeak-ng.log"),
+                dir.path().join("speech-dispatcher.log")
+            ],
+            session.log_files().unwrap()
+        );
+        let file = DebugSession::new(dir.path().join("espeak-ng.log"));
+        assert_eq!(1, file.log_files().unwrap().len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tail() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("speech-dispatcher.log"),
+            "one\ntwo\nthree\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("espeak-ng.log"), b"mod\xffule").unwrap();
+        let tail = DebugSession::new(dir.path()).tail(2).unwrap();
+        assert_eq!(
+            vec![
+                (
+                    dir.path().join("espeak-ng.log"),
+                    vec![String::from("mod\u{fffd}ule")]
+                ),
+                (
+                    dir.path().join("speech-dispatcher.log"),
+                    vec![String::from("two"), String::from("three")]
+                ),
+            ],
+            tail
+        );
+        assert_eq!(
+            vec![(dir.path().join("espeak-ng.log"), Vec::<String>::new())],
+            DebugSession::new(dir.path().join("espeak-ng.log"))
+                .tail(0)
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Separation of the events from the answers.
+//!
+//! Events can be received at any time, including while the client waits for the answer of
+//! a request. They are kept in a bounded buffer until the application reads them, so that
+//! an application enabling all the notifications without reading them doesn't use more
+//! and more memory.
+
+use std::collections::VecDeque;
+
+use crate::types::*;
+
+/// Default number of events kept in a buffer.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// What to do with an event when the buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverflowPolicy {
+    /// Discard the oldest event to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Discard the new event.
+    DropNewest,
+    /// Refuse the new event and return it to the caller.
+    Error,
+}
+
+/// Bounded queue of events.
+///
+/// The number of events discarded or refused is counted.
+///
+/// Example
+/// ```
+/// use ssip_client_async::{demux::{EventBuffer, OverflowPolicy}, Event};
+/// let mut events = EventBuffer::new(1, OverflowPolicy::DropOldest);
+/// events.push(Event::begin(1, 1)).unwrap();
+/// events.push(Event::end(1, 1)).unwrap();
+/// assert_eq!(Some(Event::end(1, 1)), events.pop());
+/// assert_eq!(1, events.overflows());
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventBuffer {
+    events: VecDeque<Event>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    overflows: u64,
+}
+
+impl Default for EventBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY, OverflowPolicy::default())
+    }
+}
+
+impl EventBuffer {
+    /// Buffer keeping at most `capacity` events.
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+            policy,
+            overflows: 0,
+        }
+    }
+
+    /// Add an event at the end of the queue.
+    ///
+    /// With [`OverflowPolicy::Error`], a full buffer returns the event as an error.
+    pub fn push(&mut self, event: Event) -> Result<(), Event> {
+        if self.events.len() < self.capacity {
+            self.events.push_back(event);
+            return Ok(());
+        }
+        self.overflows += 1;
+        match self.policy {
+            OverflowPolicy::DropOldest => {
+                if self.events.pop_front().is_some() {
+                    self.events.push_back(event);
+                }
+                Ok(())
+            }
+            OverflowPolicy::DropNewest => Ok(()),
+            OverflowPolicy::Error => Err(event),
+        }
+    }
+
+    /// Remove the oldest event.
+    pub fn pop(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    /// Number of events discarded or refused because the buffer was full.
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    /// Change the number of events kept. The oldest events beyond it are discarded.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.events.len() > capacity {
+            self.events.pop_front();
+            self.overflows += 1;
+        }
+        self.capacity = capacity;
+    }
+
+    pub fn set_policy(&mut self, policy: OverflowPolicy) {
+        self.policy = policy;
+    }
+
+    /// Discard the events. The count of overflows is kept.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{EventBuffer, OverflowPolicy};
+    use crate::types::Event;
+
+    fn event(n: usize) -> Event {
+        Event::begin(n as u32, 1)
+    }
+
+    fn fill(policy: OverflowPolicy) -> (EventBuffer, Vec<Result<(), Event>>) {
+        let mut events = EventBuffer::new(2, policy);
+        let results = (0..4).map(|n| events.push(event(n))).collect();
+        (events, results)
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let (mut events, results) = fill(OverflowPolicy::DropOldest);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(2, events.overflows());
+        assert_eq!(Some(event(2)), events.pop());
+        assert_eq!(Some(event(3)), events.pop());
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn drop_newest() {
+        let (mut events, _) = fill(OverflowPolicy::DropNewest);
+        assert_eq!(2, events.overflows());
+        assert_eq!(Some(event(0)), events.pop());
+        assert_eq!(Some(event(1)), events.pop());
+        assert_eq!(None, events.pop());
+    }
+
+    #[test]
+    fn refuse_when_full() {
+        let (events, results) = fill(OverflowPolicy::Error);
+        assert_eq!(vec![Ok(()), Ok(()), Err(event(2)), Err(event(3))], results);
+        assert_eq!(2, events.len());
+        assert_eq!(2, events.overflows());
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut events = EventBuffer::new(0, OverflowPolicy::DropOldest);
+        assert!(events.push(event(0)).is_ok());
+        assert!(events.is_empty());
+        assert_eq!(1, events.overflows());
+    }
+
+    #[test]
+    fn shrink() {
+        let (mut events, _) = fill(OverflowPolicy::DropOldest);
+        events.set_capacity(1);
+        assert_eq!(3, events.overflows());
+        assert_eq!(Some(event(3)), events.pop());
+    }
+}
@@ -0,0 +1,311 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Warnings about the behavior of the server and counters of the traffic.
+//!
+//! Clients measure the time the server takes to answer each request and report the
+//! requests answered after their time budget. Requests listing the voices may take
+//! seconds while the synthesizer enumerates them, other requests should be answered at
+//! once. A server that is slow on every request is more likely to be stuck.
+//!
+//! Example
+//! ```no_run
+//! use std::time::Duration;
+//! use ssip_client_async::{diagnostics::{Diagnostic, TimeBudgets}, fifo};
+//! let mut client = fifo::Builder::new().build()?;
+//! let mut budgets = TimeBudgets::default();
+//! budgets.set("ListSynthesisVoices", Duration::from_secs(2));
+//! client
+//!     .set_time_budgets(budgets)
+//!     .set_diagnostics(|diagnostic: &Diagnostic| eprintln!("{}", diagnostic));
+//! # Ok::<(), ssip_client_async::ClientError>(())
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants::*;
+use crate::types::*;
+
+/// Warning reported to the diagnostics hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The server answered a request after its time budget.
+    SlowServer {
+        /// Name of the variant of [`Request`].
+        request: &'static str,
+        budget: Duration,
+        elapsed: Duration,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::SlowServer {
+                request,
+                budget,
+                elapsed,
+            } => write!(
+                f,
+                "slow server: {} answered in {:?}, expected at most {:?}",
+                request, elapsed, budget
+            ),
+        }
+    }
+}
+
+/// Function called with each diagnostic.
+pub type DiagnosticsHook = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+/// Budget for requests without a specific one.
+const DEFAULT_BUDGET: Duration = Duration::from_millis(500);
+
+/// Maximum time expected between a request and its answer.
+///
+/// Requests are identified by the name of the variant of [`Request`], as in
+/// [`RequestDescription::request`](crate::protocol::RequestDescription::request).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeBudgets {
+    default: Duration,
+    requests: HashMap<&'static str, Duration>,
+}
+
+impl TimeBudgets {
+    /// Same budget for all requests.
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Set the budget of a request.
+    pub fn set(&mut self, request: &'static str, budget: Duration) -> &mut Self {
+        self.requests.insert(request, budget);
+        self
+    }
+
+    /// Budget of a request.
+    pub fn get(&self, request: &str) -> Duration {
+        self.requests.get(request).copied().unwrap_or(self.default)
+    }
+}
+
+impl Default for TimeBudgets {
+    /// Half a second, except for the requests that make the server enumerate voices or
+    /// start an output module.
+    fn default() -> Self {
+        let mut budgets = Self::new(DEFAULT_BUDGET);
+        budgets
+            .set("ListSynthesisVoices", Duration::from_secs(10))
+            .set("ListOutputModules", Duration::from_secs(2))
+            .set("SetOutputModule", Duration::from_secs(5))
+            .set("SetSynthesisVoice", Duration::from_secs(2))
+            .set("SetLanguage", Duration::from_secs(2));
+        budgets
+    }
+}
+
+/// Counters of the traffic of a client.
+///
+/// The counters are always updated and can be included in diagnostics dumps. They are
+/// reset with the `reset_stats` method of the clients.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Bytes written to the server.
+    pub bytes_sent: u64,
+    /// Bytes read from the server, including the events.
+    pub bytes_received: u64,
+    /// Requests sent, by name of the variant of [`Request`].
+    pub requests: HashMap<&'static str, u64>,
+    /// Error answers of the server, by return code.
+    pub errors: HashMap<ReturnCode, u64>,
+    /// Events received, by name of the notification type.
+    pub events: HashMap<&'static str, u64>,
+}
+
+impl Stats {
+    /// Total number of requests sent.
+    pub fn request_count(&self) -> u64 {
+        self.requests.values().sum()
+    }
+
+    /// Total number of error answers.
+    pub fn error_count(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// Total number of events.
+    pub fn event_count(&self) -> u64 {
+        self.events.values().sum()
+    }
+
+    /// Record a request sent.
+    pub(crate) fn sent(&mut self, request: &'static str) {
+        *self.requests.entry(request).or_default() += 1;
+    }
+
+    /// Count the status of an answer or an event.
+    pub(crate) fn received(&mut self, status: &ClientStatus) {
+        match status {
+            Ok(status) => {
+                let event = match status.code {
+                    EVENT_INDEX_MARK => "index_mark",
+                    EVENT_BEGIN => "begin",
+                    EVENT_END => "end",
+                    EVENT_CANCELED => "cancel",
+                    EVENT_PAUSED => "pause",
+                    EVENT_RESUMED => "resume",
+                    706..=799 => "unknown",
+                    _ => return,
+                };
+                *self.events.entry(event).or_default() += 1;
+            }
+            Err(ClientError::Ssip(status)) => *self.errors.entry(status.code).or_default() += 1,
+            Err(_) => (),
+        }
+    }
+}
+
+/// Requests waiting for an answer.
+#[derive(Default)]
+pub(crate) struct Timings {
+    budgets: TimeBudgets,
+    hook: Option<DiagnosticsHook>,
+    pending: VecDeque<(&'static str, Instant)>,
+}
+
+impl Timings {
+    pub(crate) fn set_budgets(&mut self, budgets: TimeBudgets) {
+        self.budgets = budgets;
+    }
+
+    pub(crate) fn set_hook(&mut self, hook: DiagnosticsHook) {
+        self.hook = Some(hook);
+    }
+
+    /// Same budgets and hook, without pending request.
+    pub(crate) fn same_settings(&self) -> Self {
+        Self {
+            budgets: self.budgets.clone(),
+            hook: self.hook.clone(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Record a request sent now. Nothing is recorded without hook.
+    pub(crate) fn sent(&mut self, request: &'static str) {
+        if self.hook.is_some() {
+            self.pending.push_back((request, Instant::now()));
+        }
+    }
+
+    /// Check the time of the oldest request when its answer is received.
+    pub(crate) fn received(&mut self, status: &ClientStatus) {
+        match status {
+            Ok(status) | Err(ClientError::Ssip(status)) => {
+                if (700..800).contains(&status.code) {
+                    // Notifications don't answer requests.
+                    return;
+                }
+                if let (Some(hook), Some((request, sent))) =
+                    (self.hook.as_ref(), self.pending.pop_front())
+                {
+                    let budget = self.budgets.get(request);
+                    let elapsed = sent.elapsed();
+                    if elapsed > budget {
+                        hook(&Diagnostic::SlowServer {
+                            request,
+                            budget,
+                            elapsed,
+                        });
+                    }
+                }
+            }
+            // Answers can't be matched with requests anymore.
+            Err(_) => self.pending.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{Diagnostic, Stats, TimeBudgets, Timings};
+    use crate::types::{ClientError, StatusLine};
+
+    #[test]
+    fn count_traffic() {
+        let mut stats = Stats::default();
+        stats.sent("SetRate");
+        stats.sent("SetRate");
+        stats.sent("Speak");
+        stats.received(&Ok(StatusLine::new(203, "RATE SET")));
+        stats.received(&Ok(StatusLine::new(701, "BEGIN")));
+        stats.received(&Ok(StatusLine::new(799, "FUTURE")));
+        stats.received(&Err(ClientError::Ssip(StatusLine::new(
+            410,
+            "INVALID PARAMETER",
+        ))));
+        stats.received(&Err(ClientError::TooFewLines));
+        assert_eq!(Some(&2), stats.requests.get("SetRate"));
+        assert_eq!(3, stats.request_count());
+        assert_eq!(Some(&1), stats.errors.get(&410));
+        assert_eq!(1, stats.error_count());
+        assert_eq!(Some(&1), stats.events.get("begin"));
+        assert_eq!(Some(&1), stats.events.get("unknown"));
+        assert_eq!(2, stats.event_count());
+    }
+
+    #[test]
+    fn default_budgets() {
+        let budgets = TimeBudgets::default();
+        assert_eq!(Duration::from_millis(500), budgets.get("SetRate"));
+        assert_eq!(Duration::from_secs(10), budgets.get("ListSynthesisVoices"));
+    }
+
+    #[test]
+    fn report_slow_answers() {
+        let diagnostics = Arc::new(Mutex::new(Vec::new()));
+        let mut timings = Timings::default();
+        let mut budgets = TimeBudgets::new(Duration::from_secs(60));
+        budgets.set("SetRate", Duration::ZERO);
+        timings.set_budgets(budgets);
+        timings.set_hook({
+            let diagnostics = diagnostics.clone();
+            Arc::new(move |diagnostic: &Diagnostic| {
+                diagnostics.lock().unwrap().push(diagnostic.clone())
+            })
+        });
+        timings.sent("SetRate");
+        timings.sent("GetRate");
+        std::thread::sleep(Duration::from_millis(1));
+        timings.received(&Ok(StatusLine::new(701, "BEGIN")));
+        timings.received(&Err(ClientError::Ssip(StatusLine::new(
+            410,
+            "INVALID PARAMETER",
+        ))));
+        timings.received(&Ok(StatusLine::new(251, "GET")));
+        let diagnostics = diagnostics.lock().unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert!(matches!(
+            diagnostics[0],
+            Diagnostic::SlowServer {
+                request: "SetRate",
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Texts in other encodings than UTF-8.
+//!
+//! SSIP only transmits UTF-8. Texts in legacy encodings, such as ISO-8859-2 documents, are
+//! converted with [encoding_rs](https://crates.io/crates/encoding_rs) before they are sent.
+
+use std::fmt;
+use std::io;
+
+use encoding_rs::DecoderResult;
+pub use encoding_rs::Encoding;
+
+use crate::types::ClientError;
+
+/// Bytes that are not valid in the declared encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingError {
+    /// Name of the encoding.
+    pub encoding: &'static str,
+    /// Position of the first invalid byte.
+    pub offset: usize,
+    /// Number of invalid bytes.
+    pub length: usize,
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} invalid {} bytes at offset {}",
+            self.length, self.encoding, self.offset
+        )
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl From<EncodingError> for ClientError {
+    fn from(err: EncodingError) -> Self {
+        ClientError::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Convert bytes in an encoding to UTF-8.
+///
+/// A byte order mark is not removed. Fail on the first invalid sequence instead of
+/// replacing it.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> Result<String, EncodingError> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::new();
+    let mut offset = 0;
+    loop {
+        let remaining = bytes.len() - offset;
+        text.reserve(
+            decoder
+                .max_utf8_buffer_length_without_replacement(remaining)
+                .unwrap_or(remaining),
+        );
+        let (result, read) =
+            decoder.decode_to_string_without_replacement(&bytes[offset..], &mut text, true);
+        offset += read;
+        match result {
+            DecoderResult::InputEmpty => return Ok(text),
+            DecoderResult::OutputFull => (),
+            DecoderResult::Malformed(length, consumed) => {
+                return Err(EncodingError {
+                    encoding: encoding.name(),
+                    offset: offset - length as usize - consumed as usize,
+                    length: length as usize,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{decode, EncodingError};
+
+    #[test]
+    fn decode_legacy_text() {
+        assert_eq!(
+            "Žluťoučký kůň",
+            decode(b"\xaelu\xbbou\xe8k\xfd k\xf9\xf2", encoding_rs::ISO_8859_2).unwrap()
+        );
+        assert_eq!(
+            Err(EncodingError {
+                encoding: "Shift_JIS",
+                offset: 2,
+                length: 1,
+            }),
+            decode(b"ab\x82", encoding_rs::SHIFT_JIS)
+        );
+    }
+}
@@ -9,6 +9,7 @@
 
 use std::io;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 const SPEECHD_APPLICATION_NAME: &str = "speech-dispatcher";
 const SPEECHD_SOCKET_NAME: &str = "speechd.sock";
@@ -48,30 +49,116 @@ impl FifoPath {
             _ => FifoPath::default_path(),
         }
     }
+
+    /// Tell if the connection was refused on a socket left by a server that is gone.
+    fn is_stale(err: &io::Error, path: &Path) -> bool {
+        err.kind() == io::ErrorKind::ConnectionRefused && path.exists()
+    }
+
+    /// Prepare a second attempt after a stale socket, spawning the server if requested.
+    fn before_retry(path: &Path, autospawn: bool) {
+        log::warn!("connection refused on {}, retrying", path.display());
+        if autospawn {
+            if let Err(err) = spawn_server() {
+                log::warn!("cannot spawn {}: {}", SPEECHD_APPLICATION_NAME, err);
+            }
+        }
+    }
+
+    /// Error of the second attempt.
+    fn retry_error(err: io::Error, path: &Path) -> io::Error {
+        if FifoPath::is_stale(&err, path) {
+            io::Error::new(
+                err.kind(),
+                format!(
+                    "{}: stale socket {}, is {} running?",
+                    err,
+                    path.display(),
+                    SPEECHD_APPLICATION_NAME
+                ),
+            )
+        } else {
+            err
+        }
+    }
+
+    /// Connect to the socket, retrying once if the socket is stale.
+    fn connect<S, F>(&self, autospawn: bool, connect: F) -> io::Result<S>
+    where
+        F: Fn(&Path) -> io::Result<S>,
+    {
+        let path = self.get()?;
+        match connect(&path) {
+            Err(err) if FifoPath::is_stale(&err, &path) => {
+                FifoPath::before_retry(&path, autospawn);
+                connect(&path).map_err(|err| FifoPath::retry_error(err, &path))
+            }
+            result => result,
+        }
+    }
 }
 
-#[cfg(not(feature = "async-mio"))]
-mod synchronous {
+/// Start the server like clients of Speech Dispatcher do when it's not running.
+fn spawn_server() -> io::Result<()> {
+    let status = Command::new(SPEECHD_APPLICATION_NAME)
+        .arg("--spawn")
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} --spawn: {}",
+            SPEECHD_APPLICATION_NAME, status
+        )))
+    }
+}
+
+/// Blocking client on the Unix socket.
+pub mod sync {
     use std::io::{self, BufReader, BufWriter};
     pub use std::os::unix::net::UnixStream;
     use std::path::Path;
     use std::time::Duration;
 
     use crate::client::Client;
-    use crate::net::StreamMode;
+    use crate::config::ServerConfig;
+    use crate::demux::OverflowPolicy;
+    use crate::net::{Connector, StreamMode};
+    use crate::protocol::Utf8Mode;
+    use crate::session::{Preset, SessionTemplate};
 
     use super::FifoPath;
 
     pub struct Builder {
         path: FifoPath,
+        connector: Option<Connector<UnixStream>>,
+        autospawn: bool,
         mode: StreamMode,
+        session: Option<SessionTemplate>,
+        utf8_mode: Utf8Mode,
+        label: Option<String>,
+        strict_replies: bool,
+        event_buffer: Option<(usize, OverflowPolicy)>,
+    }
+
+    impl Default for Builder {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
     impl Builder {
         pub fn new() -> Self {
             Self {
                 path: FifoPath::new(),
+                connector: None,
+                autospawn: false,
                 mode: StreamMode::Blocking,
+                session: None,
+                utf8_mode: Utf8Mode::Strict,
+                label: None,
+                strict_replies: false,
+                event_buffer: None,
             }
         }
 
@@ -83,6 +170,14 @@ mod synchronous {
             self
         }
 
+        /// Use the socket set in the configuration of the server, if any.
+        pub fn config(&mut self, config: &ServerConfig) -> &mut Self {
+            if let Some(path) = &config.socket_path {
+                self.path.set(path);
+            }
+            self
+        }
+
         pub fn timeout(&mut self, read_timeout: Duration) -> &mut Self {
             self.mode = StreamMode::TimeOut(read_timeout);
             self
@@ -93,41 +188,134 @@ mod synchronous {
             self
         }
 
+        /// Session replayed on the connection before returning the client.
+        pub fn session(&mut self, template: &SessionTemplate) -> &mut Self {
+            self.session = Some(template.clone());
+            self
+        }
+
+        /// Session with the settings of a preset. It replaces any previous session.
+        pub fn preset(&mut self, preset: Preset) -> &mut Self {
+            self.session = Some(preset.session());
+            self
+        }
+
+        /// Replace invalid UTF-8 in received lines and call the handler with the bytes of the line.
+        pub fn lossy_utf8<F>(&mut self, handler: F) -> &mut Self
+        where
+            F: Fn(&[u8]) + Send + Sync + 'static,
+        {
+            self.utf8_mode = Utf8Mode::lossy(handler);
+            self
+        }
+
+        /// Name of the connection in the logs.
+        pub fn label(&mut self, label: &str) -> &mut Self {
+            self.label = Some(label.to_string());
+            self
+        }
+
+        /// Check that the answer to each request is read before the next request is sent.
+        pub fn strict_replies(&mut self, enabled: bool) -> &mut Self {
+            self.strict_replies = enabled;
+            self
+        }
+
+        /// Number of events kept while waiting for an answer and what to do when it's full.
+        pub fn event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+            self.event_buffer = Some((capacity, policy));
+            self
+        }
+
+        /// Create the stream with a function instead of connecting to the socket path.
+        ///
+        /// The function can return a socket obtained by socket activation, passed by a
+        /// privileged helper or connected in another namespace. The builder still configures
+        /// the stream and the client as if it had connected it.
+        pub fn with_stream<F>(&mut self, connect: F) -> &mut Self
+        where
+            F: Fn() -> io::Result<UnixStream> + Send + Sync + 'static,
+        {
+            self.connector = Some(Box::new(connect));
+            self
+        }
+
+        /// Spawn the server if the socket is stale before trying to connect again.
+        ///
+        /// When the server crashed, the socket is left and the connection is refused. The
+        /// builder always retries once. With this option, it runs `speech-dispatcher --spawn`
+        /// first.
+        pub fn autospawn(&mut self, enabled: bool) -> &mut Self {
+            self.autospawn = enabled;
+            self
+        }
+
         pub fn build(&self) -> io::Result<Client<UnixStream>> {
-            let input = UnixStream::connect(self.path.get()?)?;
+            let input = match &self.connector {
+                Some(connect) => connect()?,
+                None => self
+                    .path
+                    .connect(self.autospawn, |path| UnixStream::connect(path))?,
+            };
             match self.mode {
-                StreamMode::Blocking => input.set_nonblocking(false)?,
-                StreamMode::NonBlocking => input.set_nonblocking(true)?,
+                StreamMode::Blocking | StreamMode::NonBlocking => input.set_nonblocking(false)?,
                 StreamMode::TimeOut(timeout) => input.set_read_timeout(Some(timeout))?,
             }
             let output = input.try_clone()?;
-            Ok(Client::new(BufReader::new(input), BufWriter::new(output)))
+            let control = input.try_clone()?;
+            let mut client = Client::new(BufReader::new(input), BufWriter::new(output));
+            client.set_utf8_mode(self.utf8_mode.clone());
+            if let Some(label) = &self.label {
+                client.set_label(label);
+            }
+            client.set_strict_replies(self.strict_replies);
+            if let Some((capacity, policy)) = self.event_buffer {
+                client.set_event_buffer(capacity, policy);
+            }
+            if let Some(session) = &self.session {
+                client.apply_session(session)?;
+            }
+            // The session is replayed with a blocking socket.
+            if let StreamMode::NonBlocking = self.mode {
+                control.set_nonblocking(true)?;
+            }
+            Ok(client)
         }
     }
 }
 
-#[cfg(not(feature = "async-mio"))]
-pub use synchronous::{Builder, UnixStream};
-
+/// Nonblocking client on the Unix socket for [mio](https://crates.io/crates/mio).
 #[cfg(feature = "async-mio")]
-mod asynchronous {
+pub mod mio {
     pub use mio::net::UnixStream;
     use std::io::{self, BufReader, BufWriter};
     use std::os::unix::net::UnixStream as StdUnixStream;
     use std::path::Path;
 
     use crate::client::Client;
+    use crate::config::ServerConfig;
+    use crate::net::Connector;
 
     use super::FifoPath;
 
     pub struct Builder {
         path: FifoPath,
+        connector: Option<Connector<StdUnixStream>>,
+        autospawn: bool,
+    }
+
+    impl Default for Builder {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
     impl Builder {
         pub fn new() -> Self {
             Self {
                 path: FifoPath::new(),
+                connector: None,
+                autospawn: false,
             }
         }
 
@@ -144,8 +332,44 @@ mod asynchronous {
             self
         }
 
+        /// Use the socket set in the configuration of the server, if any.
+        pub fn config(&mut self, config: &ServerConfig) -> &mut Self {
+            if let Some(path) = &config.socket_path {
+                self.path.set(path);
+            }
+            self
+        }
+
+        /// Create the stream with a function instead of connecting to the socket path.
+        ///
+        /// The function can return a socket obtained by socket activation, passed by a
+        /// privileged helper or connected in another namespace. The builder still configures
+        /// the stream and the client as if it had connected it.
+        pub fn with_stream<F>(&mut self, connect: F) -> &mut Self
+        where
+            F: Fn() -> io::Result<StdUnixStream> + Send + Sync + 'static,
+        {
+            self.connector = Some(Box::new(connect));
+            self
+        }
+
+        /// Spawn the server if the socket is stale before trying to connect again.
+        ///
+        /// When the server crashed, the socket is left and the connection is refused. The
+        /// builder always retries once. With this option, it runs `speech-dispatcher --spawn`
+        /// first.
+        pub fn autospawn(&mut self, enabled: bool) -> &mut Self {
+            self.autospawn = enabled;
+            self
+        }
+
         pub fn build(&self) -> io::Result<Client<UnixStream>> {
-            let stream = StdUnixStream::connect(self.path.get()?)?;
+            let stream = match &self.connector {
+                Some(connect) => connect()?,
+                None => self
+                    .path
+                    .connect(self.autospawn, |path| StdUnixStream::connect(path))?,
+            };
             Ok(Client::new(
                 BufReader::new(UnixStream::from_std(Self::non_blocking(
                     stream.try_clone()?,
@@ -157,22 +381,51 @@ mod asynchronous {
 }
 #[cfg(feature = "tokio")]
 pub mod asynchronous_tokio {
+    use std::os::unix::net::UnixStream as StdUnixStream;
     use std::path::Path;
+    use std::time::Duration;
     use tokio::io::{self, BufReader as AsyncBufReader, BufWriter as AsyncBufWriter};
     pub use tokio::net::{unix::OwnedReadHalf, unix::OwnedWriteHalf, UnixStream};
 
+    use crate::config::ServerConfig;
+    use crate::demux::OverflowPolicy;
+    use crate::net::{Connector, HandshakeRetry};
+    use crate::protocol::Utf8Mode;
+    use crate::session::{Preset, SessionTemplate};
     use crate::tokio::AsyncClient;
 
     use super::FifoPath;
 
     pub struct Builder {
         path: FifoPath,
+        connector: Option<Connector<StdUnixStream>>,
+        autospawn: bool,
+        session: Option<SessionTemplate>,
+        utf8_mode: Utf8Mode,
+        label: Option<String>,
+        strict_replies: bool,
+        event_buffer: Option<(usize, OverflowPolicy)>,
+        retry: HandshakeRetry,
+    }
+
+    impl Default for Builder {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
     impl Builder {
         pub fn new() -> Self {
             Self {
                 path: FifoPath::new(),
+                connector: None,
+                autospawn: false,
+                session: None,
+                utf8_mode: Utf8Mode::Strict,
+                label: None,
+                strict_replies: false,
+                event_buffer: None,
+                retry: HandshakeRetry::default(),
             }
         }
 
@@ -184,32 +437,393 @@ pub mod asynchronous_tokio {
             self
         }
 
+        /// Use the socket set in the configuration of the server, if any.
+        pub fn config(&mut self, config: &ServerConfig) -> &mut Self {
+            if let Some(path) = &config.socket_path {
+                self.path.set(path);
+            }
+            self
+        }
+
+        /// Session replayed on the connection before returning the client.
+        pub fn session(&mut self, template: &SessionTemplate) -> &mut Self {
+            self.session = Some(template.clone());
+            self
+        }
+
+        /// Session with the settings of a preset. It replaces any previous session.
+        pub fn preset(&mut self, preset: Preset) -> &mut Self {
+            self.session = Some(preset.session());
+            self
+        }
+
+        /// Replace invalid UTF-8 in received lines and call the handler with the bytes of the line.
+        pub fn lossy_utf8<F>(&mut self, handler: F) -> &mut Self
+        where
+            F: Fn(&[u8]) + Send + Sync + 'static,
+        {
+            self.utf8_mode = Utf8Mode::lossy(handler);
+            self
+        }
+
+        /// Name of the connection in the logs.
+        pub fn label(&mut self, label: &str) -> &mut Self {
+            self.label = Some(label.to_string());
+            self
+        }
+
+        /// Check that the answer to each request is read before the next request is sent.
+        pub fn strict_replies(&mut self, enabled: bool) -> &mut Self {
+            self.strict_replies = enabled;
+            self
+        }
+
+        /// Number of events kept while waiting for an answer and what to do when it's full.
+        pub fn event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+            self.event_buffer = Some((capacity, policy));
+            self
+        }
+
+        /// Try again to connect and apply the session while the server is starting.
+        ///
+        /// At most `attempts` attempts are made, about `delay` apart, as long as the error
+        /// is accepted by [`HandshakeRetry::is_retryable`]. Default is a single attempt.
+        pub fn handshake_retry(&mut self, attempts: u32, delay: Duration) -> &mut Self {
+            self.retry = HandshakeRetry::new(attempts, delay);
+            self
+        }
+
+        /// Create the stream with a function instead of connecting to the socket path.
+        ///
+        /// The function can return a socket obtained by socket activation, passed by a
+        /// privileged helper or connected in another namespace. The builder still configures
+        /// the stream and the client as if it had connected it.
+        pub fn with_stream<F>(&mut self, connect: F) -> &mut Self
+        where
+            F: Fn() -> io::Result<StdUnixStream> + Send + Sync + 'static,
+        {
+            self.connector = Some(Box::new(connect));
+            self
+        }
+
+        /// Spawn the server if the socket is stale before trying to connect again.
+        ///
+        /// When the server crashed, the socket is left and the connection is refused. The
+        /// builder always retries once. With this option, it runs `speech-dispatcher --spawn`
+        /// first.
+        pub fn autospawn(&mut self, enabled: bool) -> &mut Self {
+            self.autospawn = enabled;
+            self
+        }
+
+        async fn connect(&self) -> io::Result<UnixStream> {
+            match &self.connector {
+                Some(connect) => {
+                    let stream = connect()?;
+                    stream.set_nonblocking(true)?;
+                    UnixStream::from_std(stream)
+                }
+                None => {
+                    let path = self.path.get()?;
+                    match UnixStream::connect(&path).await {
+                        Err(err) if FifoPath::is_stale(&err, &path) => {
+                            FifoPath::before_retry(&path, self.autospawn);
+                            UnixStream::connect(&path)
+                                .await
+                                .map_err(|err| FifoPath::retry_error(err, &path))
+                        }
+                        result => result,
+                    }
+                }
+            }
+        }
+
         pub async fn build(
             &self,
         ) -> io::Result<AsyncClient<AsyncBufReader<OwnedReadHalf>, AsyncBufWriter<OwnedWriteHalf>>>
         {
-            let (read_stream, write_stream) =
-                UnixStream::connect(self.path.get()?).await?.into_split();
-            Ok(AsyncClient::new(
+            let mut attempt = 1;
+            loop {
+                match self.handshake().await {
+                    Err(err) => match self.retry.wait_after(attempt, &err) {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => return Err(err),
+                    },
+                    client => return client,
+                }
+                attempt += 1;
+            }
+        }
+
+        /// Connect and apply the session.
+        async fn handshake(
+            &self,
+        ) -> io::Result<AsyncClient<AsyncBufReader<OwnedReadHalf>, AsyncBufWriter<OwnedWriteHalf>>>
+        {
+            let (read_stream, write_stream) = self.connect().await?.into_split();
+            let mut client = AsyncClient::new(
                 AsyncBufReader::new(read_stream),
                 AsyncBufWriter::new(write_stream),
-            ))
+            );
+            client.set_utf8_mode(self.utf8_mode.clone());
+            if let Some(label) = &self.label {
+                client.set_label(label);
+            }
+            client.set_strict_replies(self.strict_replies);
+            if let Some((capacity, policy)) = self.event_buffer {
+                client.set_event_buffer(capacity, policy);
+            }
+            if let Some(session) = &self.session {
+                client.apply_session(session).await?;
+            }
+            Ok(client)
         }
     }
 }
 
-#[cfg(feature = "async-mio")]
-pub use asynchronous::{Builder, UnixStream};
+#[cfg(feature = "async-std")]
+pub mod asynchronous_async_std {
+    use async_std::io::{self, BufReader as AsyncBufReader, BufWriter as AsyncBufWriter};
+    pub use async_std::os::unix::net::UnixStream;
+    use std::os::unix::net::UnixStream as StdUnixStream;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use crate::async_std::AsyncClient;
+    use crate::config::ServerConfig;
+    use crate::demux::OverflowPolicy;
+    use crate::net::{Connector, HandshakeRetry};
+    use crate::protocol::Utf8Mode;
+    use crate::session::{Preset, SessionTemplate};
 
-impl Default for Builder {
-    fn default() -> Self {
-        Self::new()
+    use super::FifoPath;
+
+    pub struct Builder {
+        path: FifoPath,
+        connector: Option<Connector<StdUnixStream>>,
+        autospawn: bool,
+        session: Option<SessionTemplate>,
+        utf8_mode: Utf8Mode,
+        label: Option<String>,
+        strict_replies: bool,
+        event_buffer: Option<(usize, OverflowPolicy)>,
+        retry: HandshakeRetry,
+    }
+
+    impl Default for Builder {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Builder {
+        pub fn new() -> Self {
+            Self {
+                path: FifoPath::new(),
+                connector: None,
+                autospawn: false,
+                session: None,
+                utf8_mode: Utf8Mode::Strict,
+                label: None,
+                strict_replies: false,
+                event_buffer: None,
+                retry: HandshakeRetry::default(),
+            }
+        }
+
+        pub fn path<P>(&mut self, socket_path: P) -> &mut Self
+        where
+            P: AsRef<Path>,
+        {
+            self.path.set(socket_path);
+            self
+        }
+
+        /// Use the socket set in the configuration of the server, if any.
+        pub fn config(&mut self, config: &ServerConfig) -> &mut Self {
+            if let Some(path) = &config.socket_path {
+                self.path.set(path);
+            }
+            self
+        }
+
+        /// Session replayed on the connection before returning the client.
+        pub fn session(&mut self, template: &SessionTemplate) -> &mut Self {
+            self.session = Some(template.clone());
+            self
+        }
+
+        /// Session with the settings of a preset. It replaces any previous session.
+        pub fn preset(&mut self, preset: Preset) -> &mut Self {
+            self.session = Some(preset.session());
+            self
+        }
+
+        /// Replace invalid UTF-8 in received lines and call the handler with the bytes of the line.
+        pub fn lossy_utf8<F>(&mut self, handler: F) -> &mut Self
+        where
+            F: Fn(&[u8]) + Send + Sync + 'static,
+        {
+            self.utf8_mode = Utf8Mode::lossy(handler);
+            self
+        }
+
+        /// Name of the connection in the logs.
+        pub fn label(&mut self, label: &str) -> &mut Self {
+            self.label = Some(label.to_string());
+            self
+        }
+
+        /// Check that the answer to each request is read before the next request is sent.
+        pub fn strict_replies(&mut self, enabled: bool) -> &mut Self {
+            self.strict_replies = enabled;
+            self
+        }
+
+        /// Number of events kept while waiting for an answer and what to do when it's full.
+        pub fn event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+            self.event_buffer = Some((capacity, policy));
+            self
+        }
+
+        /// Try again to connect and apply the session while the server is starting.
+        ///
+        /// At most `attempts` attempts are made, about `delay` apart, as long as the error
+        /// is accepted by [`HandshakeRetry::is_retryable`]. Default is a single attempt.
+        pub fn handshake_retry(&mut self, attempts: u32, delay: Duration) -> &mut Self {
+            self.retry = HandshakeRetry::new(attempts, delay);
+            self
+        }
+
+        /// Create the stream with a function instead of connecting to the socket path.
+        ///
+        /// The function can return a socket obtained by socket activation, passed by a
+        /// privileged helper or connected in another namespace. The builder still configures
+        /// the stream and the client as if it had connected it.
+        pub fn with_stream<F>(&mut self, connect: F) -> &mut Self
+        where
+            F: Fn() -> io::Result<StdUnixStream> + Send + Sync + 'static,
+        {
+            self.connector = Some(Box::new(connect));
+            self
+        }
+
+        /// Spawn the server if the socket is stale before trying to connect again.
+        ///
+        /// When the server crashed, the socket is left and the connection is refused. The
+        /// builder always retries once. With this option, it runs `speech-dispatcher --spawn`
+        /// first.
+        pub fn autospawn(&mut self, enabled: bool) -> &mut Self {
+            self.autospawn = enabled;
+            self
+        }
+
+        async fn connect(&self) -> io::Result<UnixStream> {
+            match &self.connector {
+                Some(connect) => Ok(UnixStream::from(connect()?)),
+                None => {
+                    let path = self.path.get()?;
+                    match UnixStream::connect(&path).await {
+                        Err(err) if FifoPath::is_stale(&err, &path) => {
+                            FifoPath::before_retry(&path, self.autospawn);
+                            UnixStream::connect(&path)
+                                .await
+                                .map_err(|err| FifoPath::retry_error(err, &path))
+                        }
+                        result => result,
+                    }
+                }
+            }
+        }
+
+        pub async fn build(
+            &self,
+        ) -> io::Result<AsyncClient<AsyncBufReader<UnixStream>, AsyncBufWriter<UnixStream>>>
+        {
+            let mut attempt = 1;
+            loop {
+                match self.handshake().await {
+                    Err(err) => match self.retry.wait_after(attempt, &err) {
+                        Some(delay) => async_std::task::sleep(delay).await,
+                        None => return Err(err),
+                    },
+                    client => return client,
+                }
+                attempt += 1;
+            }
+        }
+
+        /// Connect and apply the session.
+        async fn handshake(
+            &self,
+        ) -> io::Result<AsyncClient<AsyncBufReader<UnixStream>, AsyncBufWriter<UnixStream>>>
+        {
+            let stream = self.connect().await?;
+            let mut client = AsyncClient::new(
+                AsyncBufReader::new(stream.clone()),
+                AsyncBufWriter::new(stream),
+            );
+            client.set_utf8_mode(self.utf8_mode.clone());
+            if let Some(label) = &self.label {
+                client.set_label(label);
+            }
+            client.set_strict_replies(self.strict_replies);
+            if let Some((capacity, policy)) = self.event_buffer {
+                client.set_event_buffer(capacity, policy);
+            }
+            if let Some(session) = &self.session {
+                client.apply_session(session).await?;
+            }
+            Ok(client)
+        }
     }
 }
 
+#[cfg(not(feature = "async-mio"))]
+pub use self::sync::{Builder, UnixStream};
+
+/// Builder of the client for mio.
+///
+/// The path depends on the features. It is deprecated in favor of [`mio::Builder`] and will
+/// always be the synchronous builder in a next release.
+#[cfg(feature = "async-mio")]
+#[deprecated(
+    since = "0.15.0",
+    note = "use fifo::mio::Builder, fifo::Builder will be fifo::sync::Builder"
+)]
+pub type Builder = self::mio::Builder;
+
+/// Stream of the client for mio.
+#[cfg(feature = "async-mio")]
+#[deprecated(
+    since = "0.15.0",
+    note = "use fifo::mio::UnixStream, fifo::UnixStream will be fifo::sync::UnixStream"
+)]
+pub type UnixStream = self::mio::UnixStream;
+
 #[cfg(test)]
 mod tests {
 
+    #[test]
+    fn stale_socket() -> std::io::Result<()> {
+        let socket_dir = tempfile::tempdir()?;
+        let socket_path = socket_dir.path().join("stale.socket");
+        drop(std::os::unix::net::UnixListener::bind(&socket_path)?);
+        let mut fifo_path = super::FifoPath::new();
+        fifo_path.set(&socket_path);
+        let attempts = std::cell::Cell::new(0);
+        let err = fifo_path
+            .connect(false, |path| {
+                attempts.set(attempts.get() + 1);
+                std::os::unix::net::UnixStream::connect(path)
+            })
+            .unwrap_err();
+        assert_eq!(2, attempts.get());
+        assert_eq!(std::io::ErrorKind::ConnectionRefused, err.kind());
+        assert!(err.to_string().contains("stale socket"));
+        Ok(())
+    }
+
     #[test]
     fn test_fifo_path() -> std::io::Result<()> {
         if std::env::var("XDG_RUNTIME_DIR").is_ok() {
@@ -0,0 +1,205 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Process-wide client connected on first use.
+//!
+//! The client is connected to the default socket of Speech Dispatcher with a name derived
+//! from the process environment. Threads share it through a mutex. If the connection is
+//! lost, the client reconnects on the next request.
+//!
+//! It is meant for small utilities that only need to speak a few messages. Don't use it:
+//! * In a library, since the client name and the settings belong to the application.
+//! * To receive notifications, since any thread can read the responses.
+//! * When threads need different settings, since `SET self` applies to all of them.
+//!
+//! Example
+//! ```no_run
+//! ssip_client_async::global::default().say("hello")?;
+//! # Ok::<(), ssip_client_async::ClientError>(())
+//! ```
+
+use std::io::{self, BufRead};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{
+    client::Client,
+    constants::*,
+    fifo::{self, sync::UnixStream},
+    protocol::text_lines,
+    types::*,
+};
+
+/// Size in bytes above which a chunk of text is spoken without waiting for the end of the
+/// paragraph.
+const MAX_CHUNK_SIZE: usize = 4096;
+
+static DEFAULT_CLIENT: Mutex<Option<Client<UnixStream>>> = Mutex::new(None);
+
+/// Lock on the process-wide client.
+///
+/// Other threads wait until it is dropped.
+pub struct DefaultClient {
+    client: MutexGuard<'static, Option<Client<UnixStream>>>,
+}
+
+/// Lock the process-wide client.
+pub fn default() -> DefaultClient {
+    DefaultClient {
+        client: DEFAULT_CLIENT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+    }
+}
+
+/// Read the next chunk of text: a paragraph, or the lines read so far if it is too long.
+///
+/// Return false at the end of the input.
+fn read_chunk<R: BufRead>(input: &mut R, chunk: &mut String) -> io::Result<bool> {
+    chunk.clear();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(!chunk.is_empty());
+        }
+        if line.trim().is_empty() {
+            if !chunk.is_empty() {
+                return Ok(true);
+            }
+        } else {
+            chunk.push_str(&line);
+            if chunk.len() >= MAX_CHUNK_SIZE {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Return true if the error means that the server closed the connection.
+fn is_connection_lost(err: &ClientError) -> bool {
+    match err {
+        ClientError::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+fn connect() -> ClientResult<Client<UnixStream>> {
+    let mut client = fifo::sync::Builder::new().build()?;
+    client
+        .set_client_name(ClientName::from_env())?
+        .check_client_name_set()?;
+    Ok(client)
+}
+
+impl DefaultClient {
+    /// Run requests with the client, connecting it first if needed.
+    ///
+    /// If the connection was lost, the client reconnects and the function is called once
+    /// more. The requests sent before the connection was lost may have been executed.
+    pub fn with<T, F>(&mut self, mut f: F) -> ClientResult<T>
+    where
+        F: FnMut(&mut Client<UnixStream>) -> ClientResult<T>,
+    {
+        let connected = self.client.is_some();
+        let result = f(self.connected()?);
+        match result {
+            Err(err) if is_connection_lost(&err) => {
+                *self.client = None;
+                if connected {
+                    let result = f(self.connected()?);
+                    if matches!(&result, Err(err) if is_connection_lost(err)) {
+                        *self.client = None;
+                    }
+                    result
+                } else {
+                    Err(err)
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Speak a text and return the message id.
+    pub fn say(&mut self, text: &str) -> ClientResult<MessageId> {
+        let lines = text_lines(text);
+        self.with(|client| {
+            client
+                .speak()?
+                .check_receiving_data()?
+                .send_lines(&lines)?
+                .receive_message_id()
+        })
+    }
+
+    /// Speak a text read to the end, one paragraph at a time.
+    ///
+    /// Each paragraph is spoken as soon as it is read, so that speech starts before the
+    /// end of the input. Long paragraphs are split between lines. Return the message ids.
+    pub fn say_reader<R: BufRead>(&mut self, mut input: R) -> ClientResult<Vec<MessageId>> {
+        let mut chunk = String::new();
+        let mut ids = Vec::new();
+        while read_chunk(&mut input, &mut chunk)? {
+            ids.push(self.say(&chunk)?);
+        }
+        Ok(ids)
+    }
+
+    /// Speak the standard input read to the end, as [`DefaultClient::say_reader`].
+    pub fn say_stdin(&mut self) -> ClientResult<Vec<MessageId>> {
+        self.say_reader(io::stdin().lock())
+    }
+
+    /// Close the connection. The next request connects again.
+    pub fn disconnect(&mut self) {
+        if let Some(mut client) = self.client.take() {
+            let _ = client.quit().and_then(|client| client.check_status(OK_BYE));
+        }
+    }
+
+    fn connected(&mut self) -> ClientResult<&mut Client<UnixStream>> {
+        if self.client.is_none() {
+            *self.client = Some(connect()?);
+        }
+        Ok(self.client.as_mut().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{read_chunk, MAX_CHUNK_SIZE};
+
+    fn chunks(text: &str) -> Vec<String> {
+        let mut input = text.as_bytes();
+        let mut chunk = String::new();
+        let mut chunks = Vec::new();
+        while read_chunk(&mut input, &mut chunk).unwrap() {
+            chunks.push(chunk.clone());
+        }
+        chunks
+    }
+
+    #[test]
+    fn split_paragraphs() {
+        assert_eq!(
+            vec!["one\ntwo\n", "three"],
+            chunks("\none\ntwo\n\n \nthree")
+        );
+        assert!(chunks("\n\n").is_empty());
+        let line = format!("{}\n", "a".repeat(MAX_CHUNK_SIZE / 2));
+        assert_eq!(2, chunks(&line.repeat(3)).len());
+    }
+}
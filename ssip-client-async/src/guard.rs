@@ -0,0 +1,99 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Messages canceled when they go out of scope.
+
+use std::io::{Read, Write};
+
+use crate::{
+    client::{Client, Source},
+    types::*,
+};
+
+/// Message canceled when the guard is dropped before it ends.
+///
+/// The guard is returned by [`Client::say_guarded`]. It keeps the client borrowed, the
+/// events must be received with [`SpeakGuard::receive_event`] or [`SpeakGuard::wait`] so
+/// that the guard knows when the message is finished. Notifications of end and cancel must
+/// be enabled, otherwise the message is always canceled on drop.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, ClientName};
+/// let mut client = fifo::Builder::new().build()?;
+/// client
+///     .set_client_name(ClientName::new("joe", "guard")?)?
+///     .check_client_name_set()?;
+/// {
+///     let _guard = client.say_guarded("Save button")?;
+///     // The focus moves to another element: the announcement is canceled.
+/// }
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+pub struct SpeakGuard<'a, S: Read + Write + Source> {
+    client: &'a mut Client<S>,
+    id: MessageId,
+    finished: bool,
+}
+
+impl<'a, S: Read + Write + Source> SpeakGuard<'a, S> {
+    pub(crate) fn new(client: &'a mut Client<S>, id: MessageId) -> Self {
+        Self {
+            client,
+            id,
+            finished: false,
+        }
+    }
+
+    /// Id of the message.
+    pub fn message_id(&self) -> MessageId {
+        self.id
+    }
+
+    /// Return true if the end or the cancellation of the message was received.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Receive the next event and record whether it finishes the message.
+    pub fn receive_event(&mut self) -> ClientResult<Event> {
+        let event = self.client.receive_event()?;
+        if matches!(event.ntype, EventType::End | EventType::Cancel)
+            && event.message_id() == Some(self.id)
+        {
+            self.finished = true;
+        }
+        Ok(event)
+    }
+
+    /// Receive the events until the message is finished and return them.
+    pub fn wait(mut self) -> ClientResult<Vec<Event>> {
+        let mut events = Vec::new();
+        while !self.finished {
+            events.push(self.receive_event()?);
+        }
+        Ok(events)
+    }
+
+    /// Let the message be spoken to the end and return its id.
+    pub fn release(mut self) -> MessageId {
+        self.finished = true;
+        self.id
+    }
+}
+
+impl<S: Read + Write + Source> Drop for SpeakGuard<'_, S> {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(err) = self.client.cancel_message(self.id) {
+                log::warn!("cannot cancel message {}: {}", self.id, err);
+            }
+        }
+    }
+}
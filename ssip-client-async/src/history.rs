@@ -0,0 +1,89 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Navigation in the history of messages.
+
+use std::io::{Read, Write};
+
+use crate::client::{Client, Source};
+use crate::constants::*;
+use crate::types::*;
+
+/// Cursor in the history of the messages of a client.
+///
+/// Each method sends the request and checks the answer, so that the cursor can be moved
+/// without handling the positions and the status codes.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, ClientName, ClientScope};
+/// let mut client = fifo::Builder::new().build()?;
+/// client
+///     .set_client_name(ClientName::new("joe", "history")?)?
+///     .check_client_name_set()?;
+/// let mut cursor = client.history_cursor(ClientScope::Current);
+/// let last = cursor.last()?.current_message()?;
+/// let previous = cursor.move_backward()?.current_message()?;
+/// println!("last: {}, previous: {}", last.0, previous.0);
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+pub struct HistoryCursor<'a, S: Read + Write + Source> {
+    client: &'a mut Client<S>,
+    scope: ClientScope,
+}
+
+impl<'a, S: Read + Write + Source> HistoryCursor<'a, S> {
+    /// Cursor in the history of the clients in the scope.
+    pub fn new(client: &'a mut Client<S>, scope: ClientScope) -> Self {
+        Self { client, scope }
+    }
+
+    /// Move the cursor to the first message.
+    pub fn first(&mut self) -> ClientResult<&mut Self> {
+        self.set(HistoryPosition::First, OK_CUR_SET_FIRST)
+    }
+
+    /// Move the cursor to the last message.
+    pub fn last(&mut self) -> ClientResult<&mut Self> {
+        self.set(HistoryPosition::Last, OK_CUR_SET_LAST)
+    }
+
+    /// Move the cursor to a position.
+    pub fn set_position(&mut self, pos: u16) -> ClientResult<&mut Self> {
+        self.set(HistoryPosition::Pos(pos), OK_CUR_SET_POS)
+    }
+
+    /// Move the cursor to the next message.
+    pub fn move_forward(&mut self) -> ClientResult<&mut Self> {
+        self.client
+            .history_move_cursor(CursorDirection::Forward)?
+            .check_status(OK_CUR_MOV_FOR)?;
+        Ok(self)
+    }
+
+    /// Move the cursor to the previous message.
+    pub fn move_backward(&mut self) -> ClientResult<&mut Self> {
+        self.client
+            .history_move_cursor(CursorDirection::Backward)?
+            .check_status(OK_CUR_MOV_BACK)?;
+        Ok(self)
+    }
+
+    /// Message the cursor is pointing to.
+    pub fn current_message(&mut self) -> ClientResult<CursorPosition> {
+        self.client.history_get_cursor()?.receive_cursor_position()
+    }
+
+    fn set(&mut self, pos: HistoryPosition, code: ReturnCode) -> ClientResult<&mut Self> {
+        self.client
+            .history_set_cursor(self.scope, pos)?
+            .check_status(code)?;
+        Ok(self)
+    }
+}
@@ -0,0 +1,97 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! JSON encoding of requests and responses.
+//!
+//! Tools that don't speak SSIP can describe requests in JSON and read the responses back
+//! in the same format. The encoding is the one derived by serde: a unit variant is a
+//! string and a variant with fields is an object with a single key.
+//!
+//! Example
+//! ```
+//! use ssip_client_async::{json, ClientScope, Request};
+//! let request: Request = json::from_json(r#"{"SetRate":["Current",10]}"#)?;
+//! assert_eq!(Request::SetRate(ClientScope::Current, 10), request);
+//! assert_eq!(r#""Speak""#, json::to_json(&Request::Speak)?);
+//! # Ok::<(), ssip_client_async::ClientError>(())
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::types::{ClientError, ClientResult};
+
+/// Encode a request, a response or any type of the protocol in JSON.
+pub fn to_json<T: Serialize>(value: &T) -> ClientResult<String> {
+    serde_json::to_string(value).map_err(json_error)
+}
+
+/// Decode a request, a response or any type of the protocol from JSON.
+///
+/// Values are checked as when they are created in Rust, so that a client name or a language
+/// tag that the server would reject fails here.
+pub fn from_json<T: DeserializeOwned>(json: &str) -> ClientResult<T> {
+    serde_json::from_str(json).map_err(json_error)
+}
+
+fn json_error(err: serde_json::Error) -> ClientError {
+    ClientError::invalid_data(&format!("JSON: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{from_json, to_json};
+    use crate::types::*;
+
+    #[test]
+    fn request_round_trip() {
+        let requests = vec![
+            Request::SetName(ClientName::new("joe", "hello").unwrap()),
+            Request::Speak,
+            Request::SendLines(vec!["hello".to_string(), "world".to_string()]),
+            Request::SetRate(ClientScope::Current, -5),
+            Request::SetLanguage(ClientScope::All, "fr-FR".parse().unwrap()),
+            Request::Cancel(MessageScope::Message(12)),
+        ];
+        for request in requests {
+            let json = to_json(&request).unwrap();
+            assert_eq!(request, from_json::<Request>(&json).unwrap());
+        }
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let responses = vec![
+            Response::MessageQueued,
+            Response::ReceivingData,
+            Response::EventEnd(EventId::new(21, 1)),
+            Response::GetRate(-5),
+        ];
+        for response in responses {
+            let json = to_json(&response).unwrap();
+            assert_eq!(response, from_json::<Response>(&json).unwrap());
+        }
+    }
+
+    #[test]
+    fn invalid_json() {
+        for json in [
+            "{",
+            r#""Unknown""#,
+            r#"{"SetName":{"user":"joe doe","application":"hello","component":"main"}}"#,
+        ] {
+            match from_json::<Request>(json) {
+                Err(ClientError::Io(err)) => {
+                    assert_eq!(std::io::ErrorKind::InvalidData, err.kind())
+                }
+                result => panic!("unexpected result for {}: {:?}", json, result),
+            }
+        }
+    }
+}
@@ -16,37 +16,86 @@
 //!
 //! Example
 //! ```no_run
-//! use ssip_client::{fifo, ClientName};
+//! use ssip_client_async::{fifo, ClientName};
 //! let mut client = fifo::Builder::new().build()?;
 //! client
-//!     .set_client_name(ClientName::new("joe", "hello"))?
+//!     .set_client_name(ClientName::new("joe", "hello")?)?
 //!     .check_client_name_set()?;
 //! let msg_id = client.speak()?.send_line("hello")?.receive_message_id()?;
 //! client.quit()?;
-//! # Ok::<(), ssip_client::ClientError>(())
+//! # Ok::<(), ssip_client_async::ClientError>(())
 //! ```
+//!
+//! ## Stability
+//!
+//! The types of the protocol are defined in the `ssip` crate, re-exported as [`types`]
+//! and at the root of this crate. Both paths name the same items: the root is the path
+//! to use, [`types`] is kept for code written against earlier versions. The modules and
+//! items not listed in the documentation are internal and can change in any release.
+//!
+//! The enums [`Request`], [`RequestRef`], [`Response`] and [`ClientError`] are
+//! `#[non_exhaustive]`: new requests and errors are added in minor releases, so a
+//! `match` on them must have a wildcard arm. A client sending a request that it doesn't
+//! implement yet returns [`ClientError::Unsupported`].
+//!
+//! To branch on a response, match [`Response::kind`] that returns a fieldless
+//! [`ResponseKind`], and read the data from the [`Response`].
+
+#[macro_use]
+pub mod protocol;
 
 #[macro_use]
-mod protocol;
+mod conversation;
 
 mod poll;
+mod state;
+/// Types of the protocol, also re-exported at the root of the crate.
 pub use ssip as types;
 
 pub mod client;
+#[cfg(feature = "compat")]
+pub mod compat;
+pub mod config;
 pub mod constants;
+pub mod debug;
+pub mod demux;
+pub mod diagnostics;
+#[cfg(feature = "encoding")]
+pub mod encoding;
 #[cfg(unix)]
 pub mod fifo;
+#[cfg(all(feature = "global", unix))]
+pub mod global;
+pub mod guard;
+pub mod history;
+#[cfg(feature = "json-proto")]
+pub mod json;
 pub mod net;
+pub mod prelude;
+pub mod priority;
+pub mod progress;
+pub mod ramp;
+pub mod ratelimit;
+pub mod sanitize;
+pub mod session;
 pub mod tcp;
+pub mod tracker;
 
-#[cfg(any(not(feature = "async-mio"), doc))]
 pub use client::Client;
 
-#[cfg(any(feature = "async-std", doc))]
+#[cfg(feature = "async-std")]
 pub mod async_std;
-#[cfg(any(feature = "tokio", doc))]
+#[cfg(feature = "tokio")]
+pub mod broadcast;
+#[cfg(feature = "tokio")]
+pub mod shutdown;
+#[cfg(feature = "tokio")]
 pub mod tokio;
 
 pub use constants::*;
 pub use poll::QueuedClient;
+#[cfg(any(unix, target_os = "wasi", windows))]
+pub use poll::{Pollable, RawHandle};
+pub use protocol::{Decoder, InvalidUtf8Handler, ParseMode, UnknownEventHandler, Utf8Mode};
+pub use session::SessionTemplate;
 pub use types::*;
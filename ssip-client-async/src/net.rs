@@ -7,12 +7,120 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-#[cfg(not(feature = "async-mio"))]
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+
 pub(crate) enum StreamMode {
     Blocking,
     NonBlocking,
     TimeOut(std::time::Duration),
 }
 
+/// Function creating a connected stream in place of the builder.
+pub(crate) type Connector<S> = Box<dyn Fn() -> std::io::Result<S> + Send + Sync>;
+
+/// Attempts to build the client while the server is starting.
+///
+/// Once spawned, the server may need hundreds of milliseconds before it accepts the
+/// connection and answers the first requests. The asynchronous builders try again when
+/// [`HandshakeRetry::is_retryable`] is true for the error, waiting the delay plus a random
+/// jitter of up to half the delay so that clients started together don't retry together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeRetry {
+    attempts: u32,
+    delay: Duration,
+}
+
+impl HandshakeRetry {
+    /// Make at most `attempts` attempts, including the first one. 0 is replaced by 1.
+    pub fn new(attempts: u32, delay: Duration) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            delay,
+        }
+    }
+
+    /// Maximum number of attempts.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Delay between two attempts, without the jitter.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Tell if the error can be caused by a server not ready yet.
+    ///
+    /// It's the case when the socket doesn't exist yet, when the connection is refused and
+    /// when it is closed by the server before the session is applied.
+    pub fn is_retryable(err: &io::Error) -> bool {
+        matches!(
+            err.kind(),
+            io::ErrorKind::NotFound
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        )
+    }
+
+    /// Time to wait before the next attempt after the attempt number `attempt` failed, or
+    /// `None` if the error must be returned.
+    pub fn wait_after(&self, attempt: u32, err: &io::Error) -> Option<Duration> {
+        if attempt < self.attempts && Self::is_retryable(err) {
+            log::warn!(
+                "handshake attempt {}/{} failed: {}, retrying",
+                attempt,
+                self.attempts,
+                err
+            );
+            Some(self.delay + jitter(self.delay / 2))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for HandshakeRetry {
+    /// A single attempt.
+    fn default() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+}
+
+/// Random duration between zero and `max`.
+fn jitter(max: Duration) -> Duration {
+    // The keys of the hasher are random, which is enough to spread the attempts.
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64((random % 1024) as f64 / 1024.0)
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+
+    use std::io;
+    use std::time::Duration;
+
+    use super::HandshakeRetry;
+
+    #[test]
+    fn handshake_retry() {
+        let delay = Duration::from_millis(100);
+        let retry = HandshakeRetry::new(3, delay);
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        for attempt in 1..3 {
+            let wait = retry.wait_after(attempt, &refused).unwrap();
+            assert!(wait >= delay && wait <= delay + delay / 2, "{:?}", wait);
+        }
+        assert_eq!(None, retry.wait_after(3, &refused));
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(None, retry.wait_after(1, &denied));
+        let eof = io::Error::from(io::ErrorKind::UnexpectedEof);
+        assert!(retry.wait_after(1, &eof).is_some());
+        assert_eq!(None, HandshakeRetry::default().wait_after(1, &refused));
+        assert_eq!(1, HandshakeRetry::new(0, delay).attempts());
+    }
+}
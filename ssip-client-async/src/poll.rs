@@ -27,19 +27,85 @@ mod mio {
     ///
     /// See [`mio::Token`](https://docs.rs/mio/latest/mio/struct.Token.html#).
     pub struct Token(pub usize);
+
+    pub mod event {
+        /// Source that can be registered.
+        ///
+        /// See [`mio::event::Source`](https://docs.rs/mio/latest/mio/event/trait.Source.html).
+        pub trait Source {}
+    }
 }
 
 const INITIAL_REQUEST_QUEUE_CAPACITY: usize = 4;
 
+/// Raw handle of a stream: a file descriptor on unix, a socket on windows.
+#[cfg(any(unix, target_os = "wasi"))]
+pub type RawHandle = std::os::fd::RawFd;
+
+/// Raw handle of a stream: a file descriptor on unix, a socket on windows.
+#[cfg(windows)]
+pub type RawHandle = std::os::windows::io::RawSocket;
+
+/// Stream whose readiness can be watched by an event loop.
+///
+/// The handles of a [`QueuedClient`] can be registered in any reactor that accepts raw
+/// handles: with [mio](https://crates.io/crates/mio) through `mio::unix::SourceFd`, with
+/// [popol](https://crates.io/crates/popol) directly as a `RawFd`, or with
+/// [calloop](https://crates.io/crates/calloop) through a `Generic` source on the borrowed
+/// descriptor. The input must be watched for reading and the output for writing.
+///
+/// Example
+/// ```no_run
+/// # #[cfg(unix)]
+/// # fn main() -> ssip_client_async::ClientResult<()> {
+/// use mio::{unix::SourceFd, Interest, Poll, Token};
+/// use ssip_client_async::{fifo, QueuedClient};
+/// let client = QueuedClient::new(fifo::sync::Builder::new().nonblocking().build()?);
+/// let poll = Poll::new()?;
+/// let (input, output) = (client.input_handle(), client.output_handle());
+/// poll.registry()
+///     .register(&mut SourceFd(&input), Token(0), Interest::READABLE)?;
+/// poll.registry()
+///     .register(&mut SourceFd(&output), Token(1), Interest::WRITABLE)?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(unix))]
+/// # fn main() {}
+/// ```
+#[cfg(any(unix, target_os = "wasi", windows))]
+pub trait Pollable {
+    /// Handle to register in the event loop.
+    fn raw_handle(&self) -> RawHandle;
+}
+
+#[cfg(any(unix, target_os = "wasi"))]
+impl<T: std::os::fd::AsRawFd> Pollable for T {
+    fn raw_handle(&self) -> RawHandle {
+        self.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<T: std::os::windows::io::AsRawSocket> Pollable for T {
+    fn raw_handle(&self) -> RawHandle {
+        self.as_raw_socket()
+    }
+}
+
 /// Client with a queue of requests.
 ///
 /// The client can be used with crates like [popol](https://crates.io/crates/popol) or
-/// with [mio](https://crates.io/crates/mio) if feature `async-mio` is enabled.
+/// with [mio](https://crates.io/crates/mio) if feature `async-mio` is enabled. With other
+/// event loops, register the handles of the streams, see [`Pollable`].
 ///
 /// When the output is ready, a next event can be sent.
 pub struct QueuedClient<S: Read + Write + Source> {
     client: Client<S>,
     requests: VecDeque<Request>,
+    /// True if the last request sent started a message whose lines are not sent yet.
+    receiving_data: bool,
+    /// True once QUIT was sent.
+    closed: bool,
 }
 
 impl<S: Read + Write + Source> QueuedClient<S> {
@@ -48,30 +114,21 @@ impl<S: Read + Write + Source> QueuedClient<S> {
         Self {
             client,
             requests: VecDeque::with_capacity(INITIAL_REQUEST_QUEUE_CAPACITY),
+            receiving_data: false,
+            closed: false,
         }
     }
 
-    #[cfg(all(not(feature = "async-mio"), unix))]
+    #[cfg(unix)]
     /// Input source.
     pub fn input_source(&self) -> &S {
-        self.client.input_source()
+        self.client.input_stream()
     }
 
-    #[cfg(all(not(feature = "async-mio"), unix))]
+    #[cfg(unix)]
     /// Output source.
     pub fn output_source(&self) -> &S {
-        self.client.output_source()
-    }
-
-    #[cfg(any(feature = "async-mio", doc))]
-    /// Register client
-    pub fn register(
-        &mut self,
-        poll: &mio::Poll,
-        input_token: mio::Token,
-        output_token: mio::Token,
-    ) -> std::io::Result<()> {
-        self.client.register(poll, input_token, output_token)
+        self.client.output_stream()
     }
 
     /// Push a new request in the queue.
@@ -79,6 +136,13 @@ impl<S: Read + Write + Source> QueuedClient<S> {
         self.requests.push_back(request);
     }
 
+    /// Push a copy of a borrowed request in the queue.
+    ///
+    /// The queue keeps the requests until they are sent, so the data is cloned.
+    pub fn push_ref(&mut self, request: RequestRef<'_>) {
+        self.requests.push_back(request.to_request());
+    }
+
     /// Pop the last request in the queue.
     pub fn pop(&mut self) -> Option<Request> {
         self.requests.pop_back()
@@ -94,23 +158,86 @@ impl<S: Read + Write + Source> QueuedClient<S> {
         !self.requests.is_empty()
     }
 
+    /// Return true once QUIT was sent. No other request can be sent after.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
     /// Write one pending request if any.
     ///
     /// Instance of `mio::Poll` generates a writable event only once until the socket returns `WouldBlock`.
     /// This error is mapped to `ClientError::NotReady`.
+    ///
+    /// QUIT is the last request sent. The requests pushed after are discarded and the error
+    /// `ClientError::Closed` is returned.
     pub fn send_next(&mut self) -> ClientResult<bool> {
+        if self.closed && !self.requests.is_empty() {
+            self.requests.clear();
+            return Err(ClientError::Closed);
+        }
         if let Some(request) = self.requests.pop_front() {
+            match request {
+                Request::Speak => self.receiving_data = true,
+                Request::SendLine(_) | Request::SendLines(_) => self.receiving_data = false,
+                _ => (),
+            }
+            let quit = matches!(request, Request::Quit);
             self.client.send(request)?;
+            self.closed = quit;
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Silence everything as soon as possible.
+    ///
+    /// The pending requests are discarded and replaced by requests to cancel and stop all
+    /// the messages. If a message was started, it is ended first. The responses must be
+    /// received as usual. Return the number of discarded requests.
+    pub fn emergency_stop(&mut self) -> usize {
+        let discarded = self.requests.len();
+        self.requests.clear();
+        if self.receiving_data {
+            self.requests.push_back(Request::SendLines(Vec::new()));
+        }
+        self.requests.push_back(Request::Cancel(MessageScope::All));
+        self.requests.push_back(Request::Stop(MessageScope::All));
+        discarded
+    }
+
     /// Receive one response.
     ///
-    /// Must be called each time a readable event is returned by `mio::Poll`.
+    /// Must be called each time a readable event is returned by `mio::Poll`. The bytes
+    /// received are decoded with a [`Decoder`](crate::Decoder). If the answer is not complete
+    /// yet, its lines are kept until the next call and `ClientError::NotReady` is returned.
     pub fn receive_next(&mut self) -> ClientResult<Response> {
-        self.client.receive()
+        self.client.try_receive()?.ok_or(ClientError::NotReady)
+    }
+}
+
+#[cfg(any(unix, target_os = "wasi", windows))]
+impl<S: Read + Write + Source + Pollable> QueuedClient<S> {
+    /// Handle to watch for reading.
+    pub fn input_handle(&self) -> RawHandle {
+        self.client.input_stream().raw_handle()
+    }
+
+    /// Handle to watch for writing.
+    pub fn output_handle(&self) -> RawHandle {
+        self.client.output_stream().raw_handle()
+    }
+}
+
+#[cfg(any(feature = "async-mio", doc))]
+impl<S: Read + Write + Source + mio::event::Source> QueuedClient<S> {
+    /// Register client
+    pub fn register(
+        &mut self,
+        poll: &mio::Poll,
+        input_token: mio::Token,
+        output_token: mio::Token,
+    ) -> std::io::Result<()> {
+        self.client.register(poll, input_token, output_token)
     }
 }
@@ -0,0 +1,37 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Types used by most applications.
+//!
+//! The builders are reached through the modules [`fifo`] and [`tcp`]. The asynchronous
+//! clients have the same name in each backend and are not included.
+//!
+//! Example
+//! ```no_run
+//! use ssip_client_async::prelude::*;
+//! let mut client = fifo::sync::Builder::new().build()?;
+//! client
+//!     .set_client_name(ClientName::new("joe", "hello")?)?
+//!     .check_client_name_set()?;
+//! client.set_priority(Priority::Text)?.check_status(OK_PRIORITY_SET)?;
+//! let msg_id = client.speak()?.send_line("hello")?.receive_message_id()?;
+//! # Ok::<(), ClientError>(())
+//! ```
+
+pub use crate::client::Client;
+pub use crate::constants::*;
+#[cfg(unix)]
+pub use crate::fifo;
+pub use crate::poll::QueuedClient;
+pub use crate::session::SessionTemplate;
+pub use crate::tcp;
+pub use crate::types::{
+    ClientError, ClientName, ClientResult, ClientScope, Event, EventId, EventType, MessageId,
+    MessageScope, Priority, Request, RequestRef, Response, StatusLine,
+};
@@ -0,0 +1,224 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Interactions between the priorities of the messages, as done by the server.
+
+use crate::types::*;
+
+/// What happens to a message speaking or waiting when another message arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fate {
+    /// The message is not affected.
+    Kept,
+    /// The message waits until the new one is spoken.
+    Postponed,
+    /// The message is canceled.
+    Canceled,
+}
+
+/// What happens to a message when it arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Admission {
+    /// The message is spoken, immediately or after the others.
+    Admitted,
+    /// The message is thrown away.
+    Discarded,
+}
+
+/// Consequences of sending a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prediction {
+    pub admission: Admission,
+    /// Messages that will be canceled.
+    pub canceled: Vec<MessageId>,
+    /// Messages that will wait for the new one.
+    pub postponed: Vec<MessageId>,
+}
+
+/// Rules applied by Speech Dispatcher when messages of different priorities meet.
+///
+/// * `important` is never interrupted. It interrupts any other message. Waiting messages
+///   of priority `message` and `text` are postponed, `notification` and `progress` are
+///   canceled.
+/// * `message` waits for `important` and `message`. It cancels `text`, `notification` and
+///   `progress`.
+/// * `text` waits for `important` and `message`. It cancels the previous `text`,
+///   `notification` and `progress`.
+/// * `notification` is discarded if another message is speaking or waiting. It is canceled
+///   by any other message, including another `notification`.
+/// * `progress` is like `notification`, except that it is never discarded so that the last
+///   message of a sequence is always spoken. It replaces the previous `progress`.
+///
+/// The model predicts what the server will do, for example to avoid sending a message that
+/// is going to be thrown away.
+///
+/// Example
+/// ```
+/// use ssip_client_async::{priority::{Admission, PriorityModel}, Priority};
+/// let model = PriorityModel::new();
+/// let prediction = model.predict(&Priority::Important, Some((21, &Priority::Text)), &[
+///     (22, &Priority::Message),
+///     (23, &Priority::Notification),
+/// ]);
+/// assert_eq!(Admission::Admitted, prediction.admission);
+/// assert_eq!(vec![21, 23], prediction.canceled);
+/// assert_eq!(vec![22], prediction.postponed);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PriorityModel {}
+
+impl PriorityModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What happens to an existing message when a message of priority `incoming` arrives.
+    pub fn fate(&self, incoming: &Priority, existing: &Priority, speaking: bool) -> Fate {
+        use Priority::*;
+        match (incoming, existing) {
+            (_, Important) => Fate::Kept,
+            (Important, Message | Text) if speaking => Fate::Canceled,
+            (Important, Message | Text) => Fate::Postponed,
+            (Message | Text, Message) => Fate::Kept,
+            (Notification | Progress, Message | Text) => Fate::Kept,
+            (_, Text | Notification) => Fate::Canceled,
+            (Notification, Progress) => Fate::Kept,
+            (_, Progress) => Fate::Canceled,
+        }
+    }
+
+    /// What happens to a message of priority `incoming` when other messages are pending.
+    pub fn admission<'a, I>(&self, incoming: &Priority, pending: I) -> Admission
+    where
+        I: IntoIterator<Item = &'a Priority>,
+    {
+        let blocked = *incoming == Priority::Notification
+            && pending
+                .into_iter()
+                .any(|priority| *priority != Priority::Notification);
+        if blocked {
+            Admission::Discarded
+        } else {
+            Admission::Admitted
+        }
+    }
+
+    /// Predict the consequences of sending a message of priority `incoming`, given the
+    /// message speaking and the messages waiting.
+    ///
+    /// Nothing is affected by a message that is discarded.
+    pub fn predict(
+        &self,
+        incoming: &Priority,
+        speaking: Option<(MessageId, &Priority)>,
+        queued: &[(MessageId, &Priority)],
+    ) -> Prediction {
+        let pending = speaking.iter().chain(queued.iter());
+        let admission = self.admission(incoming, pending.clone().map(|(_, priority)| *priority));
+        let mut prediction = Prediction {
+            admission,
+            canceled: Vec::new(),
+            postponed: Vec::new(),
+        };
+        if admission == Admission::Admitted {
+            let speaking_id = speaking.map(|(id, _)| id);
+            for (id, priority) in pending {
+                match self.fate(incoming, priority, Some(*id) == speaking_id) {
+                    Fate::Kept => (),
+                    Fate::Postponed => prediction.postponed.push(*id),
+                    Fate::Canceled => prediction.canceled.push(*id),
+                }
+            }
+        }
+        prediction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Admission, Fate, PriorityModel};
+    use crate::types::Priority;
+
+    #[test]
+    fn fates() {
+        let model = PriorityModel::new();
+        for priority in [
+            Priority::Important,
+            Priority::Message,
+            Priority::Text,
+            Priority::Notification,
+            Priority::Progress,
+        ] {
+            assert_eq!(
+                Fate::Kept,
+                model.fate(&priority, &Priority::Important, true)
+            );
+            assert_eq!(
+                Fate::Canceled,
+                model.fate(&priority, &Priority::Notification, false)
+            );
+        }
+        assert_eq!(
+            Fate::Postponed,
+            model.fate(&Priority::Important, &Priority::Message, false)
+        );
+        assert_eq!(
+            Fate::Canceled,
+            model.fate(&Priority::Important, &Priority::Text, true)
+        );
+        assert_eq!(
+            Fate::Kept,
+            model.fate(&Priority::Message, &Priority::Message, true)
+        );
+        assert_eq!(
+            Fate::Canceled,
+            model.fate(&Priority::Message, &Priority::Text, true)
+        );
+        assert_eq!(
+            Fate::Canceled,
+            model.fate(&Priority::Text, &Priority::Text, false)
+        );
+        assert_eq!(
+            Fate::Canceled,
+            model.fate(&Priority::Progress, &Priority::Progress, true)
+        );
+        assert_eq!(
+            Fate::Kept,
+            model.fate(&Priority::Notification, &Priority::Progress, true)
+        );
+    }
+
+    #[test]
+    fn discarded_notification() {
+        let model = PriorityModel::new();
+        let prediction = model.predict(
+            &Priority::Notification,
+            Some((21, &Priority::Text)),
+            &[(22, &Priority::Notification)],
+        );
+        assert_eq!(Admission::Discarded, prediction.admission);
+        assert!(prediction.canceled.is_empty());
+        let prediction = model.predict(
+            &Priority::Notification,
+            Some((22, &Priority::Notification)),
+            &[],
+        );
+        assert_eq!(Admission::Admitted, prediction.admission);
+        assert_eq!(vec![22], prediction.canceled);
+        let prediction = model.predict(
+            &Priority::Progress,
+            Some((21, &Priority::Text)),
+            &[(22, &Priority::Progress)],
+        );
+        assert_eq!(Admission::Admitted, prediction.admission);
+        assert_eq!(vec![22], prediction.canceled);
+        assert!(prediction.postponed.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::types::*;
+
+/// Default prefix of the names of the marks inserted in the text.
+const MARK_PREFIX: &str = "progress";
+
+/// Position reached in a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of characters of the text already spoken.
+    pub offset: usize,
+    /// Number of characters of the text.
+    pub length: usize,
+}
+
+impl Progress {
+    /// Percentage of the text already spoken.
+    pub fn percent(&self) -> f32 {
+        if self.length == 0 {
+            100.0
+        } else {
+            self.offset as f32 * 100.0 / self.length as f32
+        }
+    }
+}
+
+/// Estimate the progress of a message from its index marks.
+///
+/// A mark is inserted every few words of the text. The text must be sent as SSML, with
+/// SSML mode on and notifications of index marks enabled. The progress is updated with
+/// the events received for the message.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, progress::ProgressTracker, ClientName, NotificationType};
+/// let mut client = fifo::Builder::new().build()?;
+/// client
+///     .set_client_name(ClientName::new("joe", "progress")?)?
+///     .check_client_name_set()?
+///     .set_ssml_mode(true)?
+///     .check_status(ssip_client_async::OK_SSML_MODE_SET)?
+///     .set_notification(NotificationType::All, true)?
+///     .check_status(ssip_client_async::OK_NOTIFICATION_SET)?;
+/// let mut tracker = ProgressTracker::new("A long text to read", 2);
+/// let id = client
+///     .speak()?
+///     .check_receiving_data()?
+///     .send_line(tracker.ssml())?
+///     .receive_message_id()?;
+/// tracker.set_message_id(id);
+/// while tracker.progress().offset < tracker.progress().length {
+///     if let Some(progress) = tracker.update(&client.receive_event()?) {
+///         println!("{:.0}%", progress.percent());
+///     }
+/// }
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    ssml: String,
+    /// Character offset of each mark.
+    marks: Vec<usize>,
+    length: usize,
+    offset: usize,
+    message: Option<MessageId>,
+    /// Prefix of the mark names, followed by a dash and the index of the mark.
+    prefix: String,
+}
+
+/// Append the text escaped for XML.
+fn push_escaped(output: &mut String, ch: char) {
+    match ch {
+        '&' => output.push_str("&amp;"),
+        '<' => output.push_str("&lt;"),
+        '>' => output.push_str("&gt;"),
+        _ => output.push(ch),
+    }
+}
+
+impl ProgressTracker {
+    /// Insert a mark every `words` words of the text.
+    pub fn new(text: &str, words: usize) -> Self {
+        Self::build(text, words, MARK_PREFIX)
+    }
+
+    /// Insert a mark every `words` words of the text, with names starting with the prefix.
+    ///
+    /// The prefix must not be used by other marks of the text.
+    pub fn with_prefix(text: &str, words: usize, prefix: &MarkName) -> Self {
+        Self::build(text, words, prefix.as_str())
+    }
+
+    /// A valid mark name followed by a dash and digits is still a valid mark name.
+    fn build(text: &str, words: usize, prefix: &str) -> Self {
+        let words = words.max(1);
+        let mut ssml = String::from("<speak>");
+        let mut marks = Vec::new();
+        let mut word_count = 0;
+        let mut in_word = false;
+        let mut length = 0;
+        for (offset, ch) in text.chars().enumerate() {
+            let starts_word = !ch.is_whitespace() && !in_word;
+            in_word = !ch.is_whitespace();
+            if starts_word {
+                if word_count > 0 && word_count % words == 0 {
+                    ssml.push_str(&format!("<mark name=\"{}-{}\"/>", prefix, marks.len()));
+                    marks.push(offset);
+                }
+                word_count += 1;
+            }
+            push_escaped(&mut ssml, ch);
+            length = offset + 1;
+        }
+        ssml.push_str("</speak>");
+        Self {
+            ssml,
+            marks,
+            length,
+            offset: 0,
+            message: None,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Text with the marks to send.
+    pub fn ssml(&self) -> &str {
+        &self.ssml
+    }
+
+    /// Only track the events of this message.
+    pub fn set_message_id(&mut self, id: MessageId) -> &mut Self {
+        self.message = Some(id);
+        self
+    }
+
+    /// Current progress.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            offset: self.offset,
+            length: self.length,
+        }
+    }
+
+    /// Update the progress with an event.
+    ///
+    /// Return the new progress if the event is the beginning, the end or a mark of the message.
+    pub fn update(&mut self, event: &Event) -> Option<Progress> {
+        if matches!(&self.message, Some(message) if Some(*message) != event.message_id()) {
+            return None;
+        }
+        match &event.ntype {
+            EventType::Begin => self.offset = 0,
+            EventType::End => self.offset = self.length,
+            EventType::IndexMark(name) => {
+                self.offset = *name
+                    .strip_prefix(self.prefix.as_str())
+                    .and_then(|suffix| suffix.strip_prefix('-'))
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| self.marks.get(index))?;
+            }
+            _ => return None,
+        }
+        Some(self.progress())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::str::FromStr;
+
+    use super::{Progress, ProgressTracker};
+    use crate::types::{Event, MarkName};
+
+    #[test]
+    fn insert_marks() {
+        let tracker = ProgressTracker::new("one two  three <four>", 2);
+        assert_eq!(
+            "<speak>one two  <mark name=\"progress-0\"/>three &lt;four&gt;</speak>",
+            tracker.ssml()
+        );
+        let tracker = ProgressTracker::new("", 2);
+        assert_eq!("<speak></speak>", tracker.ssml());
+        assert_eq!(100.0, tracker.progress().percent());
+        let prefix = MarkName::from_str("p").unwrap();
+        let mut tracker = ProgressTracker::with_prefix("one two", 1, &prefix);
+        assert_eq!("<speak>one <mark name=\"p-0\"/>two</speak>", tracker.ssml());
+        assert_eq!(
+            Some(4),
+            tracker
+                .update(&Event::index_mark("p-0".to_string(), 21, 1))
+                .map(|p| p.offset)
+        );
+    }
+
+    #[test]
+    fn track_progress() {
+        let mut tracker = ProgressTracker::new("un deux trois quatre", 1);
+        tracker.set_message_id(21);
+        assert_eq!(
+            Some(Progress {
+                offset: 0,
+                length: 20
+            }),
+            tracker.update(&Event::begin(21, 1))
+        );
+        let progress = tracker
+            .update(&Event::index_mark("progress-1".to_string(), 21, 1))
+            .unwrap();
+        assert_eq!(8, progress.offset);
+        assert_eq!(40.0, progress.percent());
+        assert_eq!(
+            None,
+            tracker.update(&Event::index_mark("progress-2".to_string(), 22, 1))
+        );
+        assert_eq!(
+            None,
+            tracker.update(&Event::index_mark("other".to_string(), 21, 1))
+        );
+        assert_eq!(8, tracker.progress().offset);
+        assert_eq!(
+            Some(20),
+            tracker.update(&Event::end(21, 1)).map(|p| p.offset)
+        );
+    }
+}
@@ -7,20 +7,27 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use log::debug;
+use log::{debug, warn};
+use std::borrow::Cow;
+use std::fmt;
 use std::io::{self, BufRead, Write};
+use std::sync::Arc;
 
-#[cfg(any(feature = "async-std", doc))]
+#[cfg(feature = "async-std")]
 use async_std::io::{
-    prelude::BufReadExt, BufRead as AsyncBufReadStd, Read as AsyncReadStd, ReadExt,
-    Write as AsyncWriteStd, WriteExt,
+    prelude::BufReadExt, BufRead as AsyncBufReadStd, Write as AsyncWriteStd, WriteExt,
 };
-#[cfg(any(feature = "tokio", doc))]
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
 
 use std::str::FromStr;
 
-use crate::types::{ClientError, ClientResult, ClientStatus, EventId, StatusLine};
+use crate::constants::*;
+use crate::demux::{EventBuffer, OverflowPolicy};
+use crate::types::{
+    ClientError, ClientResult, ClientStatus, Event, EventId, EventType, HistoryClientStatus,
+    RawEventId, Request, Response, ReturnCode, StatusLine, SynthesisVoice,
+};
 
 macro_rules! invalid_input {
     ($msg:expr) => {
@@ -31,6 +38,75 @@ macro_rules! invalid_input {
     };
 }
 
+/// How strictly the lines received from the server are parsed.
+///
+/// In both modes, a continuation line is `NNN-data` and the last line is `NNN message`.
+/// Spaces after the dash are part of the data, as in the output of `HELP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Reject any other line.
+    #[default]
+    Strict,
+    /// Also accept continuation lines with another separator than a dash, lines without
+    /// code like localized or extended banners, and a last line without message. A value
+    /// of several lines is returned as [`Response::GetLines`].
+    Lenient,
+}
+
+/// Function called with the bytes of a received line that is not valid UTF-8.
+pub type InvalidUtf8Handler = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+/// Function called with the code and the lines of an event unknown to this version.
+pub type UnknownEventHandler = Arc<dyn Fn(ReturnCode, &[String]) + Send + Sync>;
+
+/// How the lines received from the server are decoded.
+#[derive(Clone, Default)]
+pub enum Utf8Mode {
+    /// Fail on invalid UTF-8.
+    #[default]
+    Strict,
+    /// Replace invalid sequences with `U+FFFD` and call the handler with the bytes of the line.
+    Lossy(InvalidUtf8Handler),
+}
+
+impl Utf8Mode {
+    /// Lossy decoding calling the handler on invalid lines.
+    pub fn lossy<F>(handler: F) -> Self
+    where
+        F: Fn(&[u8]) + Send + Sync + 'static,
+    {
+        Utf8Mode::Lossy(Arc::new(handler))
+    }
+}
+
+impl fmt::Debug for Utf8Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Utf8Mode::Strict => f.write_str("Strict"),
+            Utf8Mode::Lossy(_) => f.write_str("Lossy"),
+        }
+    }
+}
+
+/// Decode the bytes of a line.
+///
+/// Valid lines are borrowed, only lines decoded with replacement characters are allocated.
+fn decode_line<'a>(bytes: &'a [u8], mode: &Utf8Mode) -> ClientResult<Cow<'a, str>> {
+    match std::str::from_utf8(bytes) {
+        Ok(line) => Ok(Cow::Borrowed(line)),
+        Err(err) => match mode {
+            Utf8Mode::Strict => Err(ClientError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                err,
+            ))),
+            Utf8Mode::Lossy(handler) => {
+                handler(bytes);
+                Ok(String::from_utf8_lossy(bytes))
+            }
+        },
+    }
+}
+
 /// Return the only string in the list or an error if there is no line or too many.
 pub(crate) fn parse_single_value(lines: &[String]) -> ClientResult<String> {
     match lines.len() {
@@ -44,7 +120,7 @@ pub(crate) fn parse_single_value(lines: &[String]) -> ClientResult<String> {
 pub(crate) fn parse_event_id(lines: &[String]) -> ClientResult<EventId> {
     match lines.len() {
         0 | 1 => Err(ClientError::TooFewLines),
-        2 => Ok(EventId::new(&lines[0], &lines[1])),
+        2 => EventId::parse(&lines[0], &lines[1]),
         _ => Err(ClientError::TooManyLines),
     }
 }
@@ -53,13 +129,12 @@ pub(crate) fn parse_event_id(lines: &[String]) -> ClientResult<EventId> {
 pub(crate) fn parse_single_integer<T>(lines: &[String]) -> ClientResult<T>
 where
     T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
 {
-    parse_single_value(lines)?.parse::<T>().map_err(|_| {
-        ClientError::Io(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "invalid integer value",
-        ))
-    })
+    let value = parse_single_value(lines)?;
+    value
+        .parse::<T>()
+        .map_err(|err| ClientError::invalid_value("invalid integer value", &value, err))
 }
 
 pub(crate) fn parse_typed_lines<T>(lines: &[String]) -> ClientResult<Vec<T>>
@@ -72,197 +147,1132 @@ where
         .collect::<ClientResult<Vec<T>>>()
 }
 
-/// Write lines separated by CRLF.
-pub(crate) fn write_lines<W: Write + ?Sized>(output: &mut W, lines: &[&str]) -> ClientResult<()> {
-    for line in lines.iter() {
-        debug!("SSIP(out): {}", line);
-        output.write_all(line.as_bytes())?;
-        output.write_all(b"\r\n")?;
+/// Convert an event status line and its lines to a response.
+///
+/// The mark of an index mark is moved out of the lines instead of being copied.
+fn parse_event_response(code: ReturnCode, lines: &mut Vec<String>) -> ClientResult<Response> {
+    match code {
+        EVENT_INDEX_MARK => match lines.len() {
+            0..=2 => Err(ClientError::TooFewLines),
+            3 => Ok(Response::EventIndexMark(
+                parse_event_id(&lines[0..2])?,
+                std::mem::take(&mut lines[2]),
+            )),
+            _ => Err(ClientError::TooManyLines),
+        },
+        EVENT_BEGIN => Ok(Response::EventBegin(parse_event_id(lines)?)),
+        EVENT_END => Ok(Response::EventEnd(parse_event_id(lines)?)),
+        EVENT_CANCELED => Ok(Response::EventCanceled(parse_event_id(lines)?)),
+        EVENT_PAUSED => Ok(Response::EventPaused(parse_event_id(lines)?)),
+        EVENT_RESUMED => Ok(Response::EventResumed(parse_event_id(lines)?)),
+        _ => Ok(Response::UnknownEvent {
+            code,
+            lines: std::mem::take(lines),
+        }),
     }
-    Ok(())
 }
 
-/// Write lines (asyncronously) separated by CRLF.
-#[cfg(any(feature = "tokio", doc))]
-pub(crate) async fn write_lines_tokio<W: AsyncWrite + Unpin + ?Sized>(
+/// Convert an event status line and its lines to an event.
+///
+/// Return `None` if the code is an event unknown to this version. The strings of a known
+/// event are moved out of the lines, which are left empty to be overwritten by the next
+/// answer.
+pub(crate) fn parse_event(code: ReturnCode, lines: &mut [String]) -> ClientResult<Option<Event>> {
+    if (EVENT_RESUMED + 1..=799).contains(&code) {
+        return Ok(None);
+    }
+    if lines.len() < 2 {
+        return Err(ClientError::unexpected_eof("event truncated"));
+    }
+    let ntype = match code {
+        EVENT_INDEX_MARK => {
+            if lines.len() != 3 {
+                return Err(ClientError::unexpected_eof("index markevent truncated"));
+            }
+            EventType::IndexMark(std::mem::take(&mut lines[2]))
+        }
+        EVENT_BEGIN => EventType::Begin,
+        EVENT_END => EventType::End,
+        EVENT_CANCELED => EventType::Cancel,
+        EVENT_PAUSED => EventType::Pause,
+        EVENT_RESUMED => EventType::Resume,
+        _ => return Err(ClientError::invalid_data("wrong status code for event")),
+    };
+    let raw = RawEventId {
+        message: std::mem::take(&mut lines[0]),
+        client: std::mem::take(&mut lines[1]),
+    };
+    Ok(Some(Event::from_raw_id(ntype, raw)))
+}
+
+/// Name of a connection in the logs: `SSIP` or `SSIP[label]`.
+struct LogPrefix<'a>(Option<&'a str>);
+
+impl fmt::Display for LogPrefix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(label) => write!(f, "SSIP[{}]", label),
+            None => write!(f, "SSIP"),
+        }
+    }
+}
+
+/// Log an event skipped because its code is unknown and call the handler.
+pub(crate) fn report_unknown_event(
+    handler: Option<&UnknownEventHandler>,
+    code: ReturnCode,
+    lines: &[String],
+    label: Option<&str>,
+) {
+    warn!("{}: ignoring unknown event {}", LogPrefix(label), code);
+    if let Some(handler) = handler {
+        handler(code, lines);
+    }
+}
+
+/// Events received while waiting for the answer to a request.
+///
+/// The server sends notifications at any time, for example between the answer to `SPEAK`
+/// and the lines of the message. The methods expecting an answer keep them so that they
+/// are returned later as events.
+///
+/// The events are kept in a bounded [`EventBuffer`]. An event refused with
+/// [`OverflowPolicy::Error`] can't be returned as an error while the answer is still
+/// expected, so the next call to [`EventQueue::pop`] fails instead.
+#[derive(Default)]
+pub(crate) struct EventQueue {
+    buffer: EventBuffer,
+    refused: bool,
+}
+
+impl EventQueue {
+    /// Keep the answer if it is an event and return true. Unknown events are reported.
+    pub(crate) fn keep(
+        &mut self,
+        status: &StatusLine,
+        lines: &mut [String],
+        handler: Option<&UnknownEventHandler>,
+        label: Option<&str>,
+    ) -> ClientResult<bool> {
+        if !(EVENT_INDEX_MARK..=799).contains(&status.code) {
+            return Ok(false);
+        }
+        match parse_event(status.code, lines)? {
+            Some(event) => {
+                if let Err(event) = self.buffer.push(event) {
+                    warn!(
+                        "{}: event buffer full, refusing {:?}",
+                        LogPrefix(label),
+                        event
+                    );
+                    self.refused = true;
+                }
+            }
+            None => report_unknown_event(handler, status.code, lines, label),
+        }
+        Ok(true)
+    }
+
+    /// Change the capacity and the policy of the buffer.
+    pub(crate) fn configure(&mut self, capacity: usize, policy: OverflowPolicy) {
+        self.buffer.set_capacity(capacity);
+        self.buffer.set_policy(policy);
+    }
+
+    /// Same capacity and policy, without the events.
+    pub(crate) fn same_settings(&self) -> Self {
+        Self {
+            buffer: EventBuffer::new(self.buffer.capacity(), self.buffer.policy()),
+            refused: false,
+        }
+    }
+
+    /// Number of events discarded or refused because the buffer was full.
+    pub(crate) fn overflows(&self) -> u64 {
+        self.buffer.overflows()
+    }
+
+    /// Oldest event kept.
+    ///
+    /// Fail once if events were refused since the last call.
+    pub(crate) fn pop(&mut self) -> ClientResult<Option<Event>> {
+        if std::mem::take(&mut self.refused) {
+            return Err(ClientError::io_error(
+                io::ErrorKind::Other,
+                "event buffer full: events refused",
+            ));
+        }
+        Ok(self.buffer.pop())
+    }
+}
+
+/// Fail if an answer that must be a single status line has other lines.
+pub(crate) fn check_no_lines(lines: &[String]) -> ClientResult<()> {
+    match lines.first() {
+        Some(line) => Err(invalid_input!("unexpected line: {}", line)),
+        None => Ok(()),
+    }
+}
+
+/// Convert a successful status line and its lines to a response.
+fn parse_ok_response(
+    status: &StatusLine,
+    lines: Vec<String>,
+    mode: ParseMode,
+) -> ClientResult<Response> {
+    const MSG_CURSOR_SET_FIRST: &str = "CURSOR SET FIRST";
+    match status.code {
+        OK_LANGUAGE_SET => Ok(Response::LanguageSet),
+        OK_PRIORITY_SET => Ok(Response::PrioritySet),
+        OK_RATE_SET => Ok(Response::RateSet),
+        OK_PITCH_SET => Ok(Response::PitchSet),
+        OK_PUNCTUATION_SET => Ok(Response::PunctuationSet),
+        OK_CAP_LET_RECOGN_SET => Ok(Response::CapLetRecognSet),
+        OK_SPELLING_SET => Ok(Response::SpellingSet),
+        OK_CLIENT_NAME_SET => Ok(Response::ClientNameSet),
+        OK_VOICE_SET => Ok(Response::VoiceSet),
+        OK_STOPPED => Ok(Response::Stopped),
+        OK_PAUSED => Ok(Response::Paused),
+        OK_RESUMED => Ok(Response::Resumed),
+        OK_CANCELED => Ok(Response::Canceled),
+        OK_TABLE_SET => Ok(Response::TableSet),
+        OK_OUTPUT_MODULE_SET => Ok(Response::OutputModuleSet),
+        OK_PAUSE_CONTEXT_SET => Ok(Response::PauseContextSet),
+        OK_VOLUME_SET => Ok(Response::VolumeSet),
+        OK_SSML_MODE_SET => Ok(Response::SsmlModeSet),
+        // Warning OK_CUR_SET_FIRST == OK_NOTIFICATION_SET == 220. Matching message to make the difference
+        OK_NOTIFICATION_SET => {
+            if status.message == MSG_CURSOR_SET_FIRST {
+                //OK_CUR_SET_FIRST => Ok(Response::HistoryCurSetFirst)
+                Ok(Response::HistoryCurSetFirst)
+            } else {
+                Ok(Response::NotificationSet)
+            }
+        }
+        OK_CUR_SET_LAST => Ok(Response::HistoryCurSetLast),
+        OK_CUR_SET_POS => Ok(Response::HistoryCurSetPos),
+        OK_PITCH_RANGE_SET => Ok(Response::PitchRangeSet),
+        OK_DEBUG_SET => Ok(Response::DebugSet),
+        OK_CUR_MOV_FOR => Ok(Response::HistoryCurMoveFor),
+        OK_CUR_MOV_BACK => Ok(Response::HistoryCurMoveBack),
+        OK_MESSAGE_QUEUED => Ok(Response::MessageQueued),
+        OK_SND_ICON_QUEUED => Ok(Response::SoundIconQueued),
+        OK_MSG_CANCELED => Ok(Response::MessageCanceled),
+        OK_RECEIVING_DATA => Ok(Response::ReceivingData),
+        OK_BYE => Ok(Response::Bye),
+        OK_CLIENTS_LIST_SENT => Ok(Response::HistoryClientListSent(parse_typed_lines::<
+            HistoryClientStatus,
+        >(&lines)?)),
+        OK_MSGS_LIST_SENT => Ok(Response::HistoryMsgsListSent(lines)),
+        OK_LAST_MSG => Ok(Response::HistoryLastMsg(parse_single_value(&lines)?)),
+        OK_CUR_POS_RET => Ok(Response::HistoryCurPosRet(
+            parse_single_value(&lines)?.parse()?,
+        )),
+        OK_TABLE_LIST_SENT => Ok(Response::TableListSent(lines)),
+        OK_CLIENT_ID_SENT => Ok(Response::HistoryClientIdSent(parse_single_integer(&lines)?)),
+        OK_MSG_TEXT_SENT => Ok(Response::MessageTextSent),
+        OK_HELP_SENT => Ok(Response::HelpSent(lines)),
+        OK_VOICES_LIST_SENT => Ok(Response::VoicesListSent(
+            parse_typed_lines::<SynthesisVoice>(&lines)?,
+        )),
+        OK_OUTPUT_MODULES_LIST_SENT => Ok(Response::OutputModulesListSent(lines)),
+        OK_GET if lines.len() > 1 && mode == ParseMode::Lenient => Ok(Response::GetLines(lines)),
+        OK_GET => Ok(Response::Get(parse_single_value(&lines)?)),
+        OK_INSIDE_BLOCK => Ok(Response::InsideBlock),
+        OK_OUTSIDE_BLOCK => Ok(Response::OutsideBlock),
+        OK_NOT_IMPLEMENTED => Ok(Response::NotImplemented),
+        // Lists are sent with these codes, keep the data of those unknown to this version.
+        code @ 240..=259 => Ok(Response::List { code, items: lines }),
+        code => Err(ClientError::UnexpectedStatus(code)),
+    }
+}
+
+/// Convert a status line and the lines received before to a response.
+///
+/// In lenient mode, a `251 OK GET` answer with several lines is kept as
+/// [`Response::GetLines`] instead of failing with [`ClientError::TooManyLines`].
+pub(crate) fn parse_response(
+    status: StatusLine,
+    mut lines: Vec<String>,
+    mode: ParseMode,
+) -> ClientResult<Response> {
+    match ReturnCodeKind::try_from(status.code)? {
+        ReturnCodeKind::Ok(_) => parse_ok_response(&status, lines, mode),
+        ReturnCodeKind::Event(code) => parse_event_response(code, &mut lines),
+        ReturnCodeKind::ServerError(_) | ReturnCodeKind::ClientError(_) => {
+            Err(ClientError::Ssip(status))
+        }
+    }
+}
+
+/// Give a type to the value of `251 OK GET` when the request it answers is known.
+///
+/// The request is the name of the variant of [`Request`]. Values
+/// of other requests stay strings.
+pub(crate) fn type_response(request: Option<&str>, response: Response) -> ClientResult<Response> {
+    fn parse_i8(value: &str) -> ClientResult<i8> {
+        value
+            .parse()
+            .map_err(|err| ClientError::invalid_value("invalid signed 8-bit integer", value, err))
+    }
+    fn parse_u16(value: &str) -> ClientResult<u16> {
+        value.parse().map_err(|err| {
+            ClientError::invalid_value("invalid unsigned 16-bit integer", value, err)
+        })
+    }
+    match (request, response) {
+        (Some("GetRate"), Response::Get(value)) => Ok(Response::GetRate(parse_i8(&value)?)),
+        (Some("GetPitch"), Response::Get(value)) => Ok(Response::GetPitch(parse_i8(&value)?)),
+        (Some("GetVolume"), Response::Get(value)) => Ok(Response::GetVolume(parse_i8(&value)?)),
+        (Some("GetPauseContext"), Response::Get(value)) => {
+            Ok(Response::GetPauseContext(parse_u16(&value)?))
+        }
+        (_, response) => Ok(response),
+    }
+}
+
+/// Description of a request on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RequestDescription {
+    /// Name of the variant of [`Request`].
+    pub request: &'static str,
+    /// Line sent to the server. Arguments are in braces.
+    pub syntax: &'static str,
+    /// Codes returned on success. Empty when SSIP doesn't define one.
+    pub success: &'static [ReturnCode],
+    /// True if lines of data come before the status line on success.
+    pub returns_data: bool,
+}
+
+macro_rules! describe_request {
+    ($request:ident, $syntax:expr, [$( $code:expr ),*], $data:expr) => {
+        RequestDescription {
+            request: stringify!($request),
+            syntax: $syntax,
+            success: &[$( $code ),*],
+            returns_data: $data,
+        }
+    };
+}
+
+const REQUESTS: &[RequestDescription] = &[
+    describe_request!(
+        SetName,
+        "SET self CLIENT_NAME {user}:{application}:{component}",
+        [OK_CLIENT_NAME_SET],
+        false
+    ),
+    describe_request!(Speak, "SPEAK", [OK_RECEIVING_DATA], false),
+    describe_request!(SendLine, "{line}\r\n.", [OK_MESSAGE_QUEUED], true),
+    describe_request!(SendLines, "{lines}\r\n.", [OK_MESSAGE_QUEUED], true),
+    describe_request!(SpeakChar, "CHAR {char}", [OK_MESSAGE_QUEUED], true),
+    describe_request!(SpeakKey, "KEY {key}", [OK_MESSAGE_QUEUED], true),
+    describe_request!(Stop, "STOP {scope}", [OK_STOPPED], false),
+    describe_request!(Cancel, "CANCEL {scope}", [OK_CANCELED], false),
+    describe_request!(Pause, "PAUSE {scope}", [OK_PAUSED], false),
+    describe_request!(Resume, "RESUME {scope}", [OK_RESUMED], false),
+    describe_request!(
+        SetPriority,
+        "SET self PRIORITY {priority}",
+        [OK_PRIORITY_SET],
+        false
+    ),
+    describe_request!(SetDebug, "SET all DEBUG {on|off}", [OK_DEBUG_SET], true),
+    describe_request!(
+        SetOutputModule,
+        "SET {scope} OUTPUT_MODULE {name}",
+        [OK_OUTPUT_MODULE_SET],
+        false
+    ),
+    describe_request!(GetOutputModule, "GET OUTPUT_MODULE", [OK_GET], true),
+    describe_request!(
+        ListOutputModules,
+        "LIST OUTPUT_MODULES",
+        [OK_OUTPUT_MODULES_LIST_SENT],
+        true
+    ),
+    describe_request!(
+        SetLanguage,
+        "SET {scope} LANGUAGE {language}",
+        [OK_LANGUAGE_SET],
+        false
+    ),
+    describe_request!(GetLanguage, "GET LANGUAGE", [OK_GET], true),
+    describe_request!(
+        SetSsmlMode,
+        "SET self SSML_MODE {on|off}",
+        [OK_SSML_MODE_SET],
+        false
+    ),
+    describe_request!(
+        SetPunctuationMode,
+        "SET {scope} PUNCTUATION {mode}",
+        [OK_PUNCTUATION_SET],
+        false
+    ),
+    describe_request!(
+        SetSpelling,
+        "SET {scope} SPELLING {on|off}",
+        [OK_SPELLING_SET],
+        false
+    ),
+    describe_request!(
+        SetCapitalLettersRecognitionMode,
+        "SET {scope} CAP_LET_RECOGN {mode}",
+        [OK_CAP_LET_RECOGN_SET],
+        false
+    ),
+    describe_request!(SetTable, "SET {scope} TABLE {name}", [OK_TABLE_SET], false),
+    describe_request!(ListTables, "LIST TABLES", [OK_TABLE_LIST_SENT], true),
+    describe_request!(
+        SetVoiceType,
+        "SET {scope} VOICE_TYPE {voice_type}",
+        [OK_VOICE_SET],
+        false
+    ),
+    describe_request!(GetVoiceType, "GET VOICE_TYPE", [OK_GET], true),
+    describe_request!(ListVoiceTypes, "LIST VOICES", [OK_VOICES_LIST_SENT], true),
+    describe_request!(
+        SetSynthesisVoice,
+        "SET {scope} SYNTHESIS_VOICE {name}",
+        [OK_VOICE_SET],
+        false
+    ),
+    describe_request!(
+        ListSynthesisVoices,
+        "LIST SYNTHESIS_VOICES",
+        [OK_VOICES_LIST_SENT],
+        true
+    ),
+    describe_request!(
+        SetRate,
+        "SET {scope} RATE {-100..100}",
+        [OK_RATE_SET],
+        false
+    ),
+    describe_request!(GetRate, "GET RATE", [OK_GET], true),
+    describe_request!(
+        SetPitch,
+        "SET {scope} PITCH {-100..100}",
+        [OK_PITCH_SET],
+        false
+    ),
+    describe_request!(GetPitch, "GET PITCH", [OK_GET], true),
+    describe_request!(
+        SetPitchRange,
+        "SET {scope} PITCH_RANGE {-100..100}",
+        [OK_PITCH_RANGE_SET],
+        false
+    ),
+    describe_request!(
+        SetVolume,
+        "SET {scope} VOLUME {-100..100}",
+        [OK_VOLUME_SET],
+        false
+    ),
+    describe_request!(GetVolume, "GET VOLUME", [OK_GET], true),
+    describe_request!(
+        SetPauseContext,
+        "SET {scope} PAUSE_CONTEXT {number}",
+        [OK_PAUSE_CONTEXT_SET],
+        false
+    ),
+    describe_request!(GetPauseContext, "GET PAUSE_CONTEXT", [OK_GET], true),
+    describe_request!(SetHistory, "SET {scope} HISTORY {on|off}", [], false),
+    describe_request!(
+        SetNotification,
+        "SET self NOTIFICATION {type} {on|off}",
+        [OK_NOTIFICATION_SET],
+        false
+    ),
+    describe_request!(Begin, "BLOCK BEGIN", [OK_INSIDE_BLOCK], false),
+    describe_request!(End, "BLOCK END", [OK_OUTSIDE_BLOCK], false),
+    describe_request!(
+        HistoryGetClients,
+        "HISTORY GET CLIENT_LIST",
+        [OK_CLIENTS_LIST_SENT],
+        true
+    ),
+    describe_request!(
+        HistoryGetClientId,
+        "HISTORY GET CLIENT_ID",
+        [OK_CLIENT_ID_SENT],
+        true
+    ),
+    describe_request!(
+        HistoryGetClientMsgs,
+        "HISTORY GET CLIENT_MESSAGES {scope} {start}_{number}",
+        [OK_MSGS_LIST_SENT],
+        true
+    ),
+    describe_request!(HistoryGetLastMsgId, "HISTORY GET LAST", [OK_LAST_MSG], true),
+    describe_request!(
+        HistoryGetMsg,
+        "HISTORY GET MESSAGE {id}",
+        [OK_MSG_TEXT_SENT],
+        true
+    ),
+    describe_request!(
+        HistoryCursorGet,
+        "HISTORY CURSOR GET",
+        [OK_CUR_POS_RET],
+        true
+    ),
+    describe_request!(
+        HistoryCursorSet,
+        "HISTORY CURSOR SET {scope} {first|last|pos N}",
+        [OK_CUR_SET_FIRST, OK_CUR_SET_LAST, OK_CUR_SET_POS],
+        false
+    ),
+    describe_request!(
+        HistoryCursorMove,
+        "HISTORY CURSOR {forward|backward}",
+        [OK_CUR_MOV_FOR, OK_CUR_MOV_BACK],
+        false
+    ),
+    describe_request!(HistorySpeak, "HISTORY SAY {id}", [OK_MESSAGE_QUEUED], true),
+    describe_request!(HistorySort, "HISTORY SORT {direction} {key}", [], false),
+    describe_request!(
+        HistorySetShortMsgLength,
+        "HISTORY SET SHORT_MESSAGE_LENGTH {length}",
+        [],
+        false
+    ),
+    describe_request!(
+        HistorySetMsgTypeOrdering,
+        "HISTORY SET MESSAGE_TYPE_ORDERING \"{ordering}\"",
+        [],
+        false
+    ),
+    describe_request!(
+        HistorySearch,
+        "HISTORY SEARCH {scope} \"{condition}\"",
+        [OK_MSGS_LIST_SENT],
+        true
+    ),
+    describe_request!(Quit, "QUIT", [OK_BYE], false),
+];
+
+/// Describe every request supported by the client, in the order of [`Request`].
+///
+/// It can be used to generate documentation or bindings.
+pub fn describe() -> &'static [RequestDescription] {
+    REQUESTS
+}
+
+/// First code returned on success by a request, if SSIP defines one.
+pub(crate) fn success_code(request: &Request) -> Option<ReturnCode> {
+    let name: &str = request.into();
+    REQUESTS
+        .iter()
+        .find(|description| description.request == name)
+        .and_then(|description| description.success.first().copied())
+}
+
+/// Requests written together by `interrupt_say`, with their names.
+pub(crate) const INTERRUPT_BATCH: [(&str, &str); 3] = [
+    ("Cancel", "CANCEL self"),
+    ("SetPriority", "SET self PRIORITY important"),
+    ("Speak", "SPEAK"),
+];
+
+/// Text spoken by `probe_voice`, a single space that produces no sound.
+pub(crate) const PROBE_TEXT: &str = " ";
+
+/// Result of a voice probe: false if the server rejected it with a 3xx or 4xx error.
+pub(crate) fn probe_accepted(result: ClientResult<()>) -> ClientResult<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(ClientError::Ssip(status)) if (300..500).contains(&status.code) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Split a text in lines to send. A line with a single dot is escaped by doubling it.
+pub(crate) fn text_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| {
+            if line == "." {
+                String::from("..")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+}
+
+/// Split a text in pieces of at most `max_bytes` bytes, to speak it as several messages.
+///
+/// Pieces end after a line break if possible, otherwise after a whitespace, otherwise on
+/// a character boundary. A piece has at least one character, even if it is longer than
+/// `max_bytes`. Pieces with only whitespaces are skipped.
+pub fn split_text(text: &str, max_bytes: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let head = &rest[..end];
+        let cut = head
+            .rfind('\n')
+            .map(|pos| pos + 1)
+            .or_else(|| {
+                head.char_indices()
+                    .rev()
+                    .find(|(_, ch)| ch.is_whitespace())
+                    .map(|(pos, ch)| pos + ch.len_utf8())
+            })
+            .unwrap_or(end);
+        pieces.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    pieces.push(rest);
+    pieces.retain(|piece| !piece.trim().is_empty());
+    pieces
+}
+
+/// Write lines separated by CRLF.
+///
+/// Outgoing lines always end with CRLF, even if the server only sends LF.
+pub(crate) fn write_lines<W: Write + ?Sized>(
     output: &mut W,
     lines: &[&str],
+    label: Option<&str>,
 ) -> ClientResult<()> {
     for line in lines.iter() {
-        debug!("SSIP(out): {}", line);
-        output.write_all(line.as_bytes()).await?;
-        output.write_all(b"\r\n").await?;
+        debug!("{}(out): {}", LogPrefix(label), line);
+        output.write_all(line.as_bytes())?;
+        output.write_all(b"\r\n")?;
     }
     Ok(())
 }
-/// Write lines (asyncronously) separated by CRLF.
-#[cfg(any(feature = "async-std", doc))]
-pub(crate) async fn write_lines_async_std<W: AsyncWriteStd + Unpin + ?Sized>(
-    output: &mut W,
-    lines: &[&str],
-) -> ClientResult<()> {
+
+/// Encode lines separated by CRLF in a single frame.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub(crate) fn encode_lines(lines: &[&str], label: Option<&str>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(lines.iter().map(|line| line.len() + 2).sum());
     for line in lines.iter() {
-        debug!("SSIP(out): {}", line);
-        output.write_all(line.as_bytes()).await?;
-        output.write_all(b"\r\n").await?;
+        debug!("{}(out): {}", LogPrefix(label), line);
+        frame.extend_from_slice(line.as_bytes());
+        frame.extend_from_slice(b"\r\n");
     }
-    Ok(())
+    frame
 }
 
 /// Write lines separated by CRLF and flush the output.
-pub(crate) fn flush_lines<W: Write + ?Sized>(output: &mut W, lines: &[&str]) -> ClientResult<()> {
-    write_lines(output, lines)?;
-    output.flush()?;
-    Ok(())
-}
-/// Write lines separated by CRLF and flush the output asyncronously.
-#[cfg(any(feature = "tokio", doc))]
-pub(crate) async fn flush_lines_tokio<W: AsyncWrite + Unpin + ?Sized>(
+pub(crate) fn flush_lines<W: Write + ?Sized>(
     output: &mut W,
     lines: &[&str],
+    label: Option<&str>,
 ) -> ClientResult<()> {
-    write_lines_tokio(output, lines).await?;
-    output.flush().await?;
+    write_lines(output, lines, label)?;
+    output.flush()?;
     Ok(())
 }
 /// Write lines separated by CRLF and flush the output asyncronously.
-#[cfg(any(feature = "async-std", doc))]
+///
+/// The lines are encoded in a single frame first, so that a request is passed to the output
+/// in one call rather than line by line.
+#[cfg(feature = "async-std")]
 pub(crate) async fn flush_lines_async_std<W: AsyncWriteStd + Unpin + ?Sized>(
     output: &mut W,
     lines: &[&str],
+    label: Option<&str>,
 ) -> ClientResult<()> {
-    write_lines_async_std(output, lines).await?;
+    output.write_all(&encode_lines(lines, label)).await?;
     output.flush().await?;
     Ok(())
 }
 
+/// Number of bytes of lines separated by CRLF.
+pub(crate) fn frame_len(lines: &[&str]) -> u64 {
+    lines.iter().map(|line| line.len() as u64 + 2).sum()
+}
+
+/// Output counting the bytes written.
+struct CountingWriter<'a, W: Write + ?Sized> {
+    output: &'a mut W,
+    count: usize,
+}
+
+impl<W: Write + ?Sized> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.output.write(buf)?;
+        self.count += count;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Write a line formatted in place followed by CRLF and flush the output.
+///
+/// The line is formatted directly in the output, without an intermediate string. Return
+/// the number of bytes written.
+pub(crate) fn flush_line_fmt<W: Write + ?Sized>(
+    output: &mut W,
+    line: fmt::Arguments,
+    label: Option<&str>,
+) -> ClientResult<usize> {
+    debug!("{}(out): {}", LogPrefix(label), line);
+    let mut output = CountingWriter { output, count: 0 };
+    output.write_fmt(line)?;
+    output.write_all(b"\r\n")?;
+    output.flush()?;
+    Ok(output.count)
+}
+
 /// Strip prefix if found
-fn strip_prefix(line: &str, prefix: &str) -> String {
-    line.strip_prefix(prefix).unwrap_or(line).to_string()
+fn strip_prefix<'a>(line: &'a str, prefix: &str) -> &'a str {
+    line.strip_prefix(prefix).unwrap_or(line)
 }
 
-/// Parse the status line "OK msg" or "ERR msg"
-fn parse_status_line(code: u16, line: &str) -> ClientStatus {
+/// Parse the status line "OK msg" or "ERR msg". The raw line is the line received.
+fn parse_status_line(code: u16, line: &str, raw: &str) -> ClientStatus {
     if (300..700).contains(&code) {
         const TOKEN_ERR: &str = "ERR ";
         let message = strip_prefix(line, TOKEN_ERR);
-        Err(ClientError::Ssip(StatusLine { code, message }))
+        Err(ClientError::Ssip(StatusLine::from_raw(code, message, raw)))
     } else {
         const TOKEN_OK: &str = "OK ";
         let message = strip_prefix(line, TOKEN_OK);
-        Ok(StatusLine { code, message })
+        Ok(StatusLine::from_raw(code, message, raw))
+    }
+}
+
+/// Error for a line that can't be parsed in strict mode.
+fn strict_line_error(line: &str) -> ClientError {
+    match line.chars().nth(3) {
+        Some(' ') => invalid_input!("invalid status code: {}", line),
+        Some(ch) => invalid_input!("expecting space or dash, got {}: {}", ch, line),
+        None => invalid_input!("line too short: {}", line),
+    }
+}
+
+/// Maximum number of continuation lines of an event: message, client and index mark.
+const MAX_EVENT_LINES: usize = 3;
+
+/// Code and number of the lines received so far in an answer.
+#[derive(Debug, Default)]
+struct AnswerCode {
+    code: Option<ReturnCode>,
+    lines: usize,
+    /// Number of lines stored in the vector of lines.
+    stored: usize,
+}
+
+impl AnswerCode {
+    /// Check that a line has the code of the previous lines of the answer.
+    fn check(&mut self, code: ReturnCode, line: &str, is_status: bool) -> ClientResult<()> {
+        match self.code {
+            Some(expected) if expected != code => {
+                return Err(ClientError::ProtocolViolation(format!(
+                    "code {} in answer {}: {}",
+                    code, expected, line
+                )))
+            }
+            _ => self.code = Some(code),
+        }
+        if !is_status {
+            self.lines += 1;
+            if (EVENT_INDEX_MARK..=799).contains(&code) && self.lines > MAX_EVENT_LINES {
+                return Err(ClientError::ProtocolViolation(format!(
+                    "more than {} lines in event {}: {}",
+                    MAX_EVENT_LINES, code, line
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the data of a continuation line.
+    ///
+    /// The strings already in the vector are overwritten to reuse their memory.
+    fn store(&mut self, lines: &mut Vec<String>, data: &str) {
+        match lines.get_mut(self.stored) {
+            Some(line) => {
+                line.clear();
+                line.push_str(data);
+            }
+            None => lines.push(data.to_string()),
+        }
+        self.stored += 1;
+    }
+}
+
+/// Remove the line ending, either CRLF or LF alone.
+///
+/// SSIP lines end with CRLF, but some proxies and test servers only send LF.
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Error when the server closes the connection before the end of the answer.
+pub(crate) fn end_of_stream() -> ClientError {
+    ClientError::Io(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed by the server",
+    ))
+}
+
+/// Parse a line received from the server.
+///
+/// Return the status if it's the last line of the answer or `None` if it's a continuation
+/// line whose data is appended to the lines. A line with another code than the previous
+/// lines of the answer is a protocol violation.
+fn parse_answer_line(
+    line: &str,
+    mode: ParseMode,
+    lines: Option<&mut Vec<String>>,
+    answer: &mut AnswerCode,
+) -> Option<ClientStatus> {
+    if line.is_empty() {
+        return Some(Err(invalid_input!("empty line")));
+    }
+    let raw = line;
+    let line = strip_line_ending(line);
+    let code = line
+        .get(0..3)
+        .filter(|code| code.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|code| code.parse::<u16>().ok());
+    let separator = line.char_indices().nth(3);
+    if let Some(code) = code {
+        let is_status = !matches!(separator, Some((_, '-')));
+        if let Err(err) = answer.check(code, line, is_status) {
+            return Some(Err(err));
+        }
+    }
+    let data = match (code, separator) {
+        (Some(code), Some((_, ' '))) => {
+            return Some(parse_status_line(code, line[4..].trim_end(), raw))
+        }
+        (_, Some((pos, '-'))) => &line[pos + 1..],
+        _ if mode == ParseMode::Strict => return Some(Err(strict_line_error(line))),
+        (Some(code), None) => return Some(parse_status_line(code, "", raw)),
+        (Some(_), Some((pos, ch))) if !ch.is_alphanumeric() => &line[pos + ch.len_utf8()..],
+        _ => line,
+    };
+    match lines {
+        Some(lines) => {
+            answer.store(lines, data.trim_end());
+            None
+        }
+        None => Some(Err(invalid_input!("unexpected line: {}", line))),
+    }
+}
+
+/// Decode and parse the bytes of a line received from the server.
+///
+/// At the end of the answer, the extra strings are removed from the lines and the state is
+/// reset for the next answer.
+fn decode_answer_line(
+    bytes: &[u8],
+    mode: ParseMode,
+    utf8: &Utf8Mode,
+    label: Option<&str>,
+    mut lines: Option<&mut Vec<String>>,
+    answer: &mut AnswerCode,
+) -> Option<ClientStatus> {
+    let status = match decode_line(bytes, utf8) {
+        Ok(line) => {
+            debug!("{}(in): {}", LogPrefix(label), line.trim_end());
+            parse_answer_line(&line, mode, lines.as_deref_mut(), answer)
+        }
+        Err(err) => Some(Err(err)),
+    };
+    if status.is_some() {
+        if let Some(lines) = lines {
+            lines.truncate(answer.stored);
+        }
+        *answer = AnswerCode::default();
+    }
+    status
+}
+
+/// Decoder of the answers of the server, independent of the way the bytes are received.
+///
+/// The bytes are given to the decoder as they are received, in chunks of any size. Each
+/// complete answer is returned once with the lines before its status. It is used by
+/// [`QueuedClient`](crate::QueuedClient) and the `try_receive` methods of the synchronous
+/// client, so that an answer can be received in several calls. The blocking and the
+/// asynchronous receive methods feed it one line at a time.
+///
+/// Example
+/// ```
+/// use ssip_client_async::{protocol::Decoder, ParseMode, Response, Utf8Mode};
+/// let mut decoder = Decoder::new(ParseMode::Strict, Utf8Mode::Strict);
+/// decoder.feed(b"251-10\r\n251 OK");
+/// assert!(decoder.decode().is_none());
+/// decoder.feed(b" GET RETURNED\r\n");
+/// let status = decoder.decode().unwrap()?;
+/// assert_eq!(251, status.code);
+/// assert_eq!(&["10"], decoder.lines());
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Debug)]
+pub struct Decoder {
+    mode: ParseMode,
+    utf8: Utf8Mode,
+    /// Name of the connection in the logs.
+    label: Option<String>,
+    /// Bytes received and not parsed yet.
+    buffer: Vec<u8>,
+    answer: AnswerCode,
+    lines: Vec<String>,
+}
+
+impl Decoder {
+    /// Create a decoder parsing the answers in the mode and decoding the lines as specified
+    /// by the UTF-8 mode.
+    pub fn new(mode: ParseMode, utf8: Utf8Mode) -> Self {
+        Self {
+            mode,
+            utf8,
+            label: None,
+            buffer: Vec::new(),
+            answer: AnswerCode::default(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Set the name of the connection in the logs, such as `SSIP[label](in): 208 OK`.
+    pub fn set_label(&mut self, label: &str) -> &mut Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub(crate) fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.mode = mode;
+    }
+
+    pub(crate) fn set_utf8_mode(&mut self, utf8: Utf8Mode) {
+        self.utf8 = utf8;
+    }
+
+    /// New decoder with the same modes and label, without the bytes received.
+    pub(crate) fn same_settings(&self) -> Self {
+        Self {
+            mode: self.mode,
+            utf8: self.utf8.clone(),
+            label: self.label.clone(),
+            buffer: Vec::new(),
+            answer: AnswerCode::default(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Add bytes received from the server.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Parse the complete lines received so far.
+    ///
+    /// Return the status of the next answer, or `None` if it is not complete yet. The
+    /// lines of the answer are then returned by [`Decoder::lines`]. An error ends the
+    /// answer, the decoding continues with the next line.
+    pub fn decode(&mut self) -> Option<ClientStatus> {
+        let mut lines = std::mem::take(&mut self.lines);
+        let status = self.decode_into(Some(&mut lines));
+        self.lines = lines;
+        status
+    }
+
+    /// Parse the complete lines received so far, see [`Decoder::decode`].
+    ///
+    /// The lines of the answer replace the content of the vector. Without vector, a line
+    /// before the status is an error.
+    pub(crate) fn decode_into(
+        &mut self,
+        mut lines: Option<&mut Vec<String>>,
+    ) -> Option<ClientStatus> {
+        let mut start = 0;
+        let mut status = None;
+        while let Some(pos) = self.buffer[start..].iter().position(|b| *b == b'\n') {
+            let end = start + pos + 1;
+            status = decode_answer_line(
+                &self.buffer[start..end],
+                self.mode,
+                &self.utf8,
+                self.label.as_deref(),
+                lines.as_deref_mut(),
+                &mut self.answer,
+            );
+            start = end;
+            if status.is_some() {
+                break;
+            }
+        }
+        self.buffer.drain(..start);
+        status
+    }
+
+    /// Parse the next answer as a response, see [`Decoder::decode`].
+    pub fn decode_response(&mut self) -> Option<ClientResult<Response>> {
+        self.decode().map(|status| {
+            let status = status?;
+            if (EVENT_INDEX_MARK..=799).contains(&status.code) {
+                // The vector and the strings of the identifiers are reused by the next answer.
+                return parse_event_response(status.code, &mut self.lines);
+            }
+            let lines = std::mem::take(&mut self.lines);
+            parse_response(status, lines, self.mode)
+        })
+    }
+
+    /// Lines of the last answer returned by [`Decoder::decode`].
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Number of bytes received and not parsed yet.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Count the bytes read in the buffer by a `read_until` returning `count`.
+    ///
+    /// The last line of the stream may have no line ending.
+    fn received(&mut self, count: usize, received: &mut u64) -> ClientResult<()> {
+        if count == 0 {
+            return Err(end_of_stream());
+        }
+        *received += count as u64;
+        if self.buffer.last() != Some(&b'\n') {
+            self.buffer.push(b'\n');
+        }
+        Ok(())
+    }
+
+    /// Move the lines of the last answer to the vector, see [`Decoder::lines`].
+    pub(crate) fn take_lines(&mut self, lines: &mut Vec<String>) {
+        std::mem::swap(lines, &mut self.lines);
     }
 }
 
 /// Read lines from server until a status line is found.
-#[cfg(any(feature = "tokio", doc))]
+#[cfg(feature = "tokio")]
 pub(crate) async fn receive_answer_tokio<W: AsyncBufRead + Unpin + ?Sized>(
     input: &mut W,
+    decoder: &mut Decoder,
+    received: &mut u64,
     mut lines: Option<&mut Vec<String>>,
 ) -> ClientStatus {
     loop {
-        let mut line = String::new();
-        input.read_line(&mut line).await.map_err(ClientError::Io)?;
-        debug!("SSIP(in): {}", line.trim_end());
-        match line.chars().nth(3) {
-            Some(ch) => match ch {
-                ' ' => match line[0..3].parse::<u16>() {
-                    Ok(code) => return parse_status_line(code, line[4..].trim_end()),
-                    Err(err) => return Err(invalid_input!(err.to_string())),
-                },
-                '-' => match lines {
-                    Some(ref mut lines) => lines.push(line[4..].trim_end().to_string()),
-                    None => return Err(invalid_input!("unexpected line: {}", line)),
-                },
-                ch => {
-                    return Err(invalid_input!("expecting space or dash, got {}.", ch));
-                }
-            },
-            None if line.is_empty() => return Err(invalid_input!("empty line")),
-            None => return Err(invalid_input!("line too short: {}", line)),
+        if let Some(status) = decoder.decode_into(lines.as_deref_mut()) {
+            return status;
         }
+        let count = input
+            .read_until(b'\n', &mut decoder.buffer)
+            .await
+            .map_err(ClientError::Io)?;
+        decoder.received(count, received)?;
     }
 }
+
 /// Read lines from server until a status line is found.
-#[cfg(any(feature = "async-std", doc))]
+#[cfg(feature = "async-std")]
 pub(crate) async fn receive_answer_async_std<W: AsyncBufReadStd + Unpin + ?Sized>(
     input: &mut W,
+    decoder: &mut Decoder,
+    received: &mut u64,
     mut lines: Option<&mut Vec<String>>,
 ) -> ClientStatus {
     loop {
-        let mut line = String::new();
-        input.read_line(&mut line).await.map_err(ClientError::Io)?;
-        debug!("SSIP(in): {}", line.trim_end());
-        match line.chars().nth(3) {
-            Some(ch) => match ch {
-                ' ' => match line[0..3].parse::<u16>() {
-                    Ok(code) => return parse_status_line(code, line[4..].trim_end()),
-                    Err(err) => return Err(invalid_input!(err.to_string())),
-                },
-                '-' => match lines {
-                    Some(ref mut lines) => lines.push(line[4..].trim_end().to_string()),
-                    None => return Err(invalid_input!("unexpected line: {}", line)),
-                },
-                ch => {
-                    return Err(invalid_input!("expecting space or dash, got {}.", ch));
-                }
-            },
-            None if line.is_empty() => return Err(invalid_input!("empty line")),
-            None => return Err(invalid_input!("line too short: {}", line)),
+        if let Some(status) = decoder.decode_into(lines.as_deref_mut()) {
+            return status;
         }
+        let count = input
+            .read_until(b'\n', &mut decoder.buffer)
+            .await
+            .map_err(ClientError::Io)?;
+        decoder.received(count, received)?;
     }
 }
 
-/// Read lines from server until a status line is found asyncronously.
+/// Read lines from server until a status line is found.
+///
+/// Lines may end with CRLF or LF alone. The lines replace the content of the vector and are
+/// read in the buffer of the decoder. The number of bytes read is added to `received`. If
+/// the decoder and the vector of lines are reused, no memory is allocated once they are
+/// large enough.
 pub(crate) fn receive_answer<W: BufRead + ?Sized>(
     input: &mut W,
+    decoder: &mut Decoder,
+    received: &mut u64,
     mut lines: Option<&mut Vec<String>>,
 ) -> ClientStatus {
     loop {
-        let mut line = String::new();
-        input.read_line(&mut line).map_err(ClientError::Io)?;
-        debug!("SSIP(in): {}", line.trim_end());
-        match line.chars().nth(3) {
-            Some(ch) => match ch {
-                ' ' => match line[0..3].parse::<u16>() {
-                    Ok(code) => return parse_status_line(code, line[4..].trim_end()),
-                    Err(err) => return Err(invalid_input!(err.to_string())),
-                },
-                '-' => match lines {
-                    Some(ref mut lines) => lines.push(line[4..].trim_end().to_string()),
-                    None => return Err(invalid_input!("unexpected line: {}", line)),
-                },
-                ch => {
-                    return Err(invalid_input!("expecting space or dash, got {}.", ch));
-                }
-            },
-            None if line.is_empty() => return Err(invalid_input!("empty line")),
-            None => return Err(invalid_input!("line too short: {}", line)),
+        if let Some(status) = decoder.decode_into(lines.as_deref_mut()) {
+            return status;
         }
+        let count = input
+            .read_until(b'\n', &mut decoder.buffer)
+            .map_err(ClientError::Io)?;
+        decoder.received(count, received)?;
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use std::io::BufReader;
+    use std::io::{self, BufReader};
+    use std::sync::{Arc, Mutex};
 
-    use super::{receive_answer, ClientError, ClientResult};
+    use super::{receive_answer, ClientError, ClientResult, Decoder, ParseMode, Utf8Mode};
 
-    use crate::types::SynthesisVoice;
+    use crate::constants::EVENT_INDEX_MARK;
+    use crate::demux::DEFAULT_EVENT_CAPACITY;
+    use crate::types::{Event, EventId, EventType, Response, StatusLine, SynthesisVoice};
 
     #[test]
     fn single_ok_status_line() {
         let mut input = BufReader::new("208 OK CLIENT NAME SET\r\n".as_bytes());
-        let status = receive_answer(&mut input, None).unwrap();
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            None,
+        )
+        .unwrap();
         assert_eq!(208, status.code);
         assert_eq!("CLIENT NAME SET", status.message);
     }
 
+    #[test]
+    fn status_line_with_diagnostics() {
+        let mut input = BufReader::new("208 OK CLIENT NAME SET  (build 42)  \r\n".as_bytes());
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            None,
+        )
+        .unwrap();
+        assert_eq!("CLIENT NAME SET  (build 42)", status.message);
+        assert_eq!("208 OK CLIENT NAME SET  (build 42)  \r\n", status.raw());
+        assert_eq!(StatusLine::new(208, "CLIENT NAME SET  (build 42)"), status);
+    }
+
     #[test]
     fn single_success_status_line() {
         let mut input = BufReader::new("231 HAPPY HACKING\r\n".as_bytes());
-        let status = receive_answer(&mut input, None).unwrap();
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            None,
+        )
+        .unwrap();
         assert_eq!(231, status.code);
         assert_eq!("HAPPY HACKING", status.message);
     }
@@ -270,15 +1280,133 @@ mod tests {
     #[test]
     fn single_err_status_line() {
         let mut input = BufReader::new("409 ERR RATE TOO HIGH\r\n".as_bytes());
-        match receive_answer(&mut input, None).err().unwrap() {
+        match receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            None,
+        )
+        .err()
+        .unwrap()
+        {
             ClientError::Ssip(status) => {
                 assert_eq!(409, status.code);
                 assert_eq!("RATE TOO HIGH", status.message);
+                assert_eq!("409 ERR RATE TOO HIGH\r\n", status.raw());
             }
             err => panic!("{}: invalid error", err),
         }
     }
 
+    #[test]
+    fn reused_lines() {
+        let mut input = BufReader::new(
+            "249-one\r\n249-two\r\n249 OK VOICE LIST SENT\r\n251-three\r\n251 OK GET\r\n"
+                .as_bytes(),
+        );
+        let mut decoder = Decoder::new(ParseMode::Strict, Utf8Mode::Strict);
+        let mut lines = vec![String::with_capacity(32)];
+        let first = lines[0].as_ptr();
+        for (code, expected) in [(249, vec!["one", "two"]), (251, vec!["three"])] {
+            let status =
+                receive_answer(&mut input, &mut decoder, &mut 0, Some(&mut lines)).unwrap();
+            assert_eq!(code, status.code);
+            assert_eq!(expected, lines);
+            assert_eq!(first, lines[0].as_ptr());
+        }
+    }
+
+    #[test]
+    fn lf_line_endings() {
+        let mut input = BufReader::new("208 OK CLIENT NAME SET\n".as_bytes());
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            None,
+        )
+        .unwrap();
+        assert_eq!(StatusLine::new(208, "CLIENT NAME SET"), status);
+        assert_eq!("208 OK CLIENT NAME SET\n", status.raw());
+        for answer in [
+            "249-afrikaans\taf\tnone\n249-en-rhotic\ten\tr\n249 OK VOICE LIST SENT\n",
+            "249-afrikaans\taf\tnone\r\n249-en-rhotic\ten\tr\n249 OK VOICE LIST SENT\r\n",
+        ] {
+            for mode in [ParseMode::Strict, ParseMode::Lenient] {
+                let mut input = BufReader::new(answer.as_bytes());
+                let mut lines = Vec::new();
+                let status = receive_answer(
+                    &mut input,
+                    &mut Decoder::new(mode, Utf8Mode::Strict),
+                    &mut 0,
+                    Some(&mut lines),
+                )
+                .unwrap();
+                assert_eq!(StatusLine::new(249, "VOICE LIST SENT"), status);
+                assert_eq!(
+                    vec!["afrikaans\taf\tnone", "en-rhotic\ten\tr"],
+                    lines.as_slice()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn strip_line_ending() {
+        assert_eq!("a", super::strip_line_ending("a\r\n"));
+        assert_eq!("a", super::strip_line_ending("a\n"));
+        assert_eq!("a", super::strip_line_ending("a"));
+        assert_eq!("a\r", super::strip_line_ending("a\r\r\n"));
+    }
+
+    #[test]
+    fn last_line_without_line_ending() {
+        let mut input = BufReader::new("251-10\r\n251 OK GET RETURNED".as_bytes());
+        let mut lines = Vec::new();
+        let mut received = 0;
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut received,
+            Some(&mut lines),
+        )
+        .unwrap();
+        assert_eq!(251, status.code);
+        assert_eq!("GET RETURNED", status.message);
+        assert_eq!(vec!["10"], lines);
+        assert_eq!(27, received);
+    }
+
+    #[test]
+    fn log_prefix() {
+        assert_eq!("SSIP", super::LogPrefix(None).to_string());
+        assert_eq!(
+            "SSIP[progress]",
+            super::LogPrefix(Some("progress")).to_string()
+        );
+    }
+
+    #[test]
+    fn interleaved_codes() {
+        for answer in [
+            "249-klatt\ten\tnone\r\n701-21\r\n701-1\r\n701 BEGIN\r\n",
+            "249-klatt\ten\tnone\r\n250 OK MODULE LIST SENT\r\n",
+            "701-21\r\n701-1\r\n701-2\r\n701-3\r\n701 BEGIN\r\n",
+        ] {
+            let mut input = BufReader::new(answer.as_bytes());
+            let mut lines = Vec::new();
+            match receive_answer(
+                &mut input,
+                &mut Decoder::new(ParseMode::Lenient, Utf8Mode::Strict),
+                &mut 0,
+                Some(&mut lines),
+            ) {
+                Err(ClientError::ProtocolViolation(_)) => (),
+                result => panic!("{}: unexpected result {:?}", answer, result),
+            }
+        }
+    }
+
     #[test]
     fn multi_lines() {
         let mut input = BufReader::new(
@@ -286,7 +1414,13 @@ mod tests {
                 .as_bytes(),
         );
         let mut lines = Vec::new();
-        let status = receive_answer(&mut input, Some(&mut lines)).unwrap();
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            Some(&mut lines),
+        )
+        .unwrap();
         assert_eq!(249, status.code);
         assert_eq!("VOICE LIST SENT", status.message);
         assert_eq!(
@@ -295,6 +1429,224 @@ mod tests {
         );
     }
 
+    #[test]
+    fn help_lines_with_spaces() {
+        // Transcript of speech-dispatcher 0.11
+        const ANSWER: &str = "248-  SPEAK           -- say text \r\n248-  KEY             -- say a combination of keys \r\n248 OK HELP SENT\r\n";
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let mut input = BufReader::new(ANSWER.as_bytes());
+            let mut lines = Vec::new();
+            let status = receive_answer(
+                &mut input,
+                &mut Decoder::new(mode, Utf8Mode::Strict),
+                &mut 0,
+                Some(&mut lines),
+            )
+            .unwrap();
+            assert_eq!(248, status.code);
+            assert_eq!(
+                vec![
+                    "  SPEAK           -- say text",
+                    "  KEY             -- say a combination of keys"
+                ],
+                lines.as_slice()
+            );
+        }
+    }
+
+    #[test]
+    fn strict_continuation_lines() {
+        for answer in [
+            "249+afrikaans\taf\tnone\r\n249 OK VOICE LIST SENT\r\n",
+            "Welcome to speech-dispatcher\r\n249 OK VOICE LIST SENT\r\n",
+            "249\r\n",
+            "24\r\n",
+            "2x9 OK VOICE LIST SENT\r\n",
+        ] {
+            let mut input = BufReader::new(answer.as_bytes());
+            let mut lines = Vec::new();
+            match receive_answer(
+                &mut input,
+                &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+                &mut 0,
+                Some(&mut lines),
+            ) {
+                Err(ClientError::Io(err)) => {
+                    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind(), "{}", answer)
+                }
+                result => panic!("{}: unexpected result {:?}", answer, result),
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_continuation_lines() {
+        let mut input = BufReader::new(
+            "249+afrikaans\taf\tnone\r\nBienvenue à speech-dispatcher\r\n249:en-rhotic\ten\tr\r\n249-amharic\tam\tnone\r\n249\r\n"
+                .as_bytes(),
+        );
+        let mut lines = Vec::new();
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Lenient, Utf8Mode::Strict),
+            &mut 0,
+            Some(&mut lines),
+        )
+        .unwrap();
+        assert_eq!(249, status.code);
+        assert_eq!("", status.message);
+        assert_eq!(
+            vec![
+                "afrikaans\taf\tnone",
+                "Bienvenue à speech-dispatcher",
+                "en-rhotic\ten\tr",
+                "amharic\tam\tnone"
+            ],
+            lines.as_slice()
+        );
+        let mut input = BufReader::new("Welcome\r\n208 OK CLIENT NAME SET\r\n".as_bytes());
+        assert!(matches!(
+            receive_answer(
+                &mut input,
+                &mut Decoder::new(ParseMode::Lenient, Utf8Mode::Strict),
+                &mut 0,
+                None,
+            ),
+            Err(ClientError::Io(_))
+        ));
+        let mut input = BufReader::new("".as_bytes());
+        assert!(matches!(
+            receive_answer(
+                &mut input,
+                &mut Decoder::new(ParseMode::Lenient, Utf8Mode::Strict),
+                &mut 0,
+                None,
+            ),
+            Err(ClientError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_utf8_lines() {
+        let answer: &[u8] = b"249-caf\xe9\tfr\tnone\r\n249 OK VOICE LIST SENT\r\n";
+        let mut input = BufReader::new(answer);
+        let mut lines = Vec::new();
+        match receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, Utf8Mode::Strict),
+            &mut 0,
+            Some(&mut lines),
+        ) {
+            Err(ClientError::Io(err)) => assert_eq!(io::ErrorKind::InvalidData, err.kind()),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        let invalid = Arc::new(Mutex::new(Vec::new()));
+        let mode = {
+            let invalid = Arc::clone(&invalid);
+            Utf8Mode::lossy(move |bytes| invalid.lock().unwrap().push(bytes.to_vec()))
+        };
+        let mut input = BufReader::new(answer);
+        let mut lines = Vec::new();
+        let status = receive_answer(
+            &mut input,
+            &mut Decoder::new(ParseMode::Strict, mode),
+            &mut 0,
+            Some(&mut lines),
+        )
+        .unwrap();
+        assert_eq!(249, status.code);
+        assert_eq!(vec!["caf\u{fffd}\tfr\tnone"], lines);
+        assert_eq!(
+            vec![b"249-caf\xe9\tfr\tnone\r\n".to_vec()],
+            *invalid.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn decoder() {
+        let answers: &[u8] = b"249-one\r\n249-two\r\n249 OK VOICE LIST SENT\r\n\
+            250-x\r\n251 OK GET\r\n208 OK CLIENT NAME SET\n701-21\r\n701-1\r\n701 BEGIN\r\n";
+        let mut decoder = Decoder::new(ParseMode::Strict, Utf8Mode::Strict);
+        let mut answers_lines = Vec::new();
+        let mut codes = Vec::new();
+        for chunk in answers.chunks(5) {
+            decoder.feed(chunk);
+            while let Some(status) = decoder.decode() {
+                match status {
+                    Ok(status) => codes.push(status.code),
+                    Err(ClientError::ProtocolViolation(_)) => codes.push(0),
+                    Err(err) => panic!("unexpected error: {}", err),
+                }
+                answers_lines.push(decoder.lines().to_vec());
+            }
+        }
+        assert_eq!(0, decoder.pending());
+        assert_eq!(vec![249, 0, 208, 701], codes);
+        assert_eq!(vec!["one", "two"], answers_lines[0]);
+        assert!(answers_lines[2].is_empty());
+        assert_eq!(vec!["21", "1"], answers_lines[3]);
+
+        let mut decoder = Decoder::new(ParseMode::Lenient, Utf8Mode::Strict);
+        decoder.feed(b"251-10\r\n251 OK GET RETURNED\r\n702-21\r");
+        assert_eq!(
+            Some(Response::Get(String::from("10"))),
+            decoder.decode_response().map(Result::unwrap)
+        );
+        assert!(decoder.decode_response().is_none());
+        assert_eq!(b"702-21\r".len(), decoder.pending());
+
+        let mut decoder = Decoder::new(ParseMode::Strict, Utf8Mode::Strict);
+        decoder.feed(b"700-21\r\n700-1\r\n700-first\r\n700 INDEX MARK\r\n");
+        decoder.feed(b"700-22\r\n700-1\r\n700-second\r\n700 INDEX MARK\r\n");
+        decoder.feed(b"250-one\r\n250 OK MODULE LIST SENT\r\n");
+        let responses = std::iter::from_fn(|| decoder.decode_response())
+            .collect::<ClientResult<Vec<Response>>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                Response::EventIndexMark(EventId::new(21, 1), String::from("first")),
+                Response::EventIndexMark(EventId::new(22, 1), String::from("second")),
+                Response::OutputModulesListSent(vec![String::from("one")]),
+            ],
+            responses
+        );
+    }
+
+    #[test]
+    fn parse_event() {
+        let mut lines = ["21", "1", "mark"].map(String::from);
+        assert_eq!(
+            Some(Event::new(
+                EventType::IndexMark(String::from("mark")),
+                EventId::new(21, 1)
+            )),
+            super::parse_event(EVENT_INDEX_MARK, &mut lines).unwrap()
+        );
+        assert!(lines.iter().all(String::is_empty));
+        let mut lines = ["21", "1"].map(String::from);
+        assert_eq!(None, super::parse_event(799, &mut lines).unwrap());
+        assert_eq!(["21", "1"], lines);
+        assert!(super::parse_event(EVENT_INDEX_MARK, &mut lines).is_err());
+    }
+
+    #[test]
+    fn describe_requests() {
+        let requests = super::describe();
+        assert_eq!(55, requests.len());
+        let names = requests
+            .iter()
+            .map(|description| description.request)
+            .collect::<std::collections::HashSet<&str>>();
+        assert_eq!(requests.len(), names.len());
+        let rate = requests
+            .iter()
+            .find(|description| description.request == "GetRate")
+            .unwrap();
+        assert_eq!("GET RATE", rate.syntax);
+        assert_eq!(&[crate::constants::OK_GET], rate.success);
+        assert!(rate.returns_data);
+    }
+
     #[test]
     fn parse_single_value() -> ClientResult<()> {
         let no_lines = Vec::new();
@@ -330,12 +1682,16 @@ mod tests {
             Err(ClientError::TooFewLines)
         ));
 
-        let mid = String::from("message");
-        let cid = String::from("client");
-        let two_lines = vec![mid.to_owned(), cid.to_owned()];
+        let two_lines = vec![String::from("21"), String::from("1")];
         let event_id = super::parse_event_id(&two_lines)?;
-        assert_eq!(mid, event_id.message);
-        assert_eq!(cid, event_id.client);
+        assert_eq!(21, event_id.message);
+        assert_eq!(1, event_id.client);
+
+        let not_numbers = vec![String::from("message"), String::from("client")];
+        assert!(matches!(
+            super::parse_event_id(&not_numbers),
+            Err(ClientError::Io(_))
+        ));
 
         let three_lines = vec![
             String::from("one"),
@@ -363,4 +1719,187 @@ mod tests {
         assert_eq!(Some(String::from("uk-north")), voices[2].dialect);
         Ok(())
     }
+
+    fn status_line(code: u16, message: &str) -> StatusLine {
+        StatusLine::new(code, message)
+    }
+
+    #[test]
+    fn parse_response() -> ClientResult<()> {
+        let lines = vec![String::from("21")];
+        assert_eq!(
+            Response::MessageQueued,
+            super::parse_response(
+                status_line(225, "OK MESSAGE QUEUED"),
+                lines,
+                ParseMode::Strict
+            )?
+        );
+        assert_eq!(
+            Response::HistoryCurSetFirst,
+            super::parse_response(
+                status_line(220, "CURSOR SET FIRST"),
+                Vec::new(),
+                ParseMode::Strict
+            )?
+        );
+        assert_eq!(
+            Response::NotificationSet,
+            super::parse_response(
+                status_line(220, "OK NOTIFICATION SET"),
+                Vec::new(),
+                ParseMode::Strict
+            )?
+        );
+        let lines = ["21", "4", "mark"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            Response::EventIndexMark(EventId::new(21, 4), String::from("mark")),
+            super::parse_response(status_line(700, "INDEX MARK"), lines, ParseMode::Strict)?
+        );
+        assert_eq!(
+            Response::UnknownEvent {
+                code: 706,
+                lines: vec![String::from("21")],
+            },
+            super::parse_response(
+                status_line(706, "FUTURE"),
+                vec![String::from("21")],
+                ParseMode::Strict
+            )?
+        );
+        assert!(matches!(
+            super::parse_response(
+                status_line(298, "OK UNKNOWN"),
+                Vec::new(),
+                ParseMode::Strict
+            ),
+            Err(ClientError::UnexpectedStatus(298))
+        ));
+        assert!(matches!(
+            super::parse_response(
+                status_line(409, "ERR RATE TOO HIGH"),
+                Vec::new(),
+                ParseMode::Strict
+            ),
+            Err(ClientError::Ssip(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_get_lines() -> ClientResult<()> {
+        let lines = vec![String::from("espeak"), String::from("ssml")];
+        assert!(matches!(
+            super::parse_response(status_line(251, "OK GET"), lines.clone(), ParseMode::Strict),
+            Err(ClientError::TooManyLines)
+        ));
+        assert_eq!(
+            Response::GetLines(lines.clone()),
+            super::parse_response(status_line(251, "OK GET"), lines, ParseMode::Lenient)?
+        );
+        assert_eq!(
+            Response::Get(String::from("10")),
+            super::parse_response(
+                status_line(251, "OK GET"),
+                vec![String::from("10")],
+                ParseMode::Lenient
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_unknown_list() -> ClientResult<()> {
+        let lines = vec![String::from("braille"), String::from("latin")];
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert_eq!(
+                Response::List {
+                    code: 252,
+                    items: lines.clone()
+                },
+                super::parse_response(
+                    status_line(252, "OK TABLES LIST SENT"),
+                    lines.clone(),
+                    mode
+                )?
+            );
+        }
+        assert!(
+            super::parse_response(status_line(270, "OK"), Vec::new(), ParseMode::Strict).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn type_response() -> ClientResult<()> {
+        let get = || Response::Get(String::from("-10"));
+        assert_eq!(
+            Response::GetRate(-10),
+            super::type_response(Some("GetRate"), get())?
+        );
+        assert_eq!(
+            Response::GetVolume(-10),
+            super::type_response(Some("GetVolume"), get())?
+        );
+        assert!(super::type_response(Some("GetPauseContext"), get()).is_err());
+        assert_eq!(
+            Response::GetPauseContext(2),
+            super::type_response(Some("GetPauseContext"), Response::Get(String::from("2")))?
+        );
+        assert_eq!(get(), super::type_response(Some("GetLanguage"), get())?);
+        assert_eq!(get(), super::type_response(None, get())?);
+        assert!(
+            super::type_response(Some("GetPitch"), Response::Get(String::from("high"))).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn split_text() {
+        assert_eq!(vec!["one two"], super::split_text("one two", 100));
+        assert_eq!(
+            vec!["one\n", "two three ", "four"],
+            super::split_text("one\ntwo three four", 10)
+        );
+        assert_eq!(
+            vec!["abcd", "efgh", "ij"],
+            super::split_text("abcdefghij", 4)
+        );
+        assert_eq!(vec!["é", "é"], super::split_text("éé", 1));
+        assert!(super::split_text(" \n\n ", 2).is_empty());
+    }
+
+    #[test]
+    fn text_lines() {
+        assert_eq!(
+            vec!["Hello", "..", "world"],
+            super::text_lines("Hello\n.\r\nworld\n")
+        );
+        assert!(super::text_lines("").is_empty());
+    }
+
+    fn end_lines(msg_id: usize) -> Vec<String> {
+        vec![msg_id.to_string(), String::from("1")]
+    }
+
+    #[test]
+    fn event_queue() -> ClientResult<()> {
+        let mut events = super::EventQueue::default();
+        let end = status_line(702, "END");
+        for msg_id in 0..=DEFAULT_EVENT_CAPACITY {
+            assert!(events.keep(&end, &mut end_lines(msg_id), None, None)?);
+        }
+        let ok = status_line(225, "OK MESSAGE QUEUED");
+        assert!(!events.keep(&ok, &mut end_lines(0), None, None)?);
+        assert_eq!(Some(Event::end(1, 1)), events.pop()?);
+        let mut count = 1;
+        while events.pop()?.is_some() {
+            count += 1;
+        }
+        assert_eq!(DEFAULT_EVENT_CAPACITY, count);
+        Ok(())
+    }
 }
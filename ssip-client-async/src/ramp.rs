@@ -0,0 +1,231 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Gradual change of the rate or the pitch.
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::{
+    client::{Client, Source},
+    constants::{OK_PITCH_SET, OK_RATE_SET},
+    types::*,
+};
+
+/// Setting changed by a ramp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parameter {
+    Rate,
+    Pitch,
+}
+
+impl Parameter {
+    fn request(self, scope: ClientScope, value: i8) -> Request {
+        match self {
+            Parameter::Rate => Request::SetRate(scope, value),
+            Parameter::Pitch => Request::SetPitch(scope, value),
+        }
+    }
+
+    fn status(self) -> ReturnCode {
+        match self {
+            Parameter::Rate => OK_RATE_SET,
+            Parameter::Pitch => OK_PITCH_SET,
+        }
+    }
+}
+
+/// Move the rate or the pitch towards a target in bounded steps.
+///
+/// Each request changes the value by at most `step` and requests are at least `interval`
+/// apart. Changing the target between two requests doesn't send anything: only the last
+/// target is used at the next step. The ramp can be driven with [`Ramp::next`] and
+/// [`Ramp::next_deadline`] or with the synchronous client with [`Ramp::send_next`].
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, ramp::{Parameter, Ramp}, ClientScope};
+/// let mut client = fifo::Builder::new().build()?;
+/// let mut ramp = Ramp::new(Parameter::Rate, ClientScope::Current, 0);
+/// ramp.set_target(60);
+/// while !ramp.is_done() {
+///     if let Some(deadline) = ramp.next_deadline() {
+///         std::thread::sleep(deadline.saturating_duration_since(std::time::Instant::now()));
+///     }
+///     ramp.send_next(&mut client)?;
+/// }
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ramp {
+    parameter: Parameter,
+    scope: ClientScope,
+    current: i8,
+    target: i8,
+    step: u8,
+    interval: Duration,
+    last_sent: Option<Instant>,
+}
+
+impl Ramp {
+    /// Default maximum change of each request.
+    pub const DEFAULT_STEP: u8 = 10;
+    /// Default minimum time between two requests.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// New ramp starting from the current value of the setting.
+    pub fn new(parameter: Parameter, scope: ClientScope, current: i8) -> Self {
+        let current = current.clamp(-100, 100);
+        Self {
+            parameter,
+            scope,
+            current,
+            target: current,
+            step: Self::DEFAULT_STEP,
+            interval: Self::DEFAULT_INTERVAL,
+            last_sent: None,
+        }
+    }
+
+    /// Maximum change of each request. A step of 0 is replaced by 1.
+    pub fn set_step(&mut self, step: u8) -> &mut Self {
+        self.step = step.max(1);
+        self
+    }
+
+    /// Minimum time between two requests.
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Value to reach, clamped to the range -100 to 100.
+    pub fn set_target(&mut self, target: i8) -> &mut Self {
+        self.target = target.clamp(-100, 100);
+        self
+    }
+
+    /// Value to reach.
+    pub fn target(&self) -> i8 {
+        self.target
+    }
+
+    /// Last value requested.
+    pub fn current(&self) -> i8 {
+        self.current
+    }
+
+    /// The target is reached.
+    pub fn is_done(&self) -> bool {
+        self.current == self.target
+    }
+
+    /// Instant when the next request can be sent, `None` if the target is reached.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.is_done() {
+            None
+        } else {
+            self.last_sent.map(|instant| instant + self.interval)
+        }
+    }
+
+    /// Return the next request if the target is not reached and the interval is elapsed.
+    pub fn next(&mut self, now: Instant) -> Option<Request> {
+        if self.is_done() {
+            return None;
+        }
+        if matches!(self.last_sent, Some(instant) if now < instant + self.interval) {
+            return None;
+        }
+        let step = i16::from(self.step);
+        let delta = (i16::from(self.target) - i16::from(self.current)).clamp(-step, step);
+        self.current = (i16::from(self.current) + delta) as i8;
+        self.last_sent = Some(now);
+        Some(self.parameter.request(self.scope, self.current))
+    }
+
+    /// Send the next request with the synchronous client if it is time.
+    ///
+    /// Return true if a request was sent.
+    pub fn send_next<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+    ) -> ClientResult<bool> {
+        match self.next(Instant::now()) {
+            Some(request) => {
+                client
+                    .send(request)?
+                    .check_status(self.parameter.status())?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::{Duration, Instant};
+
+    use super::{Parameter, Ramp};
+    use crate::types::{ClientScope, Request};
+
+    #[test]
+    fn bounded_steps() {
+        let mut ramp = Ramp::new(Parameter::Rate, ClientScope::Current, 0);
+        ramp.set_step(25).set_interval(Duration::from_millis(100));
+        assert!(ramp.next(Instant::now()).is_none());
+        ramp.set_target(60);
+        let start = Instant::now();
+        assert_eq!(
+            Some(Request::SetRate(ClientScope::Current, 25)),
+            ramp.next(start)
+        );
+        assert_eq!(None, ramp.next(start + Duration::from_millis(50)));
+        assert_eq!(
+            Some(start + Duration::from_millis(100)),
+            ramp.next_deadline()
+        );
+        let later = start + Duration::from_millis(100);
+        assert_eq!(
+            Some(Request::SetRate(ClientScope::Current, 50)),
+            ramp.next(later)
+        );
+        let later = later + Duration::from_millis(100);
+        assert_eq!(
+            Some(Request::SetRate(ClientScope::Current, 60)),
+            ramp.next(later)
+        );
+        assert!(ramp.is_done());
+        assert_eq!(None, ramp.next_deadline());
+        assert_eq!(None, ramp.next(later + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn debounced_target() {
+        let mut ramp = Ramp::new(Parameter::Pitch, ClientScope::Current, 10);
+        let start = Instant::now();
+        ramp.set_target(0);
+        assert_eq!(
+            Some(Request::SetPitch(ClientScope::Current, 0)),
+            ramp.next(start)
+        );
+        for target in [-10, -20, -30, 120] {
+            ramp.set_target(target);
+            assert_eq!(None, ramp.next(start));
+        }
+        assert_eq!(100, ramp.target());
+        assert_eq!(0, ramp.current());
+        assert_eq!(
+            Some(Request::SetPitch(ClientScope::Current, 10)),
+            ramp.next(start + Ramp::DEFAULT_INTERVAL)
+        );
+    }
+}
@@ -0,0 +1,330 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::{
+    client::{Client, Source},
+    poll::QueuedClient,
+    protocol::text_lines,
+    types::*,
+};
+
+/// What to do with a message exceeding the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// Discard the message.
+    Drop,
+    /// Keep the message until the limit allows it. A newer message of the same priority
+    /// replaces it.
+    Supersede,
+}
+
+/// Maximum number of messages in a period of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Policy {
+    /// Maximum number of messages in the period. Zero means no limit.
+    pub count: usize,
+    pub period: Duration,
+    pub overflow: Overflow,
+}
+
+impl Policy {
+    pub fn new(count: usize, period: Duration, overflow: Overflow) -> Self {
+        Self {
+            count,
+            period,
+            overflow,
+        }
+    }
+
+    /// At most `count` messages per second.
+    pub fn per_second(count: usize, overflow: Overflow) -> Self {
+        Self::new(count, Duration::from_secs(1), overflow)
+    }
+}
+
+/// Messages sent recently for a priority.
+struct Window {
+    policy: Policy,
+    sent: VecDeque<Instant>,
+    pending: Option<Message>,
+}
+
+impl Window {
+    fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            sent: VecDeque::with_capacity(policy.count),
+            pending: None,
+        }
+    }
+
+    /// Forget messages sent before the current period.
+    fn expire(&mut self, now: Instant) {
+        while let Some(instant) = self.sent.front() {
+            if now.duration_since(*instant) >= self.policy.period {
+                self.sent.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Record a message sent now if the limit allows it.
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        if self.policy.count == 0 {
+            return true;
+        }
+        self.expire(now);
+        if self.sent.len() < self.policy.count {
+            self.sent.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Instant when the next message will be allowed.
+    fn next_slot(&self) -> Option<Instant> {
+        if self.sent.len() < self.policy.count {
+            None
+        } else {
+            self.sent
+                .front()
+                .map(|instant| *instant + self.policy.period)
+        }
+    }
+}
+
+/// Limit the rate of messages by priority.
+///
+/// Messages with a priority without policy are never limited. The limiter can be used
+/// with the synchronous [`Client`] or with the [`QueuedClient`]. Messages kept by the
+/// [`Overflow::Supersede`] policy must be sent later with [`RateLimiter::send_ready`]
+/// or [`RateLimiter::push_ready`], for example after [`RateLimiter::next_deadline`].
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, ratelimit::{Overflow, Policy, RateLimiter}, Message, Priority};
+/// let mut client = fifo::Builder::new().build()?;
+/// let mut limiter = RateLimiter::new();
+/// limiter.set_policy(Priority::Progress, Policy::per_second(2, Overflow::Supersede));
+/// limiter.send_message(&mut client, Message::new("10%", Priority::Progress))?;
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Default)]
+pub struct RateLimiter {
+    windows: HashMap<Priority, Window>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the policy for a priority.
+    pub fn set_policy(&mut self, priority: Priority, policy: Policy) -> &mut Self {
+        self.windows.insert(priority, Window::new(policy));
+        self
+    }
+
+    /// Return the message if it can be sent now. Otherwise it is dropped or kept for later.
+    pub fn submit(&mut self, message: Message, now: Instant) -> Option<Message> {
+        match self.windows.get_mut(&message.priority) {
+            Some(window) => {
+                if window.pending.is_none() && window.try_acquire(now) {
+                    Some(message)
+                } else {
+                    if window.policy.overflow == Overflow::Supersede {
+                        window.pending = Some(message);
+                    }
+                    None
+                }
+            }
+            None => Some(message),
+        }
+    }
+
+    /// Return the messages kept for later that can now be sent.
+    pub fn ready(&mut self, now: Instant) -> Vec<Message> {
+        self.windows
+            .values_mut()
+            .filter_map(|window| {
+                if window.pending.is_some() && window.try_acquire(now) {
+                    window.pending.take()
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Discard the messages kept for later, for example after an emergency stop.
+    pub fn clear(&mut self) {
+        for window in self.windows.values_mut() {
+            window.pending = None;
+        }
+    }
+
+    /// Return true if messages are waiting for the limit to allow them.
+    pub fn has_pending(&self) -> bool {
+        self.windows.values().any(|window| window.pending.is_some())
+    }
+
+    /// Instant when the next message kept for later can be sent.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.windows
+            .values()
+            .filter(|window| window.pending.is_some())
+            .filter_map(|window| window.next_slot())
+            .min()
+    }
+
+    /// Speak the message with the synchronous client if the limit allows it.
+    ///
+    /// Return the message id or `None` if the message was not sent.
+    pub fn send_message<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+        message: Message,
+    ) -> ClientResult<Option<MessageId>> {
+        match self.submit(message, Instant::now()) {
+            Some(message) => client.send_message(&message).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Speak the messages kept for later that can now be sent.
+    pub fn send_ready<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+    ) -> ClientResult<Vec<MessageId>> {
+        self.ready(Instant::now())
+            .iter()
+            .map(|message| client.send_message(message))
+            .collect()
+    }
+
+    /// Push the requests to speak the message in the queue if the limit allows it.
+    ///
+    /// Only the priority is set, the language and voice type of the message are ignored.
+    /// As with [`Client::send_message`], the priority is reset to text afterwards, so the
+    /// responses include the answer to this request. Return true if the message was queued.
+    pub fn push_message<S: Read + Write + Source>(
+        &mut self,
+        client: &mut QueuedClient<S>,
+        message: Message,
+    ) -> bool {
+        match self.submit(message, Instant::now()) {
+            Some(message) => {
+                Self::push_requests(client, message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Push the messages kept for later that can now be sent. Return the number of messages.
+    pub fn push_ready<S: Read + Write + Source>(&mut self, client: &mut QueuedClient<S>) -> usize {
+        let messages = self.ready(Instant::now());
+        let count = messages.len();
+        for message in messages {
+            Self::push_requests(client, message);
+        }
+        count
+    }
+
+    fn push_requests<S: Read + Write + Source>(client: &mut QueuedClient<S>, message: Message) {
+        let reset = message.priority != Priority::Text;
+        client.push(Request::SetPriority(message.priority));
+        client.push(Request::Speak);
+        client.push(Request::SendLines(text_lines(&message.text)));
+        if reset {
+            client.push(Request::SetPriority(Priority::Text));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::{Duration, Instant};
+
+    use super::{Overflow, Policy, RateLimiter};
+    use crate::types::{Message, Priority};
+
+    fn progress(text: &str) -> Message {
+        Message::new(text, Priority::Progress)
+    }
+
+    #[test]
+    fn unlimited_priority() {
+        let mut limiter = RateLimiter::new();
+        limiter.set_policy(Priority::Progress, Policy::per_second(1, Overflow::Drop));
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(limiter
+                .submit(Message::new("hello", Priority::Text), now)
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn zero_count() {
+        let mut limiter = RateLimiter::new();
+        limiter.set_policy(
+            Priority::Progress,
+            Policy::per_second(0, Overflow::Supersede),
+        );
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(limiter.submit(progress("1"), now).is_some());
+        }
+        assert!(!limiter.has_pending());
+        assert_eq!(None, limiter.next_deadline());
+    }
+
+    #[test]
+    fn drop_over_limit() {
+        let mut limiter = RateLimiter::new();
+        limiter.set_policy(Priority::Progress, Policy::per_second(2, Overflow::Drop));
+        let now = Instant::now();
+        assert!(limiter.submit(progress("1"), now).is_some());
+        assert!(limiter.submit(progress("2"), now).is_some());
+        assert!(limiter.submit(progress("3"), now).is_none());
+        assert!(!limiter.has_pending());
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.submit(progress("4"), later).is_some());
+    }
+
+    #[test]
+    fn supersede_over_limit() {
+        let mut limiter = RateLimiter::new();
+        limiter.set_policy(
+            Priority::Progress,
+            Policy::per_second(1, Overflow::Supersede),
+        );
+        let now = Instant::now();
+        assert!(limiter.submit(progress("10%"), now).is_some());
+        assert!(limiter.submit(progress("20%"), now).is_none());
+        assert!(limiter.submit(progress("30%"), now).is_none());
+        assert!(limiter.has_pending());
+        assert_eq!(Some(now + Duration::from_secs(1)), limiter.next_deadline());
+        assert!(limiter.ready(now).is_empty());
+        let ready = limiter.ready(now + Duration::from_secs(1));
+        assert_eq!(vec![progress("30%")], ready);
+        assert!(!limiter.has_pending());
+        assert!(limiter.submit(progress("40%"), now).is_none());
+        limiter.clear();
+        assert!(!limiter.has_pending());
+    }
+}
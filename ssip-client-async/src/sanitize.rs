@@ -0,0 +1,244 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Cleaning of the text sent to the server.
+//!
+//! Text extracted from documents often contains byte order marks, zero-width characters
+//! or control characters that synthesizers spell or stumble on. Once a sanitizer is set
+//! on a client, the lines of the messages are cleaned before they are sent.
+//!
+//! Example
+//! ```no_run
+//! use ssip_client_async::{fifo, sanitize::TextSanitizer};
+//! let mut client = fifo::Builder::new().build()?;
+//! let mut sanitizer = TextSanitizer::default();
+//! sanitizer.filter(|line: &mut String| *line = line.replace('\u{a0}', " "));
+//! client.set_text_sanitizer(sanitizer);
+//! # Ok::<(), ssip_client_async::ClientError>(())
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+/// Custom filter applied to each line after the characters are stripped.
+pub type TextFilter = Arc<dyn Fn(&mut String) + Send + Sync>;
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Zero-width space, non-joiner, joiner and word joiner.
+const ZERO_WIDTH_CHARS: [char; 4] = ['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}'];
+
+/// Characters removed from the lines and custom filters.
+///
+/// By default, byte order marks, zero-width characters and control characters other
+/// than tabulations are removed.
+#[derive(Clone)]
+pub struct TextSanitizer {
+    strip_bom: bool,
+    strip_zero_width: bool,
+    strip_control: bool,
+    filters: Vec<TextFilter>,
+}
+
+impl Default for TextSanitizer {
+    fn default() -> Self {
+        Self {
+            strip_bom: true,
+            strip_zero_width: true,
+            strip_control: true,
+            filters: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for TextSanitizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextSanitizer")
+            .field("strip_bom", &self.strip_bom)
+            .field("strip_zero_width", &self.strip_zero_width)
+            .field("strip_control", &self.strip_control)
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl TextSanitizer {
+    /// Remove the byte order marks.
+    pub fn strip_bom(&mut self, value: bool) -> &mut Self {
+        self.strip_bom = value;
+        self
+    }
+
+    /// Remove the zero-width spaces, joiners and non-joiners.
+    pub fn strip_zero_width(&mut self, value: bool) -> &mut Self {
+        self.strip_zero_width = value;
+        self
+    }
+
+    /// Remove the control characters, except tabulations.
+    pub fn strip_control(&mut self, value: bool) -> &mut Self {
+        self.strip_control = value;
+        self
+    }
+
+    /// Add a filter applied to each line, in the order filters are added.
+    pub fn filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&mut String) + Send + Sync + 'static,
+    {
+        self.filters.push(Arc::new(filter));
+        self
+    }
+
+    fn is_stripped(&self, ch: char) -> bool {
+        (self.strip_bom && ch == BYTE_ORDER_MARK)
+            || (self.strip_zero_width && ZERO_WIDTH_CHARS.contains(&ch))
+            || (self.strip_control && ch.is_control() && ch != '\t')
+    }
+
+    /// Clean a line of text. The line is only copied if it changes.
+    pub fn sanitize<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut line = if line.chars().any(|ch| self.is_stripped(ch)) {
+            Cow::Owned(line.chars().filter(|ch| !self.is_stripped(*ch)).collect())
+        } else {
+            Cow::Borrowed(line)
+        };
+        for filter in self.filters.iter() {
+            filter(line.to_mut());
+        }
+        line
+    }
+}
+
+/// Clean a line of a message with the sanitizer if any.
+///
+/// A line that becomes a single dot is escaped so that it doesn't end the message.
+pub(crate) fn sanitize_line<'a>(sanitizer: Option<&TextSanitizer>, line: &'a str) -> Cow<'a, str> {
+    match sanitizer {
+        Some(sanitizer) => match sanitizer.sanitize(line) {
+            sanitized if sanitized == "." && line != "." => Cow::Borrowed(".."),
+            sanitized => sanitized,
+        },
+        None => Cow::Borrowed(line),
+    }
+}
+
+/// Tell if a text looks like SSML: a root `speak` element, optionally after an XML
+/// declaration.
+pub fn is_ssml(text: &str) -> bool {
+    let mut text = text.trim();
+    if text.starts_with("<?xml") {
+        text = match text.find("?>") {
+            Some(pos) => text[pos + 2..].trim_start(),
+            None => return false,
+        };
+    }
+    match text.strip_prefix("<speak") {
+        Some(rest) => {
+            rest.starts_with(|ch: char| ch == '>' || ch.is_ascii_whitespace())
+                && text.ends_with("</speak>")
+        }
+        None => false,
+    }
+}
+
+/// Escape the characters reserved in XML so that plain text can be sent in SSML mode.
+///
+/// The text is only copied if it contains reserved characters.
+pub fn escape_xml(text: &str) -> Cow<'_, str> {
+    const RESERVED: [char; 5] = ['&', '<', '>', '"', '\''];
+    if !text.contains(RESERVED) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            ch => escaped.push(ch),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Text to send for a message spoken with [`Client::speak_auto`](crate::Client::speak_auto).
+///
+/// Plain text sent in SSML mode is escaped and put in a `speak` element.
+pub(crate) fn auto_text(text: &str, ssml_mode: bool) -> Cow<'_, str> {
+    if ssml_mode && !is_ssml(text) {
+        Cow::Owned(format!("<speak>{}</speak>", escape_xml(text)))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::borrow::Cow;
+
+    use super::{auto_text, escape_xml, is_ssml, sanitize_line, TextSanitizer};
+
+    #[test]
+    fn default_sanitizer() {
+        let sanitizer = TextSanitizer::default();
+        assert!(matches!(
+            sanitizer.sanitize("hello\tworld"),
+            Cow::Borrowed("hello\tworld")
+        ));
+        assert_eq!(
+            "hello world",
+            sanitizer.sanitize("\u{feff}hel\u{200b}lo\u{7} world\r")
+        );
+    }
+
+    #[test]
+    fn custom_sanitizer() {
+        let mut sanitizer = TextSanitizer::default();
+        sanitizer
+            .strip_control(false)
+            .filter(|line: &mut String| *line = line.to_uppercase());
+        assert_eq!("HELLO\u{7}", sanitizer.sanitize("\u{feff}hello\u{7}"));
+    }
+
+    #[test]
+    fn escape_sanitized_dot() {
+        let sanitizer = TextSanitizer::default();
+        assert_eq!("..", sanitize_line(Some(&sanitizer), "\u{200b}."));
+        assert_eq!("..", sanitize_line(Some(&sanitizer), ".."));
+        assert_eq!("\u{200b}.", sanitize_line(None, "\u{200b}."));
+    }
+
+    #[test]
+    fn detect_ssml() {
+        assert!(is_ssml("<speak>Hello</speak>"));
+        assert!(is_ssml(
+            "<?xml version=\"1.0\"?>\n<speak version=\"1.1\">\nHello\n</speak>\n"
+        ));
+        assert!(!is_ssml("<speaker>Hello</speaker>"));
+        assert!(!is_ssml("Use <speak> to start SSML"));
+        assert!(!is_ssml("<speak>unterminated"));
+    }
+
+    #[test]
+    fn escape_plain_text() {
+        assert!(matches!(escape_xml("Hello"), Cow::Borrowed("Hello")));
+        assert_eq!(
+            "1 &lt; 2 &amp;&amp; &quot;a&quot; &gt; &apos;b&apos;",
+            escape_xml("1 < 2 && \"a\" > 'b'")
+        );
+        assert_eq!("<speak>a &amp; b</speak>", auto_text("a & b", true));
+        assert_eq!("a & b", auto_text("a & b", false));
+        assert_eq!("<speak>Hi</speak>", auto_text("<speak>Hi</speak>", true));
+    }
+}
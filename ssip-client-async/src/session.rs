@@ -0,0 +1,344 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use crate::types::*;
+
+/// Settings applied on every connection.
+///
+/// Builders accept a template to configure the connection right after it is established,
+/// so that it always comes up in a known state. The client name is set first, then the
+/// output module, the voice settings and the notifications. With the mio builders, the
+/// sockets are non-blocking and the [`SessionTemplate::requests`] must be pushed in a
+/// [`QueuedClient`](crate::QueuedClient) instead.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, ClientName, NotificationType, SessionTemplate};
+/// let mut session = SessionTemplate::new(ClientName::new("joe", "hello")?);
+/// session.ssml_mode(true).rate(20).notification(NotificationType::All, true);
+/// let mut client = fifo::sync::Builder::new().session(&session).build()?;
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionTemplate {
+    name: ClientName,
+    priority: Option<Priority>,
+    output_module: Option<String>,
+    language: Option<LanguageTag>,
+    synthesis_voice: Option<String>,
+    voice_type: Option<VoiceType>,
+    rate: Option<i8>,
+    pitch: Option<i8>,
+    volume: Option<i8>,
+    pause_context: Option<u32>,
+    punctuation_mode: Option<PunctuationMode>,
+    ssml_mode: Option<bool>,
+    notifications: Vec<(NotificationType, bool)>,
+}
+
+impl SessionTemplate {
+    pub fn new(name: ClientName) -> Self {
+        Self {
+            name,
+            priority: None,
+            output_module: None,
+            language: None,
+            synthesis_voice: None,
+            voice_type: None,
+            rate: None,
+            pitch: None,
+            volume: None,
+            pause_context: None,
+            punctuation_mode: None,
+            ssml_mode: None,
+            notifications: Vec::new(),
+        }
+    }
+
+    /// Priority of the messages sent without explicit priority.
+    pub fn priority(&mut self, value: Priority) -> &mut Self {
+        self.priority = Some(value);
+        self
+    }
+
+    pub fn output_module(&mut self, value: &str) -> &mut Self {
+        self.output_module = Some(value.to_string());
+        self
+    }
+
+    pub fn language(&mut self, value: LanguageTag) -> &mut Self {
+        self.language = Some(value);
+        self
+    }
+
+    pub fn synthesis_voice(&mut self, value: &str) -> &mut Self {
+        self.synthesis_voice = Some(value.to_string());
+        self
+    }
+
+    pub fn voice_type(&mut self, value: VoiceType) -> &mut Self {
+        self.voice_type = Some(value);
+        self
+    }
+
+    pub fn rate(&mut self, value: i8) -> &mut Self {
+        self.rate = Some(value);
+        self
+    }
+
+    pub fn pitch(&mut self, value: i8) -> &mut Self {
+        self.pitch = Some(value);
+        self
+    }
+
+    pub fn volume(&mut self, value: i8) -> &mut Self {
+        self.volume = Some(value);
+        self
+    }
+
+    /// Number of sentences repeated when a paused text is resumed.
+    pub fn pause_context(&mut self, value: u32) -> &mut Self {
+        self.pause_context = Some(value);
+        self
+    }
+
+    pub fn punctuation_mode(&mut self, mode: PunctuationMode) -> &mut Self {
+        self.punctuation_mode = Some(mode);
+        self
+    }
+
+    pub fn ssml_mode(&mut self, mode: bool) -> &mut Self {
+        self.ssml_mode = Some(mode);
+        self
+    }
+
+    /// Enable or disable a notification. Notifications are set in the order of the calls.
+    pub fn notification(&mut self, ntype: NotificationType, value: bool) -> &mut Self {
+        self.notifications.push((ntype, value));
+        self
+    }
+
+    /// Requests to replay on a new connection.
+    pub fn requests(&self) -> Vec<Request> {
+        let mut requests = vec![Request::SetName(self.name.clone())];
+        if let Some(value) = &self.priority {
+            requests.push(Request::SetPriority(value.clone()));
+        }
+        if let Some(value) = &self.output_module {
+            requests.push(Request::SetOutputModule(
+                ClientScope::Current,
+                value.clone(),
+            ));
+        }
+        if let Some(value) = &self.language {
+            requests.push(Request::SetLanguage(ClientScope::Current, value.clone()));
+        }
+        if let Some(value) = &self.synthesis_voice {
+            requests.push(Request::SetSynthesisVoice(
+                ClientScope::Current,
+                value.clone(),
+            ));
+        }
+        if let Some(value) = &self.voice_type {
+            requests.push(Request::SetVoiceType(ClientScope::Current, value.clone()));
+        }
+        if let Some(value) = self.rate {
+            requests.push(Request::SetRate(ClientScope::Current, value));
+        }
+        if let Some(value) = self.pitch {
+            requests.push(Request::SetPitch(ClientScope::Current, value));
+        }
+        if let Some(value) = self.volume {
+            requests.push(Request::SetVolume(ClientScope::Current, value));
+        }
+        if let Some(value) = self.pause_context {
+            requests.push(Request::SetPauseContext(ClientScope::Current, value));
+        }
+        if let Some(mode) = &self.punctuation_mode {
+            requests.push(Request::SetPunctuationMode(
+                ClientScope::Current,
+                mode.clone(),
+            ));
+        }
+        if let Some(mode) = self.ssml_mode {
+            requests.push(Request::SetSsmlMode(mode));
+        }
+        match self.all_notifications() {
+            Some(value) => requests.push(Request::SetNotification(NotificationType::All, value)),
+            None => {
+                for (ntype, value) in self.notifications.iter() {
+                    requests.push(Request::SetNotification(ntype.clone(), *value));
+                }
+            }
+        }
+        requests
+    }
+
+    /// Value of all the notifications if they end up all enabled or all disabled.
+    ///
+    /// A full subscription is then replayed with a single request.
+    fn all_notifications(&self) -> Option<bool> {
+        const TYPES: [NotificationType; 6] = [
+            NotificationType::Begin,
+            NotificationType::End,
+            NotificationType::Cancel,
+            NotificationType::Pause,
+            NotificationType::Resume,
+            NotificationType::IndexMark,
+        ];
+        let mut values = [None; TYPES.len()];
+        for (ntype, value) in self.notifications.iter() {
+            for (index, known) in TYPES.iter().enumerate() {
+                if *ntype == NotificationType::All || ntype == known {
+                    values[index] = Some(*value);
+                }
+            }
+        }
+        match values[0] {
+            Some(value) if values.iter().all(|other| *other == Some(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for common kinds of clients.
+///
+/// The client name is derived from the process environment, with a component naming the
+/// kind of client.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, session::Preset};
+/// let mut client = fifo::sync::Builder::new().preset(Preset::Notifications).build()?;
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// Speak what the user does. All notifications are enabled to follow the speech.
+    ScreenReader,
+    /// Speak desktop notifications with the `notification` priority. They are discarded
+    /// when other messages are spoken.
+    Notifications,
+    /// Speak announcements with the `message` priority. The end and the cancellation of
+    /// messages are notified to know when an announcement is over.
+    KioskTts,
+}
+
+impl Preset {
+    fn component(&self) -> &'static str {
+        match self {
+            Preset::ScreenReader => "screen_reader",
+            Preset::Notifications => "notifications",
+            Preset::KioskTts => "kiosk",
+        }
+    }
+
+    /// Session with the settings of the preset.
+    pub fn session(&self) -> SessionTemplate {
+        let mut session = SessionTemplate::new(ClientName {
+            component: self.component().to_string(),
+            ..ClientName::from_env()
+        });
+        match self {
+            Preset::ScreenReader => session
+                .priority(Priority::Text)
+                .notification(NotificationType::All, true),
+            Preset::Notifications => session.priority(Priority::Notification),
+            Preset::KioskTts => session
+                .priority(Priority::Message)
+                .notification(NotificationType::End, true)
+                .notification(NotificationType::Cancel, true),
+        };
+        session
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Preset, SessionTemplate};
+    use crate::types::*;
+
+    #[test]
+    fn session_requests() {
+        let name = ClientName::new("joe", "hello").unwrap();
+        assert_eq!(
+            vec![Request::SetName(name.clone())],
+            SessionTemplate::new(name.clone()).requests()
+        );
+        let mut session = SessionTemplate::new(name.clone());
+        session
+            .notification(NotificationType::All, true)
+            .ssml_mode(true)
+            .rate(20)
+            .pause_context(2)
+            .language("fr".parse().unwrap());
+        assert_eq!(
+            vec![
+                Request::SetName(name),
+                Request::SetLanguage(ClientScope::Current, "fr".parse().unwrap()),
+                Request::SetRate(ClientScope::Current, 20),
+                Request::SetPauseContext(ClientScope::Current, 2),
+                Request::SetSsmlMode(true),
+                Request::SetNotification(NotificationType::All, true),
+            ],
+            session.requests()
+        );
+    }
+
+    #[test]
+    fn full_subscription() {
+        let name = ClientName::new("joe", "hello").unwrap();
+        let mut session = SessionTemplate::new(name.clone());
+        session
+            .notification(NotificationType::All, false)
+            .notification(NotificationType::End, true);
+        assert_eq!(
+            vec![
+                Request::SetName(name.clone()),
+                Request::SetNotification(NotificationType::All, false),
+                Request::SetNotification(NotificationType::End, true),
+            ],
+            session.requests()
+        );
+        for ntype in [
+            NotificationType::Begin,
+            NotificationType::Cancel,
+            NotificationType::Pause,
+            NotificationType::Resume,
+            NotificationType::IndexMark,
+        ] {
+            session.notification(ntype, true);
+        }
+        assert_eq!(
+            vec![
+                Request::SetName(name),
+                Request::SetNotification(NotificationType::All, true),
+            ],
+            session.requests()
+        );
+    }
+
+    #[test]
+    fn preset_requests() {
+        let requests = Preset::KioskTts.session().requests();
+        match &requests[0] {
+            Request::SetName(name) => assert_eq!("kiosk", name.component),
+            request => panic!("unexpected request: {:?}", request),
+        }
+        assert_eq!(
+            vec![
+                Request::SetPriority(Priority::Message),
+                Request::SetNotification(NotificationType::End, true),
+                Request::SetNotification(NotificationType::Cancel, true),
+            ],
+            requests[1..]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Graceful shutdown of the tokio clients and of the tasks using them.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use tokio::io::{AsyncBufRead, AsyncWrite};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::constants::OK_BYE;
+use crate::tokio::AsyncClient;
+use crate::types::{ClientError, ClientResult};
+
+type QuitFuture = Pin<Box<dyn Future<Output = ClientResult<()>> + Send>>;
+
+/// Signal received by the tasks when the shutdown starts.
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Return true if the shutdown started.
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Wait until the shutdown starts.
+    pub async fn wait(&mut self) {
+        while !self.is_triggered() {
+            if self.0.changed().await.is_err() {
+                // The handle is dropped, there will be no shutdown.
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Handle stopping the connections and the tasks on exit.
+///
+/// Connections registered with [`Shutdown::add_client`] are closed with `QUIT`. Tasks
+/// started with [`Shutdown::spawn`] receive a [`ShutdownSignal`] and are expected to return
+/// when it is triggered. Tasks registered with [`Shutdown::add_task`] can't be told to stop
+/// and are aborted.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, shutdown::Shutdown, ClientResult};
+/// # async fn run() -> ClientResult<()> {
+/// let mut shutdown = Shutdown::new();
+/// let mut client = fifo::asynchronous_tokio::Builder::new().build().await?;
+/// shutdown.spawn(|mut signal| async move {
+///     tokio::select! {
+///         _ = signal.wait() => (),
+///         event = client.receive_event() => println!("{:?}", event?),
+///     }
+///     client.quit().await?;
+///     Ok(())
+/// });
+/// for err in shutdown.shutdown().await {
+///     eprintln!("shutdown: {}", err);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Shutdown {
+    sender: watch::Sender<bool>,
+    clients: Vec<QuitFuture>,
+    tasks: Vec<JoinHandle<ClientResult<()>>>,
+    foreign_tasks: Vec<JoinHandle<ClientResult<()>>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: watch::channel(false).0,
+            clients: Vec::new(),
+            tasks: Vec::new(),
+            foreign_tasks: Vec::new(),
+        }
+    }
+
+    /// Signal to give to tasks started by other means.
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal(self.sender.subscribe())
+    }
+
+    /// Close the connection on shutdown.
+    pub fn add_client<R, W>(&mut self, mut client: AsyncClient<R, W>) -> &mut Self
+    where
+        R: AsyncBufRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        self.clients.push(Box::pin(async move {
+            client.quit().await?.check_status(OK_BYE).await?;
+            Ok(())
+        }));
+        self
+    }
+
+    /// Start a task that returns when the signal is triggered.
+    pub fn spawn<F, T>(&mut self, task: F) -> &mut Self
+    where
+        F: FnOnce(ShutdownSignal) -> T,
+        T: Future<Output = ClientResult<()>> + Send + 'static,
+    {
+        self.tasks.push(tokio::spawn(task(self.signal())));
+        self
+    }
+
+    /// Abort the task on shutdown.
+    pub fn add_task(&mut self, task: JoinHandle<ClientResult<()>>) -> &mut Self {
+        self.foreign_tasks.push(task);
+        self
+    }
+
+    /// Trigger the signal, close the connections and wait for the tasks.
+    ///
+    /// Return the errors of the connections and of the tasks, empty if everything stopped
+    /// cleanly. An aborted task is not an error.
+    pub async fn shutdown(self) -> Vec<ClientError> {
+        let mut errors = Vec::new();
+        self.sender.send_replace(true);
+        for client in self.clients {
+            if let Err(err) = client.await {
+                errors.push(err);
+            }
+        }
+        for task in self.foreign_tasks.iter() {
+            task.abort();
+        }
+        for task in self.tasks.into_iter().chain(self.foreign_tasks) {
+            match task.await {
+                Ok(Ok(())) => (),
+                Ok(Err(err)) => errors.push(err),
+                Err(err) if err.is_cancelled() => (),
+                Err(err) => errors.push(ClientError::Io(io::Error::other(err))),
+            }
+        }
+        errors
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use tokio::io::AsyncWrite;
+
+    use super::Shutdown;
+    use crate::tokio::AsyncClient;
+    use crate::types::ClientError;
+
+    /// Writer storing the data in a shared buffer.
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl AsyncWrite for SharedWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn stop_everything() {
+        let mut shutdown = Shutdown::new();
+        let output = SharedWriter::default();
+        shutdown.add_client(AsyncClient::new(
+            &b"231 HAPPY HACKING\r\n"[..],
+            output.clone(),
+        ));
+        shutdown.spawn(|mut signal| async move {
+            signal.wait().await;
+            Ok(())
+        });
+        shutdown.spawn(|mut signal| async move {
+            signal.wait().await;
+            Err(ClientError::TooFewLines)
+        });
+        shutdown.add_task(tokio::spawn(std::future::pending()));
+        let signal = shutdown.signal();
+        assert!(!signal.is_triggered());
+        let errors = shutdown.shutdown().await;
+        assert!(signal.is_triggered());
+        assert_eq!(b"QUIT\r\n", output.0.lock().unwrap().as_slice());
+        assert_eq!(1, errors.len());
+        assert!(matches!(errors[0], ClientError::TooFewLines));
+    }
+}
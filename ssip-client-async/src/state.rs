@@ -0,0 +1,358 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use log::warn;
+use std::collections::VecDeque;
+
+use crate::constants::*;
+use crate::types::*;
+
+/// Lifecycle of a connection, updated with the requests sent and the answers received.
+///
+/// Requests are identified by the name of the variant of [`Request`].
+#[derive(Debug)]
+pub(crate) struct Lifecycle {
+    state: ConnectionState,
+    named: bool,
+    /// True if `SPEAK` was sent and its answer not received yet.
+    speak_pending: bool,
+    /// Requests waiting for an answer, in the order they were sent.
+    pending: VecDeque<&'static str>,
+    /// Request answered by the last status received.
+    answered: Option<&'static str>,
+    /// Report requests sent before the answer to the previous one is read.
+    strict_replies: bool,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Connected,
+            named: false,
+            speak_pending: false,
+            pending: VecDeque::new(),
+            answered: None,
+            strict_replies: false,
+        }
+    }
+}
+
+fn is_data(request: &str) -> bool {
+    matches!(request, "SendLine" | "SendLines")
+}
+
+impl Lifecycle {
+    pub(crate) fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Same state, without pending request.
+    pub(crate) fn same_state(&self) -> Self {
+        Self {
+            state: self.state,
+            named: self.named,
+            speak_pending: self.speak_pending,
+            pending: VecDeque::new(),
+            answered: None,
+            strict_replies: self.strict_replies,
+        }
+    }
+
+    /// Report the requests sent while the answer to a previous request is unread.
+    ///
+    /// It panics in debug builds and logs a warning in release builds.
+    pub(crate) fn set_strict_replies(&mut self, enabled: bool) {
+        self.strict_replies = enabled;
+    }
+
+    /// True if `SPEAK` was sent and its answer not received yet.
+    pub(crate) fn speak_pending(&self) -> bool {
+        self.speak_pending
+    }
+
+    /// Request answered by the last status received, if known.
+    pub(crate) fn answered(&self) -> Option<&'static str> {
+        self.answered
+    }
+
+    fn idle_state(&self) -> ConnectionState {
+        if self.named {
+            ConnectionState::Ready
+        } else {
+            ConnectionState::Connected
+        }
+    }
+
+    /// Check that the request can be sent.
+    ///
+    /// Lines of a message are only accepted after `SPEAK` and other requests are rejected
+    /// while the server waits for them.
+    pub(crate) fn check(&self, request: &str) -> ClientResult<()> {
+        match self.state {
+            ConnectionState::Closed => return Err(ClientError::Closed),
+            ConnectionState::Speaking if is_data(request) => (),
+            ConnectionState::Speaking => return Err(ClientError::InvalidState(self.state)),
+            _ if is_data(request) && !self.speak_pending => {
+                return Err(ClientError::InvalidState(self.state))
+            }
+            _ => (),
+        }
+        if self.strict_replies {
+            if let Some(unread) = self.pending.front() {
+                let message = format!("{} sent before the answer to {} is read", request, unread);
+                if cfg!(debug_assertions) {
+                    panic!("{}", message);
+                }
+                warn!("{}", message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a request sent.
+    pub(crate) fn sent(&mut self, request: &'static str) {
+        self.pending.push_back(request);
+        match request {
+            "Speak" => self.speak_pending = true,
+            "Quit" => self.state = ConnectionState::Closed,
+            _ if is_data(request) => {
+                self.speak_pending = false;
+                self.state = self.idle_state();
+            }
+            _ => (),
+        }
+    }
+
+    /// Update the state with an answer.
+    pub(crate) fn received(&mut self, status: &ClientStatus) {
+        self.answered = match status {
+            Ok(status) | Err(ClientError::Ssip(status)) if (700..800).contains(&status.code) => {
+                // Notifications don't answer requests.
+                None
+            }
+            Ok(_) | Err(ClientError::Ssip(_)) => self.pending.pop_front(),
+            Err(_) => {
+                // Answers can't be matched with requests anymore.
+                self.pending.clear();
+                None
+            }
+        };
+        if self.state == ConnectionState::Closed {
+            return;
+        }
+        match status {
+            Ok(status) => match status.code {
+                OK_CLIENT_NAME_SET => {
+                    self.named = true;
+                    if self.state == ConnectionState::Connected {
+                        self.state = ConnectionState::Ready;
+                    }
+                }
+                OK_RECEIVING_DATA if self.speak_pending => {
+                    self.speak_pending = false;
+                    self.state = ConnectionState::Speaking;
+                }
+                _ => (),
+            },
+            // SPEAK may have been refused, not a request sent before it.
+            Err(ClientError::Ssip(_)) if matches!(self.answered, Some("Speak") | None) => {
+                self.speak_pending = false
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+/// Value of a setting, updated once the server accepts the request changing it.
+#[derive(Debug, Clone, Default)]
+struct Setting<T> {
+    value: T,
+    /// Values of the requests waiting for an answer, `None` if the request doesn't change it.
+    requested: VecDeque<Option<T>>,
+}
+
+impl<T: Clone> Setting<T> {
+    fn same_value(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            requested: VecDeque::new(),
+        }
+    }
+
+    /// Apply the oldest value requested if the server accepted it.
+    fn answered(&mut self, accepted: bool) {
+        if let Some(Some(value)) = self.requested.pop_front() {
+            if accepted {
+                self.value = value;
+            }
+        }
+    }
+}
+
+/// Settings of the connection that the server can't return, kept by the client.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Settings {
+    ssml_mode: Setting<bool>,
+    synthesis_voice: Setting<Option<String>>,
+}
+
+impl Settings {
+    pub(crate) fn ssml_mode(&self) -> bool {
+        self.ssml_mode.value
+    }
+
+    pub(crate) fn synthesis_voice(&self) -> Option<&str> {
+        self.synthesis_voice.value.as_deref()
+    }
+
+    /// Same values, without pending request.
+    pub(crate) fn same_values(&self) -> Self {
+        Self {
+            ssml_mode: self.ssml_mode.same_value(),
+            synthesis_voice: self.synthesis_voice.same_value(),
+        }
+    }
+
+    /// Record a request sent.
+    ///
+    /// A voice type replaces the synthesis voice of the client.
+    pub(crate) fn sent(&mut self, request: RequestRef<'_>) {
+        match request {
+            RequestRef::SetSsmlMode(mode) => self.ssml_mode.requested.push_back(Some(mode)),
+            RequestRef::SetSynthesisVoice(scope, voice) => self
+                .synthesis_voice
+                .requested
+                .push_back(matches!(scope, ClientScope::Current).then(|| Some(voice.to_string()))),
+            RequestRef::SetVoiceType(scope, _) => self
+                .synthesis_voice
+                .requested
+                .push_back(matches!(scope, ClientScope::Current).then_some(None)),
+            _ => (),
+        }
+    }
+
+    /// Update the values with the answer to a request, see [`Lifecycle::answered`].
+    pub(crate) fn received(&mut self, answered: Option<&str>, status: &ClientStatus) {
+        match (answered, status) {
+            (Some("SetSsmlMode"), _) => self.ssml_mode.answered(status.is_ok()),
+            (Some("SetSynthesisVoice" | "SetVoiceType"), _) => {
+                self.synthesis_voice.answered(status.is_ok())
+            }
+            (None, Err(err)) if !matches!(err, ClientError::Ssip(_)) => {
+                // Answers can't be matched with requests anymore.
+                self.ssml_mode.requested.clear();
+                self.synthesis_voice.requested.clear();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Lifecycle;
+    use crate::constants::*;
+    use crate::types::*;
+
+    #[test]
+    fn speak_transaction() {
+        let mut lifecycle = Lifecycle::default();
+        assert_eq!(ConnectionState::Connected, lifecycle.state());
+        assert!(matches!(
+            lifecycle.check("SendLine"),
+            Err(ClientError::InvalidState(ConnectionState::Connected))
+        ));
+        lifecycle.received(&Ok(StatusLine::new(OK_CLIENT_NAME_SET, "CLIENT NAME SET")));
+        assert_eq!(ConnectionState::Ready, lifecycle.state());
+        lifecycle.sent("Speak");
+        assert!(lifecycle.check("SendLine").is_ok());
+        lifecycle.received(&Ok(StatusLine::new(OK_RECEIVING_DATA, "RECEIVING DATA")));
+        assert_eq!(ConnectionState::Speaking, lifecycle.state());
+        assert!(matches!(
+            lifecycle.check("SetRate"),
+            Err(ClientError::InvalidState(ConnectionState::Speaking))
+        ));
+        lifecycle.sent("SendLines");
+        assert_eq!(ConnectionState::Ready, lifecycle.state());
+        lifecycle.received(&Ok(StatusLine::new(OK_MESSAGE_QUEUED, "MESSAGE QUEUED")));
+        assert_eq!(Some("SendLines"), lifecycle.answered());
+        lifecycle.sent("Quit");
+        assert!(matches!(lifecycle.check("Speak"), Err(ClientError::Closed)));
+    }
+
+    #[test]
+    fn speak_refused() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.sent("Speak");
+        lifecycle.received(&Err(ClientError::Ssip(StatusLine::new(
+            401,
+            "ERR NOT IMPLEMENTED",
+        ))));
+        assert!(lifecycle.check("SendLine").is_err());
+        assert_eq!(ConnectionState::Connected, lifecycle.state());
+    }
+
+    #[test]
+    fn pipelined_request_refused() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.sent("Cancel");
+        lifecycle.sent("Speak");
+        lifecycle.received(&Err(ClientError::Ssip(StatusLine::new(
+            401,
+            "ERR CANCEL FAILED",
+        ))));
+        assert!(lifecycle.check("SendLine").is_ok());
+        lifecycle.received(&Ok(StatusLine::new(OK_RECEIVING_DATA, "RECEIVING DATA")));
+        assert_eq!(ConnectionState::Speaking, lifecycle.state());
+    }
+
+    #[test]
+    fn match_answers() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.sent("GetRate");
+        lifecycle.sent("GetVolume");
+        lifecycle.received(&Ok(StatusLine::new(701, "BEGIN")));
+        assert_eq!(None, lifecycle.answered());
+        lifecycle.received(&Ok(StatusLine::new(OK_GET, "GET")));
+        assert_eq!(Some("GetRate"), lifecycle.answered());
+        lifecycle.received(&Err(ClientError::Ssip(StatusLine::new(
+            410,
+            "INVALID PARAMETER",
+        ))));
+        assert_eq!(Some("GetVolume"), lifecycle.answered());
+        lifecycle.received(&Ok(StatusLine::new(OK_GET, "GET")));
+        assert_eq!(None, lifecycle.answered());
+    }
+
+    #[test]
+    fn strict_replies() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.set_strict_replies(true);
+        lifecycle.sent("Speak");
+        lifecycle.received(&Ok(StatusLine::new(OK_RECEIVING_DATA, "RECEIVING DATA")));
+        assert!(lifecycle.check("SendLines").is_ok());
+        lifecycle.sent("SendLines");
+        lifecycle.received(&Ok(StatusLine::new(OK_MESSAGE_QUEUED, "MESSAGE QUEUED")));
+        lifecycle.sent("SetRate");
+        lifecycle.received(&Ok(StatusLine::new(701, "BEGIN")));
+        lifecycle.received(&Ok(StatusLine::new(OK_RATE_SET, "RATE SET")));
+        assert!(lifecycle.check("SetVolume").is_ok());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "SetVolume sent before the answer to SetRate is read")]
+    fn unread_reply() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.set_strict_replies(true);
+        lifecycle.sent("SetRate");
+        let _ = lifecycle.check("SetVolume");
+    }
+}
@@ -7,8 +7,50 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-#[cfg(not(feature = "async-mio"))]
-mod synchronous {
+#[cfg(not(target_os = "wasi"))]
+use std::io;
+#[cfg(not(target_os = "wasi"))]
+use std::time::Duration;
+
+#[cfg(not(target_os = "wasi"))]
+use socket2::{SockRef, TcpKeepalive};
+
+/// Options of the TCP socket.
+///
+/// They are set with [socket2](https://crates.io/crates/socket2), which doesn't support WASI.
+#[cfg(not(target_os = "wasi"))]
+#[derive(Debug, Clone, Copy)]
+struct SocketOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+}
+
+#[cfg(not(target_os = "wasi"))]
+impl Default for SocketOptions {
+    fn default() -> Self {
+        // Requests are small lines that must not wait for more data.
+        Self {
+            nodelay: true,
+            keepalive: None,
+        }
+    }
+}
+
+#[cfg(not(target_os = "wasi"))]
+impl SocketOptions {
+    fn apply(&self, socket: SockRef) -> io::Result<()> {
+        socket.set_tcp_nodelay(self.nodelay)?;
+        match self.keepalive {
+            Some(time) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time)),
+            None => socket.set_keepalive(false),
+        }
+    }
+}
+
+/// Blocking client on a TCP socket.
+pub mod sync {
+    #[cfg(not(target_os = "wasi"))]
+    use socket2::SockRef;
     use std::io::{self, BufReader, BufWriter};
     pub use std::net::TcpStream;
     use std::net::{SocketAddr, ToSocketAddrs};
@@ -16,7 +58,14 @@ mod synchronous {
     use std::vec;
 
     use crate::client::Client;
-    use crate::net::StreamMode;
+    use crate::config::ServerConfig;
+    use crate::demux::OverflowPolicy;
+    use crate::net::{Connector, StreamMode};
+    use crate::protocol::Utf8Mode;
+    use crate::session::{Preset, SessionTemplate};
+
+    #[cfg(not(target_os = "wasi"))]
+    use super::SocketOptions;
 
     struct Addresses(Vec<SocketAddr>);
 
@@ -29,17 +78,54 @@ mod synchronous {
 
     pub struct Builder {
         addrs: Addresses,
+        connector: Option<Connector<TcpStream>>,
+        #[cfg(not(target_os = "wasi"))]
+        options: SocketOptions,
         mode: StreamMode,
+        session: Option<SessionTemplate>,
+        utf8_mode: Utf8Mode,
+        label: Option<String>,
+        strict_replies: bool,
+        event_buffer: Option<(usize, OverflowPolicy)>,
     }
 
     impl Builder {
         pub fn new<A: ToSocketAddrs>(addrs: A) -> io::Result<Self> {
             Ok(Self {
                 addrs: Addresses(addrs.to_socket_addrs()?.collect::<Vec<SocketAddr>>()),
+                connector: None,
+                #[cfg(not(target_os = "wasi"))]
+                options: SocketOptions::default(),
                 mode: StreamMode::Blocking,
+                session: None,
+                utf8_mode: Utf8Mode::Strict,
+                label: None,
+                strict_replies: false,
+                event_buffer: None,
             })
         }
 
+        /// Connect to the port set in the configuration of the server on the local host.
+        pub fn from_config(config: &ServerConfig) -> io::Result<Self> {
+            Self::new(("localhost", config.port()))
+        }
+
+        /// Disable Nagle's algorithm so that requests are sent at once. Default is true.
+        #[cfg(not(target_os = "wasi"))]
+        pub fn nodelay(&mut self, enabled: bool) -> &mut Self {
+            self.options.nodelay = enabled;
+            self
+        }
+
+        /// Send keepalive probes after the connection is idle for the duration.
+        ///
+        /// Default is `None`: no probes are sent.
+        #[cfg(not(target_os = "wasi"))]
+        pub fn keepalive(&mut self, idle: Option<Duration>) -> &mut Self {
+            self.options.keepalive = idle;
+            self
+        }
+
         pub fn timeout(&mut self, read_timeout: Duration) -> &mut Self {
             self.mode = StreamMode::TimeOut(read_timeout);
             self
@@ -50,42 +136,163 @@ mod synchronous {
             self
         }
 
+        /// Session replayed on the connection before returning the client.
+        pub fn session(&mut self, template: &SessionTemplate) -> &mut Self {
+            self.session = Some(template.clone());
+            self
+        }
+
+        /// Session with the settings of a preset. It replaces any previous session.
+        pub fn preset(&mut self, preset: Preset) -> &mut Self {
+            self.session = Some(preset.session());
+            self
+        }
+
+        /// Replace invalid UTF-8 in received lines and call the handler with the bytes of the line.
+        pub fn lossy_utf8<F>(&mut self, handler: F) -> &mut Self
+        where
+            F: Fn(&[u8]) + Send + Sync + 'static,
+        {
+            self.utf8_mode = Utf8Mode::lossy(handler);
+            self
+        }
+
+        /// Name of the connection in the logs.
+        pub fn label(&mut self, label: &str) -> &mut Self {
+            self.label = Some(label.to_string());
+            self
+        }
+
+        /// Check that the answer to each request is read before the next request is sent.
+        pub fn strict_replies(&mut self, enabled: bool) -> &mut Self {
+            self.strict_replies = enabled;
+            self
+        }
+
+        /// Number of events kept while waiting for an answer and what to do when it's full.
+        pub fn event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+            self.event_buffer = Some((capacity, policy));
+            self
+        }
+
+        /// Create the stream with a function instead of connecting to the address.
+        ///
+        /// The function can return a socket obtained by socket activation, passed by a
+        /// privileged helper or connected in another namespace. The builder still configures
+        /// the stream and the client as if it had connected it.
+        pub fn with_stream<F>(&mut self, connect: F) -> &mut Self
+        where
+            F: Fn() -> io::Result<TcpStream> + Send + Sync + 'static,
+        {
+            self.connector = Some(Box::new(connect));
+            self
+        }
+
         pub fn build(&self) -> io::Result<Client<TcpStream>> {
-            let input = TcpStream::connect(&self.addrs)?;
+            let input = match &self.connector {
+                Some(connect) => connect()?,
+                None => TcpStream::connect(&self.addrs)?,
+            };
+            #[cfg(not(target_os = "wasi"))]
+            self.options.apply(SockRef::from(&input))?;
             match self.mode {
-                StreamMode::Blocking => input.set_nonblocking(false)?,
-                StreamMode::NonBlocking => input.set_nonblocking(true)?,
+                StreamMode::Blocking | StreamMode::NonBlocking => input.set_nonblocking(false)?,
                 StreamMode::TimeOut(timeout) => input.set_read_timeout(Some(timeout))?,
             }
             let output = input.try_clone()?;
-            Ok(Client::new(BufReader::new(input), BufWriter::new(output)))
+            let control = input.try_clone()?;
+            let mut client = Client::new(BufReader::new(input), BufWriter::new(output));
+            client.set_utf8_mode(self.utf8_mode.clone());
+            if let Some(label) = &self.label {
+                client.set_label(label);
+            }
+            client.set_strict_replies(self.strict_replies);
+            if let Some((capacity, policy)) = self.event_buffer {
+                client.set_event_buffer(capacity, policy);
+            }
+            if let Some(session) = &self.session {
+                client.apply_session(session)?;
+            }
+            // The session is replayed with a blocking socket.
+            if let StreamMode::NonBlocking = self.mode {
+                control.set_nonblocking(true)?;
+            }
+            Ok(client)
         }
     }
 }
 
-#[cfg(not(feature = "async-mio"))]
-pub use synchronous::{Builder, TcpStream};
-
+/// Nonblocking client on a TCP socket for [mio](https://crates.io/crates/mio).
 #[cfg(feature = "async-mio")]
-mod asynchronous {
+pub mod mio {
     pub use mio::net::TcpStream;
+    #[cfg(not(target_os = "wasi"))]
+    use socket2::SockRef;
     use std::io::{self, BufReader, BufWriter};
     use std::net::SocketAddr;
     use std::net::TcpStream as StdTcpStream;
+    #[cfg(not(target_os = "wasi"))]
+    use std::time::Duration;
 
     use crate::client::Client;
+    use crate::net::Connector;
+
+    #[cfg(not(target_os = "wasi"))]
+    use super::SocketOptions;
 
     pub struct Builder {
         addr: SocketAddr,
+        connector: Option<Connector<StdTcpStream>>,
+        #[cfg(not(target_os = "wasi"))]
+        options: SocketOptions,
     }
 
     impl Builder {
         pub fn new(addr: SocketAddr) -> Self {
-            Self { addr }
+            Self {
+                addr,
+                connector: None,
+                #[cfg(not(target_os = "wasi"))]
+                options: SocketOptions::default(),
+            }
+        }
+
+        /// Disable Nagle's algorithm so that requests are sent at once. Default is true.
+        #[cfg(not(target_os = "wasi"))]
+        pub fn nodelay(&mut self, enabled: bool) -> &mut Self {
+            self.options.nodelay = enabled;
+            self
+        }
+
+        /// Send keepalive probes after the connection is idle for the duration.
+        ///
+        /// Default is `None`: no probes are sent.
+        #[cfg(not(target_os = "wasi"))]
+        pub fn keepalive(&mut self, idle: Option<Duration>) -> &mut Self {
+            self.options.keepalive = idle;
+            self
+        }
+
+        /// Create the stream with a function instead of connecting to the address.
+        ///
+        /// The function can return a socket obtained by socket activation, passed by a
+        /// privileged helper or connected in another namespace. The builder still configures
+        /// the stream and the client as if it had connected it.
+        pub fn with_stream<F>(&mut self, connect: F) -> &mut Self
+        where
+            F: Fn() -> io::Result<StdTcpStream> + Send + Sync + 'static,
+        {
+            self.connector = Some(Box::new(connect));
+            self
         }
 
         pub fn build(&self) -> io::Result<Client<TcpStream>> {
-            let stream = StdTcpStream::connect(self.addr)?;
+            let stream = match &self.connector {
+                Some(connect) => connect()?,
+                None => StdTcpStream::connect(self.addr)?,
+            };
+            #[cfg(not(target_os = "wasi"))]
+            self.options.apply(SockRef::from(&stream))?;
             Ok(Client::new(
                 BufReader::new(TcpStream::from_std(stream.try_clone()?)),
                 BufWriter::new(TcpStream::from_std(stream)),
@@ -94,8 +301,207 @@ mod asynchronous {
     }
 }
 
+#[cfg(not(feature = "async-mio"))]
+pub use self::sync::{Builder, TcpStream};
+
+/// Builder of the client for mio.
+///
+/// The path depends on the features. It is deprecated in favor of [`mio::Builder`] and will
+/// always be the synchronous builder in a next release.
 #[cfg(feature = "async-mio")]
-pub use asynchronous::{Builder, TcpStream};
+#[deprecated(
+    since = "0.15.0",
+    note = "use tcp::mio::Builder, tcp::Builder will be tcp::sync::Builder"
+)]
+pub type Builder = self::mio::Builder;
 
-#[cfg(test)]
-mod tests {}
+/// Stream of the client for mio.
+#[cfg(feature = "async-mio")]
+#[deprecated(
+    since = "0.15.0",
+    note = "use tcp::mio::TcpStream, tcp::TcpStream will be tcp::sync::TcpStream"
+)]
+pub type TcpStream = self::mio::TcpStream;
+
+#[cfg(all(feature = "tokio", unix))]
+pub mod asynchronous_tokio {
+    use socket2::SockRef;
+    use std::net::{SocketAddr, ToSocketAddrs};
+    use std::time::Duration;
+    use tokio::io::{self, BufReader as AsyncBufReader, BufWriter as AsyncBufWriter};
+    pub use tokio::net::{tcp::OwnedReadHalf, tcp::OwnedWriteHalf, TcpStream};
+
+    use crate::config::ServerConfig;
+    use crate::demux::OverflowPolicy;
+    use crate::net::HandshakeRetry;
+    use crate::protocol::Utf8Mode;
+    use crate::session::{Preset, SessionTemplate};
+    use crate::tokio::AsyncClient;
+
+    use super::SocketOptions;
+
+    pub struct Builder {
+        addrs: Vec<SocketAddr>,
+        options: SocketOptions,
+        session: Option<SessionTemplate>,
+        utf8_mode: Utf8Mode,
+        label: Option<String>,
+        strict_replies: bool,
+        event_buffer: Option<(usize, OverflowPolicy)>,
+        retry: HandshakeRetry,
+    }
+
+    impl Builder {
+        pub fn new<A: ToSocketAddrs>(addrs: A) -> io::Result<Self> {
+            Ok(Self {
+                addrs: addrs.to_socket_addrs()?.collect(),
+                options: SocketOptions::default(),
+                session: None,
+                utf8_mode: Utf8Mode::Strict,
+                label: None,
+                strict_replies: false,
+                event_buffer: None,
+                retry: HandshakeRetry::default(),
+            })
+        }
+
+        /// Connect to the port set in the configuration of the server on the local host.
+        pub fn from_config(config: &ServerConfig) -> io::Result<Self> {
+            Self::new(("localhost", config.port()))
+        }
+
+        /// Disable Nagle's algorithm so that requests are sent at once. Default is true.
+        pub fn nodelay(&mut self, enabled: bool) -> &mut Self {
+            self.options.nodelay = enabled;
+            self
+        }
+
+        /// Send keepalive probes after the connection is idle for the duration.
+        ///
+        /// Default is `None`: no probes are sent.
+        pub fn keepalive(&mut self, idle: Option<Duration>) -> &mut Self {
+            self.options.keepalive = idle;
+            self
+        }
+
+        /// Session replayed on the connection before returning the client.
+        pub fn session(&mut self, template: &SessionTemplate) -> &mut Self {
+            self.session = Some(template.clone());
+            self
+        }
+
+        /// Session with the settings of a preset. It replaces any previous session.
+        pub fn preset(&mut self, preset: Preset) -> &mut Self {
+            self.session = Some(preset.session());
+            self
+        }
+
+        /// Replace invalid UTF-8 in received lines and call the handler with the bytes of the line.
+        pub fn lossy_utf8<F>(&mut self, handler: F) -> &mut Self
+        where
+            F: Fn(&[u8]) + Send + Sync + 'static,
+        {
+            self.utf8_mode = Utf8Mode::lossy(handler);
+            self
+        }
+
+        /// Name of the connection in the logs.
+        pub fn label(&mut self, label: &str) -> &mut Self {
+            self.label = Some(label.to_string());
+            self
+        }
+
+        /// Check that the answer to each request is read before the next request is sent.
+        pub fn strict_replies(&mut self, enabled: bool) -> &mut Self {
+            self.strict_replies = enabled;
+            self
+        }
+
+        /// Number of events kept while waiting for an answer and what to do when it's full.
+        pub fn event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+            self.event_buffer = Some((capacity, policy));
+            self
+        }
+
+        /// Try again to connect and apply the session while the server is starting.
+        ///
+        /// At most `attempts` attempts are made, about `delay` apart, as long as the error
+        /// is accepted by [`HandshakeRetry::is_retryable`]. Default is a single attempt.
+        pub fn handshake_retry(&mut self, attempts: u32, delay: Duration) -> &mut Self {
+            self.retry = HandshakeRetry::new(attempts, delay);
+            self
+        }
+
+        pub async fn build(
+            &self,
+        ) -> io::Result<AsyncClient<AsyncBufReader<OwnedReadHalf>, AsyncBufWriter<OwnedWriteHalf>>>
+        {
+            let mut attempt = 1;
+            loop {
+                match self.handshake().await {
+                    Err(err) => match self.retry.wait_after(attempt, &err) {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => return Err(err),
+                    },
+                    client => return client,
+                }
+                attempt += 1;
+            }
+        }
+
+        /// Connect and apply the session.
+        async fn handshake(
+            &self,
+        ) -> io::Result<AsyncClient<AsyncBufReader<OwnedReadHalf>, AsyncBufWriter<OwnedWriteHalf>>>
+        {
+            let stream = TcpStream::connect(self.addrs.as_slice()).await?;
+            self.options.apply(SockRef::from(&stream))?;
+            let (read_stream, write_stream) = stream.into_split();
+            let mut client = AsyncClient::new(
+                AsyncBufReader::new(read_stream),
+                AsyncBufWriter::new(write_stream),
+            );
+            client.set_utf8_mode(self.utf8_mode.clone());
+            if let Some(label) = &self.label {
+                client.set_label(label);
+            }
+            client.set_strict_replies(self.strict_replies);
+            if let Some((capacity, policy)) = self.event_buffer {
+                client.set_event_buffer(capacity, policy);
+            }
+            if let Some(session) = &self.session {
+                client.apply_session(session).await?;
+            }
+            Ok(client)
+        }
+    }
+}
+
+#[cfg(all(test, not(target_os = "wasi")))]
+mod tests {
+
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use socket2::SockRef;
+
+    use super::SocketOptions;
+
+    #[test]
+    fn socket_options() -> std::io::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let stream = TcpStream::connect(listener.local_addr()?)?;
+        let socket = SockRef::from(&stream);
+        SocketOptions::default().apply(SockRef::from(&stream))?;
+        assert!(socket.tcp_nodelay()?);
+        assert!(!socket.keepalive()?);
+        let options = SocketOptions {
+            nodelay: false,
+            keepalive: Some(Duration::from_secs(30)),
+        };
+        options.apply(SockRef::from(&stream))?;
+        assert!(!socket.tcp_nodelay()?);
+        assert!(socket.keepalive()?);
+        Ok(())
+    }
+}
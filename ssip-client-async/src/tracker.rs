@@ -0,0 +1,500 @@
+// ssip-client -- Speech Dispatcher client in Rust
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! State of the messages of a client, driven by the events of the server.
+
+use std::io::{Read, Write};
+
+use crate::{
+    client::{Client, Source},
+    constants::OK_VOICE_SET,
+    protocol::text_lines,
+    types::*,
+};
+
+/// State of a message sent to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageState {
+    /// Waiting for its turn.
+    Queued,
+    /// Being spoken.
+    Speaking,
+    /// Stopped by a pause, until it is resumed.
+    Paused,
+}
+
+/// Messages spoken again when the connection is restored.
+///
+/// The server forgets the messages of a client when the connection is lost. Only the
+/// messages registered with their text can be spoken again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResubmitPolicy {
+    /// Forget the messages in progress.
+    #[default]
+    DropInFlight,
+    /// Speak again all the messages in progress.
+    Resubmit,
+    /// Speak again the messages that were not started, i.e. without a begin event.
+    ResubmitIfNotStarted,
+}
+
+/// Snapshot of the messages in progress.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueueState {
+    /// The speech is paused.
+    pub paused: bool,
+    /// Message being spoken, if any.
+    pub speaking: Option<MessageId>,
+    /// Messages that are not finished, in the order they were sent.
+    pub messages: Vec<(MessageId, MessageState)>,
+}
+
+impl QueueState {
+    /// No message is waiting or being spoken.
+    pub fn is_idle(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Track the messages of a client with the notifications of the server.
+///
+/// Notifications of begin, end, cancel, pause and resume must be enabled. A pause stops the
+/// whole queue: when the server reports that a message is paused, all the messages in
+/// progress are marked as paused. When it is resumed, the message speaks again and the
+/// others wait for their turn.
+///
+/// Example
+/// ```no_run
+/// use ssip_client_async::{fifo, tracker::MessageTracker, ClientName, NotificationType};
+/// let mut client = fifo::Builder::new().build()?;
+/// client
+///     .set_client_name(ClientName::new("joe", "tracker")?)?
+///     .check_client_name_set()?
+///     .set_notification(NotificationType::All, true)?
+///     .check_status(ssip_client_async::OK_NOTIFICATION_SET)?;
+/// let mut tracker = MessageTracker::new();
+/// let id = client
+///     .speak()?
+///     .check_receiving_data()?
+///     .send_line("hello")?
+///     .receive_message_id()?;
+/// tracker.queued(id);
+/// while !tracker.queue_state().is_idle() {
+///     tracker.update(&client.receive_event()?);
+///     if tracker.queue_state().paused {
+///         println!("speech paused");
+///     }
+/// }
+/// # Ok::<(), ssip_client_async::ClientError>(())
+/// ```
+///
+/// When the connection is lost, the messages sent with [`MessageTracker::say`] can be spoken
+/// again on the new connection with [`MessageTracker::resubmit`], depending on the
+/// [`ResubmitPolicy`].
+///
+/// With a maximum depth, [`MessageTracker::try_enqueue`] refuses new messages while too many
+/// are in progress, so that a producer can slow down rather than queue speech that nobody
+/// will hear.
+#[derive(Debug, Clone, Default)]
+pub struct MessageTracker {
+    messages: Vec<Message>,
+    policy: ResubmitPolicy,
+    max_depth: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Message {
+    id: MessageId,
+    state: MessageState,
+    started: bool,
+    text: Option<String>,
+}
+
+impl MessageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the messages spoken again by [`MessageTracker::resubmit`].
+    pub fn set_resubmit_policy(&mut self, policy: ResubmitPolicy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Messages spoken again by [`MessageTracker::resubmit`].
+    pub fn resubmit_policy(&self) -> ResubmitPolicy {
+        self.policy
+    }
+
+    /// Set the maximum number of messages in progress. Default is no limit.
+    pub fn set_max_depth(&mut self, depth: Option<usize>) -> &mut Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Number of messages that can still be sent, `None` if there is no limit.
+    pub fn capacity(&self) -> Option<usize> {
+        self.max_depth
+            .map(|depth| depth.saturating_sub(self.messages.len()))
+    }
+
+    /// Return true if another message can be sent.
+    pub fn is_ready(&self) -> bool {
+        self.capacity() != Some(0)
+    }
+
+    /// Register a message accepted by the server.
+    pub fn queued(&mut self, id: MessageId) -> &mut Self {
+        self.push(id, None);
+        self
+    }
+
+    /// Register a message accepted by the server with its text, so that it can be resubmitted.
+    pub fn queued_text(&mut self, id: MessageId, text: &str) -> &mut Self {
+        match self.position(id) {
+            Some(index) => self.messages[index].text = Some(text.to_string()),
+            None => self.push(id, Some(text.to_string())),
+        }
+        self
+    }
+
+    /// Speak a text and register the message with its text.
+    pub fn say<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+        text: &str,
+    ) -> ClientResult<MessageId> {
+        let msg_id = client
+            .speak()?
+            .check_receiving_data()?
+            .send_lines(&text_lines(text))?
+            .receive_message_id()?;
+        self.queued_text(msg_id, text);
+        Ok(msg_id)
+    }
+
+    /// Speak a text as [`MessageTracker::say`] if the maximum depth is not reached.
+    ///
+    /// Fail with [`ClientError::NotReady`] without sending anything otherwise.
+    pub fn try_enqueue<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+        text: &str,
+    ) -> ClientResult<MessageId> {
+        if !self.is_ready() {
+            return Err(ClientError::NotReady);
+        }
+        self.say(client, text)
+    }
+
+    /// Receive the events until another message can be sent and return them.
+    pub fn wait_ready<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+    ) -> ClientResult<Vec<Event>> {
+        let mut events = Vec::new();
+        while !self.is_ready() {
+            let event = client.receive_event()?;
+            self.update(&event);
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Receive the events with a tokio client until another message can be sent and
+    /// return them.
+    #[cfg(feature = "tokio")]
+    pub async fn ready<R, W>(
+        &mut self,
+        client: &mut crate::tokio::AsyncClient<R, W>,
+    ) -> ClientResult<Vec<Event>>
+    where
+        R: ::tokio::io::AsyncBufRead + Unpin,
+        W: ::tokio::io::AsyncWrite + Unpin,
+    {
+        let mut events = Vec::new();
+        while !self.is_ready() {
+            let event = client.receive_event().await?;
+            self.update(&event);
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// State of a message, `None` if it is finished or unknown.
+    pub fn state(&self, id: MessageId) -> Option<MessageState> {
+        self.position(id).map(|index| self.messages[index].state)
+    }
+
+    /// Update the states with an event.
+    ///
+    /// Return the new state of the message of the event, `None` if it is finished. Events
+    /// with an invalid message id are ignored.
+    pub fn update(&mut self, event: &Event) -> Option<MessageState> {
+        let id = event.message_id()?;
+        match event.ntype {
+            EventType::Begin => self.set_speaking(id),
+            EventType::End | EventType::Cancel => {
+                if let Some(index) = self.position(id) {
+                    self.messages.remove(index);
+                }
+            }
+            EventType::Pause => {
+                self.queued(id);
+                for message in self.messages.iter_mut() {
+                    message.state = MessageState::Paused;
+                }
+            }
+            EventType::Resume => {
+                for message in self.messages.iter_mut() {
+                    message.state = MessageState::Queued;
+                }
+                self.set_speaking(id);
+            }
+            EventType::IndexMark(_) => (),
+        }
+        self.state(id)
+    }
+
+    /// Snapshot of the messages in progress.
+    pub fn queue_state(&self) -> QueueState {
+        QueueState {
+            paused: self.is_paused(),
+            speaking: self
+                .messages
+                .iter()
+                .find(|message| message.state == MessageState::Speaking)
+                .map(|message| message.id),
+            messages: self
+                .messages
+                .iter()
+                .map(|message| (message.id, message.state))
+                .collect(),
+        }
+    }
+
+    /// Forget the messages in progress after the connection is lost.
+    ///
+    /// Return the texts to speak again according to the policy, in the order they were
+    /// sent. A message is started once its begin event is received.
+    pub fn interrupted(&mut self) -> Vec<String> {
+        self.take_interrupted()
+            .into_iter()
+            .filter_map(|message| message.text)
+            .collect()
+    }
+
+    /// Speak again with a new connection the messages interrupted according to the policy.
+    ///
+    /// The messages are tracked with their new ids, which are returned. If a message can't
+    /// be sent, it and the following ones are tracked again with their old ids, so that the
+    /// next call speaks them.
+    pub fn resubmit<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+    ) -> ClientResult<Vec<MessageId>> {
+        let mut messages = self.take_interrupted().into_iter();
+        let mut ids = Vec::with_capacity(messages.len());
+        while let Some(message) = messages.next() {
+            let text = message.text.as_deref().unwrap_or_default();
+            match self.say(client, text) {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    self.messages.push(message);
+                    self.messages.extend(messages);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Change the synthesis voice once the messages in progress are finished.
+    ///
+    /// Some output modules drop or garble the messages queued when the voice changes. The
+    /// events are received until the last message tracked ends or is canceled, then the
+    /// voice is set. If no message is in progress, the voice is set at once. Return the
+    /// events received while waiting, the messages sent after the call are spoken with
+    /// the new voice.
+    pub fn switch_voice_when_idle<S: Read + Write + Source>(
+        &mut self,
+        client: &mut Client<S>,
+        voice: &str,
+    ) -> ClientResult<Vec<Event>> {
+        let mut events = Vec::new();
+        while !self.messages.is_empty() {
+            let event = client.receive_event()?;
+            self.update(&event);
+            events.push(event);
+        }
+        client
+            .set_synthesis_voice(ClientScope::Current, voice)?
+            .check_status(OK_VOICE_SET)?;
+        Ok(events)
+    }
+
+    /// Remove the messages in progress and return those to speak again with their text.
+    fn take_interrupted(&mut self) -> Vec<Message> {
+        let policy = self.policy;
+        self.messages
+            .drain(..)
+            .filter(|message| match policy {
+                ResubmitPolicy::DropInFlight => false,
+                ResubmitPolicy::Resubmit => true,
+                ResubmitPolicy::ResubmitIfNotStarted => !message.started,
+            })
+            .filter(|message| message.text.is_some())
+            .collect()
+    }
+
+    fn is_paused(&self) -> bool {
+        self.messages
+            .iter()
+            .any(|message| message.state == MessageState::Paused)
+    }
+
+    fn position(&self, id: MessageId) -> Option<usize> {
+        self.messages.iter().position(|message| message.id == id)
+    }
+
+    fn push(&mut self, id: MessageId, text: Option<String>) {
+        if self.position(id).is_none() {
+            let state = if self.is_paused() {
+                MessageState::Paused
+            } else {
+                MessageState::Queued
+            };
+            self.messages.push(Message {
+                id,
+                state,
+                started: false,
+                text,
+            });
+        }
+    }
+
+    fn set_speaking(&mut self, id: MessageId) {
+        match self.position(id) {
+            Some(index) => {
+                let message = &mut self.messages[index];
+                message.state = MessageState::Speaking;
+                message.started = true;
+            }
+            None => self.messages.push(Message {
+                id,
+                state: MessageState::Speaking,
+                started: true,
+                text: None,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{MessageState, MessageTracker, QueueState, ResubmitPolicy};
+    use crate::types::{Event, EventType};
+
+    #[test]
+    fn pause_and_resume() {
+        let mut tracker = MessageTracker::new();
+        tracker.queued(21).queued(22);
+        assert_eq!(
+            Some(MessageState::Speaking),
+            tracker.update(&Event::begin(21, 1))
+        );
+        assert_eq!(Some(21), tracker.queue_state().speaking);
+        assert_eq!(
+            Some(MessageState::Paused),
+            tracker.update(&Event::pause(21, 1))
+        );
+        assert_eq!(
+            QueueState {
+                paused: true,
+                speaking: None,
+                messages: vec![(21, MessageState::Paused), (22, MessageState::Paused)],
+            },
+            tracker.queue_state()
+        );
+        tracker.queued(23);
+        assert_eq!(Some(MessageState::Paused), tracker.state(23));
+        assert_eq!(
+            Some(MessageState::Speaking),
+            tracker.update(&Event::resume(21, 1))
+        );
+        let state = tracker.queue_state();
+        assert!(!state.paused);
+        assert_eq!(Some(21), state.speaking);
+        assert_eq!(Some(MessageState::Queued), tracker.state(22));
+        assert_eq!(None, tracker.update(&Event::end(21, 1)));
+        assert_eq!(None, tracker.update(&Event::cancel(22, 1)));
+        assert_eq!(None, tracker.update(&Event::cancel(23, 1)));
+        assert!(tracker.queue_state().is_idle());
+    }
+
+    #[test]
+    fn unknown_messages() {
+        let mut tracker = MessageTracker::new();
+        assert_eq!(
+            None,
+            tracker.update(&Event::from_raw(EventType::Begin, "invalid", "1"))
+        );
+        assert_eq!(None, tracker.update(&Event::end(21, 1)));
+        assert_eq!(
+            Some(MessageState::Speaking),
+            tracker.update(&Event::begin(21, 1))
+        );
+        assert_eq!(
+            vec![(21, MessageState::Speaking)],
+            tracker.queue_state().messages
+        );
+    }
+
+    #[test]
+    fn interrupted() {
+        let tracked = |policy| {
+            let mut tracker = MessageTracker::new();
+            tracker
+                .set_resubmit_policy(policy)
+                .queued_text(21, "one")
+                .queued_text(22, "two")
+                .queued(23)
+                .queued_text(24, "four");
+            tracker.update(&Event::begin(21, 1));
+            tracker.update(&Event::pause(21, 1));
+            tracker
+        };
+        let mut tracker = tracked(ResubmitPolicy::DropInFlight);
+        assert!(tracker.interrupted().is_empty());
+        assert!(tracker.queue_state().is_idle());
+        assert_eq!(
+            vec!["one", "two", "four"],
+            tracked(ResubmitPolicy::Resubmit).interrupted()
+        );
+        assert_eq!(
+            vec!["two", "four"],
+            tracked(ResubmitPolicy::ResubmitIfNotStarted).interrupted()
+        );
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut tracker = MessageTracker::new();
+        assert_eq!(None, tracker.capacity());
+        tracker.set_max_depth(Some(2)).queued(21);
+        assert_eq!(Some(1), tracker.capacity());
+        tracker.queued(22);
+        assert!(!tracker.is_ready());
+        tracker.update(&Event::end(21, 1));
+        assert!(tracker.is_ready());
+        tracker.set_max_depth(Some(0));
+        assert_eq!(Some(0), tracker.capacity());
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+#![cfg(all(feature = "async-std", unix))]
+
+use ssip_client_async::{fifo::asynchronous_async_std::Builder, *};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[allow(dead_code)]
+mod server;
+
+const SET_CLIENT_COMMUNICATION: (&str, &str) = (
+    "SET self CLIENT_NAME test:test:main\r\n",
+    "208 OK CLIENT NAME SET\r\n",
+);
+
+/// Create a server on a Unix socket and run the body with a connected client
+///
+/// The communication is an array of ("question", "response")
+macro_rules! test_client {
+    ($communication:expr, |$client:ident| $body:block) => {{
+        let socket_dir = tempfile::tempdir()?;
+        let socket_path = socket_dir.path().join("test_client.socket");
+        let handle = server::run_unix(&socket_path, $communication)?;
+        ::async_std::task::block_on(async {
+            let mut $client = Builder::new().path(&socket_path).build().await?;
+            $client
+                .set_client_name(ClientName::new("test", "test")?)
+                .await?
+                .check_client_name_set()
+                .await?;
+            $body
+            ClientResult::Ok(())
+        })?;
+        handle.join().unwrap().unwrap();
+        socket_dir.close()?;
+        Ok(())
+    }};
+}
+
+macro_rules! test_setter {
+    ($setter:ident, $question:expr, $answer:expr, $code:expr, $($arg:tt)*) => {
+        #[test]
+        fn $setter() -> ClientResult<()> {
+            test_client!(&[SET_CLIENT_COMMUNICATION, ($question, $answer)], |client| {
+                client.$setter($($arg)*).await?.check_status($code).await?;
+            })
+        }
+    };
+}
+
+macro_rules! test_getter {
+    ($getter:ident, $receive:ident, $recv_args:tt, $question:expr, $answer:expr, $value:expr) => {
+        #[test]
+        fn $getter() -> ClientResult<()> {
+            test_client!(&[SET_CLIENT_COMMUNICATION, ($question, $answer)], |client| {
+                let value = client.$getter().await?.$receive $recv_args.await?;
+                assert_eq!($value, value);
+            })
+        }
+    };
+}
+
+#[test]
+fn say_hello_and_quit() -> ClientResult<()> {
+    const COMMUNICATION: [(&str, &str); 5] = [
+        SET_CLIENT_COMMUNICATION,
+        ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+        ("hello\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+        ("GET RATE\r\n", "251-10\r\n251 OK GET RETURNED\r\n"),
+        ("QUIT\r\n", "231 HAPPY HACKING\r\n"),
+    ];
+    let socket_dir = tempfile::tempdir()?;
+    let socket_path = socket_dir.path().join("test_client.socket");
+    let handle = server::run_unix(&socket_path, &COMMUNICATION)?;
+    ::async_std::task::block_on(async {
+        let mut client = Builder::new().path(&socket_path).build().await?;
+        client
+            .set_client_name(ClientName::new("test", "test")?)
+            .await?
+            .check_client_name_set()
+            .await?;
+        let msg_id = client
+            .speak()
+            .await?
+            .check_receiving_data()
+            .await?
+            .send_line("hello")
+            .await?
+            .receive_message_id()
+            .await?;
+        assert_eq!(21, msg_id);
+        assert_eq!(
+            Response::GetRate(10),
+            client.get_rate().await?.receive().await?
+        );
+        client.quit().await?.check_status(OK_BYE).await?;
+        ClientResult::Ok(())
+    })?;
+    handle.join().unwrap().unwrap();
+    socket_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn send_lines_in_request() -> ClientResult<()> {
+    const COMMUNICATION: [(&str, &str); 3] = [
+        SET_CLIENT_COMMUNICATION,
+        ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+        (
+            "hello\r\n..\r\nworld\r\n.\r\n",
+            "225-22\r\n225 OK MESSAGE QUEUED\r\n",
+        ),
+    ];
+    let socket_dir = tempfile::tempdir()?;
+    let socket_path = socket_dir.path().join("test_client.socket");
+    let handle = server::run_unix(&socket_path, &COMMUNICATION)?;
+    ::async_std::task::block_on(async {
+        let mut client = Builder::new().path(&socket_path).build().await?;
+        client
+            .send(Request::SetName(ClientName::new("test", "test")?))
+            .await?;
+        assert_eq!(Response::ClientNameSet, client.receive().await?);
+        client.send(Request::Speak).await?;
+        assert_eq!(Response::ReceivingData, client.receive().await?);
+        client
+            .send(Request::SendLines(
+                ["hello", "..", "world"].map(String::from).to_vec(),
+            ))
+            .await?;
+        assert_eq!(Response::MessageQueued, client.receive().await?);
+        assert_eq!(ConnectionState::Ready, client.state());
+        ClientResult::Ok(())
+    })?;
+    handle.join().unwrap().unwrap();
+    socket_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn handshake_retry() -> ClientResult<()> {
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = attempts.clone();
+    let (local, remote) = UnixStream::pair()?;
+    let local = Mutex::new(Some(local));
+    ::async_std::task::block_on(async {
+        let client = Builder::new()
+            .with_stream(move || {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+                } else {
+                    Ok(local.lock().unwrap().take().unwrap())
+                }
+            })
+            .handshake_retry(3, Duration::from_millis(10))
+            .build()
+            .await;
+        assert!(client.is_ok());
+        ClientResult::Ok(())
+    })?;
+    assert_eq!(3, attempts.load(Ordering::SeqCst));
+    drop(remote);
+    Ok(())
+}
+
+test_setter!(
+    set_rate,
+    "SET self RATE 30\r\n",
+    "203 OK RATE SET\r\n",
+    OK_RATE_SET,
+    ClientScope::Current,
+    30
+);
+
+test_setter!(
+    set_volume,
+    "SET all VOLUME -20\r\n",
+    "218 OK VOLUME SET\r\n",
+    OK_VOLUME_SET,
+    ClientScope::All,
+    -20
+);
+
+test_setter!(
+    set_language,
+    "SET self LANGUAGE fr\r\n",
+    "201 OK LANGUAGE SET\r\n",
+    OK_LANGUAGE_SET,
+    ClientScope::Current,
+    "fr".parse().unwrap()
+);
+
+test_setter!(
+    enable_notifications,
+    "SET self NOTIFICATION all on\r\n",
+    "220 OK NOTIFICATION SET\r\n",
+    OK_NOTIFICATION_SET,
+    NotificationType::All
+);
+
+test_getter!(
+    get_rate,
+    receive_i8,
+    (),
+    "GET RATE\r\n",
+    "251-10\r\n251 OK GET RETURNED\r\n",
+    10
+);
+
+test_getter!(
+    get_pitch,
+    receive_i8,
+    (),
+    "GET PITCH\r\n",
+    "251--20\r\n251 OK GET RETURNED\r\n",
+    -20
+);
+
+test_getter!(
+    list_output_modules,
+    receive_lines,
+    (OK_OUTPUT_MODULES_LIST_SENT),
+    "LIST OUTPUT_MODULES\r\n",
+    "250-espeak-ng\r\n250-dummy\r\n250 OK MODULE LIST SENT\r\n",
+    vec!["espeak-ng", "dummy"]
+);
+
+#[test]
+fn receive_notification() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET self NOTIFICATION all on\r\n",
+                "220 OK NOTIFICATION SET\r\n",
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello, world\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n701-21\r\n701-test\r\n701 BEGIN\r\n",
+            ),
+        ],
+        |client| {
+            client
+                .enable_notifications(NotificationType::All)
+                .await?
+                .check_status(OK_NOTIFICATION_SET)
+                .await?;
+            let msg_id = client
+                .speak()
+                .await?
+                .check_receiving_data()
+                .await?
+                .send_line("Hello, world")
+                .await?
+                .receive_message_id()
+                .await?;
+            let event = client.receive_event().await?;
+            assert_eq!(EventType::Begin, event.ntype);
+            assert_eq!(Some(msg_id), event.message_id());
+        }
+    )
+}
+
+#[test]
+fn event_before_answer() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET self NOTIFICATION all on\r\n",
+                "702-21\r\n702-test\r\n702 END\r\n220 OK NOTIFICATION SET\r\n",
+            ),
+        ],
+        |client| {
+            client
+                .set_notification(NotificationType::All, true)
+                .await?
+                .check_status(OK_NOTIFICATION_SET)
+                .await?;
+            let event = client.receive_event().await?;
+            assert_eq!(EventType::End, event.ntype);
+        }
+    )
+}
+
+#[test]
+fn server_error() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self RATE 100\r\n", "409 ERR RATE TOO HIGH\r\n"),
+        ],
+        |client| {
+            match client
+                .set_rate(ClientScope::Current, 100)
+                .await?
+                .check_status(OK_RATE_SET)
+                .await
+            {
+                Err(ClientError::Ssip(status)) => assert_eq!(409, status.code),
+                result => panic!("expecting SSIP error: {:?}", result.map(|_| ())),
+            }
+        }
+    )
+}
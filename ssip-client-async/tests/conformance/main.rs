@@ -0,0 +1,707 @@
+// Copyright (c) 2021-2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Conformance with the Speech Synthesis Interface Protocol.
+//!
+//! Every request is checked against the line the specification expects on the wire, every
+//! documented reply code against the typed response. The commands of the specification
+//! that the client can't send are listed by `unimplemented_commands`.
+
+use ssip_client_async::{protocol, *};
+use std::{net::TcpStream, str::FromStr};
+
+#[path = "../server.rs"]
+#[allow(dead_code)]
+mod server;
+
+/// Commands of the specification, as written in its summary.
+const SPEC_COMMANDS: &[&str] = &[
+    "SPEAK",
+    "CHAR",
+    "KEY",
+    "SOUND_ICON",
+    "STOP",
+    "CANCEL",
+    "PAUSE",
+    "RESUME",
+    "BLOCK BEGIN",
+    "BLOCK END",
+    "SET CLIENT_NAME",
+    "SET PRIORITY",
+    "SET NOTIFICATION",
+    "SET DEBUG",
+    "SET OUTPUT_MODULE",
+    "GET OUTPUT_MODULE",
+    "LIST OUTPUT_MODULES",
+    "SET LANGUAGE",
+    "GET LANGUAGE",
+    "SET SSML_MODE",
+    "SET PUNCTUATION",
+    "SET SPELLING",
+    "SET CAP_LET_RECOGN",
+    "SET TABLE",
+    "LIST TABLES",
+    "SET VOICE_TYPE",
+    "GET VOICE_TYPE",
+    "LIST VOICES",
+    "SET SYNTHESIS_VOICE",
+    "LIST SYNTHESIS_VOICES",
+    "SET RATE",
+    "GET RATE",
+    "SET PITCH",
+    "GET PITCH",
+    "SET PITCH_RANGE",
+    "SET VOLUME",
+    "GET VOLUME",
+    "SET PAUSE_CONTEXT",
+    "SET HISTORY",
+    "HISTORY GET CLIENT_LIST",
+    "HISTORY GET CLIENT_ID",
+    "HISTORY GET CLIENT_MESSAGES",
+    "HISTORY GET LAST",
+    "HISTORY GET MESSAGE",
+    "HISTORY CURSOR GET",
+    "HISTORY CURSOR SET",
+    "HISTORY CURSOR FORWARD",
+    "HISTORY CURSOR BACKWARD",
+    "HISTORY SAY",
+    "HISTORY SORT",
+    "HISTORY SET SHORT_MESSAGE_LENGTH",
+    "HISTORY SET MESSAGE_TYPE_ORDERING",
+    "HISTORY SEARCH",
+    "HELP",
+    "QUIT",
+];
+
+/// Commands of the specification the client can't send yet.
+const UNIMPLEMENTED_COMMANDS: &[&str] = &["SOUND_ICON", "HELP"];
+
+/// Requests sending data rather than a command.
+const DATA_REQUESTS: &[&str] = &["SendLine", "SendLines"];
+
+/// Requests understood by Speech Dispatcher but missing from the specification.
+const EXTENSION_REQUESTS: &[&str] = &["GetPauseContext"];
+
+/// Tell if the syntax of a request is the command.
+///
+/// Keywords must match the words of the command in order. Placeholders match a word if it
+/// is one of their alternatives, otherwise they are skipped like the scopes.
+fn implements(syntax: &str, command: &str) -> bool {
+    let mut words = command.split(' ').peekable();
+    for token in syntax.split(' ') {
+        let word = match words.peek() {
+            Some(word) => *word,
+            None => break,
+        };
+        let is_keyword = token.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+        if token == word
+            || (!is_keyword
+                && token
+                    .trim_matches(|c| c == '{' || c == '}')
+                    .split('|')
+                    .any(|alternative| alternative.eq_ignore_ascii_case(word)))
+        {
+            words.next();
+        } else if is_keyword {
+            return false;
+        }
+    }
+    words.peek().is_none()
+}
+
+fn unimplemented() -> Vec<&'static str> {
+    SPEC_COMMANDS
+        .iter()
+        .filter(|command| {
+            !protocol::describe()
+                .iter()
+                .any(|description| implements(description.syntax, command))
+        })
+        .copied()
+        .collect()
+}
+
+#[test]
+fn unimplemented_commands() {
+    let unimplemented = unimplemented();
+    eprintln!(
+        "SSIP coverage: {}/{} commands",
+        SPEC_COMMANDS.len() - unimplemented.len(),
+        SPEC_COMMANDS.len()
+    );
+    for command in unimplemented.iter() {
+        eprintln!("  unimplemented: {}", command);
+    }
+    assert_eq!(UNIMPLEMENTED_COMMANDS, unimplemented.as_slice());
+}
+
+#[test]
+fn every_request_is_in_the_specification() {
+    for description in protocol::describe() {
+        assert!(
+            DATA_REQUESTS.contains(&description.request)
+                || EXTENSION_REQUESTS.contains(&description.request)
+                || SPEC_COMMANDS
+                    .iter()
+                    .any(|command| implements(description.syntax, command)),
+            "{} is not a command of the specification",
+            description.request
+        );
+    }
+}
+
+/// Request, line expected by the server, answer and typed response.
+type Case = (Request, &'static str, &'static str, Response);
+
+fn event_id() -> EventId {
+    EventId::new(21, 1)
+}
+
+fn request_cases() -> Vec<Case> {
+    vec![
+        (
+            Request::SetName(ClientName::new("test", "test").unwrap()),
+            "SET self CLIENT_NAME test:test:main\r\n",
+            "208 OK CLIENT NAME SET\r\n",
+            Response::ClientNameSet,
+        ),
+        (
+            Request::Speak,
+            "SPEAK\r\n",
+            "230 OK RECEIVING DATA\r\n",
+            Response::ReceivingData,
+        ),
+        (
+            Request::SendLine(String::from("hello")),
+            "hello\r\n.\r\n",
+            "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            Response::MessageQueued,
+        ),
+        (
+            Request::Speak,
+            "SPEAK\r\n",
+            "230 OK RECEIVING DATA\r\n",
+            Response::ReceivingData,
+        ),
+        (
+            Request::SendLines(vec![String::from("hello"), String::from("..")]),
+            "hello\r\n..\r\n.\r\n",
+            "225-22\r\n225 OK MESSAGE QUEUED\r\n",
+            Response::MessageQueued,
+        ),
+        (
+            Request::SpeakChar('a'),
+            "CHAR a\r\n",
+            "225-23\r\n225 OK MESSAGE QUEUED\r\n",
+            Response::MessageQueued,
+        ),
+        (
+            Request::SpeakKey(KeyName::from_str("space").unwrap()),
+            "KEY space\r\n",
+            "225-24\r\n225 OK MESSAGE QUEUED\r\n",
+            Response::MessageQueued,
+        ),
+        (
+            Request::Stop(MessageScope::Last),
+            "STOP self\r\n",
+            "210 OK STOPPED\r\n",
+            Response::Stopped,
+        ),
+        (
+            Request::Cancel(MessageScope::All),
+            "CANCEL all\r\n",
+            "213 OK CANCELED\r\n",
+            Response::Canceled,
+        ),
+        (
+            Request::Pause(MessageScope::Message(21)),
+            "PAUSE 21\r\n",
+            "211 OK PAUSED\r\n",
+            Response::Paused,
+        ),
+        (
+            Request::Resume(MessageScope::All),
+            "RESUME all\r\n",
+            "212 OK RESUMED\r\n",
+            Response::Resumed,
+        ),
+        (
+            Request::SetPriority(Priority::Important),
+            "SET self PRIORITY important\r\n",
+            "202 OK PRIORITY SET\r\n",
+            Response::PrioritySet,
+        ),
+        (
+            Request::SetDebug(true),
+            "SET all DEBUG on\r\n",
+            "262-/tmp/speechd-debug\r\n262 OK DEBUGGING SET\r\n",
+            Response::DebugSet,
+        ),
+        (
+            Request::SetOutputModule(ClientScope::Current, String::from("espeak")),
+            "SET self OUTPUT_MODULE espeak\r\n",
+            "216 OK OUTPUT MODULE SET\r\n",
+            Response::OutputModuleSet,
+        ),
+        (
+            Request::GetOutputModule,
+            "GET OUTPUT_MODULE\r\n",
+            "251-espeak\r\n251 OK GET RETURNED\r\n",
+            Response::Get(String::from("espeak")),
+        ),
+        (
+            Request::ListOutputModules,
+            "LIST OUTPUT_MODULES\r\n",
+            "250-espeak\r\n250-festival\r\n250 OK MODULE LIST SENT\r\n",
+            Response::OutputModulesListSent(vec![
+                String::from("espeak"),
+                String::from("festival"),
+            ]),
+        ),
+        (
+            Request::SetLanguage(ClientScope::All, LanguageTag::from_str("fr").unwrap()),
+            "SET all LANGUAGE fr\r\n",
+            "201 OK LANGUAGE SET\r\n",
+            Response::LanguageSet,
+        ),
+        (
+            Request::GetLanguage,
+            "GET LANGUAGE\r\n",
+            "251-fr\r\n251 OK GET RETURNED\r\n",
+            Response::Get(String::from("fr")),
+        ),
+        (
+            Request::SetSsmlMode(true),
+            "SET self SSML_MODE on\r\n",
+            "219 OK SSML MODE SET\r\n",
+            Response::SsmlModeSet,
+        ),
+        (
+            Request::SetPunctuationMode(ClientScope::Current, PunctuationMode::Some),
+            "SET self PUNCTUATION some\r\n",
+            "205 OK PUNCTUATION SET\r\n",
+            Response::PunctuationSet,
+        ),
+        (
+            Request::SetSpelling(ClientScope::Current, true),
+            "SET self SPELLING on\r\n",
+            "207 OK SPELLING SET\r\n",
+            Response::SpellingSet,
+        ),
+        (
+            Request::SetCapitalLettersRecognitionMode(
+                ClientScope::Current,
+                CapitalLettersRecognitionMode::Spell,
+            ),
+            "SET self CAP_LET_RECOGN spell\r\n",
+            "206 OK CAP LET RECOGNITION SET\r\n",
+            Response::CapLetRecognSet,
+        ),
+        (
+            Request::SetTable(ClientScope::Current, String::from("en-us-g2")),
+            "SET self TABLE en-us-g2\r\n",
+            "215 OK TABLE SET\r\n",
+            Response::TableSet,
+        ),
+        (
+            Request::ListTables,
+            "LIST TABLES\r\n",
+            "244-en-us-g1\r\n244-en-us-g2\r\n244 OK TABLE LIST SENT\r\n",
+            Response::TableListSent(vec![String::from("en-us-g1"), String::from("en-us-g2")]),
+        ),
+        (
+            Request::SetVoiceType(ClientScope::Current, VoiceType::Female1),
+            "SET self VOICE_TYPE FEMALE1\r\n",
+            "209 OK VOICE SET\r\n",
+            Response::VoiceSet,
+        ),
+        (
+            Request::GetVoiceType,
+            "GET VOICE_TYPE\r\n",
+            "251-FEMALE1\r\n251 OK GET RETURNED\r\n",
+            Response::Get(String::from("FEMALE1")),
+        ),
+        (
+            Request::ListVoiceTypes,
+            "LIST VOICES\r\n",
+            "249-MALE1\r\n249-FEMALE1\r\n249 OK VOICE LIST SENT\r\n",
+            Response::VoicesListSent(vec![
+                SynthesisVoice::new("MALE1", None, None),
+                SynthesisVoice::new("FEMALE1", None, None),
+            ]),
+        ),
+        (
+            Request::SetSynthesisVoice(ClientScope::Current, String::from("klatt")),
+            "SET self SYNTHESIS_VOICE klatt\r\n",
+            "209 OK VOICE SET\r\n",
+            Response::VoiceSet,
+        ),
+        (
+            Request::ListSynthesisVoices,
+            "LIST SYNTHESIS_VOICES\r\n",
+            "249-klatt\ten\tnone\r\n249 OK VOICE LIST SENT\r\n",
+            Response::VoicesListSent(vec![SynthesisVoice::new("klatt", Some("en"), None)]),
+        ),
+        (
+            Request::SetRate(ClientScope::Current, -10),
+            "SET self RATE -10\r\n",
+            "203 OK RATE SET\r\n",
+            Response::RateSet,
+        ),
+        (
+            Request::GetRate,
+            "GET RATE\r\n",
+            "251--10\r\n251 OK GET RETURNED\r\n",
+            Response::GetRate(-10),
+        ),
+        (
+            Request::SetPitch(ClientScope::All, 20),
+            "SET all PITCH 20\r\n",
+            "204 OK PITCH SET\r\n",
+            Response::PitchSet,
+        ),
+        (
+            Request::GetPitch,
+            "GET PITCH\r\n",
+            "251-20\r\n251 OK GET RETURNED\r\n",
+            Response::GetPitch(20),
+        ),
+        (
+            Request::SetPitchRange(ClientScope::Current, -30),
+            "SET self PITCH_RANGE -30\r\n",
+            "263 OK PITCH RANGE SET\r\n",
+            Response::PitchRangeSet,
+        ),
+        (
+            Request::SetVolume(ClientScope::Client(3), 100),
+            "SET 3 VOLUME 100\r\n",
+            "218 OK VOLUME SET\r\n",
+            Response::VolumeSet,
+        ),
+        (
+            Request::GetVolume,
+            "GET VOLUME\r\n",
+            "251-100\r\n251 OK GET RETURNED\r\n",
+            Response::GetVolume(100),
+        ),
+        (
+            Request::SetPauseContext(ClientScope::Current, 2),
+            "SET self PAUSE_CONTEXT 2\r\n",
+            "217 OK PAUSE CONTEXT SET\r\n",
+            Response::PauseContextSet,
+        ),
+        (
+            Request::GetPauseContext,
+            "GET PAUSE_CONTEXT\r\n",
+            "251-2\r\n251 OK GET RETURNED\r\n",
+            Response::GetPauseContext(2),
+        ),
+        (
+            Request::SetNotification(NotificationType::All, true),
+            "SET self NOTIFICATION all on\r\n",
+            "220 OK NOTIFICATION SET\r\n",
+            Response::NotificationSet,
+        ),
+        (
+            Request::Begin,
+            "BLOCK BEGIN\r\n",
+            "260 OK INSIDE BLOCK\r\n",
+            Response::InsideBlock,
+        ),
+        (
+            Request::End,
+            "BLOCK END\r\n",
+            "261 OK OUTSIDE BLOCK\r\n",
+            Response::OutsideBlock,
+        ),
+        (
+            Request::SetHistory(ClientScope::Current, true),
+            "SET self HISTORY on\r\n",
+            "299 OK BUT NOT IMPLEMENTED -- DOES NOTHING\r\n",
+            Response::NotImplemented,
+        ),
+        (
+            Request::HistoryGetClients,
+            "HISTORY GET CLIENT_LIST\r\n",
+            "240-1 joe:speechd_client:main 0\r\n240-2 jim:speechd_client:main 1\r\n240 OK CLIENTS LIST SENT\r\n",
+            Response::HistoryClientListSent(vec![
+                HistoryClientStatus::new(1, "joe:speechd_client:main", false),
+                HistoryClientStatus::new(2, "jim:speechd_client:main", true),
+            ]),
+        ),
+        (
+            Request::HistoryGetClientId,
+            "HISTORY GET CLIENT_ID\r\n",
+            "245-123\r\n245 OK CLIENT ID SENT\r\n",
+            Response::HistoryClientIdSent(123),
+        ),
+        (
+            Request::HistoryGetClientMsgs(ClientScope::Current, 0, 2),
+            "HISTORY GET CLIENT_MESSAGES self 0_2\r\n",
+            "241-21 joe:speechd_client:main\r\n241-22 joe:speechd_client:main\r\n241 OK MSGS LIST SENT\r\n",
+            Response::HistoryMsgsListSent(vec![
+                String::from("21 joe:speechd_client:main"),
+                String::from("22 joe:speechd_client:main"),
+            ]),
+        ),
+        (
+            Request::HistoryGetLastMsgId,
+            "HISTORY GET LAST\r\n",
+            "242-22\r\n242 OK LAST MSG SAID\r\n",
+            Response::HistoryLastMsg(String::from("22")),
+        ),
+        (
+            Request::HistoryGetMsg(22),
+            "HISTORY GET MESSAGE 22\r\n",
+            "246-hello\r\n246 OK MESSAGE SENT\r\n",
+            Response::MessageTextSent,
+        ),
+        (
+            Request::HistoryCursorGet,
+            "HISTORY CURSOR GET\r\n",
+            "243-42\r\n243 OK CURSOR POSITION RETURNED\r\n",
+            Response::HistoryCurPosRet(CursorPosition(42)),
+        ),
+        (
+            Request::HistoryCursorSet(ClientScope::Current, HistoryPosition::First),
+            "HISTORY CURSOR SET self first\r\n",
+            "220 OK CURSOR SET FIRST\r\n",
+            Response::HistoryCurSetFirst,
+        ),
+        (
+            Request::HistoryCursorSet(ClientScope::All, HistoryPosition::Last),
+            "HISTORY CURSOR SET all last\r\n",
+            "221 OK CURSOR SET LAST\r\n",
+            Response::HistoryCurSetLast,
+        ),
+        (
+            Request::HistoryCursorSet(ClientScope::Current, HistoryPosition::Pos(5)),
+            "HISTORY CURSOR SET self pos 5\r\n",
+            "222 OK CURSOR SET TO POSITION\r\n",
+            Response::HistoryCurSetPos,
+        ),
+        (
+            Request::HistoryCursorMove(CursorDirection::Forward),
+            "HISTORY CURSOR forward\r\n",
+            "223 OK CURSOR MOVED FORWARD\r\n",
+            Response::HistoryCurMoveFor,
+        ),
+        (
+            Request::HistoryCursorMove(CursorDirection::Backward),
+            "HISTORY CURSOR backward\r\n",
+            "224 OK CURSOR MOVED BACKWARD\r\n",
+            Response::HistoryCurMoveBack,
+        ),
+        (
+            Request::HistorySpeak(22),
+            "HISTORY SAY 22\r\n",
+            "225-25\r\n225 OK MESSAGE QUEUED\r\n",
+            Response::MessageQueued,
+        ),
+        (
+            Request::HistorySort(SortDirection::Descending, SortKey::Time),
+            "HISTORY SORT desc time\r\n",
+            "299 OK BUT NOT IMPLEMENTED -- DOES NOTHING\r\n",
+            Response::NotImplemented,
+        ),
+        (
+            Request::HistorySetShortMsgLength(15),
+            "HISTORY SET SHORT_MESSAGE_LENGTH 15\r\n",
+            "299 OK BUT NOT IMPLEMENTED -- DOES NOTHING\r\n",
+            Response::NotImplemented,
+        ),
+        (
+            Request::HistorySetMsgTypeOrdering(vec![Ordering::Text, Ordering::Key]),
+            "HISTORY SET MESSAGE_TYPE_ORDERING \"text key\"\r\n",
+            "299 OK BUT NOT IMPLEMENTED -- DOES NOTHING\r\n",
+            Response::NotImplemented,
+        ),
+        (
+            Request::HistorySearch(ClientScope::All, String::from("hello")),
+            "HISTORY SEARCH all \"hello\"\r\n",
+            "241-21 joe:speechd_client:main\r\n241 OK MSGS LIST SENT\r\n",
+            Response::HistoryMsgsListSent(vec![String::from("21 joe:speechd_client:main")]),
+        ),
+        (
+            Request::Quit,
+            "QUIT\r\n",
+            "231 HAPPY HACKING\r\n",
+            Response::Bye,
+        ),
+    ]
+}
+
+type Communication = &'static [(&'static str, &'static str)];
+
+fn leak_communication(exchanges: Vec<(&'static str, &'static str)>) -> Communication {
+    Box::leak(exchanges.into_boxed_slice())
+}
+
+/// Run a server with the communication and connect a client.
+fn connect(
+    communication: Communication,
+) -> (
+    Client<TcpStream>,
+    std::thread::JoinHandle<std::io::Result<()>>,
+) {
+    let (addr, handle) = server::run_tcp(communication).unwrap();
+    let client = tcp::sync::Builder::new(addr).unwrap().build().unwrap();
+    (client, handle)
+}
+
+#[test]
+fn every_request_on_the_wire() {
+    let cases = request_cases();
+    let (mut client, handle) = connect(leak_communication(
+        cases
+            .iter()
+            .map(|(_, question, answer, _)| (*question, *answer))
+            .collect(),
+    ));
+    for (request, question, _, response) in cases.iter() {
+        client.send(request.clone()).unwrap();
+        assert_eq!(
+            *response,
+            client.receive().unwrap(),
+            "answer to {}",
+            question
+        );
+    }
+    handle.join().unwrap().unwrap();
+    let tested: Vec<&'static str> = cases.iter().map(|(request, ..)| request.into()).collect();
+    for description in protocol::describe() {
+        assert!(
+            tested.contains(&description.request),
+            "{} is not tested on the wire",
+            description.request
+        );
+    }
+}
+
+/// Answers that the requests above don't receive.
+fn reply_cases() -> Vec<(&'static str, Response)> {
+    vec![
+        ("215 OK TABLE SET\r\n", Response::TableSet),
+        ("226 OK SOUND ICON QUEUED\r\n", Response::SoundIconQueued),
+        ("227 OK MESSAGE CANCELED\r\n", Response::MessageCanceled),
+        (
+            "244-table1\r\n244 OK TABLE LIST SENT\r\n",
+            Response::TableListSent(vec![String::from("table1")]),
+        ),
+        (
+            "248-HELP\r\n248 OK HELP SENT\r\n",
+            Response::HelpSent(vec![String::from("HELP")]),
+        ),
+        ("263 OK PITCH RANGE SET\r\n", Response::PitchRangeSet),
+        (
+            "700-21\r\n700-1\r\n700-mark\r\n700 INDEX MARK\r\n",
+            Response::EventIndexMark(event_id(), String::from("mark")),
+        ),
+        (
+            "701-21\r\n701-1\r\n701 BEGIN\r\n",
+            Response::EventBegin(event_id()),
+        ),
+        (
+            "702-21\r\n702-1\r\n702 END\r\n",
+            Response::EventEnd(event_id()),
+        ),
+        (
+            "703-21\r\n703-1\r\n703 CANCELED\r\n",
+            Response::EventCanceled(event_id()),
+        ),
+        (
+            "704-21\r\n704-1\r\n704 PAUSED\r\n",
+            Response::EventPaused(event_id()),
+        ),
+        (
+            "705-21\r\n705-1\r\n705 RESUMED\r\n",
+            Response::EventResumed(event_id()),
+        ),
+    ]
+}
+
+const ERROR_CODES: &[ReturnCode] = &[
+    ERR_INTERNAL,
+    ERR_COULDNT_SET_PRIORITY,
+    ERR_COULDNT_SET_LANGUAGE,
+    ERR_COULDNT_SET_RATE,
+    ERR_COULDNT_SET_PITCH,
+    ERR_COULDNT_SET_PUNCTUATION,
+    ERR_COULDNT_SET_CAP_LET_RECOG,
+    ERR_COULDNT_SET_SPELLING,
+    ERR_COULDNT_SET_VOICE,
+    ERR_COULDNT_SET_TABLE,
+    ERR_COULDNT_SET_CLIENT_NAME,
+    ERR_COULDNT_SET_OUTPUT_MODULE,
+    ERR_COULDNT_SET_PAUSE_CONTEXT,
+    ERR_COULDNT_SET_VOLUME,
+    ERR_COULDNT_SET_SSML_MODE,
+    ERR_COULDNT_SET_NOTIFICATION,
+    ERR_COULDNT_SET_DEBUG,
+    ERR_NO_SND_ICONS,
+    ERR_CANT_REPORT_VOICES,
+    ERR_NO_OUTPUT_MODULE,
+    ERR_ALREADY_INSIDE_BLOCK,
+    ERR_ALREADY_OUTSIDE_BLOCK,
+    ERR_NOT_ALLOWED_INSIDE_BLOCK,
+    ERR_COULDNT_SET_PITCH_RANGE,
+    ERR_NOT_IMPLEMENTED,
+    ERR_NO_CLIENT,
+    ERR_NO_SUCH_CLIENT,
+    ERR_NO_MESSAGE,
+    ERR_POS_LOW,
+    ERR_POS_HIGH,
+    ERR_ID_NOT_EXIST,
+    ERR_UNKNOWN_ICON,
+    ERR_UNKNOWN_PRIORITY,
+    ERR_RATE_TOO_HIGH,
+    ERR_RATE_TOO_LOW,
+    ERR_PITCH_TOO_HIGH,
+    ERR_PITCH_TOO_LOW,
+    ERR_VOLUME_TOO_HIGH,
+    ERR_VOLUME_TOO_LOW,
+    ERR_PITCH_RANGE_TOO_HIGH,
+    ERR_PITCH_RANGE_TOO_LOW,
+    ERR_INVALID_COMMAND,
+    ERR_INVALID_ENCODING,
+    ERR_MISSING_PARAMETER,
+    ERR_NOT_A_NUMBER,
+    ERR_NOT_A_STRING,
+    ERR_PARAMETER_NOT_ON_OFF,
+    ERR_PARAMETER_INVALID,
+];
+
+#[test]
+fn every_reply_code_is_typed() {
+    const QUESTION: &str = "STOP self\r\n";
+    let replies = reply_cases();
+    let errors: Vec<&'static str> = ERROR_CODES
+        .iter()
+        .map(|code| &*Box::leak(format!("{} ERR\r\n", code).into_boxed_str()))
+        .collect();
+    let (mut client, handle) = connect(leak_communication(
+        replies
+            .iter()
+            .map(|(answer, _)| *answer)
+            .chain(errors.iter().copied())
+            .map(|answer| (QUESTION, answer))
+            .collect(),
+    ));
+    for (answer, response) in replies.iter() {
+        client.send(Request::Stop(MessageScope::Last)).unwrap();
+        assert_eq!(*response, client.receive().unwrap(), "reply {}", answer);
+    }
+    for code in ERROR_CODES.iter() {
+        client.send(Request::Stop(MessageScope::Last)).unwrap();
+        match client.receive() {
+            Err(ClientError::Ssip(status)) => assert_eq!(*code, status.code),
+            result => panic!("reply {}: {:?}", code, result),
+        }
+    }
+    handle.join().unwrap().unwrap();
+}
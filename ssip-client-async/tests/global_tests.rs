@@ -0,0 +1,66 @@
+// Copyright (c) 2022 Laurent Pelecq
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+#![cfg(all(feature = "global", unix))]
+
+use ssip_client_async::{global, ClientName, ClientResult};
+
+#[allow(dead_code)]
+mod server;
+
+/// Communication of a client that speaks one message.
+fn say_communication(message_id: u32) -> &'static [(&'static str, &'static str)] {
+    let name = ClientName::from_env();
+    let communication = vec![
+        (
+            format!(
+                "SET self CLIENT_NAME {}:{}:{}\r\n",
+                name.user, name.application, name.component
+            ),
+            String::from("208 OK CLIENT NAME SET\r\n"),
+        ),
+        (
+            String::from("SPEAK\r\n"),
+            String::from("230 OK RECEIVING DATA\r\n"),
+        ),
+        (
+            String::from("hello\r\n.\r\n"),
+            format!("225-{}\r\n225 OK MESSAGE QUEUED\r\n", message_id),
+        ),
+    ];
+    let communication: Vec<(&'static str, &'static str)> = communication
+        .into_iter()
+        .map(|(question, answer)| {
+            (
+                &*Box::leak(question.into_boxed_str()),
+                &*Box::leak(answer.into_boxed_str()),
+            )
+        })
+        .collect();
+    Box::leak(communication.into_boxed_slice())
+}
+
+#[test]
+fn reconnect_default_client() -> ClientResult<()> {
+    let runtime_dir = tempfile::tempdir()?;
+    let socket_dir = runtime_dir.path().join("speech-dispatcher");
+    std::fs::create_dir(&socket_dir)?;
+    let socket_path = socket_dir.join("speechd.sock");
+    std::env::set_var("XDG_RUNTIME_DIR", runtime_dir.path());
+
+    let handle = server::run_unix(&socket_path, say_communication(1))?;
+    assert_eq!(1, global::default().say("hello")?);
+    handle.join().unwrap().unwrap();
+
+    // The first server closed the connection.
+    std::fs::remove_file(&socket_path)?;
+    let handle = server::run_unix(&socket_path, say_communication(2))?;
+    assert_eq!(2, global::default().say("hello")?);
+    handle.join().unwrap().unwrap();
+    Ok(())
+}
@@ -16,9 +16,10 @@ use std::{
 };
 
 #[cfg(feature = "async-mio")]
-use ssip_client::{client::Source, *};
+use ssip_client_async::{client::Source, *};
 
 #[cfg(feature = "async-mio")]
+#[allow(dead_code)]
 mod server;
 
 #[cfg(feature = "async-mio")]
@@ -69,11 +70,11 @@ impl<'a, 'b> State<'a, 'b> {
 }
 
 #[cfg(feature = "async-mio")]
-fn basic_async_client_communication<S: Read + Write + Source>(
+fn basic_async_client_communication<S: Read + Write + Source + mio::event::Source>(
     client: &mut QueuedClient<S>,
 ) -> ClientResult<usize> {
-    let get_requests = vec![Request::GetOutputModule, Request::GetRate];
-    let get_answers = vec!["espeak", "10"];
+    let get_requests = [Request::GetOutputModule, Request::GetRate];
+    let get_answers = ["espeak", "10"];
     let mut state = State::new(get_requests.iter(), get_answers.iter());
 
     let mut poll = Poll::new()?;
@@ -82,7 +83,7 @@ fn basic_async_client_communication<S: Read + Write + Source>(
     let output_token = Token(1);
     let timeout = Duration::new(0, 500 * 1000 * 1000 /* 500 ms */);
     client.register(&poll, input_token, output_token).unwrap();
-    client.push(Request::SetName(ClientName::new("test", "test")));
+    client.push(Request::SetName(ClientName::new("test", "test")?));
     while !state.terminated() {
         if !state.writable || !client.has_next() {
             poll.poll(&mut events, Some(timeout))?;
@@ -93,11 +94,12 @@ fn basic_async_client_communication<S: Read + Write + Source>(
             if token == input_token {
                 match dbg!(client.receive_next()?) {
                     Response::ClientNameSet => {
-                        client.push(Request::SetLanguage(ClientScope::Current, "en".to_string()))
+                        client.push(Request::SetLanguage(ClientScope::Current, "en".parse()?))
                     }
                     Response::LanguageSet => client.push(Request::Stop(MessageScope::Last)),
                     Response::Stopped => state.start_get = true,
                     Response::Get(val) => state.assert_answer(&val),
+                    Response::GetRate(val) => state.assert_answer(&val.to_string()),
                     result => panic!("Unexpected response: {:?}", result),
                 }
                 if let Some(request) = state.next_request() {
@@ -142,7 +144,7 @@ fn basic_async_unix_communication() -> ClientResult<()> {
     let socket_path = socket_dir.path().join("basic_async_communication.socket");
     assert!(!socket_path.exists());
     let handle = server::run_unix(&socket_path, &BASIC_COMMUNICATION)?;
-    let mut client = QueuedClient::new(fifo::Builder::new().path(&socket_path).build()?);
+    let mut client = QueuedClient::new(fifo::mio::Builder::new().path(&socket_path).build()?);
     let countdown = basic_async_client_communication(&mut client)?;
     handle.join().unwrap().unwrap();
     socket_dir.close()?;
@@ -153,9 +155,8 @@ fn basic_async_unix_communication() -> ClientResult<()> {
 #[test]
 #[cfg(feature = "async-mio")]
 fn basic_async_tcp_communication() -> ClientResult<()> {
-    let addr = "127.0.0.1:9999";
-    let handle = server::run_tcp(addr, &BASIC_COMMUNICATION)?;
-    let mut client = QueuedClient::new(tcp::Builder::new(addr.parse().unwrap()).build()?);
+    let (addr, handle) = server::run_tcp(&BASIC_COMMUNICATION)?;
+    let mut client = QueuedClient::new(tcp::mio::Builder::new(addr).build()?);
     let countdown = basic_async_client_communication(&mut client)?;
     handle.join().unwrap().unwrap();
     assert!(countdown > 0);
@@ -7,8 +7,9 @@
 // modified, or distributed except according to those terms.
 
 use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
-use std::net::{Shutdown, TcpListener, ToSocketAddrs};
+use std::net::{Shutdown, SocketAddr, TcpListener, ToSocketAddrs};
 use std::thread;
+use std::time::Duration;
 #[cfg(unix)]
 use std::{os::unix::net::UnixListener, path::Path};
 
@@ -21,6 +22,39 @@ fn split_lines(lines: &str) -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
+/// Pause between two chunks of a split answer so that they are not merged.
+const CHUNK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Faults injected by the server when answering.
+///
+/// Indexes refer to the position of the pair (question, answer) in the communication.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    /// Wait before sending each answer.
+    pub delay: Option<Duration>,
+    /// Split answers in chunks of at most this number of bytes, each flushed separately.
+    pub chunk_size: Option<usize>,
+    /// Line sent before the answer at the given index.
+    pub garbage: Option<(usize, &'static str)>,
+    /// Close the connection instead of sending the answer at the given index.
+    pub disconnect_at: Option<usize>,
+}
+
+/// Write the answer, possibly in several chunks.
+fn write_answer(output: &mut dyn Write, answer: &str, faults: &Faults) -> io::Result<()> {
+    match faults.chunk_size {
+        Some(size) => {
+            for chunk in answer.as_bytes().chunks(size) {
+                output.write_all(chunk)?;
+                output.flush()?;
+                thread::sleep(CHUNK_INTERVAL);
+            }
+        }
+        None => output.write_all(answer.as_bytes())?,
+    }
+    Ok(())
+}
+
 /// Handle the communication for tests.
 ///
 /// The communication is a list of (question, answer). If the client sends the expected question
@@ -29,10 +63,11 @@ fn serve_streams(
     instream: &mut dyn Read,
     outstream: &mut dyn Write,
     communication: &[(&'static str, &'static str)],
+    faults: &Faults,
 ) -> io::Result<()> {
     let mut input = BufReader::new(instream);
     let mut output = BufWriter::new(outstream);
-    for (questions, answer) in communication.iter() {
+    for (index, (questions, answer)) in communication.iter().enumerate() {
         for question in split_lines(questions).iter() {
             let mut line = String::new();
             input.read_line(&mut line)?;
@@ -43,21 +78,52 @@ fn serve_streams(
                 ));
             }
         }
-        output.write_all(answer.as_bytes())?;
+        if faults.disconnect_at == Some(index) {
+            return Ok(());
+        }
+        if let Some(delay) = faults.delay {
+            thread::sleep(delay);
+        }
+        match faults.garbage {
+            Some((garbage_index, garbage)) if garbage_index == index => {
+                output.write_all(garbage.as_bytes())?;
+            }
+            _ => (),
+        }
+        write_answer(&mut output, answer, faults)?;
         output.flush()?;
     }
     Ok(())
 }
 
+/// Communication of each connection, in the order the connections are accepted.
+pub type Scripts = &'static [&'static [(&'static str, &'static str)]];
+
+/// Wait for the threads serving the connections and return the first error.
+fn join_connections(handles: Vec<thread::JoinHandle<io::Result<()>>>) -> io::Result<()> {
+    let mut result = Ok(());
+    for handle in handles {
+        let connection_result = handle.join().expect("connection thread panicked");
+        if result.is_ok() {
+            result = connection_result;
+        }
+    }
+    result
+}
+
 /// Server traits
 pub trait Server {
     fn serve(&mut self, communication: &[(&'static str, &'static str)]) -> io::Result<()>;
+
+    /// Accept one connection per script and serve them concurrently.
+    fn serve_all(&mut self, scripts: Scripts) -> io::Result<()>;
 }
 
 /// Server on a named socket.
 #[cfg(unix)]
 pub struct UnixServer {
     listener: UnixListener,
+    faults: Faults,
 }
 
 #[cfg(unix)]
@@ -71,7 +137,16 @@ impl UnixServer {
         P: AsRef<Path>,
     {
         let listener = UnixListener::bind(socket_path.as_ref())?;
-        Ok(Self { listener })
+        Ok(Self {
+            listener,
+            faults: Faults::default(),
+        })
+    }
+
+    /// Inject faults in the communication.
+    pub fn with_faults(mut self, faults: Faults) -> Self {
+        self.faults = faults;
+        self
     }
 }
 
@@ -79,13 +154,36 @@ impl UnixServer {
 impl Server for UnixServer {
     fn serve(&mut self, communication: &[(&'static str, &'static str)]) -> io::Result<()> {
         let (mut stream, _) = self.listener.accept()?;
-        serve_streams(&mut stream.try_clone()?, &mut stream, communication)
+        serve_streams(
+            &mut stream.try_clone()?,
+            &mut stream,
+            communication,
+            &self.faults,
+        )
+    }
+
+    fn serve_all(&mut self, scripts: Scripts) -> io::Result<()> {
+        let mut handles = Vec::with_capacity(scripts.len());
+        for communication in scripts.iter() {
+            let (mut stream, _) = self.listener.accept()?;
+            let faults = self.faults.clone();
+            handles.push(thread::spawn(move || {
+                serve_streams(
+                    &mut stream.try_clone()?,
+                    &mut stream,
+                    communication,
+                    &faults,
+                )
+            }));
+        }
+        join_connections(handles)
     }
 }
 
 /// Server on a named socket.
 pub struct TcpServer {
     listener: TcpListener,
+    faults: Faults,
 }
 
 impl TcpServer {
@@ -95,16 +193,59 @@ impl TcpServer {
     /// the server will receive and the second item is the answer.
     pub fn new<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
         let listener = TcpListener::bind(addr)?;
-        Ok(Self { listener })
+        Ok(Self {
+            listener,
+            faults: Faults::default(),
+        })
+    }
+
+    /// Inject faults in the communication.
+    pub fn with_faults(mut self, faults: Faults) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
     }
 }
 
 impl Server for TcpServer {
     fn serve(&mut self, communication: &[(&'static str, &'static str)]) -> io::Result<()> {
         let (mut stream, _) = self.listener.accept()?;
-        serve_streams(&mut stream.try_clone()?, &mut stream, communication)?;
+        if self.faults.chunk_size.is_some() {
+            stream.set_nodelay(true)?;
+        }
+        serve_streams(
+            &mut stream.try_clone()?,
+            &mut stream,
+            communication,
+            &self.faults,
+        )?;
         stream.shutdown(Shutdown::Both)
     }
+
+    fn serve_all(&mut self, scripts: Scripts) -> io::Result<()> {
+        let mut handles = Vec::with_capacity(scripts.len());
+        for communication in scripts.iter() {
+            let (mut stream, _) = self.listener.accept()?;
+            if self.faults.chunk_size.is_some() {
+                stream.set_nodelay(true)?;
+            }
+            let faults = self.faults.clone();
+            handles.push(thread::spawn(move || {
+                serve_streams(
+                    &mut stream.try_clone()?,
+                    &mut stream,
+                    communication,
+                    &faults,
+                )?;
+                stream.shutdown(Shutdown::Both)
+            }));
+        }
+        join_connections(handles)
+    }
 }
 
 /// Run the server in a thread
@@ -118,25 +259,63 @@ pub fn run_server(
     })
 }
 
+/// Run the server in a thread with a script per connection
+pub fn run_server_with_scripts(
+    mut server: Box<dyn Server + Send>,
+    scripts: Scripts,
+) -> thread::JoinHandle<io::Result<()>> {
+    thread::spawn(move || server.serve_all(scripts))
+}
+
 #[cfg(unix)]
 pub fn run_unix<P>(
     socket_path: P,
     communication: &'static [(&'static str, &'static str)],
 ) -> io::Result<thread::JoinHandle<io::Result<()>>>
+where
+    P: AsRef<Path>,
+{
+    run_unix_with_faults(socket_path, communication, Faults::default())
+}
+
+#[cfg(unix)]
+pub fn run_unix_with_faults<P>(
+    socket_path: P,
+    communication: &'static [(&'static str, &'static str)],
+    faults: Faults,
+) -> io::Result<thread::JoinHandle<io::Result<()>>>
 where
     P: AsRef<Path>,
 {
     Ok(run_server(
-        Box::new(UnixServer::new(&socket_path)?),
+        Box::new(UnixServer::new(&socket_path)?.with_faults(faults)),
         communication,
     ))
 }
 
-pub fn run_tcp<A: ToSocketAddrs>(
-    addr: A,
+/// Run the server on a free port of the loopback interface and return its address.
+pub fn run_tcp(
     communication: &'static [(&'static str, &'static str)],
-) -> io::Result<thread::JoinHandle<io::Result<()>>> {
-    Ok(run_server(Box::new(TcpServer::new(addr)?), communication))
+) -> io::Result<(SocketAddr, thread::JoinHandle<io::Result<()>>)> {
+    run_tcp_with_faults(communication, Faults::default())
+}
+
+pub fn run_tcp_with_faults(
+    communication: &'static [(&'static str, &'static str)],
+    faults: Faults,
+) -> io::Result<(SocketAddr, thread::JoinHandle<io::Result<()>>)> {
+    let server = TcpServer::new("127.0.0.1:0")?.with_faults(faults);
+    let addr = server.local_addr()?;
+    Ok((addr, run_server(Box::new(server), communication)))
+}
+
+/// Run the server on a free port accepting several connections, each with its own script.
+pub fn run_tcp_with_scripts(
+    scripts: Scripts,
+) -> io::Result<(SocketAddr, thread::JoinHandle<io::Result<()>>)> {
+    let server = TcpServer::new("127.0.0.1:0")?;
+    let addr = server.local_addr()?;
+    Ok((addr, run_server_with_scripts(Box::new(server), scripts)))
 }
 
 #[cfg(test)]
@@ -148,4 +327,15 @@ mod test {
         let one_line = super::split_lines(ONE_LINE);
         assert_eq!(&[ONE_LINE], one_line.as_slice());
     }
+
+    #[test]
+    fn test_write_answer_in_chunks() {
+        let faults = super::Faults {
+            chunk_size: Some(4),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        super::write_answer(&mut output, "208 OK CLIENT NAME SET\r\n", &faults).unwrap();
+        assert_eq!(b"208 OK CLIENT NAME SET\r\n", output.as_slice());
+    }
 }
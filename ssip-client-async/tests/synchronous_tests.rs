@@ -5,32 +5,38 @@
 // license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
-
-#[cfg(not(feature = "async-mio"))]
-use ssip_client::{client::Source, *};
-#[cfg(all(unix, not(feature = "async-mio")))]
+use ssip_client_async::{client::Source, *};
+#[cfg(unix)]
 use std::os::unix::net::UnixStream;
-#[cfg(not(feature = "async-mio"))]
 use std::{
     io::{self, Read, Write},
     net::TcpStream,
-    sync::{
-        atomic::{AtomicU16, Ordering as AtomicOrdering},
-        Arc,
-    },
+    time::Duration,
 };
-
-#[cfg(not(feature = "async-mio"))]
 mod server;
+use server::Faults;
 
 /// Create a server on a Unix socket and run the client
 ///
 /// The communication is an array of (["question", ...], "response")
-#[cfg(all(unix, not(feature = "async-mio")))]
+#[cfg(unix)]
 fn test_unix_client<F>(
     communication: &'static [(&'static str, &'static str)],
     process: F,
 ) -> ClientResult<()>
+where
+    F: FnMut(&mut Client<UnixStream>) -> io::Result<()>,
+{
+    test_unix_client_with_faults(communication, None, process)
+}
+
+/// Create a server on a Unix socket, optionally injecting faults, and run the client
+#[cfg(unix)]
+fn test_unix_client_with_faults<F>(
+    communication: &'static [(&'static str, &'static str)],
+    faults: Option<Faults>,
+    process: F,
+) -> ClientResult<()>
 where
     F: FnMut(&mut Client<UnixStream>) -> io::Result<()>,
 {
@@ -38,12 +44,15 @@ where
     let socket_path = socket_dir.path().join("test_client.socket");
     assert!(!socket_path.exists());
     let mut process_wrapper = std::panic::AssertUnwindSafe(process);
-    let handle = server::run_unix(&socket_path, communication)?;
-    let mut client = ssip_client::fifo::Builder::new()
+    let handle = match faults {
+        Some(faults) => server::run_unix_with_faults(&socket_path, communication, faults)?,
+        None => server::run_unix(&socket_path, communication)?,
+    };
+    let mut client = ssip_client_async::fifo::sync::Builder::new()
         .path(&socket_path)
         .build()?;
     client
-        .set_client_name(ClientName::new("test", "test"))?
+        .set_client_name(ClientName::new("test", "test")?)?
         .check_client_name_set()?;
     process_wrapper(&mut client)?;
     handle.join().unwrap().unwrap();
@@ -51,57 +60,61 @@ where
     Ok(())
 }
 
-#[cfg(not(feature = "async-mio"))]
-lazy_static::lazy_static! {
-    static ref TCP_PORT: Arc<AtomicU16> = Arc::new(AtomicU16::new(10000));
-}
-
 /// Create a server on a inet socket and run the client
 ///
 /// The communication is an array of (["question", ...], "response")
-#[cfg(not(feature = "async-mio"))]
 fn test_tcp_client<F>(
     communication: &'static [(&'static str, &'static str)],
     process: F,
 ) -> ClientResult<()>
+where
+    F: FnMut(&mut Client<TcpStream>) -> io::Result<()>,
+{
+    test_tcp_client_with_faults(communication, None, process)
+}
+
+/// Create a server on a inet socket, optionally injecting faults, and run the client
+fn test_tcp_client_with_faults<F>(
+    communication: &'static [(&'static str, &'static str)],
+    faults: Option<Faults>,
+    process: F,
+) -> ClientResult<()>
 where
     F: FnMut(&mut Client<TcpStream>) -> io::Result<()>,
 {
     let mut process_wrapper = std::panic::AssertUnwindSafe(process);
-    let tcp_port = TCP_PORT.clone().fetch_add(1, AtomicOrdering::SeqCst);
-    let addr = format!("127.0.0.1:{}", tcp_port);
-    let handle = server::run_tcp(&addr, communication)?;
-    let mut client = ssip_client::tcp::Builder::new(&addr)?.build()?;
+    let (addr, handle) = match faults {
+        Some(faults) => server::run_tcp_with_faults(communication, faults)?,
+        None => server::run_tcp(communication)?,
+    };
+    let mut client = ssip_client_async::tcp::sync::Builder::new(addr)?.build()?;
     client
-        .set_client_name(ClientName::new("test", "test"))?
+        .set_client_name(ClientName::new("test", "test")?)?
         .check_client_name_set()?;
     process_wrapper(&mut client)?;
     handle.join().unwrap().unwrap();
     Ok(())
 }
 
-#[cfg(all(unix, not(feature = "async-mio")))]
+#[cfg(unix)]
 macro_rules! test_client {
     ($communication:expr, $closure:expr) => {
         test_unix_client($communication, $closure)
     };
 }
 
-#[cfg(all(not(unix), not(feature = "async-mio")))]
+#[cfg(not(unix))]
 macro_rules! test_client {
     ($communication:expr, $closure:expr) => {
         test_tcp_client($communication, $closure)
     };
 }
-
-#[cfg(not(feature = "async-mio"))]
 const SET_CLIENT_COMMUNICATION: (&str, &str) = (
     "SET self CLIENT_NAME test:test:main\r\n",
     "208 OK CLIENT NAME SET\r\n",
 );
 
 #[test]
-#[cfg(not(feature = "async-mio"))]
 fn connect_and_quit() -> ClientResult<()> {
     const COMMUNICATION: [(&str, &str); 2] = [
         SET_CLIENT_COMMUNICATION,
@@ -118,7 +131,6 @@ fn connect_and_quit() -> ClientResult<()> {
 }
 
 #[test]
-#[cfg(not(feature = "async-mio"))]
 fn say_one_line() -> ClientResult<()> {
     test_client!(
         &[
@@ -150,7 +162,6 @@ fn say_one_line() -> ClientResult<()> {
 macro_rules! test_setter {
     ($setter:ident, $question:expr, $answer:expr, $code:expr, $($arg:tt)*) => {
         #[test]
-        #[cfg(not(feature = "async-mio"))]
         fn $setter() -> ClientResult<()> {
             test_client!(
                 &[SET_CLIENT_COMMUNICATION, ($question, $answer)],
@@ -166,7 +177,6 @@ macro_rules! test_setter {
 macro_rules! test_getter {
     ($getter:ident, $get_args:tt, $receive:ident, $recv_arg:tt, $question:expr, $answer:expr, $value:expr) => {
         #[test]
-        #[cfg(not(feature = "async-mio"))]
         fn $getter() -> ClientResult<()> {
             test_client!(
                 &[SET_CLIENT_COMMUNICATION, ($question, $answer)],
@@ -189,7 +199,6 @@ macro_rules! test_getter {
 macro_rules! test_list {
     ($getter:ident, $question:expr, $answer:expr, $code:expr, $values:expr) => {
         #[test]
-        #[cfg(not(feature = "async-mio"))]
         fn $getter() -> ClientResult<()> {
             test_client!(
                 &[SET_CLIENT_COMMUNICATION, ($question, $answer)],
@@ -212,7 +221,6 @@ test_setter!(
 );
 
 #[test]
-#[cfg(not(feature = "async-mio"))]
 fn set_debug() -> ClientResult<()> {
     test_client!(
         &[
@@ -234,6 +242,29 @@ fn set_debug() -> ClientResult<()> {
     )
 }
 
+#[test]
+fn debug_session() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET all DEBUG on\r\n",
+                "262-/run/user/100/speech-dispatcher/log/debug\r\n262 OK DEBUGGING SET\r\n",
+            ),
+            ("SET all DEBUG off\r\n", "262 OK DEBUGGING SET\r\n"),
+        ],
+        |client| {
+            let session = debug::DebugSession::start(client)?;
+            assert_eq!(
+                std::path::Path::new("/run/user/100/speech-dispatcher/log/debug"),
+                session.path()
+            );
+            session.stop(client)?;
+            Ok(())
+        }
+    )
+}
+
 test_setter!(
     set_output_module,
     "SET self OUTPUT_MODULE espeak-ng\r\n",
@@ -264,14 +295,16 @@ test_setter!(
     "201 OK LANGUAGE SET\r\n",
     201,
     ClientScope::Current,
-    "en",
+    "en".parse().unwrap(),
 );
 
 test_getter!(
     get_language,
+    receive_language,
+    (),
     "GET LANGUAGE\r\n",
     "251-fr\r\n251 OK GET RETURNED\r\n",
-    "fr"
+    "fr".parse::<LanguageTag>().unwrap()
 );
 
 test_setter!(
@@ -310,13 +343,31 @@ test_getter!(
     100
 );
 
+test_setter!(
+    set_pause_context,
+    "SET self PAUSE_CONTEXT 3\r\n",
+    "217 OK PAUSE CONTEXT SET\r\n",
+    217,
+    ClientScope::Current,
+    3,
+);
+
+test_getter!(
+    get_pause_context,
+    receive_u16,
+    (),
+    "GET PAUSE_CONTEXT\r\n",
+    "251-3\r\n251 OK GET RETURNED\r\n",
+    3
+);
+
 test_getter!(
     get_pitch,
     receive_i8,
     (),
     "GET PITCH\r\n",
-    "251-0\r\n251 OK GET RETURNED\r\n",
-    0
+    "251--20\r\n251 OK GET RETURNED\r\n",
+    -20
 );
 
 test_setter!(
@@ -363,11 +414,46 @@ test_setter!(
     CapitalLettersRecognitionMode::Spell
 );
 
+test_setter!(
+    set_table,
+    "SET self TABLE en-us-g2\r\n",
+    "215 OK TABLE SET\r\n",
+    215,
+    ClientScope::Current,
+    "en-us-g2"
+);
+
+test_list!(
+    list_tables,
+    "LIST TABLES\r\n",
+    "244-en-us-g1\r\n244-en-us-g2\r\n244 OK TABLE LIST SENT\r\n",
+    244,
+    &["en-us-g1", "en-us-g2"]
+);
+
 test_getter!(
     get_voice_type,
+    receive_voice_type,
+    (),
     "GET VOICE_TYPE\r\n",
     "251-MALE1\r\n251 OK GET RETURNED\r\n",
-    "MALE1"
+    VoiceType::Male1
+);
+
+test_setter!(
+    enable_notifications,
+    "SET self NOTIFICATION all on\r\n",
+    "220 OK NOTIFICATION SET\r\n",
+    OK_NOTIFICATION_SET,
+    NotificationType::All
+);
+
+test_setter!(
+    disable_notifications,
+    "SET self NOTIFICATION all off\r\n",
+    "220 OK NOTIFICATION SET\r\n",
+    OK_NOTIFICATION_SET,
+    NotificationType::All
 );
 
 test_setter!(
@@ -376,19 +462,26 @@ test_setter!(
     "209 OK VOICE SET\r\n",
     209,
     ClientScope::Current,
-    "FEMALE1"
+    VoiceType::Female1
 );
 
-test_list!(
+test_getter!(
     list_voice_types,
+    receive_voice_types,
+    (),
     "LIST VOICES\r\n",
     "249-MALE1\r\n249-MALE2\r\n249-FEMALE1\r\n249-FEMALE2\r\n249-CHILD_MALE\r\n249-CHILD_FEMALE\r\n249 OK VOICE LIST SENT\r\n",
-    249,
-    &[ "MALE1", "MALE2", "FEMALE1", "FEMALE2", "CHILD_MALE", "CHILD_FEMALE" ]
+    vec![
+        VoiceType::Male1,
+        VoiceType::Male2,
+        VoiceType::Female1,
+        VoiceType::Female2,
+        VoiceType::ChildMale,
+        VoiceType::ChildFemale
+    ]
 );
 
 #[test]
-#[cfg(not(feature = "async-mio"))]
 fn list_synthesis_voices() -> ClientResult<()> {
     test_client!(
         &[
@@ -414,7 +507,220 @@ fn list_synthesis_voices() -> ClientResult<()> {
 }
 
 #[test]
-#[cfg(not(feature = "async-mio"))]
+fn lenient_parse_mode() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "LIST OUTPUT_MODULES\r\n",
+                "250-espeak-ng\r\n250+dummy\r\n250\r\n"
+            ),
+        ],
+        |client| {
+            client.set_parse_mode(ParseMode::Lenient);
+            let modules = client
+                .list_output_modules()
+                .unwrap()
+                .receive_lines(OK_OUTPUT_MODULES_LIST_SENT)
+                .unwrap();
+            assert_eq!(vec!["espeak-ng", "dummy"], modules);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn set_best_voice() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "LIST SYNTHESIS_VOICES\r\n",
+                "249-Amharic\tam\tnone\r\n249-Vietnamese (Southern)+shelby\tvi-VN-X-SOUTH\tshelby\r\n249-Vietnamese\tvi\tnone\r\n249 OK VOICE LIST SENT\r\n"
+            ),
+            ("SET self SYNTHESIS_VOICE Vietnamese\r\n", "209 OK VOICE SET\r\n"),
+        ],
+        |client| {
+            let voice = client.set_best_voice(ClientScope::Current, &["fr", "vi"]).unwrap();
+            assert_eq!(Some(SynthesisVoice::new("Vietnamese", Some("vi"), None)), voice);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn probe_voice() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("GET VOICE_TYPE\r\n", "251-MALE1\r\n251 OK GET RETURNED\r\n"),
+            (
+                "SET self SYNTHESIS_VOICE Klingon\r\n",
+                "409 ERR VOICE NOT SET\r\n"
+            ),
+            ("SET self VOICE_TYPE MALE1\r\n", "209 OK VOICE SET\r\n"),
+            (
+                "SET self SYNTHESIS_VOICE Amharic\r\n",
+                "209 OK VOICE SET\r\n"
+            ),
+            (
+                "SET self SYNTHESIS_VOICE Vietnamese\r\n",
+                "209 OK VOICE SET\r\n"
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (" \r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            (
+                "SET self SYNTHESIS_VOICE Amharic\r\n",
+                "209 OK VOICE SET\r\n"
+            ),
+        ],
+        |client| {
+            assert!(!client.probe_voice("Klingon").unwrap());
+            assert_eq!(None, client.synthesis_voice());
+            client
+                .set_synthesis_voice(ClientScope::Current, "Amharic")?
+                .check_status(OK_VOICE_SET)?;
+            assert!(client.probe_voice("Vietnamese").unwrap());
+            assert_eq!(Some("Amharic"), client.synthesis_voice());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn probe_voice_after_rejected_voice() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET self SYNTHESIS_VOICE Amharic\r\n",
+                "209 OK VOICE SET\r\n"
+            ),
+            (
+                "SET self SYNTHESIS_VOICE Klingon\r\n",
+                "409 ERR VOICE NOT SET\r\n"
+            ),
+            (
+                "SET self SYNTHESIS_VOICE Vulcan\r\n",
+                "409 ERR VOICE NOT SET\r\n"
+            ),
+            (
+                "SET self SYNTHESIS_VOICE Amharic\r\n",
+                "209 OK VOICE SET\r\n"
+            ),
+        ],
+        |client| {
+            client
+                .set_synthesis_voice(ClientScope::Current, "Amharic")?
+                .check_status(OK_VOICE_SET)?;
+            assert!(client
+                .set_synthesis_voice(ClientScope::Current, "Klingon")?
+                .check_status(OK_VOICE_SET)
+                .is_err());
+            assert_eq!(Some("Amharic"), client.synthesis_voice());
+            assert!(!client.probe_voice("Vulcan").unwrap());
+            assert_eq!(Some("Amharic"), client.synthesis_voice());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn try_receive() -> ClientResult<()> {
+    const COMMUNICATION: [(&str, &str); 2] = [
+        SET_CLIENT_COMMUNICATION,
+        (
+            "SET self NOTIFICATION all on\r\n",
+            "220 OK NOTIFICATION SET\r\n701-21\r\n701-test\r\n701 BEGIN\r\n",
+        ),
+    ];
+    let (addr, handle) = server::run_tcp(&COMMUNICATION)?;
+    let mut client = ssip_client_async::tcp::sync::Builder::new(addr)?
+        .nonblocking()
+        .build()?;
+    assert_eq!(None, client.try_receive()?);
+    client.set_client_name(ClientName::new("test", "test")?)?;
+    let response = loop {
+        match client.try_receive()? {
+            Some(response) => break response,
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    assert_eq!(Response::ClientNameSet, response);
+    client.set_notification(NotificationType::All, true)?;
+    while client.try_receive()?.is_none() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let event = loop {
+        match client.try_receive_event()? {
+            Some(event) => break event,
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    assert!(matches!(event.ntype, EventType::Begin));
+    handle.join().unwrap().unwrap();
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn try_receive_partial_answer() -> ClientResult<()> {
+    let (local, mut remote) = UnixStream::pair()?;
+    let local = std::sync::Mutex::new(Some(local));
+    let mut client = fifo::sync::Builder::new()
+        .with_stream(move || {
+            local
+                .lock()
+                .unwrap()
+                .take()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+        })
+        .nonblocking()
+        .build()?;
+    remote.write_all(b"250-espeak\r\n250-pi")?;
+    assert_eq!(None, client.try_receive()?);
+    remote.write_all(b"co\r\n250 OK MODULE LIST SENT\r\n701-21\r\n701-1\r\n")?;
+    assert_eq!(
+        Some(Response::OutputModulesListSent(vec![
+            String::from("espeak"),
+            String::from("pico")
+        ])),
+        client.try_receive()?
+    );
+    assert_eq!(None, client.try_receive_event()?);
+    remote.write_all(b"701 BEGIN\r\n")?;
+    assert_eq!(Some(Event::begin(21, 1)), client.try_receive_event()?);
+    assert_eq!(None, client.try_receive_event()?);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn queued_receive_partial_answer() -> ClientResult<()> {
+    let (local, mut remote) = UnixStream::pair()?;
+    let local = std::sync::Mutex::new(Some(local));
+    let mut client = QueuedClient::new(
+        fifo::sync::Builder::new()
+            .with_stream(move || {
+                local
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+            })
+            .nonblocking()
+            .build()?,
+    );
+    client.push(Request::GetRate);
+    assert!(client.send_next()?);
+    remote.write_all(b"251-1")?;
+    assert!(matches!(client.receive_next(), Err(ClientError::NotReady)));
+    remote.write_all(b"0\r\n251 OK GET RETURNED\r\n")?;
+    assert_eq!(Response::GetRate(10), client.receive_next()?);
+    Ok(())
+}
+
+#[test]
 fn receive_notification() -> ClientResult<()> {
     test_client!(
         &[
@@ -451,7 +757,104 @@ fn receive_notification() -> ClientResult<()> {
 }
 
 #[test]
-#[cfg(not(feature = "async-mio"))]
+fn skip_unknown_event() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET self NOTIFICATION all on\r\n",
+                "220 OK NOTIFICATION SET\r\n706-21\r\n706-test\r\n706 FUTURE\r\n702-21\r\n702-test\r\n702 END\r\n",
+            ),
+        ],
+        |client| {
+            let unknown = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            client.on_unknown_event({
+                let unknown = unknown.clone();
+                move |code, lines| unknown.lock().unwrap().push((code, lines.to_vec()))
+            });
+            client
+                .set_notification(NotificationType::All, true)
+                .unwrap()
+                .check_status(OK_NOTIFICATION_SET)
+                .unwrap();
+            let event = client.receive_event().unwrap();
+            assert!(matches!(event.ntype, EventType::End));
+            assert_eq!(
+                vec![(706, vec![String::from("21"), String::from("test")])],
+                *unknown.lock().unwrap()
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn lf_only_server() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET self NOTIFICATION all on\r\n",
+                "220 OK NOTIFICATION SET\n702-21\n702-test\n702 END\n",
+            ),
+            (
+                "LIST OUTPUT_MODULES\r\n",
+                "250-espeak-ng\n250-dummy\n250 OK MODULE LIST SENT\n",
+            ),
+        ],
+        |client| {
+            client
+                .set_notification(NotificationType::All, true)
+                .unwrap()
+                .check_status(OK_NOTIFICATION_SET)
+                .unwrap();
+            let event = client.receive_event().unwrap();
+            assert_eq!(Event::from_raw(EventType::End, "21", "test"), event);
+            assert_eq!(Some(21), event.message_id());
+            assert_eq!(None, event.client_id());
+            assert_eq!("test", event.raw().client);
+            let modules = client
+                .list_output_modules()
+                .unwrap()
+                .receive_lines(OK_OUTPUT_MODULES_LIST_SENT)
+                .unwrap();
+            assert_eq!(vec!["espeak-ng", "dummy"], modules);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn track_progress() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "<speak>one <mark name=\"progress-0\"/>two</speak>\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n700-21\r\n700-test\r\n700-progress-0\r\n700 INDEX MARK\r\n",
+            ),
+        ],
+        |client| {
+            let mut tracker = progress::ProgressTracker::new("one two", 1);
+            let id = client
+                .speak()
+                .unwrap()
+                .check_receiving_data()
+                .unwrap()
+                .send_line(tracker.ssml())
+                .unwrap()
+                .receive_message_id()
+                .unwrap();
+            tracker.set_message_id(id);
+            let event = client.receive_event().unwrap();
+            assert_eq!(Some(4), tracker.update(&event).map(|p| p.offset));
+            Ok(())
+        }
+    )
+}
+
+#[test]
 fn history_clients_list() -> ClientResult<()> {
     test_client!(
         &[
@@ -476,6 +879,52 @@ fn history_clients_list() -> ClientResult<()> {
     )
 }
 
+#[test]
+fn multiple_clients() -> ClientResult<()> {
+    const SCRIPTS: &[&[(&str, &str)]] = &[
+        &[
+            (
+                "SET self CLIENT_NAME joe:speechd_client:main\r\n",
+                "208 OK CLIENT NAME SET\r\n",
+            ),
+            ("QUIT\r\n", "231 HAPPY HACKING\r\n"),
+        ],
+        &[
+            (
+                "SET self CLIENT_NAME joe:speechd_client:status\r\n",
+                "208 OK CLIENT NAME SET\r\n",
+            ),
+            (
+                "HISTORY GET CLIENT_LIST\r\n",
+                "240-0 joe:speechd_client:main 1\r\n240-1 joe:speechd_client:status 1\r\n240 OK CLIENTS LIST SENT\r\n",
+            ),
+        ],
+    ];
+    let (addr, handle) = server::run_tcp_with_scripts(SCRIPTS)?;
+    let mut main = ssip_client_async::tcp::sync::Builder::new(addr)?.build()?;
+    let mut status = ssip_client_async::tcp::sync::Builder::new(addr)?.build()?;
+    status
+        .set_client_name(ClientName::with_component(
+            "joe",
+            "speechd_client",
+            "status",
+        )?)?
+        .check_client_name_set()?;
+    main.set_client_name(ClientName::new("joe", "speechd_client")?)?
+        .check_client_name_set()?;
+    let statuses = status.history_get_clients()?.receive_history_clients()?;
+    assert_eq!(
+        vec![
+            HistoryClientStatus::new(0, "joe:speechd_client:main", true),
+            HistoryClientStatus::new(1, "joe:speechd_client:status", true),
+        ],
+        statuses
+    );
+    main.quit()?.check_status(OK_BYE)?;
+    handle.join().unwrap().unwrap();
+    Ok(())
+}
+
 test_getter!(
     history_get_client_id,
     receive_client_id,
@@ -512,3 +961,1337 @@ test_getter!(
     "243-42\r\n243 OK CURSOR POSITION RETURNED\r\n",
     42
 );
+
+#[test]
+fn history_cursor() -> ClientResult<()> {
+    test_client!(
+        &conversation! {
+            send "SET self CLIENT_NAME test:test:main"; reply 208 "OK CLIENT NAME SET";
+            send "HISTORY CURSOR SET self last"; reply 221 "OK CURSOR SET LAST";
+            send "HISTORY CURSOR GET"; reply 243 ["42"] "OK CURSOR POSITION RETURNED";
+            send "HISTORY CURSOR backward"; reply 224 "OK CURSOR MOVED BACKWARD";
+            send "HISTORY CURSOR GET"; reply 243 ["41"] "OK CURSOR POSITION RETURNED";
+            send "HISTORY CURSOR SET self first"; reply 220 "OK CURSOR SET FIRST";
+            send "HISTORY CURSOR forward"; reply 223 "OK CURSOR MOVED FORWARD";
+            send "HISTORY CURSOR SET self pos 7"; reply 222 "OK CURSOR SET TO POSITION";
+        },
+        |client| {
+            let mut cursor = client.history_cursor(ClientScope::Current);
+            assert_eq!(
+                CursorPosition(42),
+                cursor.last().unwrap().current_message().unwrap()
+            );
+            assert_eq!(
+                CursorPosition(41),
+                cursor.move_backward().unwrap().current_message().unwrap()
+            );
+            cursor
+                .first()
+                .unwrap()
+                .move_forward()
+                .unwrap()
+                .set_position(7)
+                .unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn session_template() -> ClientResult<()> {
+    const COMMUNICATION: [(&str, &str); 4] = [
+        SET_CLIENT_COMMUNICATION,
+        ("SET self RATE 20\r\n", "203 OK RATE SET\r\n"),
+        ("SET self SSML_MODE on\r\n", "219 OK SSML MODE SET\r\n"),
+        (
+            "SET self NOTIFICATION all on\r\n",
+            "220 OK NOTIFICATION SET\r\n",
+        ),
+    ];
+    let (addr, handle) = server::run_tcp(&COMMUNICATION)?;
+    let mut session = SessionTemplate::new(ClientName::new("test", "test")?);
+    session
+        .rate(20)
+        .ssml_mode(true)
+        .notification(NotificationType::All, true);
+    let _client = ssip_client_async::tcp::sync::Builder::new(addr)?
+        .nonblocking()
+        .session(&session)
+        .build()?;
+    handle.join().unwrap().unwrap();
+    Ok(())
+}
+#[test]
+fn session_template_with_events() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SET self NOTIFICATION begin on\r\n",
+                "220 OK NOTIFICATION SET\r\n",
+            ),
+            (
+                "SET self NOTIFICATION end on\r\n",
+                "701-21\r\n701-1\r\n701 BEGIN\r\n220 OK NOTIFICATION SET\r\n",
+            ),
+        ],
+        |client| {
+            let mut session = SessionTemplate::new(ClientName::new("test", "test")?);
+            session
+                .notification(NotificationType::Begin, true)
+                .notification(NotificationType::End, true);
+            client.apply_session(&session).unwrap();
+            assert_eq!(Event::begin(21, 1), client.receive_event().unwrap());
+            Ok(())
+        }
+    )
+}
+
+const EMERGENCY_STOP_COMMUNICATION: [(&str, &str); 2] = [
+    ("CANCEL all\r\n", "213 OK CANCELED\r\n"),
+    ("STOP all\r\n", "210 OK STOPPED\r\n"),
+];
+
+#[test]
+fn emergency_stop() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            EMERGENCY_STOP_COMMUNICATION[0],
+            EMERGENCY_STOP_COMMUNICATION[1],
+        ],
+        |client| {
+            client.emergency_stop().unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn reject_request_out_of_sequence() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello, world\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+            ("QUIT\r\n", "231 HAPPY HACKING\r\n"),
+        ],
+        |client| {
+            assert_eq!(ConnectionState::Ready, client.state());
+            assert!(matches!(
+                client.send_line("Hello, world"),
+                Err(ClientError::InvalidState(ConnectionState::Ready))
+            ));
+            client.speak().unwrap().check_receiving_data().unwrap();
+            assert_eq!(ConnectionState::Speaking, client.state());
+            assert!(matches!(
+                client.set_rate(ClientScope::Current, 10),
+                Err(ClientError::InvalidState(ConnectionState::Speaking))
+            ));
+            client
+                .send_line("Hello, world")
+                .unwrap()
+                .receive_message_id()
+                .unwrap();
+            client.quit().unwrap().receive().unwrap();
+            assert_eq!(ConnectionState::Closed, client.state());
+            assert!(matches!(client.speak(), Err(ClientError::Closed)));
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn sanitize_text() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello, world\r\n..\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+        ],
+        |client| {
+            client.set_text_sanitizer(sanitize::TextSanitizer::default());
+            client
+                .speak()
+                .unwrap()
+                .check_receiving_data()
+                .unwrap()
+                .send_lines(&[
+                    "\u{feff}Hello,\u{200b} world".to_string(),
+                    "\u{7}.".to_string(),
+                ])
+                .unwrap()
+                .receive_message_id()
+                .unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn receive_optional_message_id() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello, world\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("Hello, world\r\n.\r\n", "225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("Hello, world\r\n.\r\n", "225 OK MESSAGE QUEUED\r\n"),
+        ],
+        |client| {
+            for expected in [Some(21), None] {
+                let id = client
+                    .speak()
+                    .unwrap()
+                    .check_receiving_data()
+                    .unwrap()
+                    .send_line("Hello, world")
+                    .unwrap()
+                    .receive_optional_message_id()
+                    .unwrap();
+                assert_eq!(expected, id);
+            }
+            assert!(matches!(say_hello(client), Err(ClientError::TooFewLines)));
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn receive_typed_values() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("GET RATE\r\n", "251-0\r\n251 OK GET RETURNED\r\n"),
+            ("GET PITCH\r\n", "251--20\r\n251 OK GET RETURNED\r\n"),
+            ("GET VOLUME\r\n", "251-100\r\n251 OK GET RETURNED\r\n"),
+            (
+                "GET OUTPUT_MODULE\r\n",
+                "251-espeak-ng\r\n251 OK GET RETURNED\r\n",
+            ),
+        ],
+        |client| {
+            assert_eq!(
+                Response::GetRate(0),
+                client.get_rate().unwrap().receive().unwrap()
+            );
+            assert_eq!(
+                Response::GetPitch(-20),
+                client.get_pitch().unwrap().receive().unwrap()
+            );
+            assert_eq!(
+                Response::GetVolume(100),
+                client.get_volume().unwrap().receive().unwrap()
+            );
+            assert_eq!(
+                Response::Get(String::from("espeak-ng")),
+                client.get_output_module().unwrap().receive().unwrap()
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+#[cfg(feature = "compat")]
+fn speechd_compat() -> ClientResult<()> {
+    use ssip_client_async::compat::speechd::*;
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self RATE 20\r\n", "203 OK RATE SET\r\n"),
+            ("SET self PRIORITY important\r\n", "202 OK PRIORITY SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello, world\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+            ("STOP 21\r\n", "210 OK STOPPED\r\n"),
+            ("GET RATE\r\n", "251-20\r\n251 OK GET RETURNED\r\n"),
+            ("CANCEL all\r\n", "213 OK CANCELED\r\n"),
+        ],
+        |client| {
+            spd_set_voice_rate(client, 20).unwrap();
+            let id = spd_say(client, Priority::Important, "Hello, world").unwrap();
+            assert_eq!(21, id);
+            spd_stop_uid(client, id).unwrap();
+            assert_eq!(20, spd_get_voice_rate(client).unwrap());
+            spd_cancel_all(client).unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn say_with_overrides() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("GET LANGUAGE\r\n", "251-en\r\n251 OK GET RETURNED\r\n"),
+            ("GET RATE\r\n", "251--10\r\n251 OK GET RETURNED\r\n"),
+            ("SET self LANGUAGE fr\r\n", "201 OK LANGUAGE SET\r\n"),
+            ("SET self RATE 30\r\n", "203 OK RATE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("Bonjour\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SET self LANGUAGE en\r\n", "201 OK LANGUAGE SET\r\n"),
+            ("SET self RATE -10\r\n", "203 OK RATE SET\r\n"),
+        ],
+        |client| {
+            let overrides = Overrides::default()
+                .with_language("fr".parse().unwrap())
+                .with_rate(30);
+            assert_eq!(21, client.say_with("Bonjour", &overrides).unwrap());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn say_with_rejected() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "GET RATE\r\n",
+                "702-20\r\n702-1\r\n702 END\r\n251--10\r\n251 OK GET RETURNED\r\n",
+            ),
+            ("SET self RATE 30\r\n", "203 OK RATE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("Bonjour\r\n.\r\n", "409 ERR MESSAGE TOO LONG\r\n"),
+            ("SET self RATE -10\r\n", "203 OK RATE SET\r\n"),
+        ],
+        |client| {
+            let overrides = Overrides::default().with_rate(30);
+            match client.say_with("Bonjour", &overrides) {
+                Err(ClientError::Ssip(status)) => assert_eq!(409, status.code),
+                result => panic!("expecting SSIP error: {:?}", result),
+            }
+            assert_eq!(Event::end(20, 1), client.receive_event().unwrap());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn event_buffer_overflow() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello\r\n.\r\n",
+                "701-20\r\n701-1\r\n701 BEGIN\r\n702-20\r\n702-1\r\n702 END\r\n225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "World\r\n.\r\n",
+                "701-21\r\n701-1\r\n701 BEGIN\r\n702-21\r\n702-1\r\n702 END\r\n225-22\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+        ],
+        |client| {
+            client.set_event_buffer(1, demux::OverflowPolicy::DropOldest);
+            assert_eq!(21, client.say_with("Hello", &Overrides::default()).unwrap());
+            assert_eq!(1, client.event_overflows());
+            assert_eq!(Event::end(20, 1), client.receive_event().unwrap());
+            client.set_event_buffer(1, demux::OverflowPolicy::Error);
+            assert_eq!(22, client.say_with("World", &Overrides::default()).unwrap());
+            assert_eq!(2, client.event_overflows());
+            assert!(client.receive_event().is_err());
+            assert_eq!(Event::begin(21, 1), client.receive_event().unwrap());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn interrupt_say() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "CANCEL self\r\nSET self PRIORITY important\r\nSPEAK\r\n",
+                "213 OK CANCELED\r\n202 OK PRIORITY SET\r\n230 OK RECEIVING DATA\r\n",
+            ),
+            (
+                "Low battery\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+            ("SET self PRIORITY text\r\n", "202 OK PRIORITY SET\r\n"),
+        ],
+        |client| {
+            assert_eq!(21, client.interrupt_say("Low battery").unwrap());
+            assert_eq!(ConnectionState::Ready, client.state());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn interrupt_say_cancel_rejected() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "CANCEL self\r\nSET self PRIORITY important\r\nSPEAK\r\n",
+                "401 ERR CANCEL FAILED\r\n202 OK PRIORITY SET\r\n230 OK RECEIVING DATA\r\n",
+            ),
+            (".\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SET self PRIORITY text\r\n", "202 OK PRIORITY SET\r\n"),
+        ],
+        |client| {
+            match client.interrupt_say("Low battery") {
+                Err(ClientError::Ssip(status)) => assert_eq!(401, status.code),
+                result => panic!("expecting SSIP error: {:?}", result),
+            }
+            assert_eq!(ConnectionState::Ready, client.state());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn interrupt_say_text_rejected() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "CANCEL self\r\nSET self PRIORITY important\r\nSPEAK\r\n",
+                "213 OK CANCELED\r\n202 OK PRIORITY SET\r\n230 OK RECEIVING DATA\r\n",
+            ),
+            ("Low battery\r\n.\r\n", "409 ERR MESSAGE TOO LONG\r\n"),
+            ("SET self PRIORITY text\r\n", "202 OK PRIORITY SET\r\n"),
+        ],
+        |client| {
+            match client.interrupt_say("Low battery") {
+                Err(ClientError::Ssip(status)) => assert_eq!(409, status.code),
+                result => panic!("expecting SSIP error: {:?}", result),
+            }
+            assert_eq!(ConnectionState::Ready, client.state());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn events_during_transaction() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            (
+                "SPEAK\r\n",
+                "702-20\r\n702-1\r\n702 END\r\n230 OK RECEIVING DATA\r\n",
+            ),
+            (
+                "hello\r\n.\r\n",
+                "701-21\r\n701-1\r\n701 BEGIN\r\n225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+            (
+                "STOP self\r\n",
+                "703-21\r\n703-1\r\n703 CANCELED\r\n210 OK STOPPED\r\n"
+            ),
+        ],
+        |client| {
+            let msg_id = client
+                .speak()?
+                .check_receiving_data()?
+                .send_line("hello")?
+                .receive_message_id()?;
+            assert_eq!(21, msg_id);
+            client.stop(MessageScope::Last)?.check_status(OK_STOPPED)?;
+            assert_eq!(Event::end(20, 1), client.receive_event()?);
+            assert_eq!(Event::begin(21, 1), client.receive_event()?);
+            assert_eq!(Event::cancel(21, 1), client.receive_event()?);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn pitch_range_depends_on_protocol_version() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self PITCH_RANGE 30\r\n", "263 OK PITCH RANGE SET\r\n"),
+        ],
+        |client| {
+            client.set_protocol_version(ProtocolVersion::new(0, 8));
+            assert!(matches!(
+                client.set_pitch_range(ClientScope::Current, 30),
+                Err(ClientError::Unsupported("SetPitchRange", _))
+            ));
+            client.set_protocol_version(ProtocolVersion::default());
+            assert_eq!(
+                Response::PitchRangeSet,
+                client
+                    .set_pitch_range(ClientScope::Current, 30)?
+                    .receive()?
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn speak_auto() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self SSML_MODE on\r\n", "219 OK SSML MODE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "<speak>Hello</speak>\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+            ("SET self SSML_MODE off\r\n", "219 OK SSML MODE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Fish & chips\r\n.\r\n",
+                "225-22\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+            ("SET self SSML_MODE on\r\n", "219 OK SSML MODE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "<speak>Fish &amp; chips</speak>\r\n.\r\n",
+                "225-23\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+        ],
+        |client| {
+            assert_eq!(21, client.speak_auto("<speak>Hello</speak>")?);
+            assert!(!client.ssml_mode());
+            assert_eq!(22, client.speak_auto("Fish & chips")?);
+            client.set_ssml_mode(true)?.check_status(OK_SSML_MODE_SET)?;
+            assert_eq!(23, client.speak_auto("Fish & chips")?);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn ssml_mode_rejected() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self SSML_MODE on\r\n", "409 ERR SSML MODE NOT SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Fish & chips\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+        ],
+        |client| {
+            assert!(client
+                .set_ssml_mode(true)?
+                .check_status(OK_SSML_MODE_SET)
+                .is_err());
+            assert!(!client.ssml_mode());
+            assert_eq!(21, client.speak_auto("Fish & chips")?);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn send_partial_lines() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "first\r\nsecond\r\nthird\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+        ],
+        |client| {
+            client.speak()?.check_receiving_data()?;
+            client.send_partial_lines(&["first".to_string(), "second".to_string()])?;
+            assert_eq!(
+                21,
+                client
+                    .send_lines(&["third".to_string()])?
+                    .receive_message_id()?
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn say_chunked() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "One sentence. \r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Another one.\r\n.\r\n",
+                "225-22\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+        ],
+        |client| {
+            client.set_max_message_bytes(Some(16));
+            assert_eq!(
+                vec![21, 22],
+                client.say_chunked("One sentence. Another one.")?
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn switch_voice_when_idle() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello, world\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n701-21\r\n701-1\r\n701 BEGIN\r\n702-21\r\n702-1\r\n702 END\r\n"
+            ),
+            ("SET self SYNTHESIS_VOICE bob\r\n", "209 OK VOICE SET\r\n"),
+        ],
+        |client| {
+            let mut tracker = tracker::MessageTracker::new();
+            let msg_id = client.speak()?.check_receiving_data()?.send_line("Hello, world")?.receive_message_id()?;
+            tracker.queued(msg_id);
+            let events = tracker.switch_voice_when_idle(client, "bob")?;
+            assert_eq!(vec![Event::begin(21, 1), Event::end(21, 1)], events);
+            assert!(tracker.queue_state().is_idle());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn resubmit_interrupted() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("one\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("two\r\n.\r\n", "225-22\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("two\r\n.\r\n", "225-31\r\n225 OK MESSAGE QUEUED\r\n"),
+        ],
+        |client| {
+            let mut tracker = tracker::MessageTracker::new();
+            tracker.set_resubmit_policy(tracker::ResubmitPolicy::ResubmitIfNotStarted);
+            assert_eq!(21, tracker.say(client, "one")?);
+            assert_eq!(22, tracker.say(client, "two")?);
+            tracker.update(&Event::begin(21, 1));
+            assert_eq!(vec![31], tracker.resubmit(client)?);
+            assert_eq!(
+                vec![(31, tracker::MessageState::Queued)],
+                tracker.queue_state().messages
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn resubmit_failure() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("one\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("two\r\n.\r\n", "225-22\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("one\r\n.\r\n", "225-31\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "401 ERR MESSAGE TOO LONG\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("one\r\n.\r\n", "225-41\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("two\r\n.\r\n", "225-42\r\n225 OK MESSAGE QUEUED\r\n"),
+        ],
+        |client| {
+            let mut tracker = tracker::MessageTracker::new();
+            tracker.set_resubmit_policy(tracker::ResubmitPolicy::Resubmit);
+            assert_eq!(21, tracker.say(client, "one")?);
+            assert_eq!(22, tracker.say(client, "two")?);
+            assert!(tracker.resubmit(client).is_err());
+            assert_eq!(
+                vec![
+                    (31, tracker::MessageState::Queued),
+                    (22, tracker::MessageState::Queued)
+                ],
+                tracker.queue_state().messages
+            );
+            assert_eq!(vec![41, 42], tracker.resubmit(client)?);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn say_guarded() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("one\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            ("CANCEL 21\r\n", "213 OK CANCELED\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "two\r\n.\r\n",
+                "225-22\r\n225 OK MESSAGE QUEUED\r\n701-22\r\n701-1\r\n701 BEGIN\r\n702-22\r\n702-1\r\n702 END\r\n",
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("three\r\n.\r\n", "225-23\r\n225 OK MESSAGE QUEUED\r\n"),
+        ],
+        |client| {
+            let guard = client.say_guarded("one")?;
+            assert_eq!(21, guard.message_id());
+            drop(guard);
+            let events = client.say_guarded("two")?.wait()?;
+            assert_eq!(vec![Event::begin(22, 1), Event::end(22, 1)], events);
+            assert_eq!(23, client.say_guarded("three")?.release());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn strict_replies() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self RATE 15\r\n", "203 OK RATE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "hello\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n701-21\r\n701-1\r\n701 BEGIN\r\n",
+            ),
+            ("GET RATE\r\n", "251-15\r\n251 OK GET RETURNED\r\n"),
+        ],
+        |client| {
+            client.set_strict_replies(true);
+            client
+                .set_rate(ClientScope::Current, 15)?
+                .check_status(OK_RATE_SET)?;
+            client
+                .speak()?
+                .check_receiving_data()?
+                .send_line("hello")?
+                .receive_message_id()?;
+            assert_eq!(15, client.get_rate()?.receive_i8()?);
+            assert_eq!(Event::begin(21, 1), client.receive_event()?);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn try_enqueue() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "one\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n701-21\r\n701-1\r\n701 BEGIN\r\n702-21\r\n702-1\r\n702 END\r\n",
+            ),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("two\r\n.\r\n", "225-22\r\n225 OK MESSAGE QUEUED\r\n"),
+        ],
+        |client| {
+            let mut tracker = tracker::MessageTracker::new();
+            tracker.set_max_depth(Some(1));
+            assert_eq!(21, tracker.try_enqueue(client, "one")?);
+            assert!(matches!(
+                tracker.try_enqueue(client, "two"),
+                Err(ClientError::NotReady)
+            ));
+            let events = tracker.wait_ready(client)?;
+            assert_eq!(vec![Event::begin(21, 1), Event::end(21, 1)], events);
+            assert_eq!(Some(1), tracker.capacity());
+            assert_eq!(22, tracker.try_enqueue(client, "two")?);
+            Ok(())
+        }
+    )
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn say_bytes() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            ("Dobrý den\r\n.\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+        ],
+        |client| {
+            let latin2 = encoding::Encoding::for_label(b"iso-8859-2").unwrap();
+            assert_eq!(21, client.say_bytes(b"Dobr\xfd den", latin2)?);
+            let shift_jis = encoding::Encoding::for_label(b"shift_jis").unwrap();
+            match client.say_bytes(b"ab\x82", shift_jis) {
+                Err(ClientError::Io(err)) => {
+                    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+                    assert_eq!("1 invalid Shift_JIS bytes at offset 2", err.to_string());
+                }
+                result => panic!("unexpected result: {:?}", result),
+            }
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn stats() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self RATE 100\r\n", "409 ERR RATE TOO HIGH\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "hello\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n701-21\r\n701-1\r\n701 BEGIN\r\n"
+            ),
+        ],
+        |client| {
+            assert_eq!(Some(&1), client.stats().requests.get("SetName"));
+            client.reset_stats();
+            assert_eq!(0, client.stats().request_count());
+            assert!(client
+                .set_rate(ClientScope::Current, 100)?
+                .check_status(OK_RATE_SET)
+                .is_err());
+            client
+                .speak()?
+                .check_receiving_data()?
+                .send_line("hello")?
+                .receive_message_id()?;
+            client.receive_event()?;
+            let stats = client.stats();
+            assert_eq!(3, stats.request_count());
+            assert_eq!(Some(&1), stats.requests.get("SendLine"));
+            assert_eq!(Some(&1), stats.errors.get(&409));
+            assert_eq!(Some(&1), stats.events.get("begin"));
+            assert_eq!(
+                ("SET self RATE 100\r\n".len() + "SPEAK\r\n".len() + "hello\r\n.\r\n".len()) as u64,
+                stats.bytes_sent
+            );
+            assert_eq!(
+                ("409 ERR RATE TOO HIGH\r\n".len()
+                    + "230 OK RECEIVING DATA\r\n".len()
+                    + "225-21\r\n225 OK MESSAGE QUEUED\r\n701-21\r\n701-1\r\n701 BEGIN\r\n".len())
+                    as u64,
+                stats.bytes_received
+            );
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn invalid_scope_is_not_sent() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("STOP 21\r\n", "210 OK STOPPED\r\n"),
+        ],
+        |client| {
+            assert!(matches!(
+                client.stop(MessageScope::new_unchecked(0)),
+                Err(ClientError::Scope(ScopeError::InvalidMessageId(0)))
+            ));
+            assert!(matches!(
+                client.set_rate(ClientScope::new_unchecked(0), 10),
+                Err(ClientError::Scope(ScopeError::InvalidClientId(0)))
+            ));
+            client
+                .stop(MessageScope::message(21).unwrap())?
+                .check_status(OK_STOPPED)?;
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn connection_label() -> ClientResult<()> {
+    const COMMUNICATION: [(&str, &str); 1] = [SET_CLIENT_COMMUNICATION];
+    let (addr, handle) = server::run_tcp(&COMMUNICATION)?;
+    let mut client = ssip_client_async::tcp::sync::Builder::new(addr)?
+        .label("progress")
+        .build()?;
+    assert_eq!(Some("progress"), client.label());
+    client
+        .set_client_name(ClientName::new("test", "test")?)?
+        .check_client_name_set()?;
+    handle.join().unwrap().unwrap();
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn custom_stream() -> ClientResult<()> {
+    const COMMUNICATION: [(&str, &str); 1] = [SET_CLIENT_COMMUNICATION];
+    let socket_dir = tempfile::tempdir()?;
+    let socket_path = socket_dir.path().join("custom_stream.socket");
+    let handle = server::run_unix(&socket_path, &COMMUNICATION)?;
+    let mut client = ssip_client_async::fifo::sync::Builder::new()
+        .path("/nonexistent/speechd.sock")
+        .with_stream({
+            let socket_path = socket_path.clone();
+            move || UnixStream::connect(&socket_path)
+        })
+        .build()?;
+    client
+        .set_client_name(ClientName::new("test", "test")?)?
+        .check_client_name_set()?;
+    handle.join().unwrap().unwrap();
+    socket_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn control_message_by_id() -> ClientResult<()> {
+    test_client!(
+        &conversation! {
+            send "SET self CLIENT_NAME test:test:main"; reply 208 "OK CLIENT NAME SET";
+            send "SPEAK"; reply 230 "OK RECEIVING DATA";
+            send "Hello, world", "."; reply 225 ["21"] "OK MESSAGE QUEUED";
+            send "PAUSE 21"; reply 211 "OK PAUSED";
+            send "RESUME 21"; reply 212 "OK RESUMED";
+            send "STOP 21"; reply 210 "OK STOPPED";
+            send "CANCEL 21"; reply 213 "OK CANCELED";
+        },
+        |client| {
+            let id = say_hello(client).unwrap();
+            client
+                .pause_message(id)
+                .unwrap()
+                .resume_message(id)
+                .unwrap()
+                .stop_message(id)
+                .unwrap()
+                .cancel_message(id)
+                .unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn emergency_stop_receiving_data() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (".\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            EMERGENCY_STOP_COMMUNICATION[0],
+            EMERGENCY_STOP_COMMUNICATION[1],
+        ],
+        |client| {
+            client
+                .speak()
+                .unwrap()
+                .check_receiving_data()
+                .unwrap()
+                .emergency_stop()
+                .unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn emergency_stop_speak_pending() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (".\r\n", "225-21\r\n225 OK MESSAGE QUEUED\r\n"),
+            EMERGENCY_STOP_COMMUNICATION[0],
+            EMERGENCY_STOP_COMMUNICATION[1],
+        ],
+        |client| {
+            client.speak().unwrap().emergency_stop().unwrap();
+            assert_eq!(ConnectionState::Ready, client.state());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn emergency_stop_speak_refused() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "401 ERR MESSAGE TOO LONG\r\n"),
+            EMERGENCY_STOP_COMMUNICATION[0],
+            EMERGENCY_STOP_COMMUNICATION[1],
+        ],
+        |client| {
+            client.speak().unwrap().emergency_stop().unwrap();
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn queued_quit_is_terminal() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self RATE 10\r\n", "203 OK RATE SET\r\n"),
+            ("QUIT\r\n", "231 HAPPY HACKING\r\n"),
+        ],
+        |client| {
+            let mut client = QueuedClient::new(client.try_clone()?);
+            client.push(Request::SetRate(ClientScope::Current, 10));
+            client.push(Request::Quit);
+            client.push(Request::Speak);
+            assert!(client.send_next().unwrap());
+            assert_eq!(Response::RateSet, client.receive_next().unwrap());
+            assert!(!client.is_closed());
+            assert!(client.send_next().unwrap());
+            assert!(client.is_closed());
+            assert_eq!(Response::Bye, client.receive_next().unwrap());
+            assert!(matches!(client.send_next(), Err(ClientError::Closed)));
+            assert!(!client.has_next());
+            assert!(!client.send_next().unwrap());
+            client.push(Request::Stop(MessageScope::All));
+            assert!(matches!(client.send_next(), Err(ClientError::Closed)));
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn rate_limited_push_resets_priority() -> ClientResult<()> {
+    test_client!(&[SET_CLIENT_COMMUNICATION], |client| {
+        let mut client = QueuedClient::new(client.try_clone()?);
+        let mut limiter = ratelimit::RateLimiter::new();
+        assert!(limiter.push_message(&mut client, Message::new("10%", Priority::Progress)));
+        assert_eq!(Some(Request::SetPriority(Priority::Text)), client.pop());
+        assert_eq!(
+            Some(Request::SendLines(vec![String::from("10%")])),
+            client.pop()
+        );
+        assert_eq!(Some(Request::Speak), client.pop());
+        assert_eq!(Some(Request::SetPriority(Priority::Progress)), client.pop());
+        assert!(limiter.push_message(&mut client, Message::new("Hello", Priority::Text)));
+        assert!(matches!(client.pop(), Some(Request::SendLines(_))));
+        Ok(())
+    })
+}
+
+#[test]
+fn last_status() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Hello\r\n.\r\n",
+                "701-21\r\n701-1\r\n701 BEGIN\r\n225-21\r\n225 OK MESSAGE QUEUED\r\n"
+            ),
+            ("SET self RATE 100\r\n", "409 ERR RATE TOO HIGH\r\n"),
+        ],
+        |client| {
+            assert_eq!(
+                Some("CLIENT NAME SET"),
+                client.last_status().map(|status| status.message.as_str())
+            );
+            client
+                .speak()?
+                .check_receiving_data()?
+                .send_line("Hello")?
+                .receive_message_id()?;
+            let status = client.last_status().unwrap();
+            assert_eq!(OK_MESSAGE_QUEUED, status.code);
+            assert_eq!("MESSAGE QUEUED", status.message);
+            assert!(client
+                .set_rate(ClientScope::Current, 101)?
+                .receive()
+                .is_err());
+            assert_eq!(409, client.last_status().unwrap().code);
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn queued_client_handles() -> ClientResult<()> {
+    test_client!(&[SET_CLIENT_COMMUNICATION], |client| {
+        let client = QueuedClient::new(client.try_clone()?);
+        assert_eq!(client.input_source().raw_handle(), client.input_handle());
+        assert_eq!(client.output_source().raw_handle(), client.output_handle());
+        assert_ne!(client.input_handle(), client.output_handle());
+        Ok(())
+    })
+}
+
+#[test]
+fn history_settings() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("HISTORY SORT desc time\r\n", "218 OK SORTED\r\n"),
+            (
+                "HISTORY SET SHORT_MESSAGE_LENGTH 15\r\n",
+                "218 OK SHORT MESSAGE LENGTH SET\r\n"
+            ),
+            (
+                "HISTORY CURSOR GET\r\n",
+                "243-7\r\n243 OK CURSOR POSITION RETURNED\r\n"
+            ),
+        ],
+        |client| {
+            assert_eq!(HistorySettings::default(), *client.history_settings());
+            client
+                .history_sort(SortDirection::Descending, SortKey::Time)
+                .unwrap()
+                .check_status(218)
+                .unwrap()
+                .history_set_short_message_length(15)
+                .unwrap()
+                .check_status(218)
+                .unwrap();
+            let settings = client.history_settings();
+            assert_eq!(
+                Some((SortDirection::Descending, SortKey::Time)),
+                settings.sort
+            );
+            assert_eq!(Some(15), settings.short_message_length);
+            assert_eq!(None, settings.message_type_ordering);
+            let pos = client
+                .history_get_cursor()
+                .unwrap()
+                .receive_cursor_position()
+                .unwrap();
+            assert_eq!(CursorPosition(7), pos);
+            Ok(())
+        }
+    )
+}
+const SAY_HELLO_COMMUNICATION: [(&str, &str); 3] = [
+    SET_CLIENT_COMMUNICATION,
+    ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+    (
+        "Hello, world\r\n.\r\n",
+        "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+    ),
+];
+const SPEAK_COMMUNICATION: [(&str, &str); 2] = [
+    SET_CLIENT_COMMUNICATION,
+    ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+];
+fn say_hello<S: Read + Write + Source>(client: &mut Client<S>) -> ClientResult<MessageId> {
+    client
+        .speak()?
+        .check_receiving_data()?
+        .send_line("Hello, world")?
+        .receive_message_id()
+}
+
+#[test]
+fn answers_split_in_chunks() -> ClientResult<()> {
+    let faults = Faults {
+        chunk_size: Some(3),
+        ..Default::default()
+    };
+    test_tcp_client_with_faults(&SAY_HELLO_COMMUNICATION, Some(faults), |client| {
+        assert_eq!(21, say_hello(client).unwrap());
+        Ok(())
+    })
+}
+
+#[test]
+#[cfg(unix)]
+fn delayed_answers() -> ClientResult<()> {
+    let faults = Faults {
+        delay: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    test_unix_client_with_faults(&SAY_HELLO_COMMUNICATION, Some(faults), |client| {
+        assert_eq!(21, say_hello(client).unwrap());
+        Ok(())
+    })
+}
+
+#[test]
+#[cfg(unix)]
+fn read_timeout_on_slow_server() -> ClientResult<()> {
+    let socket_dir = tempfile::tempdir()?;
+    let socket_path = socket_dir.path().join("test_client.socket");
+    let faults = Faults {
+        delay: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let handle = server::run_unix_with_faults(&socket_path, &[SET_CLIENT_COMMUNICATION], faults)?;
+    let mut client = ssip_client_async::fifo::sync::Builder::new()
+        .path(&socket_path)
+        .timeout(Duration::from_millis(20))
+        .build()?;
+    let result = client
+        .set_client_name(ClientName::new("test", "test")?)?
+        .check_client_name_set();
+    match result {
+        Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => (),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("timeout not detected"),
+    }
+    handle.join().unwrap().unwrap();
+    socket_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn slow_server_diagnostic() -> ClientResult<()> {
+    use ssip_client_async::diagnostics::{Diagnostic, TimeBudgets};
+    use std::sync::{Arc, Mutex};
+    let faults = Faults {
+        delay: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let (addr, handle) = server::run_tcp_with_faults(&SAY_HELLO_COMMUNICATION, faults)?;
+    let mut client = ssip_client_async::tcp::sync::Builder::new(addr)?.build()?;
+    let diagnostics = Arc::new(Mutex::new(Vec::new()));
+    let mut budgets = TimeBudgets::new(Duration::from_secs(60));
+    budgets.set("Speak", Duration::from_millis(10));
+    client.set_time_budgets(budgets).set_diagnostics({
+        let diagnostics = diagnostics.clone();
+        move |diagnostic: &Diagnostic| diagnostics.lock().unwrap().push(diagnostic.clone())
+    });
+    client
+        .set_client_name(ClientName::new("test", "test")?)?
+        .check_client_name_set()?;
+    assert_eq!(21, say_hello(&mut client)?);
+    handle.join().unwrap().unwrap();
+    let diagnostics = diagnostics.lock().unwrap();
+    assert_eq!(1, diagnostics.len());
+    match &diagnostics[0] {
+        Diagnostic::SlowServer {
+            request, elapsed, ..
+        } => {
+            assert_eq!("Speak", *request);
+            assert!(*elapsed >= Duration::from_millis(50));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn garbage_line_before_answer() -> ClientResult<()> {
+    let faults = Faults {
+        garbage: Some((1, "garbage\r\n")),
+        ..Default::default()
+    };
+    test_tcp_client_with_faults(&SPEAK_COMMUNICATION, Some(faults), |client| {
+        match client.speak().unwrap().check_receiving_data() {
+            Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::InvalidInput => Ok(()),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("garbage line not detected"),
+        }
+    })
+}
+
+#[test]
+fn premature_disconnect() -> ClientResult<()> {
+    let faults = Faults {
+        disconnect_at: Some(1),
+        ..Default::default()
+    };
+    test_tcp_client_with_faults(&SPEAK_COMMUNICATION, Some(faults), |client| {
+        client.speak().unwrap();
+        match client.check_receiving_data() {
+            Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("disconnection not detected"),
+        }
+    })
+}
+
+#[test]
+fn send_message() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self PRIORITY important\r\n", "202 OK PRIORITY SET\r\n"),
+            ("GET LANGUAGE\r\n", "251-en\r\n251 OK GET RETURNED\r\n"),
+            ("SET self LANGUAGE fr\r\n", "201 OK LANGUAGE SET\r\n"),
+            ("SPEAK\r\n", "230 OK RECEIVING DATA\r\n"),
+            (
+                "Bonjour\r\n..\r\nmonde\r\n.\r\n",
+                "225-21\r\n225 OK MESSAGE QUEUED\r\n",
+            ),
+            ("SET self LANGUAGE en\r\n", "201 OK LANGUAGE SET\r\n"),
+            ("SET self PRIORITY text\r\n", "202 OK PRIORITY SET\r\n"),
+        ],
+        |client| {
+            let message = Message::new("Bonjour\n.\nmonde", Priority::Important)
+                .with_language("fr".parse().unwrap());
+            assert_eq!(21, client.send_message(&message).unwrap());
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn send_message_rejected() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self PRIORITY message\r\n", "202 OK PRIORITY SET\r\n"),
+            ("GET VOICE_TYPE\r\n", "251-MALE1\r\n251 OK GET RETURNED\r\n"),
+            ("SET self VOICE_TYPE CHILD_MALE\r\n", "209 OK VOICE SET\r\n"),
+            ("SPEAK\r\n", "401 ERR NO SUCH MODULE\r\n"),
+            ("SET self VOICE_TYPE MALE1\r\n", "209 OK VOICE SET\r\n"),
+            ("SET self PRIORITY text\r\n", "202 OK PRIORITY SET\r\n"),
+        ],
+        |client| {
+            let message =
+                Message::new("Hello", Priority::Message).with_voice_type(VoiceType::ChildMale);
+            match client.send_message(&message) {
+                Err(ClientError::Ssip(status)) => assert_eq!(401, status.code),
+                result => panic!("expecting SSIP error: {:?}", result),
+            }
+            Ok(())
+        }
+    )
+}
+
+#[test]
+fn cloned_client() -> ClientResult<()> {
+    test_client!(
+        &[
+            SET_CLIENT_COMMUNICATION,
+            ("SET self RATE 15\r\n", "203 OK RATE SET\r\n"),
+            ("QUIT\r\n", "231 HAPPY HACKING\r\n"),
+        ],
+        |client| {
+            let mut other = client.try_clone().unwrap();
+            let handle = std::thread::spawn(move || {
+                other
+                    .set_rate(ClientScope::Current, 15)
+                    .unwrap()
+                    .check_status(OK_RATE_SET)
+                    .unwrap();
+            });
+            handle.join().unwrap();
+            client.quit().unwrap().check_status(OK_BYE).unwrap();
+            Ok(())
+        }
+    )
+}
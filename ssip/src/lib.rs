@@ -7,12 +7,17 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
+#[cfg(not(feature = "std"))]
+use core::error::Error as StdError;
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
 use std::fmt;
 use std::io;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use thiserror::Error as ThisError;
 
-use strum_macros::Display as StrumDisplay;
+use strum_macros::{Display as StrumDisplay, IntoStaticStr};
 
 /// Return code of SSIP commands
 pub type ReturnCode = u16;
@@ -23,8 +28,23 @@ pub type MessageId = u32;
 /// Client identifier
 pub type ClientId = u32;
 
+/// Identifier that can't be used in a scope.
+///
+/// Speech Dispatcher numbers messages and clients from 1, the id 0 is never valid.
+#[derive(ThisError, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeError {
+    #[error("invalid message id: {0}")]
+    InvalidMessageId(MessageId),
+    #[error("invalid client id: {0}")]
+    InvalidClientId(ClientId),
+}
+
 /// Message identifiers
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// Use [`MessageScope::message`] to build the scope of a message id received from
+/// elsewhere. The clients refuse to send a scope that is not valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MessageScope {
     /// Last message from current client
     Last,
@@ -44,8 +64,40 @@ impl fmt::Display for MessageScope {
     }
 }
 
+impl MessageScope {
+    /// Scope of a specific message. Fail if the id is not valid.
+    pub fn message(id: MessageId) -> Result<Self, ScopeError> {
+        let scope = MessageScope::Message(id);
+        scope.validate()?;
+        Ok(scope)
+    }
+
+    /// Scope of a specific message without checking the id.
+    pub const fn new_unchecked(id: MessageId) -> Self {
+        MessageScope::Message(id)
+    }
+
+    /// Check that the id of a specific message is valid.
+    pub fn validate(&self) -> Result<(), ScopeError> {
+        match self {
+            MessageScope::Message(0) => Err(ScopeError::InvalidMessageId(0)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<MessageId> for MessageScope {
+    fn from(id: MessageId) -> Self {
+        MessageScope::Message(id)
+    }
+}
+
 /// Client identifiers
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+///
+/// Use [`ClientScope::client`] to build the scope of a client id received from elsewhere.
+/// The clients refuse to send a scope that is not valid.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClientScope {
     /// Current client
     Current,
@@ -65,8 +117,31 @@ impl fmt::Display for ClientScope {
     }
 }
 
+impl ClientScope {
+    /// Scope of a specific client. Fail if the id is not valid.
+    pub fn client(id: ClientId) -> Result<Self, ScopeError> {
+        let scope = ClientScope::Client(id);
+        scope.validate()?;
+        Ok(scope)
+    }
+
+    /// Scope of a specific client without checking the id.
+    pub const fn new_unchecked(id: ClientId) -> Self {
+        ClientScope::Client(id)
+    }
+
+    /// Check that the id of a specific client is valid.
+    pub fn validate(&self) -> Result<(), ScopeError> {
+        match self {
+            ClientScope::Client(0) => Err(ScopeError::InvalidClientId(0)),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Priority
 #[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Priority {
     #[strum(serialize = "progress")]
     Progress,
@@ -82,6 +157,7 @@ pub enum Priority {
 
 /// Punctuation mode.
 #[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PunctuationMode {
     #[strum(serialize = "none")]
     None,
@@ -95,6 +171,7 @@ pub enum PunctuationMode {
 
 /// Capital letters recognition mode.
 #[derive(StrumDisplay, Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CapitalLettersRecognitionMode {
     #[strum(serialize = "none")]
     None,
@@ -105,7 +182,8 @@ pub enum CapitalLettersRecognitionMode {
 }
 
 /// Symbolic key names
-#[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(StrumDisplay, IntoStaticStr, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyName {
     #[strum(serialize = "space")]
     Space,
@@ -251,8 +329,130 @@ pub enum KeyName {
     Window,
 }
 
+impl KeyName {
+    /// All the key names in the order of the specification.
+    pub const ALL: &'static [KeyName] = &[
+        KeyName::Space,
+        KeyName::Underscore,
+        KeyName::DoubleQuote,
+        KeyName::Alt,
+        KeyName::Control,
+        KeyName::Hyper,
+        KeyName::Meta,
+        KeyName::Shift,
+        KeyName::Super,
+        KeyName::Backspace,
+        KeyName::Break,
+        KeyName::Delete,
+        KeyName::Down,
+        KeyName::End,
+        KeyName::Enter,
+        KeyName::Escape,
+        KeyName::F1,
+        KeyName::F2,
+        KeyName::F3,
+        KeyName::F4,
+        KeyName::F5,
+        KeyName::F6,
+        KeyName::F7,
+        KeyName::F8,
+        KeyName::F9,
+        KeyName::F10,
+        KeyName::F11,
+        KeyName::F12,
+        KeyName::F13,
+        KeyName::F14,
+        KeyName::F15,
+        KeyName::F16,
+        KeyName::F17,
+        KeyName::F18,
+        KeyName::F19,
+        KeyName::F20,
+        KeyName::F21,
+        KeyName::F22,
+        KeyName::F23,
+        KeyName::F24,
+        KeyName::Home,
+        KeyName::Insert,
+        KeyName::KpMultiply,
+        KeyName::KpPlus,
+        KeyName::KpMinus,
+        KeyName::KpDot,
+        KeyName::KpDivide,
+        KeyName::Kp0,
+        KeyName::Kp1,
+        KeyName::Kp2,
+        KeyName::Kp3,
+        KeyName::Kp4,
+        KeyName::Kp5,
+        KeyName::Kp6,
+        KeyName::Kp7,
+        KeyName::Kp8,
+        KeyName::Kp9,
+        KeyName::KpEnter,
+        KeyName::Left,
+        KeyName::Menu,
+        KeyName::Next,
+        KeyName::NumLock,
+        KeyName::Pause,
+        KeyName::Print,
+        KeyName::Prior,
+        KeyName::Return,
+        KeyName::Right,
+        KeyName::ScrollLock,
+        KeyName::Tab,
+        KeyName::Up,
+        KeyName::Window,
+    ];
+
+    /// Name of the key on the wire.
+    pub fn as_str(&self) -> &'static str {
+        self.into()
+    }
+}
+
+impl FromStr for KeyName {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyName::ALL
+            .iter()
+            .find(|key| key.as_str() == s)
+            .cloned()
+            .ok_or_else(|| ClientError::invalid_text("unknown key name", s))
+    }
+}
+
+impl TryFrom<&str> for KeyName {
+    type Error = ClientError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Convert the characters that have a symbolic name, such as space or control characters.
+impl TryFrom<char> for KeyName {
+    type Error = ClientError;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            ' ' => Ok(KeyName::Space),
+            '_' => Ok(KeyName::Underscore),
+            '"' => Ok(KeyName::DoubleQuote),
+            '\u{8}' => Ok(KeyName::Backspace),
+            '\t' => Ok(KeyName::Tab),
+            '\n' | '\r' => Ok(KeyName::Return),
+            '\u{1b}' => Ok(KeyName::Escape),
+            '\u{7f}' => Ok(KeyName::Delete),
+            _ => Err(ClientError::invalid_data("no key name for character")),
+        }
+    }
+}
+
 /// Notification type
 #[derive(StrumDisplay, Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NotificationType {
     #[strum(serialize = "begin")]
     Begin,
@@ -271,7 +471,11 @@ pub enum NotificationType {
 }
 
 /// Notification event type (returned by server)
-#[derive(StrumDisplay, Debug, Clone)]
+///
+/// With feature `dbus`, events are sent over D-Bus as [`SpeechEvent`] because zvariant can't
+/// encode an enum whose variants don't all have the same fields.
+#[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventType {
     Begin,
     End,
@@ -282,66 +486,405 @@ pub enum EventType {
 }
 
 /// Event identifier
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "dbus", derive(zvariant::Type))]
 pub struct EventId {
-    // Message id
-    pub message: String,
-    // Client id
-    pub client: String,
+    /// Message id
+    pub message: MessageId,
+    /// Client id
+    pub client: ClientId,
 }
 
 impl EventId {
-    // New event identifier
-    pub fn new(message: &str, client: &str) -> Self {
-        Self {
-            message: message.to_string(),
-            client: client.to_string(),
-        }
+    /// New event identifier
+    pub fn new(message: MessageId, client: ClientId) -> Self {
+        Self { message, client }
     }
+
+    /// Parse the identifiers sent by the server.
+    pub fn parse(message: &str, client: &str) -> ClientResult<Self> {
+        let invalid = |text, err| ClientError::invalid_value("invalid event id", text, err);
+        Ok(Self::new(
+            message.parse().map_err(|err| invalid(message, err))?,
+            client.parse().map_err(|err| invalid(client, err))?,
+        ))
+    }
+}
+
+impl fmt::Display for EventId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.message, self.client)
+    }
+}
+
+/// Event identifiers as sent by the server, even if they are not numbers.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "dbus", derive(zvariant::Type))]
+pub struct RawEventId {
+    /// Message id
+    pub message: String,
+    /// Client id
+    pub client: String,
 }
 
 /// Notification event
-#[derive(Debug, Clone)]
+///
+/// The identifiers are parsed when the event is received. If the server sends an identifier
+/// that is not a number, the accessor returns `None` and the original strings are available
+/// with [`Event::raw`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SerializedEvent", into = "SerializedEvent")
+)]
 pub struct Event {
     pub ntype: EventType,
-    pub id: EventId,
+    message: Option<MessageId>,
+    client: Option<ClientId>,
+    raw: RawEventId,
+}
+
+/// Serialized form of an event, with the identifiers as sent by the server.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedEvent {
+    ntype: EventType,
+    id: RawEventId,
+}
+
+#[cfg(feature = "serde")]
+impl From<SerializedEvent> for Event {
+    fn from(event: SerializedEvent) -> Self {
+        Event::from_raw_id(event.ntype, event.id)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Event> for SerializedEvent {
+    fn from(event: Event) -> Self {
+        SerializedEvent {
+            ntype: event.ntype,
+            id: event.raw,
+        }
+    }
+}
+
+/// Event in a form that can be sent over D-Bus, with signature `(ss(ss))`.
+///
+/// Example
+/// ```
+/// use ssip::{Event, SpeechEvent};
+/// let event = SpeechEvent::from(Event::index_mark("m1".to_string(), 21, 1));
+/// assert_eq!("index_mark", event.kind);
+/// assert_eq!(Event::index_mark("m1".to_string(), 21, 1), Event::try_from(event)?);
+/// # Ok::<(), ssip::ClientError>(())
+/// ```
+#[cfg(feature = "dbus")]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, zvariant::Type,
+)]
+pub struct SpeechEvent {
+    /// Type of the event: `begin`, `end`, `cancel`, `pause`, `resume` or `index_mark`.
+    pub kind: String,
+    /// Mark of an index mark, empty for the other events.
+    pub mark: String,
+    /// Identifiers as sent by the server.
+    pub id: RawEventId,
+}
+
+#[cfg(feature = "dbus")]
+impl From<Event> for SpeechEvent {
+    fn from(event: Event) -> Self {
+        let (kind, mark) = match event.ntype {
+            EventType::Begin => ("begin", String::new()),
+            EventType::End => ("end", String::new()),
+            EventType::Cancel => ("cancel", String::new()),
+            EventType::Pause => ("pause", String::new()),
+            EventType::Resume => ("resume", String::new()),
+            EventType::IndexMark(mark) => ("index_mark", mark),
+        };
+        SpeechEvent {
+            kind: kind.to_string(),
+            mark,
+            id: event.raw,
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl TryFrom<SpeechEvent> for Event {
+    type Error = ClientError;
+
+    fn try_from(event: SpeechEvent) -> Result<Self, Self::Error> {
+        let ntype = match event.kind.as_str() {
+            "begin" => EventType::Begin,
+            "end" => EventType::End,
+            "cancel" => EventType::Cancel,
+            "pause" => EventType::Pause,
+            "resume" => EventType::Resume,
+            "index_mark" => EventType::IndexMark(event.mark),
+            _ => return Err(ClientError::invalid_data("invalid event type")),
+        };
+        Ok(Event::from_raw_id(ntype, event.id))
+    }
 }
 
 impl Event {
-    pub fn new(ntype: EventType, message: &str, client: &str) -> Event {
+    pub fn new(ntype: EventType, id: EventId) -> Event {
+        Event {
+            ntype,
+            message: Some(id.message),
+            client: Some(id.client),
+            raw: RawEventId {
+                message: id.message.to_string(),
+                client: id.client.to_string(),
+            },
+        }
+    }
+
+    /// Event with the identifiers as sent by the server.
+    pub fn from_raw(ntype: EventType, message: &str, client: &str) -> Event {
+        Event::from_raw_id(
+            ntype,
+            RawEventId {
+                message: message.to_string(),
+                client: client.to_string(),
+            },
+        )
+    }
+
+    /// Event taking the identifiers as sent by the server without copying them.
+    pub fn from_raw_id(ntype: EventType, raw: RawEventId) -> Event {
         Event {
             ntype,
-            id: EventId::new(message, client),
+            message: raw.message.parse().ok(),
+            client: raw.client.parse().ok(),
+            raw,
+        }
+    }
+
+    pub fn begin(message: MessageId, client: ClientId) -> Event {
+        Event::new(EventType::Begin, EventId::new(message, client))
+    }
+
+    pub fn end(message: MessageId, client: ClientId) -> Event {
+        Event::new(EventType::End, EventId::new(message, client))
+    }
+
+    pub fn index_mark(mark: String, message: MessageId, client: ClientId) -> Event {
+        Event::new(EventType::IndexMark(mark), EventId::new(message, client))
+    }
+
+    pub fn cancel(message: MessageId, client: ClientId) -> Event {
+        Event::new(EventType::Cancel, EventId::new(message, client))
+    }
+
+    pub fn pause(message: MessageId, client: ClientId) -> Event {
+        Event::new(EventType::Pause, EventId::new(message, client))
+    }
+
+    pub fn resume(message: MessageId, client: ClientId) -> Event {
+        Event::new(EventType::Resume, EventId::new(message, client))
+    }
+
+    /// Identifiers of the event, `None` if the server didn't send numbers.
+    pub fn id(&self) -> Option<EventId> {
+        Some(EventId::new(self.message?, self.client?))
+    }
+
+    /// Message id, `None` if the server didn't send a number.
+    pub fn message_id(&self) -> Option<MessageId> {
+        self.message
+    }
+
+    /// Client id, `None` if the server didn't send a number.
+    pub fn client_id(&self) -> Option<ClientId> {
+        self.client
+    }
+
+    /// Name of the index mark, if it is an index mark event.
+    pub fn mark(&self) -> Option<&str> {
+        match &self.ntype {
+            EventType::IndexMark(mark) => Some(mark),
+            _ => None,
+        }
+    }
+
+    /// Identifiers exactly as sent by the server.
+    pub fn raw(&self) -> &RawEventId {
+        &self.raw
+    }
+}
+
+/// Generic voice type, independent of the synthesizer
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VoiceType {
+    Male1,
+    Male2,
+    Male3,
+    Female1,
+    Female2,
+    Female3,
+    ChildMale,
+    ChildFemale,
+    /// Voice type not defined by SSIP
+    Other(String),
+}
+
+impl fmt::Display for VoiceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoiceType::Male1 => write!(f, "MALE1"),
+            VoiceType::Male2 => write!(f, "MALE2"),
+            VoiceType::Male3 => write!(f, "MALE3"),
+            VoiceType::Female1 => write!(f, "FEMALE1"),
+            VoiceType::Female2 => write!(f, "FEMALE2"),
+            VoiceType::Female3 => write!(f, "FEMALE3"),
+            VoiceType::ChildMale => write!(f, "CHILD_MALE"),
+            VoiceType::ChildFemale => write!(f, "CHILD_FEMALE"),
+            VoiceType::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for VoiceType {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "" => Err(ClientError::unexpected_eof("missing voice type")),
+            "MALE1" => Ok(VoiceType::Male1),
+            "MALE2" => Ok(VoiceType::Male2),
+            "MALE3" => Ok(VoiceType::Male3),
+            "FEMALE1" => Ok(VoiceType::Female1),
+            "FEMALE2" => Ok(VoiceType::Female2),
+            "FEMALE3" => Ok(VoiceType::Female3),
+            "CHILD_MALE" => Ok(VoiceType::ChildMale),
+            "CHILD_FEMALE" => Ok(VoiceType::ChildFemale),
+            _ => Ok(VoiceType::Other(s.to_string())),
         }
     }
+}
+
+/// Language tag such as `en` or `pt-BR`, checked against the syntax of BCP 47.
+///
+/// The tag is normalized: the language is in lower case, the script in title case and the
+/// region in upper case. Underscores are accepted as separators.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for LanguageTag {
+    type Error = ClientError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
 
-    pub fn begin(message: &str, client: &str) -> Event {
-        Event::new(EventType::Begin, message, client)
+impl From<LanguageTag> for String {
+    fn from(tag: LanguageTag) -> Self {
+        tag.0
     }
+}
+
+impl FromStr for LanguageTag {
+    type Err = ClientError;
 
-    pub fn end(message: &str, client: &str) -> Event {
-        Event::new(EventType::End, message, client)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ClientError::unexpected_eof("missing language"));
+        }
+        let mut subtags = Vec::new();
+        // After a singleton, subtags are extensions or private use.
+        let mut extension = false;
+        for (index, subtag) in s.split(['-', '_']).enumerate() {
+            let valid = if index == 0 {
+                (2..=3).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphabetic())
+            } else {
+                (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+            };
+            if !valid {
+                return Err(ClientError::invalid_text("invalid language tag", s));
+            }
+            let is_alpha = subtag.chars().all(|c| c.is_ascii_alphabetic());
+            let normalized = if index == 0 || extension {
+                subtag.to_ascii_lowercase()
+            } else if subtag.len() == 2 && is_alpha {
+                subtag.to_ascii_uppercase()
+            } else if subtag.len() == 4 && is_alpha {
+                let (first, rest) = subtag.split_at(1);
+                first.to_ascii_uppercase() + &rest.to_ascii_lowercase()
+            } else {
+                subtag.to_ascii_lowercase()
+            };
+            extension = extension || subtag.len() == 1;
+            subtags.push(normalized);
+        }
+        Ok(LanguageTag(subtags.join("-")))
     }
+}
+
+/// Name of an index mark inserted in SSML with `<mark name="..."/>`.
+///
+/// Only ASCII letters, digits, `-`, `_`, `.` and `:` are accepted, so that the name can't
+/// close the attribute in SSML or break the lines of the event reporting the mark.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MarkName(String);
 
-    pub fn index_mark(mark: String, message: &str, client: &str) -> Event {
-        Event::new(EventType::IndexMark(mark), message, client)
+impl MarkName {
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
 
-    pub fn cancel(message: &str, client: &str) -> Event {
-        Event::new(EventType::Cancel, message, client)
+    fn is_valid_char(ch: char) -> bool {
+        ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.' | ':')
     }
+}
 
-    pub fn pause(message: &str, client: &str) -> Event {
-        Event::new(EventType::Pause, message, client)
+impl fmt::Display for MarkName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
     }
+}
 
-    pub fn resume(message: &str, client: &str) -> Event {
-        Event::new(EventType::Resume, message, client)
+impl FromStr for MarkName {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err(ClientError::unexpected_eof("missing mark name"))
+        } else if s.chars().all(MarkName::is_valid_char) {
+            Ok(MarkName(s.to_string()))
+        } else {
+            Err(ClientError::invalid_text("invalid mark name", s))
+        }
     }
 }
 
 /// Synthesis voice
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SynthesisVoice {
     pub name: String,
     pub language: Option<String>,
@@ -366,6 +909,80 @@ impl SynthesisVoice {
             None => None,
         }
     }
+
+    /// Subtags of the voice language followed by the dialect, in lower case.
+    fn subtags(&self) -> Vec<String> {
+        let mut subtags: Vec<String> = self
+            .language
+            .iter()
+            .flat_map(|language| split_language_tag(language))
+            .collect();
+        if let Some(dialect) = &self.dialect {
+            for subtag in split_language_tag(dialect) {
+                if !subtags.contains(&subtag) {
+                    subtags.push(subtag);
+                }
+            }
+        }
+        subtags
+    }
+
+    /// Check if the voice speaks the language.
+    ///
+    /// The language is a BCP-47 tag like `en` or `en-US`, compared case-insensitively with
+    /// `-` or `_` as separator. The voice matches if its language starts with all the subtags.
+    /// The optional variant must be one of the voice subtags after the language, usually
+    /// its dialect.
+    pub fn matches(&self, lang: &str, variant: Option<&str>) -> bool {
+        let subtags = self.subtags();
+        let requested = split_language_tag(lang);
+        if requested.is_empty()
+            || requested.len() > subtags.len()
+            || requested.iter().zip(subtags.iter()).any(|(r, s)| r != s)
+        {
+            return false;
+        }
+        match variant {
+            Some(variant) => {
+                let variant = variant.to_lowercase();
+                subtags.iter().skip(1).any(|subtag| *subtag == variant)
+            }
+            None => true,
+        }
+    }
+}
+
+/// Split a language tag in lower case subtags.
+fn split_language_tag(tag: &str) -> Vec<String> {
+    tag.split(['-', '_'])
+        .filter(|subtag| !subtag.is_empty())
+        .map(|subtag| subtag.to_lowercase())
+        .collect()
+}
+
+/// Choose the voice that best matches a list of language tags in order of preference.
+///
+/// For each preference, the voices matching the full tag are tried first, then the tag is
+/// shortened as in the BCP-47 lookup before trying the next preference. For example `en-AU`
+/// selects a voice for `en` if there is no Australian voice. Among the matching voices, the
+/// least specific is preferred so that `en` selects a generic voice over `en-GB`.
+pub fn choose_voice<'a>(
+    voices: &'a [SynthesisVoice],
+    preferences: &[&str],
+) -> Option<&'a SynthesisVoice> {
+    let find = |tag: &str| {
+        let len = split_language_tag(tag).len();
+        voices
+            .iter()
+            .filter(|voice| voice.matches(tag, None))
+            .min_by_key(|voice| voice.subtags().len() - len)
+    };
+    preferences.iter().find_map(|tag| {
+        let subtags = split_language_tag(tag);
+        (1..=subtags.len())
+            .rev()
+            .find_map(|len| find(&subtags[..len].join("-")))
+    })
 }
 
 impl FromStr for SynthesisVoice {
@@ -391,22 +1008,107 @@ impl FromStr for SynthesisVoice {
 /// Examples:
 /// - 216 OK OUTPUT MODULE SET
 /// - 409 ERR RATE TOO HIGH
-#[derive(Debug, PartialEq, Eq)]
+///
+/// The line as sent by the server is also kept for diagnostics, but it is ignored when
+/// comparing status lines.
+#[derive(Debug, Clone)]
 pub struct StatusLine {
     pub code: ReturnCode,
     pub message: String,
+    raw: String,
+}
+
+impl StatusLine {
+    pub fn new(code: ReturnCode, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            raw: format!("{} {}", code, message),
+        }
+    }
+
+    /// Status with the line as sent by the server.
+    pub fn from_raw(code: ReturnCode, message: &str, raw: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            raw: raw.to_string(),
+        }
+    }
+
+    /// Replace the line as sent by the server.
+    pub fn with_raw(mut self, raw: &str) -> Self {
+        self.raw = raw.to_string();
+        self
+    }
+
+    /// Line as sent by the server, including the end of line if any.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
 }
 
+impl PartialEq for StatusLine {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code && self.message == other.message
+    }
+}
+
+impl Eq for StatusLine {}
+
 impl fmt::Display for StatusLine {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} {}", self.code, self.message)
     }
 }
+/// Stage of the connection, as seen by the client.
+#[derive(StrumDisplay, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// The client name is not set yet.
+    #[strum(serialize = "connected")]
+    Connected,
+    /// The client name is set.
+    #[strum(serialize = "ready")]
+    Ready,
+    /// The server accepted `SPEAK` and waits for the lines of the message.
+    #[strum(serialize = "speaking")]
+    Speaking,
+    /// `QUIT` was sent.
+    #[strum(serialize = "closed")]
+    Closed,
+}
+
+/// Text received from the server that can't be parsed.
+///
+/// It is wrapped in an I/O error of kind [`io::ErrorKind::InvalidData`]. The error of the
+/// parser, if any, is the source.
+#[derive(ThisError, Debug)]
+#[error("{what}: {text:?}")]
+pub struct ParseError {
+    what: &'static str,
+    text: String,
+    #[source]
+    source: Option<Box<dyn StdError + Send + Sync>>,
+}
+
+impl ParseError {
+    /// What was expected.
+    pub fn what(&self) -> &'static str {
+        self.what
+    }
+
+    /// Text that can't be parsed.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
 /// Client error, either I/O error or SSIP error.
 #[derive(ThisError, Debug)]
+#[non_exhaustive]
 pub enum ClientError {
     #[error("I/O: {0}")]
-    Io(io::Error),
+    Io(#[source] io::Error),
     #[error("Not ready")]
     NotReady,
     #[error("SSIP: {0}")]
@@ -417,6 +1119,21 @@ pub enum ClientError {
     TooManyLines,
     #[error("Unexpected status: {0}")]
     UnexpectedStatus(ReturnCode),
+    #[error("Corrupted stream: a request was partially written")]
+    CorruptedStream,
+    #[error("Connection closed")]
+    Closed,
+    #[error("Request not allowed when {0}")]
+    InvalidState(ConnectionState),
+    /// The server sent an answer that doesn't follow the protocol.
+    #[error("Protocol violation: {0}")]
+    ProtocolViolation(String),
+    /// The request doesn't exist in the version of the protocol of the server.
+    #[error("{0} not supported by protocol {1}")]
+    Unsupported(&'static str, ProtocolVersion),
+    /// The request has a scope with an invalid id.
+    #[error("Invalid scope: {0}")]
+    Scope(#[from] ScopeError),
 }
 
 impl ClientError {
@@ -434,6 +1151,42 @@ impl ClientError {
     pub fn unexpected_eof(msg: &str) -> Self {
         ClientError::io_error(io::ErrorKind::UnexpectedEof, msg)
     }
+
+    /// Invalid data I/O error keeping the text that can't be parsed.
+    pub fn invalid_text(what: &'static str, text: &str) -> Self {
+        Self::unparsable(what, text, None)
+    }
+
+    /// Invalid data I/O error keeping the text and the error of the parser.
+    pub fn invalid_value<E>(what: &'static str, text: &str, source: E) -> Self
+    where
+        E: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        Self::unparsable(what, text, Some(source.into()))
+    }
+
+    fn unparsable(
+        what: &'static str,
+        text: &str,
+        source: Option<Box<dyn StdError + Send + Sync>>,
+    ) -> Self {
+        Self::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            ParseError {
+                what,
+                text: text.to_string(),
+                source,
+            },
+        ))
+    }
+
+    /// The text that can't be parsed, if the error comes from a parser.
+    pub fn parse_error(&self) -> Option<&ParseError> {
+        match self {
+            Self::Io(err) => err.get_ref().and_then(|err| err.downcast_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl From<io::Error> for ClientError {
@@ -446,6 +1199,16 @@ impl From<io::Error> for ClientError {
     }
 }
 
+impl From<ClientError> for io::Error {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::Io(err) => err,
+            ClientError::NotReady => io::Error::from(io::ErrorKind::WouldBlock),
+            err => io::Error::new(io::ErrorKind::Other, err),
+        }
+    }
+}
+
 /// Client result.
 pub type ClientResult<T> = Result<T, ClientError>;
 
@@ -453,38 +1216,345 @@ pub type ClientResult<T> = Result<T, ClientError>;
 pub type ClientStatus = ClientResult<StatusLine>;
 
 /// Client name
+///
+/// The fields are sent as `user:application:component`. They can't contain colons or whitespaces.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "ClientNameFields")
+)]
 pub struct ClientName {
     pub user: String,
     pub application: String,
     pub component: String,
 }
 
+/// Fields of a deserialized client name, checked before the name is created.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct ClientNameFields {
+    user: String,
+    application: String,
+    component: String,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ClientNameFields> for ClientName {
+    type Error = ClientError;
+
+    fn try_from(fields: ClientNameFields) -> Result<Self, Self::Error> {
+        ClientName::with_component(&fields.user, &fields.application, &fields.component)
+    }
+}
+
 impl ClientName {
-    pub fn new(user: &str, application: &str) -> Self {
-        ClientName::with_component(user, application, "main")
+    /// Default component name
+    const DEFAULT_COMPONENT: &'static str = "main";
+
+    /// Name used when a field can't be derived from the environment
+    const UNKNOWN: &'static str = "unknown";
+
+    /// Client name with the default component. Fail if a field is empty or contains illegal
+    /// characters.
+    pub fn new(user: &str, application: &str) -> ClientResult<Self> {
+        ClientName::with_component(user, application, ClientName::DEFAULT_COMPONENT)
+    }
+
+    /// Client name with a specific component. Fail if a field is empty or contains illegal
+    /// characters.
+    pub fn with_component(user: &str, application: &str, component: &str) -> ClientResult<Self> {
+        Ok(ClientName {
+            user: ClientName::check_field("user", user)?,
+            application: ClientName::check_field("application", application)?,
+            component: ClientName::check_field("component", component)?,
+        })
+    }
+
+    /// Client name with a component unique to the connection.
+    ///
+    /// The default component is followed by the process id and a counter, so that the
+    /// connections of one application can be told apart in the history of the server.
+    /// Fail if a field is empty or contains illegal characters.
+    pub fn with_auto_component(user: &str, application: &str) -> ClientResult<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let component = format!(
+            "{}-{}-{}",
+            ClientName::DEFAULT_COMPONENT,
+            std::process::id(),
+            COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+        );
+        ClientName::with_component(user, application, &component)
     }
 
-    pub fn with_component(user: &str, application: &str, component: &str) -> Self {
+    /// Client name derived from the process environment.
+    ///
+    /// The user comes from `USER`, `LOGNAME` or `USERNAME` and the application is the name of the
+    /// executable. The conversion is lossy, so that it never fails: values that are not valid
+    /// Unicode are skipped, `unknown` is used when no value is found, and the characters that
+    /// can't be sent in a client name, such as `:` or spaces, are replaced by underscores. Use
+    /// [`ClientName::new`] to reject such names instead.
+    pub fn from_env() -> Self {
+        let user = ["USER", "LOGNAME", "USERNAME"]
+            .iter()
+            .find_map(|key| std::env::var(key).ok());
+        let application = std::env::current_exe()
+            .ok()
+            .and_then(|path| {
+                path.file_stem()
+                    .and_then(|name| name.to_str())
+                    .map(String::from)
+            })
+            .or_else(|| {
+                std::env::args_os().next().and_then(|arg| {
+                    let path = std::path::Path::new(&arg);
+                    path.file_name().unwrap_or(&arg).to_str().map(String::from)
+                })
+            });
         ClientName {
-            user: user.to_string(),
-            application: application.to_string(),
-            component: component.to_string(),
+            user: ClientName::sanitize_field(user.as_deref().unwrap_or(ClientName::UNKNOWN)),
+            application: ClientName::sanitize_field(
+                application.as_deref().unwrap_or(ClientName::UNKNOWN),
+            ),
+            component: ClientName::DEFAULT_COMPONENT.to_string(),
         }
     }
-}
 
-/// Cursor motion in history
-#[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
-pub enum CursorDirection {
-    #[strum(serialize = "backward")]
-    Backward,
-    #[strum(serialize = "forward")]
-    Forward,
+    /// Return true if the character can't be sent in a client name.
+    fn is_illegal_char(ch: char) -> bool {
+        ch == ':' || ch.is_whitespace() || ch.is_control()
+    }
+
+    /// Return the field or an error if it is empty or contains illegal characters.
+    fn check_field(label: &str, value: &str) -> ClientResult<String> {
+        if value.is_empty() {
+            Err(ClientError::io_error(
+                io::ErrorKind::InvalidInput,
+                &format!("empty client {}", label),
+            ))
+        } else if value.chars().any(ClientName::is_illegal_char) {
+            Err(ClientError::io_error(
+                io::ErrorKind::InvalidInput,
+                &format!("illegal character in client {}: {:?}", label, value),
+            ))
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    /// Replace illegal characters by underscores.
+    fn sanitize_field(value: &str) -> String {
+        let value = value
+            .chars()
+            .map(|ch| {
+                if ClientName::is_illegal_char(ch) {
+                    '_'
+                } else {
+                    ch
+                }
+            })
+            .collect::<String>();
+        if value.is_empty() {
+            ClientName::UNKNOWN.to_string()
+        } else {
+            value
+        }
+    }
+}
+
+/// Message to speak with its own priority and settings
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Message {
+    pub text: String,
+    pub priority: Priority,
+    /// Language used for this message only
+    pub language: Option<LanguageTag>,
+    /// Voice type used for this message only
+    pub voice_type: Option<VoiceType>,
+}
+
+impl Message {
+    pub fn new(text: &str, priority: Priority) -> Self {
+        Message {
+            text: text.to_string(),
+            priority,
+            language: None,
+            voice_type: None,
+        }
+    }
+
+    /// Override the language for this message.
+    pub fn with_language(mut self, language: LanguageTag) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Override the voice type for this message.
+    pub fn with_voice_type(mut self, voice_type: VoiceType) -> Self {
+        self.voice_type = Some(voice_type);
+        self
+    }
+
+    /// Settings overridden by the message, except the priority.
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            language: self.language.clone(),
+            voice_type: self.voice_type.clone(),
+            ..Overrides::default()
+        }
+    }
+}
+
+/// Settings used for one message only
+///
+/// Clients read the current value of each overridden setting, apply the overrides, speak
+/// and restore the values read. The requests are the same for all the clients:
+/// [`Overrides::queries`] reads the values, [`Overrides::previous`] turns the responses to
+/// these queries into the overrides restoring them, and [`Overrides::requests`] applies them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Overrides {
+    pub language: Option<LanguageTag>,
+    pub voice_type: Option<VoiceType>,
+    pub rate: Option<i8>,
+    pub pitch: Option<i8>,
+    pub volume: Option<i8>,
+    pub pause_context: Option<u32>,
+}
+
+impl Overrides {
+    pub fn with_language(mut self, language: LanguageTag) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub fn with_voice_type(mut self, voice_type: VoiceType) -> Self {
+        self.voice_type = Some(voice_type);
+        self
+    }
+
+    pub fn with_rate(mut self, rate: i8) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: i8) -> Self {
+        self.pitch = Some(pitch);
+        self
+    }
+
+    pub fn with_volume(mut self, volume: i8) -> Self {
+        self.volume = Some(volume);
+        self
+    }
+
+    pub fn with_pause_context(mut self, pause_context: u32) -> Self {
+        self.pause_context = Some(pause_context);
+        self
+    }
+
+    /// True if no setting is overridden.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Requests reading the current value of the overridden settings.
+    pub fn queries(&self) -> Vec<Request> {
+        [
+            self.language.as_ref().map(|_| Request::GetLanguage),
+            self.voice_type.as_ref().map(|_| Request::GetVoiceType),
+            self.rate.map(|_| Request::GetRate),
+            self.pitch.map(|_| Request::GetPitch),
+            self.volume.map(|_| Request::GetVolume),
+            self.pause_context.map(|_| Request::GetPauseContext),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Overrides restoring the settings, from the responses to [`Overrides::queries`] in order.
+    pub fn previous(&self, responses: Vec<Response>) -> ClientResult<Overrides> {
+        fn unexpected() -> ClientError {
+            ClientError::invalid_data("unexpected response to a query of the settings")
+        }
+        let mut responses = responses.into_iter();
+        let mut next = || responses.next().ok_or_else(unexpected);
+        let mut previous = Overrides::default();
+        if self.language.is_some() {
+            previous.language = match next()? {
+                Response::Get(value) => Some(value.parse()?),
+                _ => return Err(unexpected()),
+            };
+        }
+        if self.voice_type.is_some() {
+            previous.voice_type = match next()? {
+                Response::Get(value) => Some(value.parse()?),
+                _ => return Err(unexpected()),
+            };
+        }
+        if self.rate.is_some() {
+            previous.rate = match next()? {
+                Response::GetRate(value) => Some(value),
+                _ => return Err(unexpected()),
+            };
+        }
+        if self.pitch.is_some() {
+            previous.pitch = match next()? {
+                Response::GetPitch(value) => Some(value),
+                _ => return Err(unexpected()),
+            };
+        }
+        if self.volume.is_some() {
+            previous.volume = match next()? {
+                Response::GetVolume(value) => Some(value),
+                _ => return Err(unexpected()),
+            };
+        }
+        if self.pause_context.is_some() {
+            previous.pause_context = match next()? {
+                Response::GetPauseContext(value) => Some(u32::from(value)),
+                _ => return Err(unexpected()),
+            };
+        }
+        Ok(previous)
+    }
+
+    /// Requests setting the overridden values for the current client.
+    pub fn requests(&self) -> Vec<Request> {
+        let scope = || ClientScope::Current;
+        [
+            self.language
+                .clone()
+                .map(|language| Request::SetLanguage(scope(), language)),
+            self.voice_type
+                .clone()
+                .map(|voice_type| Request::SetVoiceType(scope(), voice_type)),
+            self.rate.map(|rate| Request::SetRate(scope(), rate)),
+            self.pitch.map(|pitch| Request::SetPitch(scope(), pitch)),
+            self.volume
+                .map(|volume| Request::SetVolume(scope(), volume)),
+            self.pause_context
+                .map(|pause_context| Request::SetPauseContext(scope(), pause_context)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Cursor motion in history
+#[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CursorDirection {
+    #[strum(serialize = "backward")]
+    Backward,
+    #[strum(serialize = "forward")]
+    Forward,
 }
 
 /// Sort direction in history
 #[derive(StrumDisplay, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SortDirection {
     #[strum(serialize = "asc")]
     Ascending,
@@ -494,6 +1564,7 @@ pub enum SortDirection {
 
 /// Property messages are ordered by in history
 #[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SortKey {
     #[strum(serialize = "client_name")]
     ClientName,
@@ -509,6 +1580,7 @@ pub enum SortKey {
 
 /// Sort ordering
 #[derive(StrumDisplay, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Ordering {
     #[strum(serialize = "text")]
     Text,
@@ -522,6 +1594,7 @@ pub enum Ordering {
 
 /// Position in history
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HistoryPosition {
     First,
     Last,
@@ -538,8 +1611,53 @@ impl fmt::Display for HistoryPosition {
     }
 }
 
+/// Position of the cursor in history
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CursorPosition(pub u16);
+
+impl FromStr for CursorPosition {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(CursorPosition)
+            .map_err(|err| ClientError::invalid_value("invalid cursor position", s, err))
+    }
+}
+
+/// History settings of the client
+///
+/// SSIP has no command to read these settings. They are the values requested by the client,
+/// `None` if they were never set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct HistorySettings {
+    pub sort: Option<(SortDirection, SortKey)>,
+    pub short_message_length: Option<u32>,
+    pub message_type_ordering: Option<Vec<Ordering>>,
+}
+
+impl HistorySettings {
+    /// Record the setting changed by a request, if any.
+    pub fn update<'a, R: Into<RequestRef<'a>>>(&mut self, request: R) {
+        match request.into() {
+            RequestRef::HistorySort(direction, key) => {
+                self.sort = Some((direction.clone(), key.clone()))
+            }
+            RequestRef::HistorySetShortMsgLength(length) => {
+                self.short_message_length = Some(length)
+            }
+            RequestRef::HistorySetMsgTypeOrdering(ordering) => {
+                self.message_type_ordering = Some(ordering.to_vec())
+            }
+            _ => (),
+        }
+    }
+}
+
 /// History client status
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HistoryClientStatus {
     pub id: ClientId,
     pub name: String,
@@ -559,29 +1677,96 @@ impl HistoryClientStatus {
 impl FromStr for HistoryClientStatus {
     type Err = ClientError;
 
+    /// Parse `id name status`. The id and the status are taken at both ends of the line,
+    /// so that the name may contain spaces.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut iter = s.splitn(3, ' ');
-        match iter.next() {
-            Some("") => Err(ClientError::unexpected_eof("expecting client id")),
-            Some(client_id) => match client_id.parse::<u32>() {
-                Ok(id) => match iter.next() {
-                    Some(name) => match iter.next() {
-                        Some("0") => Ok(HistoryClientStatus::new(id, name, false)),
-                        Some("1") => Ok(HistoryClientStatus::new(id, name, true)),
-                        Some(_) => Err(ClientError::invalid_data("invalid client status")),
-                        None => Err(ClientError::unexpected_eof("expecting client status")),
-                    },
-                    None => Err(ClientError::unexpected_eof("expecting client name")),
-                },
-                Err(_) => Err(ClientError::invalid_data("invalid client id")),
-            },
-            None => Err(ClientError::unexpected_eof("expecting client id")),
+        if s.is_empty() {
+            return Err(ClientError::unexpected_eof("expecting client id"));
+        }
+        let (client_id, rest) = match s.split_once(' ') {
+            Some((client_id, rest)) => (client_id, Some(rest)),
+            None => (s, None),
+        };
+        let id = client_id
+            .parse::<u32>()
+            .map_err(|err| ClientError::invalid_value("invalid client id", s, err))?;
+        let rest = rest.ok_or_else(|| ClientError::unexpected_eof("expecting client name"))?;
+        match rest.rsplit_once(' ') {
+            Some((name, "0")) => Ok(HistoryClientStatus::new(id, name, false)),
+            Some((name, "1")) => Ok(HistoryClientStatus::new(id, name, true)),
+            Some(_) => Err(ClientError::invalid_text("invalid client status", s)),
+            None => Err(ClientError::unexpected_eof("expecting client status")),
         }
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+/// Version of the protocol, identified by the release of Speech Dispatcher.
+///
+/// The client assumes the server speaks the current version unless told otherwise. A
+/// request that doesn't exist in the version of the server fails with
+/// [`ClientError::Unsupported`] before anything is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    /// Latest version known to this crate.
+    pub const CURRENT: ProtocolVersion = ProtocolVersion::new(0, 12);
+
+    /// First version with `SET PITCH_RANGE`.
+    const PITCH_RANGE: ProtocolVersion = ProtocolVersion::new(0, 9);
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    /// Tell if the request exists in this version.
+    pub fn supports(&self, request: RequestRef<'_>) -> bool {
+        match request {
+            RequestRef::SetPitchRange(_, _) => *self >= Self::PITCH_RANGE,
+            _ => true,
+        }
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        Self::CURRENT
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, '.');
+        let mut number = || {
+            let invalid = || ClientError::invalid_text("invalid protocol version", s);
+            parts
+                .next()
+                .ok_or_else(invalid)?
+                .parse::<u16>()
+                .map_err(|err| ClientError::invalid_value("invalid protocol version", s, err))
+        };
+        Ok(Self::new(number()?, number()?))
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, IntoStaticStr)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Request for SSIP server.
+///
+/// Converting a reference to `&'static str` gives the name of the variant.
+#[non_exhaustive]
 pub enum Request {
     SetName(ClientName),
     // Speech related requests
@@ -601,13 +1786,15 @@ pub enum Request {
     SetOutputModule(ClientScope, String),
     GetOutputModule,
     ListOutputModules,
-    SetLanguage(ClientScope, String),
+    SetLanguage(ClientScope, LanguageTag),
     GetLanguage,
     SetSsmlMode(bool),
     SetPunctuationMode(ClientScope, PunctuationMode),
     SetSpelling(ClientScope, bool),
     SetCapitalLettersRecognitionMode(ClientScope, CapitalLettersRecognitionMode),
-    SetVoiceType(ClientScope, String),
+    SetTable(ClientScope, String),
+    ListTables,
+    SetVoiceType(ClientScope, VoiceType),
     GetVoiceType,
     ListVoiceTypes,
     SetSynthesisVoice(ClientScope, String),
@@ -616,9 +1803,11 @@ pub enum Request {
     GetRate,
     SetPitch(ClientScope, i8),
     GetPitch,
+    SetPitchRange(ClientScope, i8),
     SetVolume(ClientScope, i8),
     GetVolume,
     SetPauseContext(ClientScope, u32),
+    GetPauseContext,
     SetNotification(NotificationType, bool),
     // Blocks
     Begin,
@@ -642,8 +1831,249 @@ pub enum Request {
     Quit,
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, IntoStaticStr)]
+/// Request for SSIP server borrowing its data.
+///
+/// It has the same variants as [`Request`] but refers to the names, texts and settings
+/// instead of owning them, so that a request can be sent without cloning them. Scopes and
+/// numbers are copied.
+///
+/// Converting a reference to `&'static str` gives the name of the variant, the same as
+/// for [`Request`].
+#[non_exhaustive]
+pub enum RequestRef<'a> {
+    SetName(&'a ClientName),
+    // Speech related requests
+    Speak,
+    SendLine(&'a str),
+    SendLines(&'a [String]),
+    SpeakChar(char),
+    SpeakKey(&'a KeyName),
+    // Flow control
+    Stop(MessageScope),
+    Cancel(MessageScope),
+    Pause(MessageScope),
+    Resume(MessageScope),
+    // Setter and getter
+    SetPriority(&'a Priority),
+    SetDebug(bool),
+    SetOutputModule(ClientScope, &'a str),
+    GetOutputModule,
+    ListOutputModules,
+    SetLanguage(ClientScope, &'a LanguageTag),
+    GetLanguage,
+    SetSsmlMode(bool),
+    SetPunctuationMode(ClientScope, &'a PunctuationMode),
+    SetSpelling(ClientScope, bool),
+    SetCapitalLettersRecognitionMode(ClientScope, &'a CapitalLettersRecognitionMode),
+    SetTable(ClientScope, &'a str),
+    ListTables,
+    SetVoiceType(ClientScope, &'a VoiceType),
+    GetVoiceType,
+    ListVoiceTypes,
+    SetSynthesisVoice(ClientScope, &'a str),
+    ListSynthesisVoices,
+    SetRate(ClientScope, i8),
+    GetRate,
+    SetPitch(ClientScope, i8),
+    GetPitch,
+    SetPitchRange(ClientScope, i8),
+    SetVolume(ClientScope, i8),
+    GetVolume,
+    SetPauseContext(ClientScope, u32),
+    GetPauseContext,
+    SetNotification(&'a NotificationType, bool),
+    // Blocks
+    Begin,
+    End,
+    // History
+    SetHistory(ClientScope, bool),
+    HistoryGetClients,
+    HistoryGetClientId,
+    HistoryGetClientMsgs(ClientScope, u32, u32),
+    HistoryGetLastMsgId,
+    HistoryGetMsg(MessageId),
+    HistoryCursorGet,
+    HistoryCursorSet(ClientScope, &'a HistoryPosition),
+    HistoryCursorMove(&'a CursorDirection),
+    HistorySpeak(MessageId),
+    HistorySort(&'a SortDirection, &'a SortKey),
+    HistorySetShortMsgLength(u32),
+    HistorySetMsgTypeOrdering(&'a [Ordering]),
+    HistorySearch(ClientScope, &'a str),
+    // Misc.
+    Quit,
+}
+
+impl<'a> From<&'a Request> for RequestRef<'a> {
+    fn from(request: &'a Request) -> Self {
+        match request {
+            Request::SetName(a) => RequestRef::SetName(a),
+            Request::Speak => RequestRef::Speak,
+            Request::SendLine(a) => RequestRef::SendLine(a),
+            Request::SendLines(a) => RequestRef::SendLines(a),
+            Request::SpeakChar(a) => RequestRef::SpeakChar(*a),
+            Request::SpeakKey(a) => RequestRef::SpeakKey(a),
+            Request::Stop(a) => RequestRef::Stop(*a),
+            Request::Cancel(a) => RequestRef::Cancel(*a),
+            Request::Pause(a) => RequestRef::Pause(*a),
+            Request::Resume(a) => RequestRef::Resume(*a),
+            Request::SetPriority(a) => RequestRef::SetPriority(a),
+            Request::SetDebug(a) => RequestRef::SetDebug(*a),
+            Request::SetOutputModule(a, b) => RequestRef::SetOutputModule(*a, b),
+            Request::GetOutputModule => RequestRef::GetOutputModule,
+            Request::ListOutputModules => RequestRef::ListOutputModules,
+            Request::SetLanguage(a, b) => RequestRef::SetLanguage(*a, b),
+            Request::GetLanguage => RequestRef::GetLanguage,
+            Request::SetSsmlMode(a) => RequestRef::SetSsmlMode(*a),
+            Request::SetPunctuationMode(a, b) => RequestRef::SetPunctuationMode(*a, b),
+            Request::SetSpelling(a, b) => RequestRef::SetSpelling(*a, *b),
+            Request::SetCapitalLettersRecognitionMode(a, b) => {
+                RequestRef::SetCapitalLettersRecognitionMode(*a, b)
+            }
+            Request::SetTable(a, b) => RequestRef::SetTable(*a, b),
+            Request::ListTables => RequestRef::ListTables,
+            Request::SetVoiceType(a, b) => RequestRef::SetVoiceType(*a, b),
+            Request::GetVoiceType => RequestRef::GetVoiceType,
+            Request::ListVoiceTypes => RequestRef::ListVoiceTypes,
+            Request::SetSynthesisVoice(a, b) => RequestRef::SetSynthesisVoice(*a, b),
+            Request::ListSynthesisVoices => RequestRef::ListSynthesisVoices,
+            Request::SetRate(a, b) => RequestRef::SetRate(*a, *b),
+            Request::GetRate => RequestRef::GetRate,
+            Request::SetPitch(a, b) => RequestRef::SetPitch(*a, *b),
+            Request::SetPitchRange(a, b) => RequestRef::SetPitchRange(*a, *b),
+            Request::GetPitch => RequestRef::GetPitch,
+            Request::SetVolume(a, b) => RequestRef::SetVolume(*a, *b),
+            Request::GetVolume => RequestRef::GetVolume,
+            Request::SetPauseContext(a, b) => RequestRef::SetPauseContext(*a, *b),
+            Request::GetPauseContext => RequestRef::GetPauseContext,
+            Request::SetNotification(a, b) => RequestRef::SetNotification(a, *b),
+            Request::Begin => RequestRef::Begin,
+            Request::End => RequestRef::End,
+            Request::SetHistory(a, b) => RequestRef::SetHistory(*a, *b),
+            Request::HistoryGetClients => RequestRef::HistoryGetClients,
+            Request::HistoryGetClientId => RequestRef::HistoryGetClientId,
+            Request::HistoryGetClientMsgs(a, b, c) => RequestRef::HistoryGetClientMsgs(*a, *b, *c),
+            Request::HistoryGetLastMsgId => RequestRef::HistoryGetLastMsgId,
+            Request::HistoryGetMsg(a) => RequestRef::HistoryGetMsg(*a),
+            Request::HistoryCursorGet => RequestRef::HistoryCursorGet,
+            Request::HistoryCursorSet(a, b) => RequestRef::HistoryCursorSet(*a, b),
+            Request::HistoryCursorMove(a) => RequestRef::HistoryCursorMove(a),
+            Request::HistorySpeak(a) => RequestRef::HistorySpeak(*a),
+            Request::HistorySort(a, b) => RequestRef::HistorySort(a, b),
+            Request::HistorySetShortMsgLength(a) => RequestRef::HistorySetShortMsgLength(*a),
+            Request::HistorySetMsgTypeOrdering(a) => RequestRef::HistorySetMsgTypeOrdering(a),
+            Request::HistorySearch(a, b) => RequestRef::HistorySearch(*a, b),
+            Request::Quit => RequestRef::Quit,
+        }
+    }
+}
+
+impl RequestRef<'_> {
+    /// Check that the scopes of the request are valid.
+    pub fn validate_scopes(&self) -> Result<(), ScopeError> {
+        match self {
+            RequestRef::Stop(scope)
+            | RequestRef::Cancel(scope)
+            | RequestRef::Pause(scope)
+            | RequestRef::Resume(scope) => scope.validate(),
+            RequestRef::SetOutputModule(scope, _)
+            | RequestRef::SetLanguage(scope, _)
+            | RequestRef::SetPunctuationMode(scope, _)
+            | RequestRef::SetSpelling(scope, _)
+            | RequestRef::SetCapitalLettersRecognitionMode(scope, _)
+            | RequestRef::SetTable(scope, _)
+            | RequestRef::SetVoiceType(scope, _)
+            | RequestRef::SetSynthesisVoice(scope, _)
+            | RequestRef::SetRate(scope, _)
+            | RequestRef::SetPitch(scope, _)
+            | RequestRef::SetPitchRange(scope, _)
+            | RequestRef::SetVolume(scope, _)
+            | RequestRef::SetPauseContext(scope, _)
+            | RequestRef::SetHistory(scope, _)
+            | RequestRef::HistoryGetClientMsgs(scope, _, _)
+            | RequestRef::HistoryCursorSet(scope, _)
+            | RequestRef::HistorySearch(scope, _) => scope.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create the owned request, cloning the borrowed data.
+    pub fn to_request(&self) -> Request {
+        match self {
+            RequestRef::SetName(a) => Request::SetName((*a).clone()),
+            RequestRef::Speak => Request::Speak,
+            RequestRef::SendLine(a) => Request::SendLine(a.to_string()),
+            RequestRef::SendLines(a) => Request::SendLines(a.to_vec()),
+            RequestRef::SpeakChar(a) => Request::SpeakChar(*a),
+            RequestRef::SpeakKey(a) => Request::SpeakKey((*a).clone()),
+            RequestRef::Stop(a) => Request::Stop(*a),
+            RequestRef::Cancel(a) => Request::Cancel(*a),
+            RequestRef::Pause(a) => Request::Pause(*a),
+            RequestRef::Resume(a) => Request::Resume(*a),
+            RequestRef::SetPriority(a) => Request::SetPriority((*a).clone()),
+            RequestRef::SetDebug(a) => Request::SetDebug(*a),
+            RequestRef::SetOutputModule(a, b) => Request::SetOutputModule(*a, b.to_string()),
+            RequestRef::GetOutputModule => Request::GetOutputModule,
+            RequestRef::ListOutputModules => Request::ListOutputModules,
+            RequestRef::SetLanguage(a, b) => Request::SetLanguage(*a, (*b).clone()),
+            RequestRef::GetLanguage => Request::GetLanguage,
+            RequestRef::SetSsmlMode(a) => Request::SetSsmlMode(*a),
+            RequestRef::SetPunctuationMode(a, b) => Request::SetPunctuationMode(*a, (*b).clone()),
+            RequestRef::SetSpelling(a, b) => Request::SetSpelling(*a, *b),
+            RequestRef::SetCapitalLettersRecognitionMode(a, b) => {
+                Request::SetCapitalLettersRecognitionMode(*a, (*b).clone())
+            }
+            RequestRef::SetTable(a, b) => Request::SetTable(*a, b.to_string()),
+            RequestRef::ListTables => Request::ListTables,
+            RequestRef::SetVoiceType(a, b) => Request::SetVoiceType(*a, (*b).clone()),
+            RequestRef::GetVoiceType => Request::GetVoiceType,
+            RequestRef::ListVoiceTypes => Request::ListVoiceTypes,
+            RequestRef::SetSynthesisVoice(a, b) => Request::SetSynthesisVoice(*a, b.to_string()),
+            RequestRef::ListSynthesisVoices => Request::ListSynthesisVoices,
+            RequestRef::SetRate(a, b) => Request::SetRate(*a, *b),
+            RequestRef::GetRate => Request::GetRate,
+            RequestRef::SetPitch(a, b) => Request::SetPitch(*a, *b),
+            RequestRef::SetPitchRange(a, b) => Request::SetPitchRange(*a, *b),
+            RequestRef::GetPitch => Request::GetPitch,
+            RequestRef::SetVolume(a, b) => Request::SetVolume(*a, *b),
+            RequestRef::GetVolume => Request::GetVolume,
+            RequestRef::SetPauseContext(a, b) => Request::SetPauseContext(*a, *b),
+            RequestRef::GetPauseContext => Request::GetPauseContext,
+            RequestRef::SetNotification(a, b) => Request::SetNotification((*a).clone(), *b),
+            RequestRef::Begin => Request::Begin,
+            RequestRef::End => Request::End,
+            RequestRef::SetHistory(a, b) => Request::SetHistory(*a, *b),
+            RequestRef::HistoryGetClients => Request::HistoryGetClients,
+            RequestRef::HistoryGetClientId => Request::HistoryGetClientId,
+            RequestRef::HistoryGetClientMsgs(a, b, c) => Request::HistoryGetClientMsgs(*a, *b, *c),
+            RequestRef::HistoryGetLastMsgId => Request::HistoryGetLastMsgId,
+            RequestRef::HistoryGetMsg(a) => Request::HistoryGetMsg(*a),
+            RequestRef::HistoryCursorGet => Request::HistoryCursorGet,
+            RequestRef::HistoryCursorSet(a, b) => Request::HistoryCursorSet(*a, (*b).clone()),
+            RequestRef::HistoryCursorMove(a) => Request::HistoryCursorMove((*a).clone()),
+            RequestRef::HistorySpeak(a) => Request::HistorySpeak(*a),
+            RequestRef::HistorySort(a, b) => Request::HistorySort((*a).clone(), (*b).clone()),
+            RequestRef::HistorySetShortMsgLength(a) => Request::HistorySetShortMsgLength(*a),
+            RequestRef::HistorySetMsgTypeOrdering(a) => {
+                Request::HistorySetMsgTypeOrdering(a.to_vec())
+            }
+            RequestRef::HistorySearch(a, b) => Request::HistorySearch(*a, b.to_string()),
+            RequestRef::Quit => Request::Quit,
+        }
+    }
+}
+
+impl From<RequestRef<'_>> for Request {
+    fn from(request: RequestRef<'_>) -> Self {
+        request.to_request()
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Response from SSIP server.
+#[non_exhaustive]
 pub enum Response {
     LanguageSet,                                     // 201
     PrioritySet,                                     // 202
@@ -679,7 +2109,7 @@ pub enum Response {
     HistoryClientListSent(Vec<HistoryClientStatus>), // 240
     HistoryMsgsListSent(Vec<String>),                // 241
     HistoryLastMsg(String),                          // 242
-    HistoryCurPosRet(String),                        // 243
+    HistoryCurPosRet(CursorPosition),                // 243
     TableListSent(Vec<String>),                      // 244
     HistoryClientIdSent(ClientId),                   // 245
     MessageTextSent,                                 // 246
@@ -687,15 +2117,169 @@ pub enum Response {
     VoicesListSent(Vec<SynthesisVoice>),             // 249
     OutputModulesListSent(Vec<String>),              // 250
     Get(String),                                     // 251
-    InsideBlock,                                     // 260
-    OutsideBlock,                                    // 261
-    NotImplemented,                                  // 299
-    EventIndexMark(EventId, String),                 // 700
-    EventBegin(EventId),                             // 701
-    EventEnd(EventId),                               // 702
-    EventCanceled(EventId),                          // 703
-    EventPaused(EventId),                            // 704
-    EventResumed(EventId),                           // 705
+    GetRate(i8),                                     // 251 answering GET RATE
+    GetPitch(i8),                                    // 251 answering GET PITCH
+    GetVolume(i8),                                   // 251 answering GET VOLUME
+    GetPauseContext(u16),                            // 251 answering GET PAUSE_CONTEXT
+    GetLines(Vec<String>),                           // 251 with several lines, in lenient mode
+    /// List with a code in 240-259 unknown to this version, such as lists added by other
+    /// versions of the server.
+    List {
+        code: ReturnCode,
+        items: Vec<String>,
+    },
+    InsideBlock,                     // 260
+    OutsideBlock,                    // 261
+    NotImplemented,                  // 299
+    EventIndexMark(EventId, String), // 700
+    EventBegin(EventId),             // 701
+    EventEnd(EventId),               // 702
+    EventCanceled(EventId),          // 703
+    EventPaused(EventId),            // 704
+    EventResumed(EventId),           // 705
+    /// Event with a code unknown to this version, with the lines received before the status.
+    UnknownEvent {
+        code: ReturnCode,
+        lines: Vec<String>,
+    },
+}
+impl Response {
+    /// Kind of the response, to branch on it without matching the data.
+    pub fn kind(&self) -> ResponseKind {
+        match self {
+            Response::LanguageSet => ResponseKind::LanguageSet,
+            Response::PrioritySet => ResponseKind::PrioritySet,
+            Response::RateSet => ResponseKind::RateSet,
+            Response::PitchSet => ResponseKind::PitchSet,
+            Response::PunctuationSet => ResponseKind::PunctuationSet,
+            Response::CapLetRecognSet => ResponseKind::CapLetRecognSet,
+            Response::SpellingSet => ResponseKind::SpellingSet,
+            Response::ClientNameSet => ResponseKind::ClientNameSet,
+            Response::VoiceSet => ResponseKind::VoiceSet,
+            Response::Stopped => ResponseKind::Stopped,
+            Response::Paused => ResponseKind::Paused,
+            Response::Resumed => ResponseKind::Resumed,
+            Response::Canceled => ResponseKind::Canceled,
+            Response::TableSet => ResponseKind::TableSet,
+            Response::OutputModuleSet => ResponseKind::OutputModuleSet,
+            Response::PauseContextSet => ResponseKind::PauseContextSet,
+            Response::VolumeSet => ResponseKind::VolumeSet,
+            Response::SsmlModeSet => ResponseKind::SsmlModeSet,
+            Response::NotificationSet => ResponseKind::NotificationSet,
+            Response::PitchRangeSet => ResponseKind::PitchRangeSet,
+            Response::DebugSet => ResponseKind::DebugSet,
+            Response::HistoryCurSetFirst => ResponseKind::HistoryCurSetFirst,
+            Response::HistoryCurSetLast => ResponseKind::HistoryCurSetLast,
+            Response::HistoryCurSetPos => ResponseKind::HistoryCurSetPos,
+            Response::HistoryCurMoveFor => ResponseKind::HistoryCurMoveFor,
+            Response::HistoryCurMoveBack => ResponseKind::HistoryCurMoveBack,
+            Response::MessageQueued => ResponseKind::MessageQueued,
+            Response::SoundIconQueued => ResponseKind::SoundIconQueued,
+            Response::MessageCanceled => ResponseKind::MessageCanceled,
+            Response::ReceivingData => ResponseKind::ReceivingData,
+            Response::Bye => ResponseKind::Bye,
+            Response::HistoryClientListSent(..) => ResponseKind::HistoryClientListSent,
+            Response::HistoryMsgsListSent(..) => ResponseKind::HistoryMsgsListSent,
+            Response::HistoryLastMsg(..) => ResponseKind::HistoryLastMsg,
+            Response::HistoryCurPosRet(..) => ResponseKind::HistoryCurPosRet,
+            Response::TableListSent(..) => ResponseKind::TableListSent,
+            Response::HistoryClientIdSent(..) => ResponseKind::HistoryClientIdSent,
+            Response::MessageTextSent => ResponseKind::MessageTextSent,
+            Response::HelpSent(..) => ResponseKind::HelpSent,
+            Response::VoicesListSent(..) => ResponseKind::VoicesListSent,
+            Response::OutputModulesListSent(..) => ResponseKind::OutputModulesListSent,
+            Response::Get(..) => ResponseKind::Get,
+            Response::GetRate(..) => ResponseKind::GetRate,
+            Response::GetPitch(..) => ResponseKind::GetPitch,
+            Response::GetVolume(..) => ResponseKind::GetVolume,
+            Response::GetPauseContext(..) => ResponseKind::GetPauseContext,
+            Response::GetLines(..) => ResponseKind::GetLines,
+            Response::List { .. } => ResponseKind::List,
+            Response::InsideBlock => ResponseKind::InsideBlock,
+            Response::OutsideBlock => ResponseKind::OutsideBlock,
+            Response::NotImplemented => ResponseKind::NotImplemented,
+            Response::EventIndexMark(..) => ResponseKind::EventIndexMark,
+            Response::EventBegin(..) => ResponseKind::EventBegin,
+            Response::EventEnd(..) => ResponseKind::EventEnd,
+            Response::EventCanceled(..) => ResponseKind::EventCanceled,
+            Response::EventPaused(..) => ResponseKind::EventPaused,
+            Response::EventResumed(..) => ResponseKind::EventResumed,
+            Response::UnknownEvent { .. } => ResponseKind::UnknownEvent,
+        }
+    }
+}
+
+/// Kind of a [`Response`], without its data.
+///
+/// Control flow can depend on the kind while the data is read from the [`Response`].
+#[derive(StrumDisplay, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ResponseKind {
+    LanguageSet,           // 201
+    PrioritySet,           // 202
+    RateSet,               // 203
+    PitchSet,              // 204
+    PunctuationSet,        // 205
+    CapLetRecognSet,       // 206
+    SpellingSet,           // 207
+    ClientNameSet,         // 208
+    VoiceSet,              // 209
+    Stopped,               // 210
+    Paused,                // 211
+    Resumed,               // 212
+    Canceled,              // 213
+    TableSet,              // 215
+    OutputModuleSet,       // 216
+    PauseContextSet,       // 217
+    VolumeSet,             // 218
+    SsmlModeSet,           // 219
+    NotificationSet,       // 220
+    PitchRangeSet,         // 263
+    DebugSet,              // 262
+    HistoryCurSetFirst,    // 220
+    HistoryCurSetLast,     // 221
+    HistoryCurSetPos,      // 222
+    HistoryCurMoveFor,     // 223
+    HistoryCurMoveBack,    // 224
+    MessageQueued,         // 225,
+    SoundIconQueued,       // 226
+    MessageCanceled,       // 227
+    ReceivingData,         // 230
+    Bye,                   // 231
+    HistoryClientListSent, // 240
+    HistoryMsgsListSent,   // 241
+    HistoryLastMsg,        // 242
+    HistoryCurPosRet,      // 243
+    TableListSent,         // 244
+    HistoryClientIdSent,   // 245
+    MessageTextSent,       // 246
+    HelpSent,              // 248
+    VoicesListSent,        // 249
+    OutputModulesListSent, // 250
+    Get,                   // 251
+    GetRate,               // 251 answering GET RATE
+    GetPitch,              // 251 answering GET PITCH
+    GetVolume,             // 251 answering GET VOLUME
+    GetPauseContext,       // 251 answering GET PAUSE_CONTEXT
+    GetLines,              // 251 with several lines, in lenient mode
+    List,                  // 240-259 unknown to this version
+    InsideBlock,           // 260
+    OutsideBlock,          // 261
+    NotImplemented,        // 299
+    EventIndexMark,        // 700
+    EventBegin,            // 701
+    EventEnd,              // 702
+    EventCanceled,         // 703
+    EventPaused,           // 704
+    EventResumed,          // 705
+    UnknownEvent,
+}
+
+impl From<&Response> for ResponseKind {
+    fn from(response: &Response) -> Self {
+        response.kind()
+    }
 }
 
 #[cfg(test)]
@@ -704,7 +2288,12 @@ mod tests {
     use std::io;
     use std::str::FromStr;
 
-    use super::{ClientError, HistoryClientStatus, HistoryPosition, MessageScope, SynthesisVoice};
+    use super::{
+        choose_voice, ClientError, ClientName, ClientScope, CursorPosition, Event, EventId,
+        EventType, HistoryClientStatus, HistoryPosition, HistorySettings, KeyName, LanguageTag,
+        MarkName, MessageScope, Ordering, Overrides, ProtocolVersion, Request, RequestRef,
+        Response, ResponseKind, ScopeError, SortDirection, SortKey, SynthesisVoice, VoiceType,
+    };
 
     #[test]
     fn parse_synthesis_voice() {
@@ -722,11 +2311,184 @@ mod tests {
         assert!(v2.dialect.is_none());
     }
 
+    #[test]
+    fn event_ids() {
+        assert_eq!(EventId::new(21, 1), EventId::parse("21", "1").unwrap());
+        assert!(EventId::parse("21", "joe").is_err());
+        let event = Event::from_raw(EventType::IndexMark(String::from("m")), "21", "joe");
+        assert_eq!(Some(21), event.message_id());
+        assert_eq!(None, event.client_id());
+        assert_eq!(None, event.id());
+        assert_eq!("joe", event.raw().client);
+        assert_eq!(Some("m"), event.mark());
+        assert_eq!(
+            Event::end(21, 1),
+            Event::from_raw(EventType::End, "21", "1")
+        );
+        assert_eq!(None, Event::end(21, 1).mark());
+    }
+
+    #[test]
+    fn synthesis_voice_matches() {
+        let voice = SynthesisVoice::new("Alan", Some("en_GB"), None);
+        assert!(voice.matches("en", None));
+        assert!(voice.matches("EN-gb", None));
+        assert!(voice.matches("en", Some("GB")));
+        assert!(!voice.matches("en-US", None));
+        assert!(!voice.matches("en", Some("US")));
+        assert!(!voice.matches("fr", None));
+        assert!(!voice.matches("", None));
+        let voice = SynthesisVoice::new("Paul", Some("en"), Some("US"));
+        assert!(voice.matches("en-US", None));
+        assert!(voice.matches("en", Some("us")));
+        let voice = SynthesisVoice::new("Unknown", None, None);
+        assert!(!voice.matches("en", None));
+    }
+
+    #[test]
+    fn choose_best_voice() {
+        let voices = vec![
+            SynthesisVoice::new("Alan", Some("en-GB"), None),
+            SynthesisVoice::new("Scottish", Some("en-GB-scotland"), None),
+            SynthesisVoice::new("Paul", Some("en"), Some("US")),
+            SynthesisVoice::new("Generic", Some("en"), None),
+            SynthesisVoice::new("Amélie", Some("fr"), Some("FR")),
+        ];
+        let choose =
+            |preferences: &[&str]| choose_voice(&voices, preferences).map(|v| v.name.as_str());
+        assert_eq!(Some("Paul"), choose(&["en-us"]));
+        assert_eq!(Some("Alan"), choose(&["en-GB"]));
+        assert_eq!(Some("Generic"), choose(&["en"]));
+        assert_eq!(Some("Amélie"), choose(&["de-DE", "fr-CA", "en"]));
+        assert_eq!(Some("Generic"), choose(&["en-AU"]));
+        assert_eq!(None, choose(&["de"]));
+        assert_eq!(None, choose(&[]));
+    }
+
+    #[test]
+    fn key_names() {
+        for key in KeyName::ALL {
+            assert_eq!(key.to_string(), key.as_str());
+            assert_eq!(Some(key), KeyName::try_from(key.as_str()).ok().as_ref());
+        }
+        assert_eq!("kp-enter", KeyName::KpEnter.as_str());
+        assert!(KeyName::try_from("hyperspace").is_err());
+        assert_eq!(Some(KeyName::Space), KeyName::try_from(' ').ok());
+        assert_eq!(Some(KeyName::Return), KeyName::try_from('\n').ok());
+        assert!(KeyName::try_from('a').is_err());
+    }
+
+    #[test]
+    fn parse_mark_name() {
+        assert_eq!(
+            "chapter-1.2",
+            MarkName::from_str("chapter-1.2").unwrap().as_str()
+        );
+        for name in &["", "a b", "a\"/><x", "a\r\n700-x"] {
+            assert!(MarkName::from_str(name).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_language_tag() {
+        for (tag, normalized) in [
+            ("fr", "fr"),
+            ("EN-us", "en-US"),
+            ("pt_br", "pt-BR"),
+            ("zh-hant-tw", "zh-Hant-TW"),
+            ("es-419", "es-419"),
+            ("de-CH-1996", "de-CH-1996"),
+            ("en-x-US", "en-x-us"),
+        ] {
+            assert_eq!(normalized, tag.parse::<LanguageTag>().unwrap().as_str());
+        }
+        for tag in [
+            "",
+            "English",
+            "e",
+            "en-",
+            "en--us",
+            "fr-toolongsubtag",
+            "fr-ç",
+        ] {
+            assert!(tag.parse::<LanguageTag>().is_err(), "{tag} accepted");
+        }
+    }
+
+    #[test]
+    fn parse_voice_type() {
+        for (name, voice_type) in [
+            ("MALE1", VoiceType::Male1),
+            ("male3", VoiceType::Male3),
+            ("FEMALE2", VoiceType::Female2),
+            ("CHILD_MALE", VoiceType::ChildMale),
+            ("child_female", VoiceType::ChildFemale),
+            ("ROBOT", VoiceType::Other(String::from("ROBOT"))),
+        ] {
+            assert_eq!(voice_type, VoiceType::from_str(name).unwrap());
+        }
+        assert!(VoiceType::from_str("").is_err());
+        assert_eq!("CHILD_FEMALE", VoiceType::ChildFemale.to_string());
+        assert_eq!("ROBOT", VoiceType::Other(String::from("ROBOT")).to_string());
+    }
+
+    #[test]
+    fn error_trait() {
+        fn is_error<E: super::StdError + Send + Sync + 'static>() {}
+        is_error::<ClientError>();
+        is_error::<super::ParseError>();
+        is_error::<super::ScopeError>();
+    }
+
+    /// With feature `std`, the crate doesn't need `core::error::Error`, stable from Rust 1.81.
+    #[cfg(feature = "std")]
+    #[test]
+    fn minimum_rust_version() {
+        let version = env!("CARGO_PKG_RUST_VERSION")
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap())
+            .collect::<Vec<_>>();
+        assert!(version < vec![1, 81], "MSRV raised to {:?}", version);
+    }
+
+    #[test]
+    fn parse_error_source() {
+        use std::error::Error;
+        let err = EventId::parse("21", "x1").unwrap_err();
+        let parse_error = err.parse_error().unwrap();
+        assert_eq!("invalid event id", parse_error.what());
+        assert_eq!("x1", parse_error.text());
+        assert_eq!("I/O: invalid event id: \"x1\"", err.to_string());
+        let source = err.source().and_then(|err| err.source()).unwrap();
+        assert!(source.is::<std::num::ParseIntError>());
+        let err = MarkName::from_str("a b").unwrap_err();
+        assert_eq!("a b", err.parse_error().unwrap().text());
+        assert!(ClientError::TooFewLines.parse_error().is_none());
+    }
+
     #[test]
     fn format_message_scope() {
         assert_eq!("self", format!("{}", MessageScope::Last).as_str());
         assert_eq!("all", format!("{}", MessageScope::All).as_str());
         assert_eq!("123", format!("{}", MessageScope::Message(123)).as_str());
+        assert_eq!(MessageScope::Message(21), MessageScope::from(21));
+    }
+
+    #[test]
+    fn validate_scopes() {
+        assert_eq!(Ok(MessageScope::Message(21)), MessageScope::message(21));
+        assert_eq!(
+            Err(ScopeError::InvalidMessageId(0)),
+            MessageScope::message(0)
+        );
+        assert_eq!(Ok(ClientScope::Client(1)), ClientScope::client(1));
+        assert_eq!(Err(ScopeError::InvalidClientId(0)), ClientScope::client(0));
+        let stop = Request::Stop(MessageScope::new_unchecked(0));
+        assert!(RequestRef::from(&stop).validate_scopes().is_err());
+        let rate = Request::SetRate(ClientScope::new_unchecked(0), 10);
+        assert!(RequestRef::from(&rate).validate_scopes().is_err());
+        let rate = Request::SetRate(ClientScope::All, 10);
+        assert!(RequestRef::from(&rate).validate_scopes().is_ok());
     }
 
     #[test]
@@ -746,9 +2508,22 @@ mod tests {
             HistoryClientStatus::new(11, "joe:speechd_client:main", true),
             HistoryClientStatus::from_str("11 joe:speechd_client:main 1").unwrap()
         );
+        assert_eq!(
+            HistoryClientStatus::new(12, "joe smith:speechd client:main", true),
+            HistoryClientStatus::from_str("12 joe smith:speechd client:main 1").unwrap()
+        );
+        assert_eq!(
+            HistoryClientStatus::new(13, "", false),
+            HistoryClientStatus::from_str("13  0").unwrap()
+        );
+        assert_eq!(
+            HistoryClientStatus::new(14, "zoé:lecteur d’écran:主", true),
+            HistoryClientStatus::from_str("14 zoé:lecteur d’écran:主 1").unwrap()
+        );
         for line in &[
             "9 joe:speechd_client:main xxx",
             "xxx joe:speechd_client:main 1",
+            "9 joe smith:speechd_client:main on",
         ] {
             match HistoryClientStatus::from_str(line) {
                 Ok(_) => panic!("parsing should have failed"),
@@ -764,4 +2539,231 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_cursor_position() {
+        assert_eq!(CursorPosition(42), CursorPosition::from_str("42").unwrap());
+        assert!(CursorPosition::from_str("-1").is_err());
+        assert!(CursorPosition::from_str("first").is_err());
+    }
+
+    #[test]
+    fn overrides_requests() {
+        let overrides = Overrides::default()
+            .with_language(LanguageTag::from_str("fr").unwrap())
+            .with_rate(20)
+            .with_pause_context(3);
+        assert_eq!(
+            vec![
+                Request::GetLanguage,
+                Request::GetRate,
+                Request::GetPauseContext
+            ],
+            overrides.queries()
+        );
+        let previous = overrides
+            .previous(vec![
+                Response::Get(String::from("en")),
+                Response::GetRate(-5),
+                Response::GetPauseContext(0),
+            ])
+            .unwrap();
+        assert_eq!(
+            vec![
+                Request::SetLanguage(ClientScope::Current, LanguageTag::from_str("en").unwrap()),
+                Request::SetRate(ClientScope::Current, -5),
+                Request::SetPauseContext(ClientScope::Current, 0),
+            ],
+            previous.requests()
+        );
+        assert!(overrides.previous(vec![Response::GetRate(-5)]).is_err());
+        assert!(Overrides::default().is_empty());
+    }
+
+    #[test]
+    fn protocol_version() {
+        let version: ProtocolVersion = "0.8.8".parse().unwrap();
+        assert_eq!(ProtocolVersion::new(0, 8), version);
+        assert_eq!("0.8", version.to_string());
+        assert!("0".parse::<ProtocolVersion>().is_err());
+        let pitch_range = RequestRef::SetPitchRange(ClientScope::Current, 10);
+        assert!(!version.supports(pitch_range));
+        assert!(version.supports(RequestRef::Speak));
+        assert!(ProtocolVersion::default().supports(pitch_range));
+    }
+
+    #[test]
+    fn request_ref() {
+        let name = ClientName::new("joe", "hello").unwrap();
+        let request = Request::SetName(name.clone());
+        let borrowed = RequestRef::from(&request);
+        assert_eq!(RequestRef::SetName(&name), borrowed);
+        assert_eq!(request, borrowed.to_request());
+        let name: &'static str = (&borrowed).into();
+        assert_eq!("SetName", name);
+        let lines = vec!["hello".to_string()];
+        let request = RequestRef::SendLines(&lines);
+        assert_eq!(Request::SendLines(lines.clone()), Request::from(request));
+        let mut settings = HistorySettings::default();
+        settings.update(RequestRef::HistorySetShortMsgLength(20));
+        assert_eq!(Some(20), settings.short_message_length);
+    }
+
+    #[test]
+    fn update_history_settings() {
+        let mut settings = HistorySettings::default();
+        settings.update(&Request::Speak);
+        assert_eq!(HistorySettings::default(), settings);
+        settings.update(&Request::HistorySort(
+            SortDirection::Descending,
+            SortKey::Time,
+        ));
+        settings.update(&Request::HistorySetShortMsgLength(20));
+        settings.update(&Request::HistorySetMsgTypeOrdering(vec![
+            Ordering::Text,
+            Ordering::Key,
+        ]));
+        assert_eq!(
+            HistorySettings {
+                sort: Some((SortDirection::Descending, SortKey::Time)),
+                short_message_length: Some(20),
+                message_type_ordering: Some(vec![Ordering::Text, Ordering::Key]),
+            },
+            settings
+        );
+    }
+
+    #[test]
+    fn response_kind() {
+        assert_eq!(ResponseKind::RateSet, Response::RateSet.kind());
+        assert_eq!(ResponseKind::GetRate, Response::GetRate(-10).kind());
+        assert_eq!(
+            ResponseKind::EventEnd,
+            ResponseKind::from(&Response::EventEnd(EventId::new(21, 1)))
+        );
+        assert_eq!(
+            ResponseKind::UnknownEvent,
+            Response::UnknownEvent {
+                code: 799,
+                lines: Vec::new()
+            }
+            .kind()
+        );
+        assert_eq!(
+            ResponseKind::List,
+            Response::List {
+                code: 247,
+                items: Vec::new()
+            }
+            .kind()
+        );
+        assert_eq!("HelpSent", ResponseKind::HelpSent.to_string());
+    }
+
+    #[test]
+    fn client_name_validation() {
+        let name = ClientName::with_component("joe", "hello", "status").unwrap();
+        assert_eq!("joe", name.user);
+        assert_eq!("hello", name.application);
+        assert_eq!("status", name.component);
+        assert_eq!("main", ClientName::new("joe", "hello").unwrap().component);
+        for (user, application) in &[
+            ("jo:e", "hello"),
+            ("joe", "hello world"),
+            ("joe\t", "a"),
+            ("", "hello"),
+            ("joe", ""),
+        ] {
+            match ClientName::new(user, application) {
+                Ok(_) => panic!("illegal client name accepted"),
+                Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::InvalidInput => (),
+                Err(_) => panic!("expecting error 'invalid input'"),
+            }
+        }
+    }
+
+    #[test]
+    fn client_name_with_auto_component() {
+        let first = ClientName::with_auto_component("joe", "hello").unwrap();
+        let second = ClientName::with_auto_component("joe", "hello").unwrap();
+        assert_ne!(first.component, second.component);
+        let prefix = format!("main-{}-", std::process::id());
+        assert!(first.component.starts_with(&prefix));
+        assert!(ClientName::with_auto_component("jo:e", "hello").is_err());
+    }
+
+    #[test]
+    fn client_name_from_env() {
+        let name = ClientName::from_env();
+        for field in &[&name.user, &name.application, &name.component] {
+            assert!(!field.is_empty());
+            assert!(!field.contains(':'));
+            assert!(!field.contains(char::is_whitespace));
+        }
+        assert_eq!("a_b_c", ClientName::sanitize_field("a:b c"));
+        assert_eq!("unknown", ClientName::sanitize_field(""));
+    }
+
+    #[test]
+    #[cfg(feature = "dbus")]
+    fn dbus_event() {
+        use zvariant::{serialized::Context, to_bytes, Type, LE};
+
+        let context = Context::new_dbus(LE, 0);
+        assert_eq!("(ss(ss))", super::SpeechEvent::SIGNATURE.to_string());
+        for event in [
+            super::Event::index_mark("mark".to_string(), 21, 1),
+            super::Event::begin(21, 1),
+            super::Event::from_raw(super::EventType::End, "21", "joe"),
+        ] {
+            let data = to_bytes(context, &super::SpeechEvent::from(event.clone())).unwrap();
+            let (decoded, _): (super::SpeechEvent, _) = data.deserialize().unwrap();
+            assert_eq!(event, super::Event::try_from(decoded).unwrap());
+        }
+        let data = to_bytes(context, &super::EventId::new(21, 1)).unwrap();
+        let (id, _): (super::EventId, _) = data.deserialize().unwrap();
+        assert_eq!(super::EventId::new(21, 1), id);
+        let unknown = super::SpeechEvent {
+            kind: String::from("unknown"),
+            mark: String::new(),
+            id: super::RawEventId {
+                message: String::from("21"),
+                client: String::from("1"),
+            },
+        };
+        assert!(super::Event::try_from(unknown).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_event() {
+        let event = super::Event::index_mark("mark".to_string(), 21, 1);
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            r#"{"ntype":{"IndexMark":"mark"},"id":{"message":"21","client":"1"}}"#,
+            json
+        );
+        let event: super::Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(Some(21), event.message_id());
+        assert!(matches!(event.ntype, super::EventType::IndexMark(mark) if mark == "mark"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialize_checks_names() {
+        let name: ClientName =
+            serde_json::from_str(r#"{"user":"joe","application":"hello","component":"main"}"#)
+                .unwrap();
+        assert_eq!(
+            ClientName::with_component("joe", "hello", "main").unwrap(),
+            name
+        );
+        assert!(serde_json::from_str::<ClientName>(
+            r#"{"user":"joe doe","application":"hello","component":"main"}"#
+        )
+        .is_err());
+        let tag: super::LanguageTag = serde_json::from_str(r#""en-US""#).unwrap();
+        assert_eq!(r#""en-US""#, serde_json::to_string(&tag).unwrap());
+        assert!(serde_json::from_str::<super::LanguageTag>(r#""en US""#).is_err());
+    }
 }